@@ -0,0 +1,172 @@
+use std::{cell::RefCell, hint::black_box};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+use vrpr::{
+    config::Config,
+    gp::{GPContext, GPParams},
+    sim::{
+        baseline,
+        ctx::{RoutingContext, RoutingProgram, SequencingContext, SequencingProgram},
+        instance::Fleet,
+        problem::Problem,
+        scenario::Scenario,
+        simulate_days_with, Float, SimulationScratch, VehicleState,
+    },
+};
+
+// the reference instances, one of each shape of the benchmark
+const INSTANCES: [&str; 3] = ["h100c102", "h100r102", "h100rc102"];
+
+fn problem(name: &str, config: &Config) -> Problem {
+    let path = format!("{}/datasets/100/{name}.csv", env!("CARGO_MANIFEST_DIR"));
+    let fleet = Fleet::default();
+    let mut problem = Problem::load(
+        &path,
+        fleet.truck_speed,
+        fleet.truck_capacity,
+        fleet.num_trucks,
+        config.num_depots,
+        config.num_satellites,
+    )
+    .unwrap();
+    config.configure(&mut problem).unwrap();
+    problem
+}
+
+fn time_slot(problem: &Problem, config: &Config) -> Float {
+    problem.depot().close / config.num_time_slot
+}
+
+// a seeded population as small as a benchmark repeats quickly
+fn gpc(config: &Config) -> GPContext<SmallRng> {
+    GPContext {
+        rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        params: GPParams {
+            num_population: 32,
+            ..config.gp_params()
+        },
+    }
+}
+
+// routing rules of a random population over every request of an instance
+fn program_evaluation(c: &mut Criterion) {
+    let config = Config::from_env().unwrap();
+    let problem = problem(INSTANCES[0], &config);
+    let pop = gpc(&config).ramp_half_and_half::<RoutingContext>();
+    let vehicle_state = VehicleState::new(&problem, 0);
+    let total_demand = problem.total_demand();
+    let mut group = c.benchmark_group("program_evaluation");
+    group.throughput(Throughput::Elements(
+        (problem.requests.len() * pop.len()) as u64,
+    ));
+    group.bench_function(INSTANCES[0], |b| {
+        b.iter(|| {
+            for request in &problem.requests {
+                let context = RoutingContext {
+                    vehicle_state: &vehicle_state,
+                    problem: &problem,
+                    time: request.time,
+                    request,
+                    num_feasible: problem.num_vehicles(),
+                    total_demand,
+                };
+                for program in &pop {
+                    black_box(program.calc(&context));
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+// the classical rules over each reference instance
+fn simulation(c: &mut Criterion) {
+    let config = Config::from_env().unwrap();
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let mut scratch = SimulationScratch::default();
+    let mut group = c.benchmark_group("simulation");
+    for name in INSTANCES {
+        let problem = problem(name, &config);
+        let time_slot = time_slot(&problem, &config);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                black_box(simulate_days_with(
+                    &mut scratch,
+                    &problem,
+                    &routing,
+                    &sequencing,
+                    Scenario::default(),
+                    time_slot,
+                    |_, _| (),
+                ))
+            })
+        });
+    }
+    group.finish();
+}
+
+type Individual = (RoutingProgram<'static>, SequencingProgram<'static>);
+
+// every individual simulated and ranked by the objective, and the worse
+// half replaced by offspring of the better one
+fn generation(c: &mut Criterion) {
+    let config = Config::from_env().unwrap();
+    let problem = problem(INSTANCES[0], &config);
+    let time_slot = time_slot(&problem, &config);
+    let gpc = gpc(&config);
+    let pop = (gpc.ramp_half_and_half::<RoutingContext>().into_iter())
+        .zip(gpc.ramp_half_and_half::<SequencingContext>())
+        .collect::<Vec<Individual>>();
+    let mut scratch = SimulationScratch::default();
+    let mut group = c.benchmark_group("generation");
+    group.throughput(Throughput::Elements(pop.len() as u64));
+    group.bench_function(INSTANCES[0], |b| {
+        b.iter_batched(
+            || pop.clone(),
+            |pop| {
+                let mut ranked = pop
+                    .into_iter()
+                    .map(|(routing, sequencing)| {
+                        let result = simulate_days_with(
+                            &mut scratch,
+                            &problem,
+                            &routing,
+                            &sequencing,
+                            Scenario::default(),
+                            time_slot,
+                            |_, _| (),
+                        );
+                        let fitness = config.objective.fitness(&problem, &result);
+                        (fitness, (routing, sequencing))
+                    })
+                    .collect::<Vec<_>>();
+                ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let elites = ranked.len() / 2;
+                ranked.truncate(elites);
+                let mut next = ranked.into_iter().map(|(_, i)| i).collect::<Vec<_>>();
+                while next.len() < gpc.params.num_population {
+                    let (p1, p2) = {
+                        let mut rng = gpc.rng.borrow_mut();
+                        let parents = &next[..elites];
+                        (parents.choose(&mut *rng), parents.choose(&mut *rng))
+                    };
+                    let (p1, p2) = (p1.unwrap().clone(), p2.unwrap().clone());
+                    let (routing, _) = gpc.crossover(&p1.0, &p2.0).unwrap();
+                    let (sequencing, _) = gpc.crossover(&p1.1, &p2.1).unwrap();
+                    let mutating = gpc.rng.borrow_mut().gen_bool(gpc.params.mutation_rate);
+                    next.push(match mutating {
+                        true => (gpc.mutation(&routing).unwrap(), sequencing),
+                        false => (routing, sequencing),
+                    });
+                }
+                next
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, program_evaluation, simulation, generation);
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    significance::mann_whitney,
+    sim::ctx::{ROUTING_NAMES, SEQUENCING_NAMES},
+    sweep::SweepResult,
+};
+
+// "routing:index" and "sequencing:index" of every terminal, as `ABLATE` takes
+// them
+pub fn terminals() -> Vec<String> {
+    let routing = (0..ROUTING_NAMES.len()).map(|i| format!("routing:{i}"));
+    let sequencing = (0..SEQUENCING_NAMES.len()).map(|i| format!("sequencing:{i}"));
+    routing.chain(sequencing).collect()
+}
+
+// the short name of a terminal given like `ABLATE`, if there is one
+pub fn name(terminal: &str) -> Option<&'static str> {
+    let (rule, index) = terminal.split_once(':')?;
+    let index = index.parse::<usize>().ok()?;
+    match rule {
+        "routing" => ROUTING_NAMES.get(index).copied(),
+        "sequencing" => SEQUENCING_NAMES.get(index).copied(),
+        _ => None,
+    }
+}
+
+// the runs with `terminal` disabled, against the fitness of the runs with
+// every terminal
+pub struct AblationResult {
+    pub terminal: String,
+    pub result: SweepResult,
+    pub baseline: Vec<f32>,
+}
+
+impl AblationResult {
+    pub fn baseline_mean(&self) -> f32 {
+        self.baseline.iter().sum::<f32>() / self.baseline.len() as f32
+    }
+
+    // positive when the GP does worse without the terminal
+    pub fn degradation(&self) -> f32 {
+        self.result.mean_fitness() - self.baseline_mean()
+    }
+}
+
+impl Display for AblationResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): fitness {} ± {} over {} runs, degradation {:+} ({:+.1}%)",
+            self.terminal,
+            name(&self.terminal).unwrap_or("?"),
+            self.result.mean_fitness(),
+            self.result.std_fitness(),
+            self.result.runs.len(),
+            self.degradation(),
+            self.degradation() / self.baseline_mean().abs() * 100.0
+        )?;
+        // the effect is positive when the runs with all terminals do better
+        match mann_whitney(&self.baseline, &self.result.fitness()) {
+            Some(test) => write!(f, ", {test}"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn ablated_terminals() {
+    use crate::sim::problem::Ablation;
+
+    let terminals = terminals();
+    assert_eq!(
+        terminals.len(),
+        ROUTING_NAMES.len() + SEQUENCING_NAMES.len()
+    );
+    assert!(terminals.iter().all(|terminal| name(terminal).is_some()));
+    assert_eq!(name("sequencing:3"), Some("DEM"));
+    assert_eq!(name("routing:18"), None);
+    let ablation = Ablation::parse("routing:3,sequencing:0,routing:17").unwrap();
+    assert_eq!(
+        (ablation.routing, ablation.sequencing),
+        (1 << 3 | 1 << 17, 1)
+    );
+    assert!(Ablation::parse("routing:32").is_none());
+    let mut values = [1.0; 4];
+    Ablation::apply(ablation.routing, &mut values);
+    assert_eq!(values, [1.0, 1.0, 1.0, 0.0]);
+}
@@ -0,0 +1,203 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    fs,
+};
+
+use anyhow::{ensure, Context};
+use serde::Serialize;
+use serde_json::Value;
+
+// one run directory: the settings its MAIN log started with and the last
+// `full_result` of its GP log, from whichever of its files hold them
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Run {
+    pub name: String,
+    pub manifest: BTreeMap<String, Value>,
+    pub result: BTreeMap<String, Value>,
+    // the settings that differ from those of most runs
+    pub mismatched: Vec<String>,
+}
+
+impl Run {
+    // lines that are not log lines are skipped
+    pub fn parse(name: &str, lines: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let mut run = Self {
+            name: name.to_string(),
+            ..Self::default()
+        };
+        for line in lines {
+            let Ok(Value::Object(mut line)) = serde_json::from_str(line.as_ref()) else {
+                continue;
+            };
+            let (Some(Value::String(logger)), Some(Value::String(message))) =
+                (line.remove("__"), line.remove("_"))
+            else {
+                continue;
+            };
+            match (logger.as_str(), message.as_str()) {
+                ("MAIN", "start" | "fleet") => {
+                    for (_, value) in line.into_iter().filter(|(key, _)| key != "_t") {
+                        if let Value::Object(settings) = value {
+                            run.manifest.extend(settings);
+                        }
+                    }
+                }
+                ("MAIN", "instance") => {
+                    run.manifest
+                        .extend(line.into_iter().filter(|(key, _)| key != "_t"));
+                }
+                ("GP", "full_result") => {
+                    run.result.clear();
+                    for (key, value) in line.into_iter().filter(|(key, _)| key != "_t") {
+                        match (key.as_str(), value) {
+                            ("result", Value::Array(result)) if result.len() == 2 => {
+                                run.result.insert("distance".to_string(), result[0].clone());
+                                run.result.insert("failed".to_string(), result[1].clone());
+                            }
+                            (_, value) => {
+                                run.result.insert(key, value);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        run
+    }
+}
+
+// every subdirectory of `dir` is a run, read from all the files in it
+pub fn load(dir: &str) -> anyhow::Result<Vec<Run>> {
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("cannot read {dir}"))? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let mut lines = Vec::new();
+        for file in fs::read_dir(entry.path())? {
+            let file = file?;
+            if file.file_type()?.is_file() {
+                let content = fs::read(file.path())?;
+                lines.extend(
+                    String::from_utf8_lossy(&content)
+                        .lines()
+                        .map(str::to_string),
+                );
+            }
+        }
+        runs.push(Run::parse(&entry.file_name().to_string_lossy(), lines));
+    }
+    ensure!(!runs.is_empty(), "{dir} has no run directories");
+    runs.sort_by(|a, b| a.name.cmp(&b.name));
+    mark_mismatches(&mut runs);
+    Ok(runs)
+}
+
+// flags the settings of each run that differ from the most common value
+// among the runs with a manifest, a missing setting counting as a value
+pub fn mark_mismatches(runs: &mut [Run]) {
+    let keys = keys(runs.iter().map(|run| &run.manifest));
+    for key in keys {
+        let value = |run: &Run| run.manifest.get(&key).cloned().unwrap_or(Value::Null);
+        let mut counts = Vec::<(Value, usize)>::new();
+        for run in runs.iter().filter(|run| !run.manifest.is_empty()) {
+            let value = value(run);
+            match counts.iter_mut().find(|(other, _)| *other == value) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+        // ties go to the value seen first
+        let Some((common, _)) = counts.into_iter().rev().max_by_key(|(_, count)| *count) else {
+            continue;
+        };
+        for run in runs.iter_mut().filter(|run| !run.manifest.is_empty()) {
+            if value(run) != common {
+                run.mismatched.push(key.clone());
+            }
+        }
+    }
+}
+
+fn keys<'a>(maps: impl Iterator<Item = &'a BTreeMap<String, Value>>) -> BTreeSet<String> {
+    maps.flat_map(|map| map.keys().cloned()).collect()
+}
+
+// strings unquoted, fields with commas or quotes quoted
+fn field(value: Option<&Value>) -> String {
+    let value = match value {
+        Some(Value::String(value)) => value.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(value) => value.to_string(),
+    };
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+// the runs as CSV, one row per run with the union of their settings and
+// results as columns
+pub struct Table<'a>(pub &'a [Run]);
+
+impl Display for Table<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let settings = keys(self.0.iter().map(|run| &run.manifest));
+        let results = keys(self.0.iter().map(|run| &run.result));
+        let header = settings.iter().chain(&results).map(String::as_str);
+        write!(f, "run,{},mismatched", header.collect::<Vec<_>>().join(","))?;
+        for run in self.0 {
+            let settings = settings.iter().map(|key| field(run.manifest.get(key)));
+            let results = results.iter().map(|key| field(run.result.get(key)));
+            write!(
+                f,
+                "\n{},{},{}",
+                field(Some(&Value::String(run.name.clone()))),
+                settings.chain(results).collect::<Vec<_>>().join(","),
+                run.mismatched.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn aggregated_runs() {
+    let run = |name: &str, pop_size: usize, fitness: f32| {
+        let lines = [
+            format!(
+                r#"{{"__":"MAIN","_t":1,"_":"start","config":{{"pop_size":{pop_size},"num_gen":50}}}}"#
+            ),
+            r#"{"__":"MAIN","_t":2,"_":"instance","path":"h100,c102.csv"}"#.to_string(),
+            "not a log line".to_string(),
+            r#"{"__":"GP","_t":3,"_":"full_result","result":[1500.0,3],"fitness":0.5}"#.to_string(),
+            format!(
+                r#"{{"__":"GP","_t":4,"_":"full_result","result":[1400.0,2],"fitness":{fitness}}}"#
+            ),
+        ];
+        Run::parse(name, lines)
+    };
+    let mut runs = [run("a", 100, 0.25), run("b", 100, 0.5), run("c", 50, 0.75)];
+    runs[1].manifest.remove("num_gen");
+    mark_mismatches(&mut runs);
+    assert_eq!(runs[0].manifest.len(), 3);
+    assert_eq!(runs[0].result["distance"], 1400.0);
+    assert_eq!(runs[0].result["fitness"], 0.25);
+    assert!(runs[0].mismatched.is_empty());
+    assert_eq!(runs[1].mismatched, ["num_gen"]);
+    assert_eq!(runs[2].mismatched, ["pop_size"]);
+    let table = Table(&runs).to_string();
+    let mut lines = table.lines();
+    assert_eq!(
+        lines.next(),
+        Some("run,num_gen,path,pop_size,distance,failed,fitness,mismatched")
+    );
+    assert_eq!(
+        lines.nth(1),
+        Some(r#"b,,"h100,c102.csv",100,1400.0,2,0.5,num_gen"#)
+    );
+}
@@ -0,0 +1,113 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{BufReader, BufWriter},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+// throughput of one benchmark, in units of work such as program evaluations
+// or simulations
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Measurement {
+    pub name: String,
+    pub count: usize,
+    pub seconds: f64,
+}
+
+impl Measurement {
+    pub fn per_second(&self) -> f64 {
+        self.count as f64 / self.seconds
+    }
+}
+
+// repeats `f`, which returns the units of work it did, for at least `seconds`
+// after a warm-up call
+pub fn measure(name: &str, seconds: f64, mut f: impl FnMut() -> usize) -> Measurement {
+    f();
+    let start = Instant::now();
+    let mut count = 0;
+    while count == 0 || start.elapsed().as_secs_f64() < seconds {
+        count += f();
+    }
+    Measurement {
+        name: name.to_string(),
+        count,
+        seconds: start.elapsed().as_secs_f64(),
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub measurements: Vec<Measurement>,
+}
+
+impl Report {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    // benchmarks of `baseline` that lost more than `tolerance` of their
+    // throughput, with the share of it left; those missing from either
+    // report are not compared
+    pub fn regressions(&self, baseline: &Self, tolerance: f64) -> Vec<(String, f64)> {
+        self.measurements
+            .iter()
+            .filter_map(|measurement| {
+                let before = baseline
+                    .measurements
+                    .iter()
+                    .find(|before| before.name == measurement.name)?;
+                let ratio = measurement.per_second() / before.per_second();
+                (ratio < 1.0 - tolerance).then(|| (measurement.name.clone(), ratio))
+            })
+            .collect()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for measurement in &self.measurements {
+            writeln!(
+                f,
+                "{}: {:.1} per second ({} in {:.2}s)",
+                measurement.name,
+                measurement.per_second(),
+                measurement.count,
+                measurement.seconds
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn bench_regressions() {
+    let report = |simulations, evaluations| Report {
+        measurements: vec![
+            Measurement {
+                name: "simulation".to_string(),
+                count: simulations,
+                seconds: 2.0,
+            },
+            Measurement {
+                name: "generation".to_string(),
+                count: evaluations,
+                seconds: 2.0,
+            },
+        ],
+    };
+    let baseline = report(100, 1000);
+    assert!(report(95, 1200).regressions(&baseline, 0.1).is_empty());
+    let regressions = report(80, 1000).regressions(&baseline, 0.1);
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].0, "simulation");
+    assert!((regressions[0].1 - 0.8).abs() < 1e-9);
+    assert!(measure("noop", 0.0, || 1).count >= 1);
+}
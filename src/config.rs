@@ -0,0 +1,487 @@
+use std::{env, str::FromStr, sync::Arc};
+
+use anyhow::Context;
+
+use crate::{
+    gp::{grammar::Grammar, Control, FunctionSet, GPParams, Representation},
+    objective::{self, Objective, WeightedSum},
+    sim::{
+        ctx::{RoutingContext, SequencingContext},
+        distance::{CoordinateSystem, Matrix},
+        ensemble::{Ensemble, Vote},
+        function_set,
+        insertion::Insertion,
+        local_search::Memetic,
+        problem::{Ablation, EpochPolicy, Problem, TerminalNoise, TieBreak, TimeWindowMode},
+        scenario::{Aggregation, Noise},
+        training::{self, Perturb, Stress, TrainingTransform, Warp},
+        Float,
+    },
+};
+
+// `name` parsed, none when unset or empty
+fn var<T: FromStr>(name: &str) -> anyhow::Result<Option<T>> {
+    parsed(name, |s| s.parse().ok())
+}
+
+// `name` parsed, an error unless `valid`
+fn checked<T: FromStr>(name: &str, valid: impl FnOnce(&T) -> bool) -> anyhow::Result<Option<T>> {
+    parsed(name, |s| s.parse().ok().filter(valid))
+}
+
+// `name` read by `parse`, none when unset or empty; a value it rejects is an
+// error rather than the default, so that a typo does not go unnoticed
+fn parsed<T>(name: &str, parse: impl FnOnce(&str) -> Option<T>) -> anyhow::Result<Option<T>> {
+    match env::var(name) {
+        Ok(value) if !value.is_empty() => parse(&value)
+            .map(Some)
+            .with_context(|| format!("{name}: invalid value {value:?}")),
+        _ => Ok(None),
+    }
+}
+
+// the settings of a run, each read from the environment variable of its name
+// in capitals once at startup
+pub struct Config {
+    pub const_rate: f64,
+    pub weight: f32,
+    pub num_time_slot: Float,
+    pub num_gen: usize,
+    pub pop_size: usize,
+    pub max_depth: usize,
+    pub crossover_rate: f64,
+    pub mutation_rate: f64,
+    pub train_factor: Float,
+    pub num_depots: usize,
+    pub num_days: usize,
+    pub day_length: Option<Float>,
+    pub rollover_penalty: f32,
+    pub num_satellites: usize,
+    pub satellite_capacity: Option<Float>,
+    pub num_line_haul: usize,
+    pub line_haul_capacity: Option<Float>,
+    pub num_drones: Option<usize>,
+    pub drone_speed: Option<Float>,
+    pub drone_capacity: Option<Float>,
+    pub drone_range: Option<Float>,
+    pub coordinates: Option<CoordinateSystem>,
+    pub distance_matrix: Option<String>,
+    pub time_matrix: Option<String>,
+    pub travel_noise: Noise,
+    pub service_noise: Noise,
+    pub cancel_rate: Float,
+    pub no_show_rate: Float,
+    pub lateness_penalty: Float,
+    pub max_lateness: Float,
+    pub time_windows: TimeWindowMode,
+    pub max_route_duration: Float,
+    pub max_travel_distance: Float,
+    pub break_after: Float,
+    pub break_duration: Float,
+    pub shift_end: Float,
+    pub open_routes: bool,
+    pub wait_weight: f32,
+    pub tardiness_weight: f32,
+    pub objective: Box<dyn Objective>,
+    pub cost_per_distance: Float,
+    pub max_queue_len: usize,
+    pub reload_time: Option<Float>,
+    pub max_trips: usize,
+    pub num_docks: Option<usize>,
+    pub reassign_interval: Float,
+    pub pool_unassigned: bool,
+    pub lookahead_k: usize,
+    pub lookahead_horizon: Float,
+    pub defer: bool,
+    pub num_scenarios: usize,
+    pub scenario_seed: u64,
+    pub scenario_agg: Aggregation,
+    pub stress_factor: Float,
+    pub truck_speed: Option<Float>,
+    pub truck_capacity: Option<Float>,
+    pub training: Vec<Box<dyn TrainingTransform>>,
+    pub num_trucks: Option<usize>,
+    pub neighbor_radius: Float,
+    pub rolling_window: Float,
+    pub exact_queue: usize,
+    pub sequencing_search: usize,
+    // `STATIC`, every request known from the start
+    pub reveal: bool,
+    pub terminal_noise: TerminalNoise,
+    pub ensemble: Option<(Vote, usize)>,
+    pub heuristics: Vec<String>,
+    pub segments: usize,
+    pub imitation: Option<String>,
+    pub imitation_pool: usize,
+    pub insertion: Option<Insertion>,
+    pub memetic: Option<Memetic>,
+    pub tie_break: TieBreak,
+    pub ablate: Ablation,
+    // `CONTINUOUS_ARRIVALS=true` is the older name of `EPOCHS=immediate`
+    pub continuous_arrivals: bool,
+    pub epochs: EpochPolicy,
+    pub penalize_non_finite: bool,
+    pub fitness_sharing: Option<f32>,
+    pub max_seconds: Option<f64>,
+    pub max_evaluations: Option<usize>,
+    pub checkpoint: String,
+    pub num_best_saved: usize,
+    pub warm_start: Option<String>,
+    pub warm_start_share: f64,
+    pub seed_baseline: bool,
+    pub sweep_runs: usize,
+    pub sweep_samples: Option<usize>,
+    pub sweep_seed: u64,
+    pub race_stages: usize,
+    pub race_min_stages: usize,
+    pub race_alpha: f32,
+    pub island_coordinator: Option<String>,
+    pub migration_interval: usize,
+    pub num_migrants: usize,
+    pub bench_seconds: f64,
+    pub bench_baseline: Option<String>,
+    pub bench_save: Option<String>,
+    pub bench_tolerance: f64,
+    pub incremental: bool,
+    pub incremental_checkpoints: usize,
+    pub explain_samples: usize,
+    // window scale factors of a tightness experiment
+    pub tightness_factors: Vec<Float>,
+    // the noise of the copies of a robustness report, and their number
+    pub perturb: Perturb,
+    pub robustness_copies: usize,
+    pub sensitivity_points: usize,
+    pub aggregate_format: String,
+    pub dashboard_address: String,
+    pub phenotype_situations: usize,
+    pub tui: bool,
+    pub deduplicate: bool,
+    pub self_adaptive: Option<f64>,
+    pub modules: Option<usize>,
+    pub module_interval: usize,
+    pub module_rate: f64,
+    pub fold: bool,
+    pub control: Option<Control>,
+    pub crowding: bool,
+    pub profile: bool,
+    pub satellite_vehicle_speed: Option<Float>,
+    pub satellite_vehicle_capacity: Option<Float>,
+    pub representation: Representation,
+    pub linear_length: usize,
+    pub grammar_length: usize,
+    pub pareto: Option<String>,
+    // `FUNCTION_SET`
+    pub function_set: FunctionSet,
+    // `GRAMMAR`, read and checked with `REPRESENTATION=grammatical` only
+    pub grammar: Option<Grammar>,
+}
+
+// `GRAMMAR`, or every node of the function set for either rule, checked to
+// derive valid rules within `max_depth`
+fn grammar(max_depth: usize, function_set: &FunctionSet) -> anyhow::Result<Grammar> {
+    let grammar = match env::var("GRAMMAR") {
+        Ok(path) => Grammar::load(&path).with_context(|| format!("grammar {path}"))?,
+        Err(_) => Grammar::parse(
+            &[
+                Grammar::expressions::<RoutingContext>("routing", function_set),
+                Grammar::expressions::<SequencingContext>("sequencing", function_set),
+            ]
+            .concat(),
+        )?,
+    };
+    grammar.check::<RoutingContext>("routing", max_depth)?;
+    grammar.check::<SequencingContext>("sequencing", max_depth)?;
+    Ok(grammar)
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let weight = var("WEIGHT")?.unwrap_or(0.1);
+        let rollover_penalty = var("ROLLOVER_PENALTY")?.unwrap_or(0.5);
+        let wait_weight = var("WAIT_WEIGHT")?.unwrap_or(0.0);
+        let tardiness_weight = var("TARDINESS_WEIGHT")?.unwrap_or(0.0);
+        let lateness_penalty = var("LATENESS_PENALTY")?.unwrap_or(1.0);
+        let max_lateness = var("MAX_LATENESS")?.unwrap_or(Float::INFINITY);
+        let train_factor = var("TRAIN_FACTOR")?.unwrap_or(0.2);
+        let stress_factor = var("STRESS_FACTOR")?.unwrap_or(1.0);
+        let continuous_arrivals = var("CONTINUOUS_ARRIVALS")?.unwrap_or(false);
+        let max_depth = var("MAX_DEPTH")?.unwrap_or(6);
+        let representation = parsed("REPRESENTATION", Representation::parse)?.unwrap_or_default();
+        let function_set = match env::var("FUNCTION_SET") {
+            Ok(path) => function_set::load(&path)?,
+            Err(_) => FunctionSet::default(),
+        };
+        Ok(Self {
+            const_rate: var("CONST_RATE")?.unwrap_or(0.1),
+            weight,
+            num_time_slot: var("NUM_TIME_SLOT")?.unwrap_or(50.0),
+            num_gen: var("NUM_GEN")?.unwrap_or(100),
+            pop_size: var("POP_SIZE")?.unwrap_or(100),
+            max_depth,
+            crossover_rate: var("CROSSOVER_RATE")?.unwrap_or(0.8),
+            mutation_rate: var("MUTATION_RATE")?.unwrap_or(0.1),
+            train_factor,
+            num_depots: var("NUM_DEPOTS")?.unwrap_or(1),
+            num_days: var("NUM_DAYS")?.unwrap_or(1),
+            day_length: var("DAY_LENGTH")?,
+            rollover_penalty,
+            num_satellites: var("NUM_SATELLITES")?.unwrap_or(0),
+            satellite_capacity: var("SATELLITE_CAPACITY")?,
+            num_line_haul: var("NUM_LINE_HAUL")?.unwrap_or(2),
+            line_haul_capacity: var("LINE_HAUL_CAPACITY")?,
+            num_drones: var("NUM_DRONES")?,
+            drone_speed: var("DRONE_SPEED")?,
+            drone_capacity: var("DRONE_CAPACITY")?,
+            drone_range: var("DRONE_RANGE")?,
+            coordinates: parsed("COORDINATES", CoordinateSystem::parse)?,
+            distance_matrix: env::var("DISTANCE_MATRIX").ok(),
+            time_matrix: env::var("TIME_MATRIX").ok(),
+            travel_noise: parsed("TRAVEL_NOISE", Noise::parse)?.unwrap_or_default(),
+            service_noise: parsed("SERVICE_NOISE", Noise::parse)?.unwrap_or_default(),
+            cancel_rate: var("CANCEL_RATE")?.unwrap_or(0.0),
+            no_show_rate: var("NO_SHOW_RATE")?.unwrap_or(0.0),
+            lateness_penalty,
+            max_lateness,
+            time_windows: parsed("TIME_WINDOWS", |s| {
+                TimeWindowMode::parse(s, lateness_penalty, max_lateness)
+            })?
+            .unwrap_or(TimeWindowMode::Hard),
+            max_route_duration: var("MAX_ROUTE_DURATION")?.unwrap_or(Float::INFINITY),
+            max_travel_distance: var("MAX_TRAVEL_DISTANCE")?.unwrap_or(Float::INFINITY),
+            break_after: var("BREAK_AFTER")?.unwrap_or(Float::INFINITY),
+            break_duration: var("BREAK_DURATION")?.unwrap_or(30.0),
+            shift_end: var("SHIFT_END")?.unwrap_or(Float::INFINITY),
+            open_routes: var("OPEN_ROUTES")?.unwrap_or(false),
+            wait_weight,
+            tardiness_weight,
+            objective: {
+                let weighted = || WeightedSum {
+                    weight,
+                    rollover_penalty,
+                    wait_weight,
+                    tardiness_weight,
+                };
+                parsed("FITNESS", |s| objective::parse(s, weighted()))?
+                    .unwrap_or_else(|| Box::new(weighted()))
+            },
+            cost_per_distance: var("COST_PER_DISTANCE")?.unwrap_or(1.0),
+            max_queue_len: var("MAX_QUEUE_LEN")?.unwrap_or(usize::MAX),
+            reload_time: var("RELOAD_TIME")?,
+            max_trips: var("MAX_TRIPS")?.unwrap_or(usize::MAX),
+            num_docks: var("NUM_DOCKS")?,
+            reassign_interval: var("REASSIGN_INTERVAL")?.unwrap_or(Float::INFINITY),
+            pool_unassigned: var("POOL_UNASSIGNED")?.unwrap_or(false),
+            lookahead_k: var("LOOKAHEAD_K")?.unwrap_or(1),
+            lookahead_horizon: var("LOOKAHEAD_HORIZON")?.unwrap_or(Float::INFINITY),
+            defer: var("DEFER")?.unwrap_or(false),
+            num_scenarios: var("NUM_SCENARIOS")?.unwrap_or(1),
+            scenario_seed: var("SCENARIO_SEED")?.unwrap_or(0),
+            scenario_agg: parsed("SCENARIO_AGG", Aggregation::parse)?.unwrap_or(Aggregation::Mean),
+            stress_factor,
+            truck_speed: var("TRUCK_SPEED")?,
+            truck_capacity: var("TRUCK_CAPACITY")?,
+            training: parsed("TRAINING", training::parse)?.unwrap_or_else(|| {
+                vec![
+                    Box::new(Warp(train_factor)),
+                    Box::new(Stress(stress_factor)),
+                ]
+            }),
+            num_trucks: var("NUM_TRUCKS")?,
+            neighbor_radius: var("NEIGHBOR_RADIUS")?.unwrap_or(10.0),
+            rolling_window: var("ROLLING_WINDOW")?.unwrap_or(0.0),
+            exact_queue: var("EXACT_QUEUE")?.unwrap_or(0),
+            sequencing_search: var("SEQUENCING_SEARCH")?.unwrap_or(0),
+            reveal: var("STATIC")?.unwrap_or(false),
+            terminal_noise: parsed("TERMINAL_NOISE", TerminalNoise::parse)?.unwrap_or_default(),
+            ensemble: parsed("ENSEMBLE", Ensemble::parse)?,
+            heuristics: env::var("HEURISTICS")
+                .map(|s| s.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            segments: var("SEGMENTS")?.unwrap_or(1),
+            imitation: env::var("IMITATION").ok(),
+            imitation_pool: var("IMITATION_POOL")?.unwrap_or(4),
+            insertion: parsed("INSERTION", Insertion::parse)?,
+            memetic: parsed("MEMETIC", Memetic::parse)?,
+            tie_break: parsed("TIE_BREAK", TieBreak::parse)?.unwrap_or(TieBreak::Index),
+            ablate: parsed("ABLATE", Ablation::parse)?.unwrap_or_default(),
+            continuous_arrivals,
+            epochs: parsed("EPOCHS", EpochPolicy::parse)?.unwrap_or(match continuous_arrivals {
+                true => EpochPolicy::Immediate,
+                false => EpochPolicy::Slot,
+            }),
+            penalize_non_finite: var("PENALIZE_NON_FINITE")?.unwrap_or(false),
+            fitness_sharing: var("FITNESS_SHARING")?.filter(|radius| *radius > 0.0),
+            max_seconds: var("MAX_SECONDS")?,
+            max_evaluations: var("MAX_EVALUATIONS")?,
+            checkpoint: env::var("CHECKPOINT").unwrap_or_else(|_| "checkpoint.json".to_string()),
+            num_best_saved: var("NUM_BEST_SAVED")?.unwrap_or(5),
+            warm_start: env::var("WARM_START").ok(),
+            warm_start_share: var("WARM_START_SHARE")?.unwrap_or(0.5),
+            seed_baseline: var("SEED_BASELINE")?.unwrap_or(false),
+            sweep_runs: var("SWEEP_RUNS")?.unwrap_or(1),
+            sweep_samples: var("SWEEP_SAMPLES")?,
+            sweep_seed: var("SWEEP_SEED")?.unwrap_or(0),
+            race_stages: var("RACE_STAGES")?.unwrap_or(20),
+            race_min_stages: var("RACE_MIN_STAGES")?.unwrap_or(5),
+            race_alpha: var("RACE_ALPHA")?.unwrap_or(0.05),
+            island_coordinator: env::var("ISLAND_COORDINATOR").ok(),
+            migration_interval: checked("MIGRATION_INTERVAL", |interval| *interval > 0)?
+                .unwrap_or(10),
+            num_migrants: var("NUM_MIGRANTS")?.unwrap_or(5),
+            bench_seconds: var("BENCH_SECONDS")?.unwrap_or(2.0),
+            bench_baseline: env::var("BENCH_BASELINE").ok(),
+            bench_save: env::var("BENCH_SAVE").ok(),
+            bench_tolerance: var("BENCH_TOLERANCE")?.unwrap_or(0.1),
+            incremental: var("INCREMENTAL")?.unwrap_or(false),
+            incremental_checkpoints: var("INCREMENTAL_CHECKPOINTS")?.unwrap_or(10),
+            explain_samples: var("EXPLAIN_SAMPLES")?.unwrap_or(20),
+            tightness_factors: parsed("TIGHTNESS_FACTORS", |s| {
+                (s.split(','))
+                    .map(|factor| factor.trim().parse().ok().filter(|f: &Float| *f > 0.0))
+                    .collect()
+            })
+            .context("TIGHTNESS_FACTORS must be positive numbers")?
+            .unwrap_or_else(|| vec![0.5, 0.75, 1.0, 1.5, 2.0]),
+            perturb: parsed("PERTURB", Perturb::parse)
+                .context("PERTURB must be xy:s, demand:s and time:s with s >= 0")?
+                .unwrap_or(Perturb {
+                    xy: 1.0,
+                    demand: 0.1,
+                    time: 10.0,
+                    seed: 0,
+                }),
+            robustness_copies: var("ROBUSTNESS_COPIES")?.unwrap_or(20),
+            sensitivity_points: var("SENSITIVITY_POINTS")?.unwrap_or(21),
+            aggregate_format: env::var("AGGREGATE_FORMAT").unwrap_or_else(|_| "csv".to_string()),
+            dashboard_address: env::var("DASHBOARD_ADDRESS")
+                .unwrap_or_else(|_| "127.0.0.1:8080".to_string()),
+            phenotype_situations: var("PHENOTYPE_SITUATIONS")?.unwrap_or(20),
+            tui: var("TUI")?.unwrap_or(false),
+            deduplicate: var("DEDUPLICATE")?.unwrap_or(false),
+            self_adaptive: var("SELF_ADAPTIVE")?.filter(|tau| *tau > 0.0),
+            modules: var("MODULES")?.filter(|k| *k > 0),
+            module_interval: checked("MODULE_INTERVAL", |interval| *interval > 0)?.unwrap_or(5),
+            module_rate: checked("MODULE_RATE", |rate| (0.0..=1.0).contains(rate))?.unwrap_or(0.5),
+            fold: var("FOLD")?.unwrap_or(false),
+            control: parsed("CONTROL", Control::parse)?,
+            crowding: var("CROWDING")?.unwrap_or(false),
+            profile: var("PROFILE")?.unwrap_or(false),
+            satellite_vehicle_speed: var("SATELLITE_VEHICLE_SPEED")?,
+            satellite_vehicle_capacity: var("SATELLITE_VEHICLE_CAPACITY")?,
+            representation,
+            linear_length: checked("LINEAR_LENGTH", |length| *length > 0)?.unwrap_or(32),
+            grammar_length: checked("GRAMMAR_LENGTH", |length| *length > 0)?.unwrap_or(128),
+            pareto: env::var("PARETO").ok(),
+            grammar: match representation {
+                Representation::Grammatical => Some(grammar(max_depth, &function_set)?),
+                _ => None,
+            },
+            function_set,
+        })
+    }
+
+    // the settings of the instance and simulation over those of `problem`
+    pub fn configure(&self, problem: &mut Problem) -> anyhow::Result<()> {
+        problem.num_days = self.num_days;
+        if let Some(length) = self.day_length {
+            problem.day_length = length;
+        }
+        problem.num_line_haul = self.num_line_haul;
+        if let Some(capacity) = self.satellite_capacity {
+            problem.satellite_capacity = capacity;
+        }
+        if let Some(capacity) = self.line_haul_capacity {
+            problem.line_haul_capacity = capacity;
+        }
+        if let Some(speed) = self.satellite_vehicle_speed {
+            problem.satellite_vehicle_speed = speed;
+        }
+        if let Some(capacity) = self.satellite_vehicle_capacity {
+            problem.satellite_vehicle_capacity = capacity;
+        }
+        if let Some(num_drones) = self.num_drones {
+            problem.num_drones = num_drones;
+        }
+        if let Some(speed) = self.drone_speed {
+            problem.drone_speed = speed;
+        }
+        if let Some(capacity) = self.drone_capacity {
+            problem.drone_capacity = capacity;
+        }
+        if let Some(range) = self.drone_range {
+            problem.drone_range = range;
+        }
+        if let Some(coordinates) = self.coordinates {
+            problem.coordinates = coordinates;
+        }
+        problem.cancel_rate = self.cancel_rate;
+        problem.no_show_rate = self.no_show_rate;
+        problem.time_windows = self.time_windows;
+        problem.max_route_duration = self.max_route_duration;
+        problem.max_travel_distance = self.max_travel_distance;
+        problem.break_after = self.break_after;
+        problem.break_duration = self.break_duration;
+        problem.shift_end = self.shift_end;
+        problem.open_routes = self.open_routes;
+        problem.cost_per_distance = self.cost_per_distance;
+        problem.max_queue_len = self.max_queue_len;
+        if let Some(reload_time) = self.reload_time {
+            problem.reload_time = reload_time;
+        }
+        problem.max_trips = self.max_trips;
+        if let Some(num_docks) = self.num_docks {
+            problem.num_docks = num_docks;
+        }
+        // no docks would leave every reload waiting forever
+        anyhow::ensure!(problem.num_docks >= 1, "NUM_DOCKS must be at least 1");
+        problem.reassign_interval = self.reassign_interval;
+        problem.pool_unassigned = self.pool_unassigned;
+        problem.lookahead_k = self.lookahead_k;
+        problem.lookahead_horizon = self.lookahead_horizon;
+        problem.defer = self.defer;
+        problem.epochs = self.epochs;
+        problem.tie_break = self.tie_break;
+        problem.ablation = self.ablate;
+        problem.neighbor_radius = self.neighbor_radius;
+        problem.rolling_window = self.rolling_window;
+        problem.exact_queue = self.exact_queue;
+        problem.sequencing_search = self.sequencing_search;
+        problem.insertion = self.insertion;
+        problem.profile = self.profile;
+        problem.distances = problem.coordinates.provider();
+        if let Some(distances) = self.distance_matrix.as_deref() {
+            let matrix = Matrix::load(distances, self.time_matrix.as_deref(), problem.num_nodes())?;
+            problem.distances = Arc::new(matrix);
+        }
+        if self.reveal {
+            *problem = problem.reveal();
+        }
+        Ok(())
+    }
+
+    pub fn gp_params(&self) -> GPParams {
+        GPParams {
+            num_population: self.pop_size,
+            max_depth: self.max_depth,
+            const_rate: self.const_rate,
+            crossover_rate: self.crossover_rate,
+            mutation_rate: self.mutation_rate,
+            function_set: self.function_set.clone(),
+        }
+    }
+}
+
+#[test]
+fn invalid_settings() {
+    env::set_var("VRPR_TEST_SETTING", "12");
+    assert_eq!(var::<usize>("VRPR_TEST_SETTING").unwrap(), Some(12));
+    assert!(checked::<usize>("VRPR_TEST_SETTING", |n| *n < 10).is_err());
+    env::set_var("VRPR_TEST_SETTING", "twelve");
+    let error = var::<usize>("VRPR_TEST_SETTING").unwrap_err();
+    assert!(error.to_string().contains("VRPR_TEST_SETTING"));
+    assert!(parsed("VRPR_TEST_SETTING", TieBreak::parse).is_err());
+    env::set_var("VRPR_TEST_SETTING", "");
+    assert_eq!(var::<usize>("VRPR_TEST_SETTING").unwrap(), None);
+    env::remove_var("VRPR_TEST_SETTING");
+    assert_eq!(var::<usize>("VRPR_TEST_SETTING").unwrap(), None);
+}
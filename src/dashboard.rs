@@ -0,0 +1,203 @@
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Style},
+    symbols::Marker,
+    text::Line,
+    widgets::{Axis, Block, Chart, Dataset, GraphType, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use crate::progress::Generation;
+
+// live fitness curves and statistics of a GP run, redrawn every generation
+pub struct Dashboard {
+    terminal: DefaultTerminal,
+    num_gen: usize,
+    start: Instant,
+    best: Vec<(f64, f64)>,
+    mean: Vec<(f64, f64)>,
+    last: Option<Generation>,
+    interrupted: &'static AtomicBool,
+}
+
+impl Dashboard {
+    // raw mode swallows Ctrl-C, so it and `q` set `interrupted` like the
+    // signal would
+    pub fn new(num_gen: usize, interrupted: &'static AtomicBool) -> io::Result<Self> {
+        let terminal = ratatui::try_init()?;
+        thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                if let Event::Key(key) = event {
+                    let ctrl_c = key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL);
+                    if key.kind == KeyEventKind::Press && (key.code == KeyCode::Char('q') || ctrl_c)
+                    {
+                        interrupted.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        });
+        let mut dashboard = Self {
+            terminal,
+            num_gen,
+            start: Instant::now(),
+            best: Vec::new(),
+            mean: Vec::new(),
+            last: None,
+            interrupted,
+        };
+        dashboard.draw(false)?;
+        Ok(dashboard)
+    }
+
+    pub fn update(&mut self, generation: Generation) -> io::Result<()> {
+        let gen = generation.gen as f64;
+        self.best.push((gen, generation.best as f64));
+        if generation.mean.is_finite() {
+            self.mean.push((gen, generation.mean as f64));
+        }
+        self.last = Some(generation);
+        self.draw(false)
+    }
+
+    // keeps the last generation on screen until a key stops the run
+    pub fn wait(&mut self) -> io::Result<()> {
+        self.draw(true)?;
+        while !self.interrupted.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, finished: bool) -> io::Result<()> {
+        let Self {
+            num_gen,
+            start,
+            best,
+            mean,
+            last,
+            ..
+        } = self;
+        let title = match (finished, &last) {
+            (true, _) => "finished, q to quit".to_string(),
+            (false, Some(last)) => format!(
+                "generation {} of {num_gen}, {:.0} s, q to stop",
+                last.gen,
+                start.elapsed().as_secs_f64()
+            ),
+            (false, None) => "evaluating the first generation, q to stop".to_string(),
+        };
+        self.terminal
+            .draw(|frame| Self::render(frame, &title, *num_gen, best, mean, last.as_ref()))?;
+        Ok(())
+    }
+
+    fn render(
+        frame: &mut Frame,
+        title: &str,
+        num_gen: usize,
+        best: &[(f64, f64)],
+        mean: &[(f64, f64)],
+        last: Option<&Generation>,
+    ) {
+        let [chart, stats, rules] = Layout::vertical([
+            Constraint::Percentage(50),
+            Constraint::Length(6),
+            Constraint::Min(4),
+        ])
+        .areas(frame.area());
+
+        let (low, high) = best
+            .iter()
+            .chain(mean)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), (_, y)| {
+                (low.min(*y), high.max(*y))
+            });
+        let (low, high) = match (low.is_finite(), high > low) {
+            (true, true) => (low, high),
+            (true, false) => (low - 0.5, low + 0.5),
+            _ => (0.0, 1.0),
+        };
+        let label = |value: f64| format!("{value:.4}");
+        let datasets = vec![
+            Dataset::default()
+                .name("best")
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Green))
+                .data(best),
+            Dataset::default()
+                .name("mean")
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Yellow))
+                .data(mean),
+        ];
+        frame.render_widget(
+            Chart::new(datasets)
+                .block(Block::bordered().title(format!(" fitness, {title} ")))
+                .x_axis(
+                    Axis::default()
+                        .title("generation")
+                        .bounds([1.0, num_gen.max(2) as f64])
+                        .labels(["1".to_string(), num_gen.to_string()]),
+                )
+                .y_axis(Axis::default().bounds([low, high]).labels([
+                    label(low),
+                    label((low + high) / 2.0),
+                    label(high),
+                ])),
+            chart,
+        );
+
+        let Some(last) = last else {
+            return;
+        };
+        let lines = vec![
+            Line::from(format!(
+                "best fitness {:.6}, mean {:.6}, distance {:.2}, failed {}",
+                last.best, last.mean, last.distance, last.failed
+            )),
+            Line::from(format!(
+                "population {}, diversity {:.3}",
+                last.population, last.diversity
+            )),
+            Line::from(format!(
+                "nodes per individual {} to {}, mean {:.1}",
+                last.min_nodes, last.max_nodes, last.mean_nodes
+            )),
+            Line::from(format!(
+                "{:.0} evaluations per second",
+                last.evaluations_per_second
+            )),
+        ];
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" population ")),
+            stats,
+        );
+        let lines = vec![
+            Line::from(format!("routing: {}", last.routing)),
+            Line::from(format!("sequencing: {}", last.sequencing)),
+        ];
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: true })
+                .block(Block::bordered().title(" best rules ")),
+            rules,
+        );
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
@@ -0,0 +1,506 @@
+use std::{collections::HashMap, fs};
+
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, RngCore, SeedableRng};
+
+use super::{
+    program::{DecodeError, Program, ProgramContext},
+    BreedError, FunctionSet, GPContext,
+};
+
+// times the codons of a genome are read over before its mapping fails
+pub const MAX_WRAPS: usize = 2;
+
+// a symbol of a production: expression text, or a nonterminal at the
+// nesting of the parentheses around it
+#[derive(Debug)]
+enum Symbol {
+    Text(String),
+    NonTerminal { index: usize, nesting: usize },
+}
+
+#[derive(Debug)]
+struct Production {
+    symbols: Vec<Symbol>,
+    // the nesting of the deepest node of its own text
+    depth: usize,
+    // levels of the shallowest program it derives, `usize::MAX` for none
+    min_depth: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GrammarError {
+    #[error("line {0}: expected `<name> ::= alternative | ...`")]
+    Syntax(usize),
+    #[error("<{0}> is used but has no rule")]
+    Undefined(String),
+    #[error("<{0}> has {1} alternatives, a codon picks among at most 256")]
+    TooManyAlternatives(String, usize),
+    #[error("<{0}> derives no program")]
+    Infinite(String),
+    #[error("<{name}> derives no program of depth {max_depth} or less")]
+    TooDeep { name: String, max_depth: usize },
+    #[error("<{name}> derives {text:?}: {error}")]
+    Invalid {
+        name: String,
+        text: String,
+        error: DecodeError,
+    },
+}
+
+// a BNF grammar of rule expressions, which the codons of a genome choose the
+// alternatives of, leftmost nonterminal first; e.g.
+//     <routing> ::= sum(mul(<weight>, <expr>), mul(<weight>, <expr>))
+//     <expr> ::= <op>(<expr>, <expr>) | TERM0 | TERM3
+//     <op> ::= sum | sub | max
+//     <weight> ::= 0.25 | 0.5 | 1
+// nonterminals of a single alternative take no codon, and lines starting
+// with `|` continue the rule above
+#[derive(Debug)]
+pub struct Grammar {
+    names: Vec<String>,
+    rules: Vec<Vec<Production>>,
+}
+
+impl Grammar {
+    pub fn parse(bnf: &str) -> Result<Self, GrammarError> {
+        let mut indices = HashMap::new();
+        let mut intern = |name: &str, names: &mut Vec<String>| {
+            *indices.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                names.len() - 1
+            })
+        };
+        let mut names = Vec::new();
+        // the line, rule and text of every alternative
+        let mut alternatives = Vec::<(usize, usize, Vec<&str>)>::new();
+        for (line, text) in bnf.lines().enumerate() {
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(rest) = text.strip_prefix('|') {
+                let (_, _, rule) = alternatives
+                    .last_mut()
+                    .ok_or(GrammarError::Syntax(line + 1))?;
+                rule.extend(rest.split('|'));
+                continue;
+            }
+            let (name, rest) = text
+                .split_once("::=")
+                .ok_or(GrammarError::Syntax(line + 1))?;
+            let name = (name.trim().strip_prefix('<'))
+                .and_then(|name| name.strip_suffix('>'))
+                .ok_or(GrammarError::Syntax(line + 1))?;
+            alternatives.push((
+                line + 1,
+                intern(name, &mut names),
+                rest.split('|').collect(),
+            ));
+        }
+        let mut rules = Vec::new();
+        rules.resize_with(names.len(), Vec::new);
+        for (line, rule, texts) in alternatives {
+            for text in texts {
+                let mut production = Production {
+                    symbols: Vec::new(),
+                    depth: 0,
+                    min_depth: usize::MAX,
+                };
+                let (mut nesting, mut rest) = (0, text.trim());
+                while let Some(c) = rest.chars().next() {
+                    let len = match c {
+                        '<' => {
+                            let end = rest.find('>').ok_or(GrammarError::Syntax(line))?;
+                            let index = intern(&rest[1..end], &mut names);
+                            production
+                                .symbols
+                                .push(Symbol::NonTerminal { index, nesting });
+                            end + 1
+                        }
+                        _ => {
+                            match c {
+                                '(' => nesting += 1,
+                                ')' => nesting = nesting.saturating_sub(1),
+                                c if !c.is_whitespace() && c != ',' => {
+                                    production.depth = production.depth.max(nesting)
+                                }
+                                _ => (),
+                            }
+                            match production.symbols.last_mut() {
+                                Some(Symbol::Text(text)) => text.push(c),
+                                _ => production.symbols.push(Symbol::Text(c.to_string())),
+                            }
+                            c.len_utf8()
+                        }
+                    };
+                    rest = &rest[len..];
+                }
+                rules[rule].push(production);
+            }
+        }
+        rules.resize_with(names.len(), Vec::new);
+        let mut grammar = Self { names, rules };
+        for (name, rule) in grammar.names.iter().zip(&grammar.rules) {
+            match rule.len() {
+                0 => return Err(GrammarError::Undefined(name.clone())),
+                n if n > 256 => return Err(GrammarError::TooManyAlternatives(name.clone(), n)),
+                _ => (),
+            }
+        }
+        grammar.min_depths()?;
+        Ok(grammar)
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?)?)
+    }
+
+    // every expression of the internals and terminals of the function set,
+    // and the constants trees are grown with, as the rule `<start>`
+    pub fn expressions<C: ProgramContext>(start: &str, set: &FunctionSet) -> String {
+        let (internals, terminals) = (set.internals::<C>(), set.terminals::<C>());
+        let internals = internals.iter().map(|&internal| {
+            let operands = vec![format!("<{start}>"); C::internal_num_children(internal)];
+            format!(
+                "{}({})",
+                Program::<C>::internal_name(internal),
+                operands.join(", ")
+            )
+        });
+        let terminals = terminals.iter().map(|&t| Program::<C>::terminal_name(t));
+        let constants = (-4..=4).map(|c| c.to_string());
+        let alternatives = internals.chain(terminals).chain(constants);
+        format!(
+            "<{start}> ::= {}\n",
+            alternatives.collect::<Vec<_>>().join(" | ")
+        )
+    }
+
+    // the levels of the shallowest program of every alternative, to a
+    // fixed point
+    fn min_depths(&mut self) -> Result<(), GrammarError> {
+        loop {
+            let mut changed = false;
+            for rule in 0..self.rules.len() {
+                for production in 0..self.rules[rule].len() {
+                    let p = &self.rules[rule][production];
+                    let depth = p
+                        .symbols
+                        .iter()
+                        .fold(p.depth, |depth, symbol| match symbol {
+                            Symbol::NonTerminal { index, nesting } => {
+                                depth.max(nesting.saturating_add(self.min_depth(*index)))
+                            }
+                            Symbol::Text(_) => depth,
+                        });
+                    if depth < p.min_depth {
+                        self.rules[rule][production].min_depth = depth;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        match (0..self.rules.len()).find(|rule| self.min_depth(*rule) == usize::MAX) {
+            Some(rule) => Err(GrammarError::Infinite(self.names[rule].clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn min_depth(&self, rule: usize) -> usize {
+        self.rules[rule]
+            .iter()
+            .map(|p| p.min_depth)
+            .min()
+            .unwrap_or(usize::MAX)
+    }
+
+    pub fn start(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    // the expression `codons` map into from `rule` and the codons read, none
+    // when they run out after `MAX_WRAPS` wraps or the expression would be
+    // deeper than `max_depth`
+    pub fn derive(&self, rule: usize, codons: &[u8], max_depth: usize) -> Option<(String, usize)> {
+        let mut text = String::new();
+        let mut read = 0;
+        self.expand(rule, 0, codons, max_depth, &mut read, &mut text)?;
+        Some((text, read))
+    }
+
+    fn expand(
+        &self,
+        rule: usize,
+        nesting: usize,
+        codons: &[u8],
+        max_depth: usize,
+        read: &mut usize,
+        text: &mut String,
+    ) -> Option<()> {
+        let productions = &self.rules[rule];
+        let choice = match productions.len() {
+            1 => 0,
+            n => {
+                if *read >= codons.len() * (1 + MAX_WRAPS) {
+                    return None;
+                }
+                *read += 1;
+                usize::from(codons[(*read - 1) % codons.len()]) % n
+            }
+        };
+        let production = &productions[choice];
+        if nesting + production.min_depth > max_depth {
+            return None;
+        }
+        for symbol in &production.symbols {
+            match symbol {
+                Symbol::Text(t) => text.push_str(t),
+                Symbol::NonTerminal { index, nesting: n } => {
+                    self.expand(*index, nesting + n, codons, max_depth, read, text)?
+                }
+            }
+        }
+        Some(())
+    }
+
+    pub fn decode<C: ProgramContext>(
+        &self,
+        rule: usize,
+        codons: &[u8],
+        max_depth: usize,
+    ) -> Option<Program<C>> {
+        self.derive(rule, codons, max_depth)?.0.parse().ok()
+    }
+
+    // codons deriving a random expression from `rule` within `max_depth`
+    // levels, which has to be at least its shallowest: every nonterminal
+    // takes one of the alternatives that still fit
+    pub fn generate(&self, rule: usize, max_depth: usize, rng: &mut impl Rng) -> Vec<u8> {
+        let mut codons = Vec::new();
+        self.generate_into(rule, max_depth, rng, &mut codons);
+        codons
+    }
+
+    fn generate_into(&self, rule: usize, budget: usize, rng: &mut impl Rng, codons: &mut Vec<u8>) {
+        let productions = &self.rules[rule];
+        let fitting = (0..productions.len())
+            .filter(|p| productions[*p].min_depth <= budget)
+            .collect::<Vec<_>>();
+        let choice = *fitting.choose(rng).expect("a fitting alternative");
+        let n = productions.len();
+        if n > 1 {
+            // any codon picking it
+            let codon = choice + n * rng.gen_range(0..=(255 - choice) / n);
+            codons.push(codon as u8);
+        }
+        for symbol in &productions[choice].symbols {
+            if let Symbol::NonTerminal { index, nesting } = symbol {
+                self.generate_into(*index, budget - nesting, rng, codons);
+            }
+        }
+    }
+
+    // that `rule` derives programs of `C` within `max_depth` levels: some at
+    // all, and every one of a sample of random ones
+    pub fn check<C: ProgramContext>(
+        &self,
+        name: &str,
+        max_depth: usize,
+    ) -> Result<(), GrammarError> {
+        let rule = self
+            .start(name)
+            .ok_or_else(|| GrammarError::Undefined(name.to_string()))?;
+        if self.min_depth(rule) > max_depth {
+            return Err(GrammarError::TooDeep {
+                name: name.to_string(),
+                max_depth,
+            });
+        }
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let codons = self.generate(rule, max_depth, &mut rng);
+            let (text, _) = self
+                .derive(rule, &codons, max_depth)
+                .expect("generated to fit");
+            if let Err(error) = text.parse::<Program<C>>() {
+                return Err(GrammarError::Invalid {
+                    name: name.to_string(),
+                    text,
+                    error,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: RngCore> GPContext<R> {
+    // ramped from 1 to `max_depth` levels, or the shallowest `rule` derives
+    pub fn ramp_grammatical<C: ProgramContext>(
+        &self,
+        grammar: &Grammar,
+        rule: usize,
+    ) -> Vec<Vec<u8>> {
+        let max_depth = self.params.max_depth;
+        (0..self.params.num_population)
+            .map(|i| {
+                let depth = (1 + i % max_depth).max(grammar.min_depth(rule));
+                loop {
+                    let codons = grammar.generate(rule, depth, &mut *self.rng.borrow_mut());
+                    if grammar.decode::<C>(rule, &codons, max_depth).is_some() {
+                        break codons;
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // `child` if it maps into a program within the maximum depth, a copy of
+    // `parent` otherwise
+    fn mapping<C: ProgramContext>(
+        &self,
+        grammar: &Grammar,
+        rule: usize,
+        child: Vec<u8>,
+        parent: &[u8],
+    ) -> Vec<u8> {
+        match grammar.decode::<C>(rule, &child, self.params.max_depth) {
+            Some(_) => child,
+            None => parent.to_vec(),
+        }
+    }
+
+    // one-point crossover at a point of each parent, the offspring cut to
+    // `max_length`
+    pub fn grammatical_crossover<C: ProgramContext>(
+        &self,
+        grammar: &Grammar,
+        rule: usize,
+        (p1, p2): (&[u8], &[u8]),
+        max_length: usize,
+    ) -> Result<(Vec<u8>, Vec<u8>), BreedError> {
+        if p1.is_empty() || p2.is_empty() {
+            return Err(BreedError::Empty);
+        }
+        let point1 = self.rng.borrow_mut().gen_range(1..=p1.len());
+        let point2 = self.rng.borrow_mut().gen_range(1..=p2.len());
+        let child = |head: &[u8], tail: &[u8], parent| {
+            let mut codons = [head, tail].concat();
+            codons.truncate(max_length);
+            self.mapping::<C>(grammar, rule, codons, parent)
+        };
+        Ok((
+            child(&p1[..point1], &p2[point2..], p1),
+            child(&p2[..point2], &p1[point1..], p2),
+        ))
+    }
+
+    // a codon drawn anew
+    pub fn grammatical_mutation<C: ProgramContext>(
+        &self,
+        grammar: &Grammar,
+        rule: usize,
+        p: &[u8],
+    ) -> Result<Vec<u8>, BreedError> {
+        if p.is_empty() {
+            return Err(BreedError::Empty);
+        }
+        let mut child = p.to_vec();
+        let mut rng = self.rng.borrow_mut();
+        child[rng.gen_range(0..p.len())] = rng.gen();
+        drop(rng);
+        Ok(self.mapping::<C>(grammar, rule, child, p))
+    }
+
+    // crossover with the codons of a random expression
+    pub fn grammatical_headless_crossover<C: ProgramContext>(
+        &self,
+        grammar: &Grammar,
+        rule: usize,
+        p: &[u8],
+        max_length: usize,
+    ) -> Result<Vec<u8>, BreedError> {
+        let depth = (self.rng.borrow_mut().gen_range(1..=self.params.max_depth))
+            .max(grammar.min_depth(rule));
+        let random = grammar.generate(rule, depth, &mut *self.rng.borrow_mut());
+        Ok(self
+            .grammatical_crossover::<C>(grammar, rule, (p, &random), max_length)?
+            .0)
+    }
+}
+
+#[test]
+fn grammatical_mapping() {
+    use std::cell::RefCell;
+
+    use crate::sim::ctx::SequencingContext;
+
+    use super::GPParams;
+
+    type C = SequencingContext<'static>;
+    let grammar = Grammar::parse(
+        "<sequencing> ::= sum(mul(<weight>, <expr>), mul(<weight>, <expr>))
+         <expr> ::= <op>(<expr>, <expr>)
+                  | TERM0 | TERM3
+         <op> ::= sum | max
+         <weight> ::= 0.5 | 1",
+    )
+    .unwrap();
+    let rule = grammar.start("sequencing").unwrap();
+    assert_eq!(grammar.min_depth(rule), 2);
+    // weight 1 and expr max(TERM0, TERM3), then the same again from the
+    // codons wrapped around
+    let (text, read) = grammar.derive(rule, &[1, 0, 1, 1, 2], 3).unwrap();
+    assert_eq!(
+        text,
+        "sum(mul(1, max(TERM0, TERM3)), mul(1, max(TERM0, TERM3)))"
+    );
+    assert_eq!(read, 10);
+    assert!(grammar.derive(rule, &[1, 0, 1, 1, 2], 2).is_none());
+    assert!(grammar.derive(rule, &[0], 6).is_none());
+    assert!(grammar.check::<C>("sequencing", 3).is_ok());
+    assert!(matches!(
+        grammar.check::<C>("sequencing", 1),
+        Err(GrammarError::TooDeep { .. })
+    ));
+    assert!(matches!(
+        Grammar::parse("<a> ::= sum(<a>, <b>)\n<b> ::= 1"),
+        Err(GrammarError::Infinite(_))
+    ));
+    assert!(matches!(
+        Grammar::parse("<a> ::= <b>"),
+        Err(GrammarError::Undefined(_))
+    ));
+    let invalid = Grammar::parse("<a> ::= sum(1) | TERM0").unwrap();
+    assert!(invalid.check::<C>("a", 3).is_err());
+    let expressions = Grammar::expressions::<C>("sequencing", &FunctionSet::default());
+    let default = Grammar::parse(&expressions).unwrap();
+    assert!(default.check::<C>("sequencing", 3).is_ok());
+
+    let gpc = GPContext {
+        rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        params: GPParams {
+            num_population: 8,
+            max_depth: 3,
+            const_rate: 0.1,
+            crossover_rate: 0.8,
+            mutation_rate: 0.1,
+            function_set: FunctionSet::default(),
+        },
+    };
+    let pop = gpc.ramp_grammatical::<C>(&grammar, rule);
+    for _ in 0..32 {
+        let (c1, c2) = gpc
+            .grammatical_crossover::<C>(&grammar, rule, (&pop[2], &pop[7]), 16)
+            .unwrap();
+        let m = gpc.grammatical_mutation::<C>(&grammar, rule, &c1).unwrap();
+        for child in [c1, c2, m] {
+            let program = grammar.decode::<C>(rule, &child, 3).unwrap();
+            assert!(program.to_string().starts_with("sum(mul("));
+            assert!(program.depth() <= 3);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    program::{Node, Program, ProgramContext, MAX_PROGRAM_NODE_CHILDREN},
+    BreedError, GPContext,
+};
+
+// the registers instructions read and write; every one starts at 0 and the
+// rule's value is left in the first
+pub const NUM_REGISTERS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Operand {
+    Register(usize),
+    // a constant or terminal, encoded like a program node
+    Leaf(u8),
+}
+
+// `dest = internal(operands)`, unary internals reading the first operand
+// only
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub internal: usize,
+    pub dest: usize,
+    pub operands: [Operand; MAX_PROGRAM_NODE_CHILDREN],
+}
+
+// a rule as a sequence of instructions run in order, the linear alternative
+// to growing the program tree itself; it evaluates as the tree of the first
+// register it decodes into, so the instructions leading nowhere (introns)
+// are only carried along for breeding
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinearProgram {
+    pub instructions: Vec<Instruction>,
+}
+
+impl LinearProgram {
+    // the indices of the instructions the first register ends up depending
+    // on, in order
+    pub fn effective<C: ProgramContext>(&self) -> Vec<usize> {
+        let mut live = [false; NUM_REGISTERS];
+        live[0] = true;
+        let mut effective = Vec::new();
+        for (index, instruction) in self.instructions.iter().enumerate().rev() {
+            if !live[instruction.dest] {
+                continue;
+            }
+            live[instruction.dest] = false;
+            let num_children = C::internal_num_children(instruction.internal);
+            for operand in &instruction.operands[..num_children] {
+                if let Operand::Register(register) = *operand {
+                    live[register] = true;
+                }
+            }
+            effective.push(index);
+        }
+        effective.reverse();
+        effective
+    }
+
+    // the tree of the first register, none when deeper than `max_depth`;
+    // the effective instructions are at most as many as its levels
+    pub fn decode<C: ProgramContext>(&self, max_depth: usize) -> Option<Program<C>> {
+        let zero = Program::from_vec(vec![Node::Const(0.0).into()]);
+        let mut registers = vec![(zero, 0); NUM_REGISTERS];
+        for index in self.effective::<C>() {
+            let instruction = &self.instructions[index];
+            let num_children = C::internal_num_children(instruction.internal);
+            let operands = instruction.operands[..num_children]
+                .iter()
+                .map(|operand| match *operand {
+                    Operand::Register(register) => registers[register].clone(),
+                    Operand::Leaf(leaf) => (Program::from_vec(vec![leaf]), 0),
+                })
+                .collect::<Vec<_>>();
+            let depth = 1 + operands.iter().map(|(_, depth)| *depth).max().unwrap_or(0);
+            if depth > max_depth {
+                return None;
+            }
+            let mut program = Program::new();
+            program.generate_at(
+                0,
+                num_children,
+                Node::Internal(instruction.internal).into(),
+                |program, i, child_index| {
+                    GPContext::<SmallRng>::copy_subtree(program, child_index, &operands[i].0, 0)
+                },
+            );
+            registers[instruction.dest] = (program, depth);
+        }
+        Some(registers.swap_remove(0).0)
+    }
+}
+
+impl<R: RngCore> GPContext<R> {
+    // a register, or a leaf drawn like those of trees
+    fn gen_operand<C: ProgramContext>(&self) -> Operand {
+        if self.rng.borrow_mut().gen_bool(0.5) {
+            return Operand::Register(self.rng.borrow_mut().gen_range(0..NUM_REGISTERS));
+        }
+        let mut leaf = Program::<C>::new();
+        self.gen_terminal_at(&mut leaf, 0);
+        Operand::Leaf(leaf.nodes[0])
+    }
+
+    fn gen_instruction<C: ProgramContext>(&self) -> Instruction {
+        let internals = self.params.function_set.internals::<C>();
+        let internal = *internals.choose(&mut *self.rng.borrow_mut()).unwrap();
+        let dest = self.rng.borrow_mut().gen_range(0..NUM_REGISTERS);
+        Instruction {
+            internal,
+            dest,
+            operands: [self.gen_operand::<C>(), self.gen_operand::<C>()],
+        }
+    }
+
+    pub fn gen_linear<C: ProgramContext>(&self, length: usize) -> LinearProgram {
+        LinearProgram {
+            instructions: (0..length).map(|_| self.gen_instruction::<C>()).collect(),
+        }
+    }
+
+    // lengths ramped from 1 to `max_depth`, so that every program decodes
+    pub fn ramp_linear<C: ProgramContext>(&self) -> Vec<LinearProgram> {
+        (0..self.params.num_population)
+            .map(|i| self.gen_linear::<C>(1 + i % self.params.max_depth))
+            .collect()
+    }
+
+    // `child` if it decodes within the maximum depth, a copy of `parent`
+    // otherwise
+    fn within_depth<C: ProgramContext>(
+        &self,
+        child: LinearProgram,
+        parent: &LinearProgram,
+    ) -> LinearProgram {
+        match child.decode::<C>(self.params.max_depth) {
+            Some(_) => child,
+            None => parent.clone(),
+        }
+    }
+
+    // two-point crossover: a random run of instructions of each parent
+    // swapped for the other's, the offspring cut to `max_length`
+    pub fn linear_crossover<C: ProgramContext>(
+        &self,
+        p1: &LinearProgram,
+        p2: &LinearProgram,
+        max_length: usize,
+    ) -> Result<(LinearProgram, LinearProgram), BreedError> {
+        if p1.instructions.is_empty() || p2.instructions.is_empty() {
+            return Err(BreedError::Empty);
+        }
+        let segment = |p: &LinearProgram| {
+            let mut rng = self.rng.borrow_mut();
+            let start = rng.gen_range(0..p.instructions.len());
+            start..rng.gen_range(start + 1..=p.instructions.len())
+        };
+        let (segment1, segment2) = (segment(p1), segment(p2));
+        let child =
+            |p: &LinearProgram, segment: &std::ops::Range<usize>, inserted: &[Instruction]| {
+                let mut instructions = [
+                    &p.instructions[..segment.start],
+                    inserted,
+                    &p.instructions[segment.end..],
+                ]
+                .concat();
+                instructions.truncate(max_length);
+                self.within_depth::<C>(LinearProgram { instructions }, p)
+            };
+        Ok((
+            child(p1, &segment1, &p2.instructions[segment2.clone()]),
+            child(p2, &segment2, &p1.instructions[segment1]),
+        ))
+    }
+
+    // an instruction inserted or deleted, or one of its internal,
+    // destination and operands drawn anew, evenly
+    pub fn linear_mutation<C: ProgramContext>(
+        &self,
+        p: &LinearProgram,
+        max_length: usize,
+    ) -> Result<LinearProgram, BreedError> {
+        if p.instructions.is_empty() {
+            return Err(BreedError::Empty);
+        }
+        let mut child = p.clone();
+        let index = self.rng.borrow_mut().gen_range(0..p.instructions.len());
+        let fresh = self.gen_instruction::<C>();
+        let micro = self.rng.borrow_mut().gen_bool(0.5);
+        if micro {
+            let instruction = &mut child.instructions[index];
+            match self
+                .rng
+                .borrow_mut()
+                .gen_range(0..2 + MAX_PROGRAM_NODE_CHILDREN)
+            {
+                0 => instruction.internal = fresh.internal,
+                1 => instruction.dest = fresh.dest,
+                k => instruction.operands[k - 2] = fresh.operands[k - 2],
+            }
+        } else {
+            let inserting = child.instructions.len() == 1
+                || (child.instructions.len() < max_length && self.rng.borrow_mut().gen_bool(0.5));
+            match inserting {
+                true => child.instructions.insert(index, fresh),
+                false => {
+                    child.instructions.remove(index);
+                }
+            }
+        }
+        Ok(self.within_depth::<C>(child, p))
+    }
+
+    // crossover with a program of random length
+    pub fn linear_headless_crossover<C: ProgramContext>(
+        &self,
+        p: &LinearProgram,
+        max_length: usize,
+    ) -> Result<LinearProgram, BreedError> {
+        let length = self.rng.borrow_mut().gen_range(1..=self.params.max_depth);
+        let random = self.gen_linear::<C>(length);
+        Ok(self.linear_crossover::<C>(p, &random, max_length)?.0)
+    }
+}
+
+#[test]
+fn linear_programs() {
+    use std::cell::RefCell;
+
+    use crate::sim::ctx::SequencingContext;
+    use rand::SeedableRng;
+
+    use super::{FunctionSet, GPParams};
+
+    type C = SequencingContext<'static>;
+    let leaf = |terminal| Operand::Leaf(Node::Terminal(terminal).into());
+    // r1 = TERM0 + TERM1; r2 = r1 * TERM2 (an intron); r0 = max(r1, r1)
+    let program = LinearProgram {
+        instructions: vec![
+            Instruction {
+                internal: 0,
+                dest: 1,
+                operands: [leaf(0), leaf(1)],
+            },
+            Instruction {
+                internal: 2,
+                dest: 2,
+                operands: [Operand::Register(1), leaf(2)],
+            },
+            Instruction {
+                internal: 5,
+                dest: 0,
+                operands: [Operand::Register(1), Operand::Register(1)],
+            },
+        ],
+    };
+    assert_eq!(program.effective::<C>(), [0, 2]);
+    let decoded = program.decode::<C>(2).unwrap();
+    assert_eq!(
+        decoded.to_string(),
+        "max(sum(TERM0, TERM1), sum(TERM0, TERM1))"
+    );
+    assert!(program.decode::<C>(1).is_none());
+    assert_eq!(
+        LinearProgram::default().decode::<C>(0).unwrap().to_string(),
+        "0"
+    );
+
+    let gpc = GPContext {
+        rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        params: GPParams {
+            num_population: 8,
+            max_depth: 3,
+            const_rate: 0.1,
+            crossover_rate: 0.8,
+            mutation_rate: 0.1,
+            function_set: FunctionSet::default(),
+        },
+    };
+    let pop = gpc.ramp_linear::<C>();
+    for _ in 0..32 {
+        let (c1, c2) = gpc.linear_crossover::<C>(&pop[2], &pop[7], 4).unwrap();
+        let m = gpc.linear_mutation::<C>(&c1, 4).unwrap();
+        for child in [c1, c2, m] {
+            assert!((1..=4).contains(&child.instructions.len()));
+            assert!(child.decode::<C>(3).is_some_and(|p| p.depth() <= 3));
+        }
+    }
+    assert!(matches!(
+        gpc.linear_mutation::<C>(&LinearProgram::default(), 4),
+        Err(BreedError::Empty)
+    ));
+}
@@ -1,27 +1,122 @@
-use std::{cell::RefCell, ops::Range};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Range,
+};
 
 use rand::{
+    rngs::SmallRng,
     seq::{IteratorRandom, SliceRandom},
     Rng, RngCore,
 };
 
-use crate::CONST_RATE;
-
 use self::program::{Node, Program, ProgramContext, MAX_PROGRAM_NODE_CHILDREN};
 
+pub mod grammar;
+pub mod linear;
 pub mod program;
 
-pub struct GPContext<R: RngCore> {
-    pub rng: RefCell<R>,
+// settings of the evolution, given by the caller so that runs with different
+// ones can share a process
+#[derive(Clone, Debug)]
+pub struct GPParams {
     pub num_population: usize,
     pub max_depth: usize,
+    // chance of a leaf being a constant rather than a terminal
+    pub const_rate: f64,
+    pub crossover_rate: f64,
+    pub mutation_rate: f64,
+    pub function_set: FunctionSet,
+}
+
+// a subset of the internals and terminals of the contexts new subtrees are
+// drawn from, by index, the context's own choice where left out; programs
+// still encode every node by its index among all of them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionSet {
+    pub internals: Option<Vec<usize>>,
+    // by `ProgramContext::RULE`
+    pub terminals: BTreeMap<&'static str, Vec<usize>>,
+}
+
+impl FunctionSet {
+    pub fn internals<C: ProgramContext>(&self) -> Cow<'_, [usize]> {
+        match &self.internals {
+            Some(internals) => Cow::Borrowed(internals),
+            None => C::generated_internals(),
+        }
+    }
+
+    pub fn terminals<C: ProgramContext>(&self) -> Cow<'_, [usize]> {
+        match self.terminals.get(C::RULE) {
+            Some(terminals) => Cow::Borrowed(terminals),
+            None => C::generated_terminals(),
+        }
+    }
+}
+
+// controls for whether the evolution contributes anything: crossover with
+// random trees instead of other parents (headless chicken), or every
+// generation drawn anew like the first (random search)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    HeadlessChicken,
+    RandomSearch,
+}
+
+impl Control {
+    pub fn parse(str: &str) -> Option<Self> {
+        match str {
+            "headless_chicken" => Some(Self::HeadlessChicken),
+            "random_search" => Some(Self::RandomSearch),
+            _ => None,
+        }
+    }
+}
+
+// the genome rules are bred as: the program tree itself, the instructions
+// of a `linear::LinearProgram` decoding into it, or codons mapped into it
+// through a `grammar::Grammar`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Representation {
+    #[default]
+    Tree,
+    Linear,
+    Grammatical,
+}
+
+impl Representation {
+    pub fn parse(str: &str) -> Option<Self> {
+        match str {
+            "tree" => Some(Self::Tree),
+            "linear" => Some(Self::Linear),
+            "grammatical" => Some(Self::Grammatical),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BreedError {
+    #[error("the program has no root")]
+    Empty,
+    #[error("a parent of depth {depth} is deeper than the maximum of {max_depth}")]
+    TooDeep { depth: usize, max_depth: usize },
+}
+
+pub struct GPContext<R: RngCore> {
+    pub rng: RefCell<R>,
+    pub params: GPParams,
 }
 
 impl<R: RngCore> GPContext<R> {
     pub fn gen_terminal_at<C: ProgramContext>(&self, program: &mut Program<C>, index: usize) {
-        let terminal = self.rng.borrow_mut().gen_bool(1.0 - *CONST_RATE);
+        let terminal = self.rng.borrow_mut().gen_bool(1.0 - self.params.const_rate);
         if terminal {
-            let term_index = self.rng.borrow_mut().gen_range(0..C::num_terminals());
+            let terminals = self.params.function_set.terminals::<C>();
+            let term_index = terminals[self.rng.borrow_mut().gen_range(0..terminals.len())];
             program.generate_at(index, 0, Node::Terminal(term_index).into(), |_, _, _| {})
         } else {
             program.generate_at(
@@ -39,7 +134,8 @@ impl<R: RngCore> GPContext<R> {
         index: usize,
         gen_child_fn: impl FnMut(&mut Program<C>, usize, usize),
     ) {
-        let int_index = self.rng.borrow_mut().gen_range(0..C::num_internals());
+        let internals = self.params.function_set.internals::<C>();
+        let int_index = internals[self.rng.borrow_mut().gen_range(0..internals.len())];
         program.generate_at(
             index,
             C::internal_num_children(int_index),
@@ -54,16 +150,19 @@ impl<R: RngCore> GPContext<R> {
         index: usize,
         gen_child_fn: impl FnMut(&mut Program<C>, usize, usize),
     ) {
+        let set = &self.params.function_set;
+        let (terminals, internals) = (set.terminals::<C>(), set.internals::<C>());
         let type_index = self
             .rng
             .borrow_mut()
-            .gen_range(0..(C::num_internals() + C::num_terminals()));
-        let (value, num_children) = if type_index < C::num_terminals() {
-            (Node::Terminal(type_index).into(), 0)
+            .gen_range(0..(internals.len() + terminals.len()));
+        let (value, num_children) = if type_index < terminals.len() {
+            (Node::Terminal(terminals[type_index]).into(), 0)
         } else {
+            let internal = internals[type_index - terminals.len()];
             (
-                Node::Internal(type_index - C::num_terminals()).into(),
-                C::internal_num_children(type_index - C::num_terminals()),
+                Node::Internal(internal).into(),
+                C::internal_num_children(internal),
             )
         };
         program.generate_at(index, num_children, value, gen_child_fn);
@@ -114,20 +213,34 @@ impl<R: RngCore> GPContext<R> {
         }
     }
 
-    pub fn mutation<C: ProgramContext>(&self, p: &Program<C>) -> Program<C> {
+    // empty programs and those deeper than `max_depth` cannot be bred
+    fn check_depth<C: ProgramContext>(&self, program: &Program<C>) -> Result<usize, BreedError> {
+        if program.nodes.first().is_none_or(|root| Node::from(*root).is_null()) {
+            return Err(BreedError::Empty);
+        }
+        let depth = Self::depth_to_bottom(program, 0);
+        let max_depth = self.params.max_depth;
+        if depth > max_depth {
+            return Err(BreedError::TooDeep { depth, max_depth });
+        }
+        Ok(depth)
+    }
+
+    pub fn mutation<C: ProgramContext>(&self, p: &Program<C>) -> Result<Program<C>, BreedError> {
+        self.check_depth(p)?;
         let mut p: Program<C> = p.clone();
         let swap_pos = *p
             .all_active_indices()
             .choose(&mut *self.rng.borrow_mut())
-            .unwrap();
+            .ok_or(BreedError::Empty)?;
         p.clear_subtree(swap_pos);
         self.gen_grow_at(
             &mut p,
             swap_pos,
-            self.max_depth - Self::depth_from_top(swap_pos),
+            self.params.max_depth - Self::depth_from_top(swap_pos),
         );
         p.verify();
-        p
+        Ok(p)
     }
 
     fn copy_subtree<C: ProgramContext>(
@@ -163,15 +276,15 @@ impl<R: RngCore> GPContext<R> {
         &self,
         p1: &'a Program<C>,
         p2: &'a Program<C>,
-    ) -> (Program<C>, Program<C>) {
+    ) -> Result<(Program<C>, Program<C>), BreedError> {
+        let depth1 = self.check_depth(p1)?;
+        let depth2 = self.check_depth(p2)?;
         let mut c1 = p1.clone();
         let mut c2 = p2.clone();
-        let depth1 = Self::depth_to_bottom(&c1, 0);
-        let depth2 = Self::depth_to_bottom(&c2, 0);
 
         let depth_point1 = self.rng.borrow_mut().gen_range(0..=depth1);
-        let min_depth_point2 = (depth_point1 + depth2).saturating_sub(self.max_depth);
-        let max_depth_point2 = (self.max_depth - depth1 + depth_point1).min(depth2);
+        let min_depth_point2 = (depth_point1 + depth2).saturating_sub(self.params.max_depth);
+        let max_depth_point2 = (self.params.max_depth - depth1 + depth_point1).min(depth2);
 
         let depth_point2 = self
             .rng
@@ -181,31 +294,70 @@ impl<R: RngCore> GPContext<R> {
         let swap_idx1 = Self::all_index_of_layer(depth_point1)
             .filter(|i| *i < c1.nodes.len() && !Node::from(c1.nodes[*i]).is_null())
             .choose(&mut *self.rng.borrow_mut())
-            .expect("should not be None");
+            .ok_or(BreedError::Empty)?;
         let swap_idx2 = Self::all_index_of_layer(depth_point2)
             .filter(|i| *i < c2.nodes.len() && !Node::from(c2.nodes[*i]).is_null())
             .choose(&mut *self.rng.borrow_mut())
-            .expect("should not be None");
+            .ok_or(BreedError::Empty)?;
 
         c1.clear_subtree(swap_idx1);
         c2.clear_subtree(swap_idx2);
 
         Self::copy_subtree(&mut c1, swap_idx1, p2, swap_idx2);
         Self::copy_subtree(&mut c2, swap_idx2, p1, swap_idx1);
-        assert!(Self::depth_to_bottom(&c1, 0) <= self.max_depth);
-        assert!(Self::depth_to_bottom(&c2, 0) <= self.max_depth);
+        debug_assert!(Self::depth_to_bottom(&c1, 0) <= self.params.max_depth);
+        debug_assert!(Self::depth_to_bottom(&c2, 0) <= self.params.max_depth);
 
         c1.verify();
         c2.verify();
 
-        (c1, c2)
+        Ok((c1, c2))
+    }
+
+    // crossover with a tree grown at random to a random depth, keeping the
+    // offspring that holds the root of `p`
+    pub fn headless_crossover<C: ProgramContext>(
+        &self,
+        p: &Program<C>,
+    ) -> Result<Program<C>, BreedError> {
+        let depth = self.rng.borrow_mut().gen_range(0..=self.params.max_depth);
+        let mut random = Program::new();
+        self.gen_grow_at(&mut random, 0, depth);
+        Ok(self.crossover(p, &random)?.0)
+    }
+
+    // replaces a random subtree by a copy of one of `modules`, where it fits
+    // under `max_depth`; a plain mutation when none fits
+    pub fn module_mutation<C: ProgramContext>(
+        &self,
+        p: &Program<C>,
+        modules: &[Program<C>],
+    ) -> Result<Program<C>, BreedError> {
+        self.check_depth(p)?;
+        let Some(module) = modules.choose(&mut *self.rng.borrow_mut()) else {
+            return self.mutation(p);
+        };
+        let depth = Self::depth_to_bottom(module, 0);
+        let fitting = p
+            .all_active_indices()
+            .into_iter()
+            .filter(|index| Self::depth_from_top(*index) + depth <= self.params.max_depth)
+            .choose(&mut *self.rng.borrow_mut());
+        let Some(swap_pos) = fitting else {
+            return self.mutation(p);
+        };
+        let mut p = p.clone();
+        p.clear_subtree(swap_pos);
+        Self::copy_subtree(&mut p, swap_pos, module, 0);
+        p.verify();
+        Ok(p)
     }
 
     pub fn ramp_half_and_half<C: ProgramContext>(&self) -> Vec<Program<C>> {
         let mut v = Vec::new();
-        let half_size = self.num_population / 2;
-        for depth in 1..self.max_depth {
-            for _ in 0..half_size / self.max_depth {
+        let half_size = self.params.num_population / 2;
+        for depth in 1..self.params.max_depth {
+            for _ in 0..half_size / self.params.max_depth {
                 let mut p = Program::new();
                 self.gen_full_at(&mut p, 0, depth);
                 p.verify();
@@ -217,9 +369,9 @@ impl<R: RngCore> GPContext<R> {
             }
         }
 
-        while v.len() < self.num_population {
+        while v.len() < self.params.num_population {
             let mut p = Program::new();
-            self.gen_grow_at(&mut p, 0, self.max_depth);
+            self.gen_grow_at(&mut p, 0, self.params.max_depth);
             p.verify();
             v.push(p);
         }
@@ -228,6 +380,50 @@ impl<R: RngCore> GPContext<R> {
     }
 }
 
+// the `num_modules` subtrees of depth 1 to `max_depth` found in the most of
+// `elites`, each given by its programs, and in at least two of them; a
+// subtree counts once per elite, in canonical form, and larger ones go first
+// among as frequent
+pub fn acquire_modules<'p, C: ProgramContext + 'p>(
+    elites: impl IntoIterator<Item = Vec<&'p Program<C>>>,
+    max_depth: usize,
+    num_modules: usize,
+) -> Vec<Program<C>> {
+    let mut found = HashMap::<u64, (usize, Program<C>)>::new();
+    for programs in elites {
+        let mut seen = HashSet::new();
+        for program in programs {
+            for index in program.all_active_indices() {
+                let depth = GPContext::<SmallRng>::depth_to_bottom(program, index);
+                if !(1..=max_depth).contains(&depth) {
+                    continue;
+                }
+                let subtree = program.subtree(index);
+                let hash = subtree.structural_hash();
+                if seen.insert(hash) {
+                    found.entry(hash).or_insert((0, subtree)).0 += 1;
+                }
+            }
+        }
+    }
+    let mut modules = found
+        .into_iter()
+        .filter(|(_, (count, _))| *count >= 2)
+        .collect::<Vec<_>>();
+    modules.sort_by_key(|(hash, (count, module))| {
+        (
+            Reverse(*count),
+            Reverse(module.all_active_indices().len()),
+            *hash,
+        )
+    });
+    modules
+        .into_iter()
+        .take(num_modules)
+        .map(|(_, (_, module))| module)
+        .collect()
+}
+
 #[test]
 fn tst() {
     use rand::rngs::ThreadRng;
@@ -235,3 +431,102 @@ fn tst() {
     assert_eq!(GPContext::<ThreadRng>::all_index_of_layer(1), 1..3);
     assert_eq!(GPContext::<ThreadRng>::all_index_of_layer(2), 3..7);
 }
+
+#[test]
+fn const_rate_param() {
+    use crate::sim::ctx::SequencingContext;
+    use rand::{rngs::SmallRng, SeedableRng};
+    // contexts with different settings side by side
+    let gpc = |const_rate| GPContext {
+        rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        params: GPParams {
+            num_population: 8,
+            max_depth: 3,
+            const_rate,
+            crossover_rate: 0.8,
+            mutation_rate: 0.1,
+            function_set: FunctionSet::default(),
+        },
+    };
+    for (const_rate, constant) in [(0.0, false), (1.0, true)] {
+        let gpc = gpc(const_rate);
+        for _ in 0..8 {
+            let mut program = Program::<SequencingContext>::new();
+            gpc.gen_terminal_at(&mut program, 0);
+            assert_eq!(matches!(Node::from(program.nodes[0]), Node::Const(_)), constant);
+        }
+    }
+}
+
+#[test]
+fn breed_errors() {
+    use crate::sim::ctx::SequencingContext;
+    use rand::{rngs::SmallRng, SeedableRng};
+    let gpc = GPContext {
+        rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        params: GPParams {
+            num_population: 8,
+            max_depth: 2,
+            const_rate: 0.1,
+            crossover_rate: 0.8,
+            mutation_rate: 0.1,
+            function_set: FunctionSet::default(),
+        },
+    };
+    let empty = Program::<SequencingContext>::new();
+    let leaf = Program::<SequencingContext>::terminal(0);
+    let mut deep = Program::<SequencingContext>::new();
+    gpc.gen_full_at(&mut deep, 0, 3);
+    assert!(matches!(gpc.mutation(&empty), Err(BreedError::Empty)));
+    assert!(matches!(
+        gpc.crossover(&leaf, &deep),
+        Err(BreedError::TooDeep {
+            depth: 3,
+            max_depth: 2
+        })
+    ));
+    assert!(gpc.crossover(&leaf, &leaf).is_ok());
+    assert!(matches!(
+        gpc.headless_crossover(&deep),
+        Err(BreedError::TooDeep { .. })
+    ));
+    for _ in 0..8 {
+        assert!(gpc.headless_crossover(&leaf).unwrap().depth() <= 2);
+    }
+    assert_eq!(
+        Control::parse("headless_chicken"),
+        Some(Control::HeadlessChicken)
+    );
+    assert_eq!(Control::parse("headless"), None);
+}
+
+#[test]
+fn module_acquisition() {
+    use crate::sim::ctx::SequencingContext;
+    use rand::SeedableRng;
+    type P = Program<SequencingContext<'static>>;
+    let gpc = GPContext {
+        rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        params: GPParams {
+            num_population: 8,
+            max_depth: 3,
+            const_rate: 0.1,
+            crossover_rate: 0.8,
+            mutation_rate: 0.1,
+            function_set: FunctionSet::default(),
+        },
+    };
+    let a = "max(sum(TERM1, TERM0), TERM2)".parse::<P>().unwrap();
+    let b = "div(TERM3, sum(TERM0, TERM1))".parse::<P>().unwrap();
+    let c = "sub(TERM4, TERM5)".parse::<P>().unwrap();
+    // in a single elite only, however often
+    assert!(acquire_modules([vec![&a, &b], vec![&c, &c]], 2, 4).is_empty());
+    let modules = acquire_modules([vec![&a], vec![&b], vec![&c]], 2, 4);
+    assert_eq!(modules.len(), 1);
+    assert_eq!(modules[0].to_string(), "sum(TERM0, TERM1)");
+    for _ in 0..8 {
+        let mutated = gpc.module_mutation(&c, &modules).unwrap();
+        assert!(mutated.to_string().contains("sum(TERM0, TERM1)"));
+        assert!(mutated.depth() <= 3);
+    }
+}
@@ -1,17 +1,68 @@
 use base64::{prelude::BASE64_STANDARD, Engine};
 use core::f32;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use smallvec::SmallVec;
 use std::{
+    borrow::Cow,
     fmt::{self, Debug, Display, Formatter},
     marker::PhantomData,
+    str::FromStr,
 };
 
 pub const MAX_PROGRAM_NODE_CHILDREN: usize = 2;
 
+// serialized programs start with a null root, which no legacy run-length
+// encoded program has, followed by the format version and the encoding of the
+// nodes; anything else is decoded as the legacy format
+const MAGIC: u8 = 255;
+const VERSION: u8 = 1;
+const RAW: u8 = 0;
+const VARINT_RLE: u8 = 1;
+// far beyond any depth the GP grows to, guards against huge runs
+pub const MAX_NODES: usize = 1 << 16;
+
+// the values a subtree can take, bounds included
+pub type Interval = (f32, f32);
+pub const UNBOUNDED: Interval = (f32::NEG_INFINITY, f32::INFINITY);
+
 pub trait ProgramContext {
+    // the rule the context evaluates, naming it in function sets
+    const RULE: &'static str;
+
     fn num_terminals() -> usize;
     fn num_internals() -> usize;
     fn internal_num_children(index: usize) -> usize;
+    // the order of the children does not change the value
+    fn is_commutative(_index: usize) -> bool {
+        false
+    }
+
+    // the terminals and internals the GP generates when the function set
+    // leaves them out, all of them unless narrowed
+    fn generated_terminals() -> Cow<'static, [usize]> {
+        Cow::Owned((0..Self::num_terminals()).collect())
+    }
+
+    fn generated_internals() -> Cow<'static, [usize]> {
+        Cow::Owned((0..Self::num_internals()).collect())
+    }
+
+    // the values a terminal takes, unbounded unless known
+    fn terminal_range(_index: usize) -> Interval {
+        UNBOUNDED
+    }
+
+    // the values an internal takes given those of its children, exactly its
+    // value when they are points; unbounded unless known
+    fn internal_range(_index: usize, _children: &[Interval]) -> Interval {
+        UNBOUNDED
+    }
+
+    // the child whose value the internal always takes, like the lower
+    // operand of a `min` the other never goes below
+    fn dominant_child(_index: usize, _children: &[Interval]) -> Option<usize> {
+        None
+    }
 
     fn terminal(&self, index: usize) -> f32;
     fn internal(
@@ -27,6 +78,68 @@ pub trait ProgramContext {
     fn format_internal(index: usize, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "INT{index}")
     }
+
+    fn latex_terminal(index: usize) -> String {
+        format!("\\mathit{{TERM}}_{{{index}}}")
+    }
+
+    // the operands come with whether they are sums or differences, which need
+    // parentheses inside a product; so does the result
+    fn latex_internal(index: usize, children: &[(String, bool)]) -> (String, bool) {
+        let children = children
+            .iter()
+            .map(|(child, _)| child.as_str())
+            .collect::<Vec<_>>();
+        (
+            format!(
+                "\\operatorname{{INT{index}}}\\left({}\\right)",
+                children.join(", ")
+            ),
+            false,
+        )
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error(transparent)]
+    Base64(#[from] base64::DecodeError),
+    #[error("run-length encoding needs (value, count) pairs, found {0} bytes")]
+    OddLength(usize),
+    #[error("unsupported program format version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unknown node encoding {0}")]
+    UnknownEncoding(u8),
+    #[error("the program ends in the middle of a run")]
+    Truncated,
+    #[error("the program has more than {MAX_NODES} nodes")]
+    TooLong,
+    #[error("the program has no root")]
+    MissingRoot,
+    #[error("node {index}: terminal {terminal} is out of range, there are {num_terminals}")]
+    UnknownTerminal {
+        index: usize,
+        terminal: usize,
+        num_terminals: usize,
+    },
+    #[error("node {index}: internal {internal} is out of range, there are {num_internals}")]
+    UnknownInternal {
+        index: usize,
+        internal: usize,
+        num_internals: usize,
+    },
+    #[error("node {index}: child {child} is missing")]
+    MissingChild { index: usize, child: usize },
+    #[error("at {position}: expected {expected}, found {found:?}")]
+    Syntax {
+        position: usize,
+        expected: &'static str,
+        found: String,
+    },
+    #[error("unknown node {0:?}")]
+    UnknownNode(String),
+    #[error("constant {0} is not a multiple of 1/16 between -4 and 4")]
+    ConstOutOfRange(f32),
 }
 
 #[derive(Debug)]
@@ -185,26 +298,46 @@ impl<C: ProgramContext> Program<C> {
         }
     }
 
-    fn calc_at(&self, c: &C, i: usize, term_cache: &[f32]) -> f32 {
+    fn calc_at(
+        &self,
+        internal: &impl Fn(usize, SmallVec<[f32; MAX_PROGRAM_NODE_CHILDREN]>) -> f32,
+        i: usize,
+        term_cache: &[f32],
+    ) -> f32 {
         match Node::from(self.nodes[i]) {
             Node::Const(x) => x,
             Node::Terminal(idx) => term_cache[idx],
             Node::Internal(idx) => {
                 let children: SmallVec<[f32; MAX_PROGRAM_NODE_CHILDREN]> =
                     Self::child_indices(i, C::internal_num_children(idx))
-                        .map(|i| self.calc_at(c, i, term_cache))
+                        .map(|i| self.calc_at(internal, i, term_cache))
                         .collect();
-                c.internal(idx, children)
+                internal(idx, children)
             }
             Node::Null => unreachable!(),
         }
     }
 
+    pub fn terminals(c: &C) -> Vec<f32> {
+        (0..C::num_terminals()).map(|i| c.terminal(i)).collect()
+    }
+
     pub fn calc(&self, c: &C) -> f32 {
-        let term_cache = (0..C::num_terminals())
-            .map(|i| c.terminal(i))
-            .collect::<Vec<_>>();
-        self.calc_at(c, 0, &term_cache)
+        self.calc_with(c, &Self::terminals(c))
+    }
+
+    // with the terminal values computed beforehand
+    pub fn calc_with(&self, c: &C, terminals: &[f32]) -> f32 {
+        self.calc_at(&|index, children| c.internal(index, children), 0, terminals)
+    }
+
+    // for programs whose internal nodes do not depend on the context
+    pub fn calc_terminals(
+        &self,
+        terminals: &[f32],
+        internal: impl Fn(usize, SmallVec<[f32; MAX_PROGRAM_NODE_CHILDREN]>) -> f32,
+    ) -> f32 {
+        self.calc_at(&internal, 0, terminals)
     }
 
     pub fn collect_all_active_indices(&self, dest: &mut Vec<usize>, index: usize) {
@@ -222,6 +355,44 @@ impl<C: ProgramContext> Program<C> {
         indices
     }
 
+    pub fn terminal_name(index: usize) -> String {
+        Symbol(|f: &mut Formatter<'_>| C::format_terminal(index, f)).to_string()
+    }
+
+    pub fn internal_name(index: usize) -> String {
+        Symbol(|f: &mut Formatter<'_>| C::format_internal(index, f)).to_string()
+    }
+
+    // the terminals the program reads, in index order
+    pub fn used_terminals(&self) -> Vec<usize> {
+        let mut used = self
+            .all_active_indices()
+            .into_iter()
+            .filter_map(|index| match Node::from(self.nodes[index]) {
+                Node::Terminal(terminal) => Some(terminal),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        used.sort_unstable();
+        used.dedup();
+        used
+    }
+
+    // how often each terminal and each internal occurs among the active
+    // nodes, by index; every evaluation visits them all once
+    pub fn node_counts(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut terminals = vec![0; C::num_terminals()];
+        let mut internals = vec![0; C::num_internals()];
+        for index in self.all_active_indices() {
+            match Node::from(self.nodes[index]) {
+                Node::Terminal(terminal) => terminals[terminal] += 1,
+                Node::Internal(internal) => internals[internal] += 1,
+                _ => {}
+            }
+        }
+        (terminals, internals)
+    }
+
     pub fn run_length_encode(v: &[u8]) -> Vec<u8> {
         let mut res: Vec<u8> = Vec::new();
         for byte in v {
@@ -235,26 +406,138 @@ impl<C: ProgramContext> Program<C> {
         res
     }
 
-    pub fn run_length_decode(v: &[u8]) -> Vec<u8> {
-        assert!(v.len() % 2 == 0);
+    pub fn run_length_decode(v: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        if v.len() % 2 != 0 {
+            return Err(DecodeError::OddLength(v.len()));
+        }
         let mut res = Vec::new();
         for i in 0..v.len() / 2 {
             for _ in 0..v[i * 2 + 1] + 1 {
                 res.push(v[i * 2]);
             }
         }
+        Ok(res)
+    }
+
+    // (value, run length - 1) pairs with the run length as a LEB128 varint
+    pub fn varint_run_length_encode(v: &[u8]) -> Vec<u8> {
+        let mut res = Vec::new();
+        for run in v.chunk_by(|a, b| a == b) {
+            res.push(run[0]);
+            let mut count = run.len() - 1;
+            while count >= 0x80 {
+                res.push((count & 0x7f) as u8 | 0x80);
+                count >>= 7;
+            }
+            res.push(count as u8);
+        }
         res
     }
 
+    pub fn varint_run_length_decode(v: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut res = Vec::new();
+        let mut bytes = v.iter();
+        while let Some(&value) = bytes.next() {
+            let (mut count, mut shift) = (0, 0);
+            loop {
+                let byte = *bytes.next().ok_or(DecodeError::Truncated)?;
+                count |= usize::from(byte & 0x7f) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                if shift >= usize::BITS || count > MAX_NODES {
+                    return Err(DecodeError::TooLong);
+                }
+            }
+            if res.len() + count >= MAX_NODES {
+                return Err(DecodeError::TooLong);
+            }
+            res.resize(res.len() + count + 1, value);
+        }
+        Ok(res)
+    }
+
+    // the current format, with whichever node encoding is smaller
+    pub fn encode(&self) -> Vec<u8> {
+        let rle = Self::varint_run_length_encode(&self.nodes);
+        let (encoding, payload) = if rle.len() < self.nodes.len() {
+            (VARINT_RLE, rle)
+        } else {
+            (RAW, self.nodes.clone())
+        };
+        [MAGIC, VERSION, encoding]
+            .into_iter()
+            .chain(payload)
+            .collect()
+    }
+
+    pub fn decode(v: &[u8]) -> Result<Self, DecodeError> {
+        let nodes = match v {
+            [MAGIC, VERSION, RAW, nodes @ ..] => nodes.to_vec(),
+            [MAGIC, VERSION, VARINT_RLE, runs @ ..] => Self::varint_run_length_decode(runs)?,
+            [MAGIC, VERSION, encoding, ..] => return Err(DecodeError::UnknownEncoding(*encoding)),
+            [MAGIC, VERSION] => return Err(DecodeError::Truncated),
+            [MAGIC, version, ..] => return Err(DecodeError::UnsupportedVersion(*version)),
+            legacy => Self::run_length_decode(legacy)?,
+        };
+        if nodes.len() > MAX_NODES {
+            return Err(DecodeError::TooLong);
+        }
+        let program = Self::from_vec(nodes);
+        program.validate()?;
+        Ok(program)
+    }
+
     pub fn base64(&self) -> String {
-        BASE64_STANDARD.encode(Self::run_length_encode(&self.nodes))
+        BASE64_STANDARD.encode(self.encode())
     }
 
-    pub fn from_base64(str: &str) -> Self {
-        Self {
-            nodes: Self::run_length_decode(&BASE64_STANDARD.decode(str).expect("invalid base64")),
-            _marker: PhantomData,
+    pub fn from_base64(str: &str) -> Result<Self, DecodeError> {
+        Self::decode(&BASE64_STANDARD.decode(str)?)
+    }
+
+    // every node reachable from the root is known to the context and has all
+    // of its children, so the program can be evaluated without panicking
+    pub fn validate(&self) -> Result<(), DecodeError> {
+        if self
+            .nodes
+            .first()
+            .is_none_or(|node| Node::from(*node).is_null())
+        {
+            return Err(DecodeError::MissingRoot);
         }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            match Node::from(self.nodes[index]) {
+                Node::Terminal(terminal) if terminal >= C::num_terminals() => {
+                    return Err(DecodeError::UnknownTerminal {
+                        index,
+                        terminal,
+                        num_terminals: C::num_terminals(),
+                    })
+                }
+                Node::Internal(internal) if internal >= C::num_internals() => {
+                    return Err(DecodeError::UnknownInternal {
+                        index,
+                        internal,
+                        num_internals: C::num_internals(),
+                    })
+                }
+                Node::Internal(internal) => {
+                    for child in Self::child_indices(index, C::internal_num_children(internal)) {
+                        match self.nodes.get(child).map(|node| Node::from(*node)) {
+                            None | Some(Node::Null) => {
+                                return Err(DecodeError::MissingChild { index, child })
+                            }
+                            Some(_) => stack.push(child),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
     }
 
     pub fn verify(&self) {
@@ -268,6 +551,162 @@ impl<C: ProgramContext> Program<C> {
         });
     }
 
+    // preorder node bytes of the subtree at `index`, with the operands of
+    // commutative internals in byte order
+    fn canonical_preorder(&self, index: usize) -> Vec<u8> {
+        let byte = self.nodes[index];
+        match Node::from(byte) {
+            Node::Internal(i) => {
+                let mut children = Self::child_indices(index, C::internal_num_children(i))
+                    .map(|child_index| self.canonical_preorder(child_index))
+                    .collect::<Vec<_>>();
+                if C::is_commutative(i) {
+                    children.sort();
+                }
+                std::iter::once(byte).chain(children.concat()).collect()
+            }
+            _ => vec![byte],
+        }
+    }
+
+    fn fill_preorder<'b>(&mut self, index: usize, preorder: &mut impl Iterator<Item = &'b u8>) {
+        let byte = *preorder.next().unwrap();
+        let num_children = match Node::from(byte) {
+            Node::Internal(i) => C::internal_num_children(i),
+            _ => 0,
+        };
+        self.generate_at(index, num_children, byte, |p, _, child_index| {
+            p.fill_preorder(child_index, preorder)
+        });
+    }
+
+    // structurally equivalent programs share one canonical form: commutative
+    // operands are sorted and nodes outside the tree are dropped, constants
+    // are already quantized by their encoding
+    pub fn canonicalize(&mut self) {
+        let preorder = self.canonical_preorder(0);
+        self.nodes.clear();
+        self.fill_preorder(0, &mut preorder.iter());
+    }
+
+    // the canonical form of the subtree at `index`, as a program of its own
+    pub fn subtree(&self, index: usize) -> Self {
+        let preorder = self.canonical_preorder(index);
+        let mut program = Self::new();
+        program.fill_preorder(0, &mut preorder.iter());
+        program
+    }
+
+    // interval analysis: subtrees that always take the same value, if a
+    // constant can hold it, become that constant, and internals that always
+    // take the value of a child become that child. Terminals take the values
+    // of their ranges with `ranged`, any finite value otherwise. Returns the
+    // number of nodes removed
+    pub fn fold(&mut self, ranged: bool) -> usize {
+        let before = self.all_active_indices().len();
+        self.fold_at(0, ranged);
+        before - self.all_active_indices().len()
+    }
+
+    fn fold_at(&mut self, index: usize, ranged: bool) -> Interval {
+        match Node::from(self.nodes[index]) {
+            Node::Const(value) => (value, value),
+            Node::Terminal(terminal) if ranged => C::terminal_range(terminal),
+            Node::Terminal(_) => UNBOUNDED,
+            Node::Internal(internal) => {
+                let children = Self::child_indices(index, C::internal_num_children(internal))
+                    .collect::<SmallVec<[usize; MAX_PROGRAM_NODE_CHILDREN]>>();
+                let ranges = children
+                    .iter()
+                    .map(|child| self.fold_at(*child, ranged))
+                    .collect::<SmallVec<[Interval; MAX_PROGRAM_NODE_CHILDREN]>>();
+                if let Some(dominant) = C::dominant_child(internal, &ranges) {
+                    let preorder = self.canonical_preorder(children[dominant]);
+                    self.clear_subtree(index);
+                    self.fill_preorder(index, &mut preorder.iter());
+                    return ranges[dominant];
+                }
+                let range = C::internal_range(internal, &ranges);
+                let byte = u8::from(Node::Const(range.0));
+                if range.0 == range.1
+                    && matches!(Node::from(byte), Node::Const(value) if value == range.0)
+                {
+                    self.clear_subtree(index);
+                    self.nodes[index] = byte;
+                }
+                range
+            }
+            Node::Null => unreachable!(),
+        }
+    }
+
+    // FNV-1a of the canonical form, stable between runs and builds
+    pub fn structural_hash(&self) -> u64 {
+        self.canonical_preorder(0)
+            .into_iter()
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    // share of the nodes the programs do not have in common at the same
+    // positions of the tree, 0 for equal and 1 for disjoint programs; both
+    // should be canonical
+    pub fn aligned_distance(&self, other: &Self) -> f32 {
+        let size = |p: &Self| {
+            p.nodes
+                .iter()
+                .filter(|n| !Node::from(**n).is_null())
+                .count()
+        };
+        let common = self
+            .nodes
+            .iter()
+            .zip(&other.nodes)
+            .filter(|(a, b)| a == b && !Node::from(**a).is_null())
+            .count();
+        match size(self) + size(other) {
+            0 => 0.0,
+            total => 1.0 - 2.0 * common as f32 / total as f32,
+        }
+    }
+
+    // node-overlap distance of the canonical forms
+    pub fn distance(&self, other: &Self) -> f32 {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        a.canonicalize();
+        b.canonicalize();
+        a.aligned_distance(&b)
+    }
+
+    // math mode LaTeX of the expression, for papers
+    pub fn latex(&self) -> String {
+        self.latex_at(0).0
+    }
+
+    fn latex_at(&self, index: usize) -> (String, bool) {
+        match Node::from(self.nodes[index]) {
+            Node::Const(value) if value < 0.0 => (format!("\\left({value}\\right)"), false),
+            Node::Const(value) => (value.to_string(), false),
+            Node::Terminal(terminal) => (C::latex_terminal(terminal), false),
+            Node::Internal(internal) => {
+                let children = Self::child_indices(index, C::internal_num_children(internal))
+                    .map(|child_index| self.latex_at(child_index))
+                    .collect::<Vec<_>>();
+                C::latex_internal(internal, &children)
+            }
+            Node::Null => ("\\mathit{INVALID}".to_string(), false),
+        }
+    }
+
+    // levels below the root, from the deepest node in use
+    pub fn depth(&self) -> usize {
+        self.nodes
+            .iter()
+            .rposition(|node| !Node::from(*node).is_null())
+            .map_or(0, |index| (index + 1).ilog2() as usize)
+    }
+
     pub fn clear_subtree(&mut self, index: usize) {
         let mut indices = Vec::new();
         self.collect_all_active_indices(&mut indices, index);
@@ -290,6 +729,135 @@ impl<C: ProgramContext> Display for Program<C> {
     }
 }
 
+// the node array, validated on the way back in
+impl<C: ProgramContext> Serialize for Program<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.nodes.serialize(serializer)
+    }
+}
+
+impl<'de, C: ProgramContext> Deserialize<'de> for Program<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let program = Self::from_vec(Vec::deserialize(deserializer)?);
+        program.validate().map_err(de::Error::custom)?;
+        Ok(program)
+    }
+}
+
+struct Symbol<F>(F);
+
+impl<F: Fn(&mut Formatter<'_>) -> fmt::Result> Display for Symbol<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        (self.0)(f)
+    }
+}
+
+// tokens of an expression as printed by `Display`
+struct Parser<'s> {
+    str: &'s str,
+    position: usize,
+    terminals: Vec<String>,
+    internals: Vec<String>,
+}
+
+impl<'s> Parser<'s> {
+    fn token(&mut self) -> (usize, &'s str) {
+        let rest = self.str[self.position..].trim_start();
+        let start = self.str.len() - rest.len();
+        let len = match rest.chars().next() {
+            Some('(' | ')' | ',') => 1,
+            _ => rest
+                .find(|c: char| c.is_whitespace() || "(),".contains(c))
+                .unwrap_or(rest.len()),
+        };
+        self.position = start + len;
+        (start, &rest[..len])
+    }
+
+    fn expect(&mut self, expected: &'static str) -> Result<(), DecodeError> {
+        match self.token() {
+            (_, token) if token == expected => Ok(()),
+            (position, token) => Err(DecodeError::Syntax {
+                position,
+                expected,
+                found: token.to_string(),
+            }),
+        }
+    }
+
+    fn node<C: ProgramContext>(
+        &mut self,
+        program: &mut Program<C>,
+        index: usize,
+    ) -> Result<(), DecodeError> {
+        let (position, token) = self.token();
+        program.ensure_index_exist(index);
+        if let Some(internal) = self.internals.iter().position(|name| name == token) {
+            program.nodes[index] = Node::Internal(internal).into();
+            self.expect("(")?;
+            let children = Program::<C>::child_indices(index, C::internal_num_children(internal));
+            for (i, child_index) in children.enumerate() {
+                if i != 0 {
+                    self.expect(",")?;
+                }
+                self.node(program, child_index)?;
+            }
+            self.expect(")")
+        } else if let Some(terminal) = self.terminals.iter().position(|name| name == token) {
+            program.nodes[index] = Node::Terminal(terminal).into();
+            Ok(())
+        } else if let Ok(value) = token.parse::<f32>() {
+            let byte = u8::from(Node::Const(value));
+            match Node::from(byte) {
+                Node::Const(decoded) if decoded == value => {
+                    program.nodes[index] = byte;
+                    Ok(())
+                }
+                _ => Err(DecodeError::ConstOutOfRange(value)),
+            }
+        } else if token.is_empty() || "(),".contains(token) {
+            Err(DecodeError::Syntax {
+                position,
+                expected: "a node",
+                found: token.to_string(),
+            })
+        } else {
+            Err(DecodeError::UnknownNode(token.to_string()))
+        }
+    }
+}
+
+// the expressions printed by `Display`, e.g. `sum(TERM1, 0.5)`
+impl<C: ProgramContext> FromStr for Program<C> {
+    type Err = DecodeError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            str,
+            position: 0,
+            terminals: (0..C::num_terminals())
+                .map(|i| Symbol(|f: &mut Formatter<'_>| C::format_terminal(i, f)).to_string())
+                .collect(),
+            internals: (0..C::num_internals())
+                .map(|i| Symbol(|f: &mut Formatter<'_>| C::format_internal(i, f)).to_string())
+                .collect(),
+        };
+        let mut program = Self::new();
+        parser.node(&mut program, 0)?;
+        parser.expect("")?;
+        program.validate()?;
+        Ok(program)
+    }
+}
+
+impl<C: ProgramContext> TryFrom<&str> for Program<C> {
+    type Error = DecodeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::from_base64(value)
+    }
+}
+
 #[test]
 fn rle() {
     use crate::sim::ctx::SequencingContext;
@@ -300,7 +868,156 @@ fn rle() {
     assert_eq!(
         Program::<SequencingContext>::run_length_decode(
             &Program::<SequencingContext>::run_length_encode(&[1, 2, 3, 3, 3])
-        ),
+        )
+        .unwrap(),
         &[1, 2, 3, 3, 3]
     );
 }
+
+#[test]
+fn decode_errors() {
+    use crate::sim::ctx::SequencingContext;
+    type P = Program<SequencingContext<'static>>;
+    let encode = |nodes: Vec<Node>| P::from_vec(nodes.into_iter().map(u8::from).collect()).base64();
+    let program = encode(vec![Node::Internal(0), Node::Terminal(1), Node::Const(0.5)]);
+    assert_eq!(
+        P::from_base64(&program).unwrap().to_string(),
+        "sum(TERM1, 0.5)"
+    );
+    assert_eq!(P::from_base64(&program).unwrap().used_terminals(), [1]);
+    assert!(matches!(P::from_base64("!"), Err(DecodeError::Base64(_))));
+    assert!(matches!(
+        P::from_base64("AQ=="),
+        Err(DecodeError::OddLength(1))
+    ));
+    assert!(matches!(P::from_base64(""), Err(DecodeError::MissingRoot)));
+    assert!(matches!(
+        P::from_base64(&encode(vec![Node::Internal(0), Node::Terminal(1)])),
+        Err(DecodeError::MissingChild { index: 0, child: 2 })
+    ));
+    assert!(matches!(
+        P::from_base64(&encode(vec![Node::Terminal(63)])),
+        Err(DecodeError::UnknownTerminal { terminal: 63, .. })
+    ));
+
+    let json = serde_json::to_string(&P::from_base64(&program).unwrap()).unwrap();
+    assert_eq!(json, "[193,130,72]");
+    assert!(serde_json::from_str::<P>(&json).is_ok());
+    assert!(serde_json::from_str::<P>("[193,130]").is_err());
+}
+
+#[test]
+fn program_format() {
+    use crate::sim::ctx::SequencingContext;
+    type P = Program<SequencingContext<'static>>;
+    let nodes = [Node::Internal(0), Node::Terminal(1), Node::Const(0.5)].map(u8::from);
+    let legacy = BASE64_STANDARD.encode(P::run_length_encode(&nodes));
+    assert_eq!(P::from_base64(&legacy).unwrap().nodes, nodes);
+
+    let program = P::from_vec(nodes.to_vec());
+    assert_eq!(program.encode()[2], RAW);
+    assert_eq!(P::from_base64(&program.base64()).unwrap().nodes, nodes);
+    let mut sparse = program.clone();
+    sparse.ensure_index_exist(300);
+    assert_eq!(sparse.encode()[2], VARINT_RLE);
+    assert_eq!(P::decode(&sparse.encode()).unwrap().nodes, sparse.nodes);
+
+    assert!(matches!(
+        P::decode(&[MAGIC, VERSION + 1]),
+        Err(DecodeError::UnsupportedVersion(_))
+    ));
+    assert!(matches!(
+        P::decode(&[MAGIC, VERSION, VARINT_RLE, 129, 0x80]),
+        Err(DecodeError::Truncated)
+    ));
+}
+
+#[test]
+fn canonical_form() {
+    use crate::sim::ctx::SequencingContext;
+    type P = Program<SequencingContext<'static>>;
+    let program = |nodes: Vec<Node>| P::from_vec(nodes.into_iter().map(u8::from).collect());
+    // sum(sub(T2, T1), T0) and sum(T0, sub(T2, T1))
+    let a = program(vec![
+        Node::Internal(0),
+        Node::Internal(1),
+        Node::Terminal(0),
+        Node::Terminal(2),
+        Node::Terminal(1),
+    ]);
+    let mut b = program(vec![
+        Node::Internal(0),
+        Node::Terminal(0),
+        Node::Internal(1),
+        Node::Null,
+        Node::Null,
+        Node::Terminal(2),
+        Node::Terminal(1),
+    ]);
+    assert_eq!(a.structural_hash(), b.structural_hash());
+    b.canonicalize();
+    assert_eq!(b.to_string(), "sum(TERM0, sub(TERM2, TERM1))");
+    let swapped = program(vec![
+        Node::Internal(0),
+        Node::Internal(1),
+        Node::Terminal(0),
+        Node::Terminal(1),
+        Node::Terminal(2),
+    ]);
+    assert_ne!(a.structural_hash(), swapped.structural_hash());
+}
+
+#[test]
+fn node_overlap() {
+    use crate::sim::ctx::SequencingContext;
+    type P = Program<SequencingContext<'static>>;
+    let program = |nodes: Vec<Node>| P::from_vec(nodes.into_iter().map(u8::from).collect());
+    let a = program(vec![
+        Node::Internal(0),
+        Node::Terminal(0),
+        Node::Terminal(1),
+    ]);
+    let b = program(vec![
+        Node::Internal(0),
+        Node::Terminal(1),
+        Node::Terminal(0),
+    ]);
+    let c = program(vec![
+        Node::Internal(1),
+        Node::Terminal(0),
+        Node::Terminal(2),
+    ]);
+    assert_eq!(a.distance(&b), 0.0);
+    assert_eq!(a.distance(&c), 1.0 - 2.0 / 6.0);
+    assert_eq!(a.distance(&program(vec![Node::Terminal(3)])), 1.0);
+}
+
+#[test]
+fn parse_expressions() {
+    use crate::sim::ctx::SequencingContext;
+    type P = Program<SequencingContext<'static>>;
+    let program = "max(sum(TERM1, -0.25), div(TERM11, 2))"
+        .parse::<P>()
+        .unwrap();
+    assert_eq!(
+        program.to_string(),
+        "max(sum(TERM1, -0.25), div(TERM11, 2))"
+    );
+    assert_eq!(program.depth(), 2);
+    assert!(matches!(
+        "sum(TERM1)".parse::<P>(),
+        Err(DecodeError::Syntax { expected: ",", .. })
+    ));
+    assert!(matches!(
+        "TERM12".parse::<P>(),
+        Err(DecodeError::UnknownNode(_))
+    ));
+    assert!(matches!(
+        "mul(TERM0, 0.3)".parse::<P>(),
+        Err(DecodeError::ConstOutOfRange(_))
+    ));
+    assert!(matches!(
+        "TERM0 TERM1".parse::<P>(),
+        Err(DecodeError::Syntax { .. })
+    ));
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+
+// island model over TCP: every island sends its best individuals to the
+// coordinator every few generations and gets back the best ones last sent
+// by the other islands; messages are JSON lists of migrants, one per line
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Migrant {
+    // `base64` encodings of the rules
+    pub routing: String,
+    pub sequencing: String,
+    pub fitness: f32,
+}
+
+pub struct Island {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Island {
+    pub fn connect(address: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let writer = TcpStream::connect(address)?;
+        Ok(Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        })
+    }
+
+    pub fn exchange(&mut self, emigrants: &[Migrant]) -> anyhow::Result<Vec<Migrant>> {
+        writeln!(self.writer, "{}", serde_json::to_string(emigrants)?)?;
+        let mut line = String::new();
+        anyhow::ensure!(
+            self.reader.read_line(&mut line)? > 0,
+            "the coordinator closed the connection"
+        );
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[derive(Default)]
+struct Pool {
+    // the last migrants of every island
+    migrants: BTreeMap<usize, Vec<Migrant>>,
+    best: Option<f32>,
+}
+
+impl Pool {
+    // as many of the best migrants of the other islands as were sent
+    fn exchange(&mut self, island: usize, emigrants: Vec<Migrant>) -> Vec<Migrant> {
+        let count = emigrants.len();
+        self.migrants.insert(island, emigrants);
+        let mut immigrants = self
+            .migrants
+            .iter()
+            .filter(|(other, _)| **other != island)
+            .flat_map(|(_, migrants)| migrants.iter().cloned())
+            .collect::<Vec<_>>();
+        immigrants.sort_by(|a, b| a.fitness.total_cmp(&b.fitness));
+        immigrants.truncate(count);
+        immigrants
+    }
+}
+
+fn serve_island(
+    island: usize,
+    stream: TcpStream,
+    pool: &Mutex<Pool>,
+    on_best: &(dyn Fn(usize, &Migrant) + Send + Sync),
+) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let emigrants: Vec<Migrant> = serde_json::from_str(&line?)?;
+        let immigrants = {
+            let mut pool = pool.lock().expect("mutex lock failure");
+            for migrant in &emigrants {
+                if pool.best.is_none_or(|best| migrant.fitness < best) {
+                    pool.best = Some(migrant.fitness);
+                    on_best(island, migrant);
+                }
+            }
+            pool.exchange(island, emigrants)
+        };
+        writeln!(writer, "{}", serde_json::to_string(&immigrants)?)?;
+    }
+    Ok(())
+}
+
+// runs the coordinator until the process is stopped, islands are numbered in
+// the order they connect; `on_best` sees every new global best
+pub fn coordinate(
+    listener: TcpListener,
+    on_best: impl Fn(usize, &Migrant) + Send + Sync + 'static,
+    on_error: impl Fn(usize, anyhow::Error) + Send + Sync + 'static,
+) -> anyhow::Result<()> {
+    let pool = Arc::new(Mutex::new(Pool::default()));
+    let on_best = Arc::new(on_best);
+    let on_error = Arc::new(on_error);
+    for (island, stream) in listener.incoming().enumerate() {
+        let stream = stream?;
+        let (pool, on_best, on_error) = (pool.clone(), on_best.clone(), on_error.clone());
+        thread::spawn(move || {
+            if let Err(err) = serve_island(island, stream, &pool, &*on_best) {
+                on_error(island, err);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[test]
+fn migrant_exchange() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let bests = Arc::new(Mutex::new(Vec::new()));
+    let seen = bests.clone();
+    thread::spawn(move || {
+        coordinate(
+            listener,
+            move |island, migrant| seen.lock().unwrap().push((island, migrant.fitness)),
+            |_, err| panic!("{err}"),
+        )
+    });
+    let migrant = |fitness| Migrant {
+        routing: String::new(),
+        sequencing: String::new(),
+        fitness,
+    };
+    let mut a = Island::connect(address).unwrap();
+    assert!(a.exchange(&[migrant(2.0)]).unwrap().is_empty());
+    let mut b = Island::connect(address).unwrap();
+    let received = b.exchange(&[migrant(1.0), migrant(3.0)]).unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].fitness, 2.0);
+    assert_eq!(a.exchange(&[migrant(2.0)]).unwrap()[0].fitness, 1.0);
+    assert_eq!(*bests.lock().unwrap(), [(0, 2.0), (1, 1.0)]);
+}
@@ -0,0 +1,37 @@
+use lazy_static::lazy_static;
+
+use log::Logger;
+
+pub mod ablation;
+pub mod aggregate;
+pub mod bench;
+pub mod config;
+#[cfg(feature = "tui")]
+pub mod dashboard;
+pub mod gp;
+pub mod island;
+pub mod log;
+pub mod objective;
+pub mod pareto;
+pub mod progress;
+pub mod race;
+pub mod robustness;
+pub mod routes;
+pub mod significance;
+pub mod sim;
+pub mod sweep;
+pub mod tightness;
+pub mod web;
+
+lazy_static! {
+    pub static ref MAIN: Logger = Logger::new("MAIN");
+    pub static ref HEU: Logger = Logger::new("HEU");
+    pub static ref SIM: Logger = Logger::new("SIM");
+    pub static ref GP: Logger = Logger::new("GP");
+    pub static ref LASTPOP: Logger = Logger::new("LASTPOP");
+    pub static ref POP: Logger = Logger::new("POP");
+    pub static ref LASTROUTE: Logger = Logger::new("LASTROUTE");
+    pub static ref ROUTE: Logger = Logger::new("ROUTE");
+    pub static ref ROUTEEVAL: Logger = Logger::new("ROUTEEVAL");
+    pub static ref DEBUG: Logger = Logger::new("DEBUG");
+}
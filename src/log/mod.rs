@@ -1,7 +1,14 @@
-use std::{env::var, fmt::Display, fs::File, io::Write, sync::Mutex};
+use std::{
+    cell::Cell,
+    env::var,
+    fmt::Display,
+    fs::File,
+    io::{self, Write},
+    sync::Mutex,
+};
 
 use chrono::Local;
-use miniserde::{json, Serialize};
+use serde::Serialize;
 
 #[macro_export]
 macro_rules! log {
@@ -20,6 +27,19 @@ macro_rules! log {
     };
 }
 
+thread_local! {
+    static MUTED: Cell<bool> = const { Cell::new(false) };
+}
+
+// runs `f` with every logger silent on this thread, for what only explores
+// outcomes that never happen
+pub fn muted<T>(f: impl FnOnce() -> T) -> T {
+    let before = MUTED.replace(true);
+    let result = f();
+    MUTED.set(before);
+    result
+}
+
 pub enum LogTarget {
     Stdout,
     Stderr,
@@ -32,17 +52,31 @@ fn now() -> i64 {
         .expect("out of range datetime")
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("cannot open log file {path}: {source}")]
+pub struct LogError {
+    pub path: String,
+    pub source: io::Error,
+}
+
 impl LogTarget {
-    pub fn parse(str: &str) -> Option<LogTarget> {
+    // an empty string turns logging off, anything but `stdout` and `stderr`
+    // is a file created or truncated for the run
+    pub fn parse(str: &str) -> Result<Option<LogTarget>, LogError> {
         if str.trim().is_empty() {
-            return None;
+            return Ok(None);
         }
 
-        Some(match str {
+        Ok(Some(match str {
             "stdout" => LogTarget::Stdout,
             "stderr" => LogTarget::Stderr,
-            str => LogTarget::File(Mutex::new(File::open(str).expect("fail to open log file"))),
-        })
+            path => LogTarget::File(Mutex::new(File::create(path).map_err(|source| {
+                LogError {
+                    path: path.to_string(),
+                    source,
+                }
+            })?)),
+        }))
     }
 }
 
@@ -58,14 +92,24 @@ impl Logger {
         format!("{time},{name},{value}")
     }
 
-    pub fn new(name: &str) -> Self {
-        Self {
+    // logs to the target in `LOG_<name>`
+    pub fn try_new(name: &str) -> Result<Self, LogError> {
+        Ok(Self {
             name: name.to_string(),
-            target: LogTarget::parse(&var(format!("LOG_{name}")).unwrap_or_default()),
-        }
+            target: LogTarget::parse(&var(format!("LOG_{name}")).unwrap_or_default())?,
+        })
+    }
+
+    // like `try_new`, for loggers created on first use where an unusable
+    // target can only abort
+    pub fn new(name: &str) -> Self {
+        Self::try_new(name).unwrap_or_else(|err| panic!("LOG_{name}: {err}"))
     }
 
     fn write(&self, value: impl Display) {
+        if MUTED.get() {
+            return;
+        }
         match &self.target {
             Some(LogTarget::Stdout) => print!("{}", value),
             Some(LogTarget::Stderr) => eprint!("{}", value),
@@ -100,12 +144,15 @@ impl Logger {
         if comma {
             self.write(',');
         }
-        self.write(json::to_string(key));
+        self.write(serde_json::to_string(key).expect("key serialization failed"));
         self.write(':');
-        self.write(json::to_string(value));
+        self.write(serde_json::to_string(value).expect("value serialization failed"));
     }
 
     pub fn log(&self, value: impl Display) {
+        if MUTED.get() {
+            return;
+        }
         let value = self.args(value);
         match &self.target {
             Some(LogTarget::Stdout) => println!("{}", value),
@@ -119,6 +166,28 @@ impl Logger {
     }
 
     pub fn enabled(&self) -> bool {
-        self.target.is_some()
+        self.target.is_some() && !MUTED.get()
     }
 }
+
+#[test]
+fn log_target_errors() {
+    assert!(matches!(LogTarget::parse(" "), Ok(None)));
+    assert!(matches!(
+        LogTarget::parse("stderr"),
+        Ok(Some(LogTarget::Stderr))
+    ));
+    let err = LogTarget::parse("/nonexistent/dir/run.log").err().unwrap();
+    assert_eq!(err.path, "/nonexistent/dir/run.log");
+}
+
+#[test]
+fn muted_loggers() {
+    let logger = Logger {
+        name: "TEST".to_string(),
+        target: Some(LogTarget::Stderr),
+    };
+    assert!(logger.enabled());
+    assert!(muted(|| muted(|| !logger.enabled()) && !logger.enabled()));
+    assert!(logger.enabled());
+}
@@ -1,171 +1,1321 @@
 use std::{
     cell::RefCell,
-    env::{self, args},
+    cmp::Reverse,
+    collections::HashSet,
+    env::args,
+    fs::File,
+    hint::black_box,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::TcpListener,
+    num::NonZeroUsize,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 
-use gp::{program::Node, GPContext};
-use lazy_static::lazy_static;
-use log::Logger;
+#[cfg(feature = "tui")]
+use vrpr::dashboard;
+use vrpr::{
+    ablation, aggregate, bench, config, gp, island, log, objective, pareto, progress, race,
+    robustness, routes, significance, sim, sweep, tightness, web, GP, HEU, LASTPOP, LASTROUTE,
+    MAIN, POP,
+};
+
+use ablation::AblationResult;
+use aggregate::Table;
+use anyhow::Context;
+use bench::{measure, Report};
+use config::Config;
+#[cfg(feature = "tui")]
+use dashboard::Dashboard;
+use gp::{
+    acquire_modules,
+    grammar::Grammar,
+    linear::LinearProgram,
+    program::{DecodeError, Program, ProgramContext, MAX_NODES, MAX_PROGRAM_NODE_CHILDREN},
+    BreedError, Control, GPContext, Representation,
+};
+use island::{Island, Migrant};
 use lru::LruCache;
+use objective::Objective;
 use ordered_float::OrderedFloat;
+use pareto::Archive;
+use progress::Generation;
+use race::Race;
 use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use robustness::Robustness;
+use routes::{Replay, RouteDiff, RouteLog};
+use serde::{Deserialize, Serialize};
+use significance::mann_whitney;
 use sim::{
-    ctx::{RoutingProgram, SequencingProgram},
-    problem::Problem,
-    Simulation,
+    baseline,
+    ctx::{RoutingContext, RoutingProgram, SequencingContext, SequencingProgram},
+    dataset,
+    ensemble::Ensemble,
+    exact::{DEFAULT_BEAM_WIDTH, MAX_EXACT_QUEUE},
+    explain::Explanation,
+    insertion::Insertion,
+    instance::{Fleet, Instance},
+    local_search::{self, Improvement, Memetic},
+    narrow,
+    phenotype::Phenotype,
+    problem::{Problem, TerminalNoise, TieBreak},
+    registry::{Entry, Registry, RoutingEntry, Rules, SequencingEntry},
+    scenario::{Aggregation, Scenario},
+    sensitivity::Sensitivity,
+    simulate_days, simulate_days_with, simulate_rules, simulate_segments,
+    stats::InstanceStats,
+    trace::{simulate_traced, Trace},
+    training::{self, Perturb},
+    Float, Segment, SimulationScratch, VehicleState,
 };
+use sweep::{Parameter, SweepResult};
+use tightness::Tightness;
+use web::WebDashboard;
 
-pub mod gp;
-pub mod log;
-pub mod sim;
-
-lazy_static! {
-    static ref MAIN: Logger = Logger::new("MAIN");
-    static ref HEU: Logger = Logger::new("HEU");
-    static ref SIM: Logger = Logger::new("SIM");
-    static ref GP: Logger = Logger::new("GP");
-    static ref LASTPOP: Logger = Logger::new("LASTPOP");
-    static ref LASTROUTE: Logger = Logger::new("LASTROUTE");
-    static ref ROUTE: Logger = Logger::new("ROUTE");
-    static ref ROUTEEVAL: Logger = Logger::new("ROUTEEVAL");
-    static ref DEBUG: Logger = Logger::new("DEBUG");
-    static ref CONST_RATE: f64 = env::var("CONST_RATE")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.1);
-    static ref WEIGHT: f32 = env::var("WEIGHT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.1);
-    static ref NUM_TIME_SLOT: f32 = env::var("NUM_TIME_SLOT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(50.0);
-    static ref NUM_GEN: usize = env::var("NUM_GEN")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(100);
-    static ref POP_SIZE: usize = env::var("POP_SIZE")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(100);
-    static ref MAX_DEPTH: usize = env::var("MAX_DEPTH")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(6);
-    static ref CROSSOVER_RATE: f64 = env::var("CROSSOVER_RATE")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.8);
-    static ref MUTATION_RATE: f64 = env::var("MUTATION_RATE")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.1);
-    static ref TRAIN_FACTOR: f32 = env::var("TRAIN_FACTOR")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.2);
-    static ref STRESS_FACTOR: f32 = env::var("STRESS_FACTOR")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(1.0);
-}
-
-fn fitness(problem: &Problem, result: (f32, usize)) -> f32 {
-    let (distance, num_fail) = result;
-    let tot_dist = problem.truck_speed * problem.depot.close * problem.num_trucks as f32;
-    let weight = *WEIGHT;
-    distance / tot_dist * weight
-        + (num_fail as f32) / (problem.requests.len() as f32) * (1.0 - weight)
+#[derive(Serialize)]
+struct ConfigSnapshot {
+    const_rate: f64,
+    weight: f32,
+    num_time_slot: Float,
+    num_gen: usize,
+    pop_size: usize,
+    max_depth: usize,
+    crossover_rate: f64,
+    mutation_rate: f64,
+    train_factor: Float,
+    stress_factor: Float,
+    num_depots: usize,
+    num_satellites: usize,
+    num_scenarios: usize,
+    scenario_seed: u64,
+    tie_break: TieBreak,
+}
+
+impl ConfigSnapshot {
+    fn new(config: &Config) -> Self {
+        Self {
+            const_rate: config.const_rate,
+            weight: config.weight,
+            num_time_slot: config.num_time_slot,
+            num_gen: config.num_gen,
+            pop_size: config.pop_size,
+            max_depth: config.max_depth,
+            crossover_rate: config.crossover_rate,
+            mutation_rate: config.mutation_rate,
+            train_factor: config.train_factor,
+            stress_factor: config.stress_factor,
+            num_depots: config.num_depots,
+            num_satellites: config.num_satellites,
+            num_scenarios: config.num_scenarios,
+            scenario_seed: config.scenario_seed,
+            tie_break: config.tie_break,
+        }
+    }
+}
+
+// settings a run would only trip over midway, or silently misbehave with
+fn validate(problem: &Problem, config: &Config) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut check = |ok: bool, error: String| {
+        if !ok {
+            errors.push(error);
+        }
+    };
+    let share = |name: &str, value: f64| {
+        (
+            (0.0..=1.0).contains(&value),
+            format!("{name} ({value}) must be in [0, 1]"),
+        )
+    };
+    for (ok, error) in [
+        share("WEIGHT", config.weight as f64),
+        share("CONST_RATE", config.const_rate),
+        share("CROSSOVER_RATE", config.crossover_rate),
+        share("MUTATION_RATE", config.mutation_rate),
+        share("WARM_START_SHARE", config.warm_start_share),
+    ] {
+        check(ok, error);
+    }
+    check(
+        config.crossover_rate + config.mutation_rate <= 1.0,
+        format!(
+            "CROSSOVER_RATE and MUTATION_RATE add up to {}, more than 1",
+            config.crossover_rate + config.mutation_rate
+        ),
+    );
+    check(
+        config.stress_factor > 0.0,
+        format!("STRESS_FACTOR ({}) must be positive", config.stress_factor),
+    );
+    check(
+        !config.tui || cfg!(feature = "tui"),
+        "TUI needs a build with --features tui".to_string(),
+    );
+    check(
+        config.rolling_window >= 0.0,
+        format!(
+            "ROLLING_WINDOW ({}) must not be negative",
+            config.rolling_window
+        ),
+    );
+    check(
+        config.segments >= 1,
+        format!("SEGMENTS ({}) must be at least 1", config.segments),
+    );
+    check(
+        config.imitation_pool >= 1,
+        format!(
+            "IMITATION_POOL ({}) must be at least 1",
+            config.imitation_pool
+        ),
+    );
+    check(
+        config.exact_queue <= MAX_EXACT_QUEUE,
+        format!(
+            "EXACT_QUEUE ({}) must be at most {MAX_EXACT_QUEUE}",
+            config.exact_queue
+        ),
+    );
+    check(
+        config.train_factor > 0.0,
+        format!("TRAIN_FACTOR ({}) must be positive", config.train_factor),
+    );
+    check(
+        config.num_time_slot >= 1.0,
+        format!(
+            "NUM_TIME_SLOT ({}) must be at least 1",
+            config.num_time_slot
+        ),
+    );
+    check(
+        config.num_gen >= 1,
+        "NUM_GEN must be at least 1".to_string(),
+    );
+    check(
+        config.num_scenarios >= 1,
+        "NUM_SCENARIOS must be at least 1".to_string(),
+    );
+    // parents are picked by tournaments of 8
+    check(
+        config.pop_size >= 8,
+        format!("POP_SIZE ({}) must be at least 8", config.pop_size),
+    );
+    check(
+        config.max_depth >= 1,
+        "MAX_DEPTH must be at least 1".to_string(),
+    );
+    // a full tree of the maximum depth still has to load back from a log
+    let nodes = max_program_nodes(config.max_depth);
+    check(
+        nodes.is_some_and(|nodes| nodes < MAX_NODES),
+        format!(
+            "MAX_DEPTH ({}) allows programs of more than the {MAX_NODES} nodes a saved program may have",
+            config.max_depth
+        ),
+    );
+    check(
+        config.ablate.routing >> RoutingContext::num_terminals() == 0
+            && config.ablate.sequencing >> SequencingContext::num_terminals() == 0,
+        "ABLATE disables terminals the rules do not have".to_string(),
+    );
+    check(
+        !problem.requests.is_empty(),
+        "the instance has no requests".to_string(),
+    );
+    check(
+        problem.depot().close > 0.0,
+        format!("the depot closes at {}", problem.depot().close),
+    );
+    errors
+}
+
+// the nodes of a full tree of `depth`, as programs are laid out
+fn max_program_nodes(depth: usize) -> Option<usize> {
+    let leaves = MAX_PROGRAM_NODE_CHILDREN.checked_pow(depth.try_into().ok()?)?;
+    (leaves - 1)
+        .checked_div(MAX_PROGRAM_NODE_CHILDREN - 1)?
+        .checked_add(leaves)
+}
+
+// upper bounds of a GP run with the current settings
+#[derive(Serialize)]
+struct DryRun {
+    config: ConfigSnapshot,
+    requests: usize,
+    vehicles: usize,
+    days: usize,
+    evaluations: usize,
+    simulations: usize,
+    population_bytes: usize,
+    cache_bytes: usize,
+    // kept for `INCREMENTAL` evaluation
+    checkpoints: usize,
+}
+
+impl DryRun {
+    fn new(problem: &Problem, config: &Config) -> Self {
+        // every generation evaluates at most its offspring, the budget is only
+        // checked between generations
+        let generations = config.num_gen * config.pop_size;
+        let evaluations = config.max_evaluations.map_or(generations, |max| {
+            generations.min(max.div_ceil(config.pop_size) * config.pop_size)
+        });
+        let scenarios = Scenario {
+            seed: config.scenario_seed,
+            travel_noise: config.travel_noise,
+            service_noise: config.service_noise,
+        }
+        .replications(config.num_scenarios);
+        // two hashes and the scenario set, see `Individual::evaluate`
+        let key_bytes = 2 * 16 + 2 + Scenario::set_key(&scenarios).len();
+        let entry_bytes =
+            size_of::<(String, (f32, usize, f32))>() + key_bytes + 2 * size_of::<usize>();
+        let program_bytes = max_program_nodes(config.max_depth).unwrap_or(usize::MAX);
+        Self {
+            config: ConfigSnapshot::new(config),
+            requests: problem.requests.len(),
+            vehicles: problem.num_vehicles(),
+            days: problem.num_days,
+            evaluations,
+            simulations: evaluations * config.num_scenarios * problem.num_days,
+            // parents and offspring, each with both rules
+            population_bytes: 2 * config.pop_size * 2usize.saturating_mul(program_bytes),
+            cache_bytes: evaluations * entry_bytes,
+            checkpoints: match config.incremental && Trace::supported(problem) {
+                true => 2 * config.pop_size * config.num_scenarios * config.incremental_checkpoints,
+                false => 0,
+            },
+        }
+    }
 }
 
 #[allow(non_snake_case)]
-fn heuristics(problem: &Problem) -> anyhow::Result<()> {
-    let CR = RoutingProgram::terminal(3);
-    let CS = SequencingProgram::from_vec(vec![
-        Node::Internal(5).into(),
-        Node::Terminal(0).into(),
-        Node::Terminal(4).into(),
-    ]);
-    let W = SequencingProgram::terminal(3);
-    let WIQ = RoutingProgram::terminal(1);
-    for (name, r, s) in [("C+C", &CR, &CS), ("C+W", &CR, &W), ("WIQ+C", &WIQ, &CS)] {
-        let mut simulation = Simulation::new(problem, r, s);
-        let result = simulation.simulate_until(problem.depot.close / *NUM_TIME_SLOT, f32::MAX);
-        log!(
-            HEU,
-            "heuristic_result",
-            name = name,
-            result = result,
-            fitness = fitness(problem, result)
-        );
+fn heuristics(problem: &Problem, config: &Config) -> anyhow::Result<()> {
+    let CR = Entry::Program(baseline::nearest_vehicle());
+    let CS = Entry::Program(baseline::cost());
+    let W = Entry::Program(baseline::demand());
+    let WIQ = Entry::Program(baseline::work_in_queue());
+    // the nearest vehicle with searched queue orders, an upper baseline for
+    // the sequencing rules
+    let mut searched = problem.clone();
+    if searched.sequencing_search == 0 {
+        searched.sequencing_search = DEFAULT_BEAM_WIDTH;
+    }
+    // the insertion dispatchers, which need no rules
+    let inserting = |insertion| Problem {
+        insertion: Some(insertion),
+        ..problem.clone()
+    };
+    let (cheapest, regret2, regret3) = (
+        inserting(Insertion::Cheapest),
+        inserting(Insertion::Regret(2)),
+        inserting(Insertion::Regret(3)),
+    );
+    // a random feasible vehicle and first-in-first-out queues, the floor
+    let (random, fifo) = select("random", "fifo")?;
+    for (name, problem, r, s) in [
+        ("C+C", problem, &CR, &CS),
+        ("C+W", problem, &CR, &W),
+        ("WIQ+C", problem, &WIQ, &CS),
+        ("C+OPT", &searched, &CR, &CS),
+        ("CI", &cheapest, &CR, &CS),
+        ("REGRET2", &regret2, &CR, &CS),
+        ("REGRET3", &regret3, &CR, &CS),
+        ("RAND+FIFO", problem, &random, &fifo),
+    ] {
+        heuristic(name, problem, r, s, config);
+    }
+    // and the pairs of rules named in `HEURISTICS`
+    for pair in config.heuristics.iter() {
+        let (routing, sequencing) = pair
+            .split_once('+')
+            .with_context(|| format!("HEURISTICS: {pair:?} is not routing+sequencing"))?;
+        let (r, s) = select(routing, sequencing)?;
+        heuristic(pair, problem, &r, &s, config);
     }
     Ok(())
 }
 
-#[derive(Debug, Clone)]
+fn heuristic(
+    name: &str,
+    problem: &Problem,
+    r: &RoutingEntry,
+    s: &SequencingEntry,
+    config: &Config,
+) {
+    let time_slot = problem.depot().close / config.num_time_slot;
+    let result = simulate_rules(problem, r, s, Scenario::default(), time_slot, |_, _| ());
+    log!(
+        HEU,
+        "heuristic_result",
+        name = name,
+        result = (result.distance, result.failed),
+        lateness = result.lateness,
+        overtime = result.overtime,
+        rolled_over = result.rolled_over,
+        profit = problem.profit(result.revenue, result.distance),
+        objective = config.objective.values(problem, &result),
+        metrics = result,
+        fitness = config.objective.fitness(problem, &result)
+    );
+}
+
+// a pair of rules given by their names in the registry, or as expressions or
+// base64 strings
+fn select(
+    routing: &str,
+    sequencing: &str,
+) -> anyhow::Result<(RoutingEntry<'static>, SequencingEntry<'static>)> {
+    let registry = Registry::default();
+    let routing = match registry.select_routing(routing) {
+        Some(rule) => rule,
+        None => Entry::Program(program(routing).context("invalid routing rule")?),
+    };
+    let sequencing = match registry.select_sequencing(sequencing) {
+        Some(rule) => rule,
+        None => Entry::Program(program(sequencing).context("invalid sequencing rule")?),
+    };
+    Ok((routing, sequencing))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Individual<'a> {
     routing: RoutingProgram<'a>,
     sequencing: SequencingProgram<'a>,
+    // the rules of the later periods of the day with `SEGMENTS`, none for a
+    // policy of a single period
+    #[serde(default)]
+    segments: Vec<Segment<'a>>,
+    // its own crossover and mutation rates with `SELF_ADAPTIVE`, the global
+    // ones when none
+    #[serde(default)]
+    rates: Option<(f64, f64)>,
+    // what the rules of the first period are decoded from with
+    // `REPRESENTATION`, none for rules bred as trees
+    #[serde(default)]
+    genome: Option<Genome>,
     pub result: Option<(f32, usize, f32)>,
+    // the fitness scaled by the crowding of its niche, with fitness sharing
+    #[serde(skip)]
+    shared: Option<f32>,
+    // the structural key of the individual it was bred from, whose traced
+    // runs it starts from with `INCREMENTAL`
+    #[serde(skip)]
+    parent: Option<(u64, u64)>,
 }
 
-impl<'a> Individual<'a> {
-    pub fn ramp_half_and_half(gpc: &GPContext<impl RngCore>) -> Vec<Self> {
-        let r_pop = gpc.ramp_half_and_half();
-        let s_pop = gpc.ramp_half_and_half();
-        r_pop
-            .into_iter()
-            .zip(s_pop)
-            .map(|(routing, sequencing)| Self {
+// subtrees common among the elites with `MODULES`, which mutation grafts
+// whole
+#[derive(Default)]
+struct Modules<'a> {
+    routing: Vec<RoutingProgram<'a>>,
+    sequencing: Vec<SequencingProgram<'a>>,
+}
+
+impl<'a> Modules<'a> {
+    fn acquire(elites: &[Individual<'a>], num_modules: usize) -> Self {
+        // shallow enough to graft into most of a tree
+        const DEPTH: usize = 2;
+        let periods = elites.iter().map(Individual::periods).collect::<Vec<_>>();
+        Self {
+            routing: acquire_modules(
+                periods.iter().map(|p| p.iter().map(|(r, _)| r).collect()),
+                DEPTH,
+                num_modules,
+            ),
+            sequencing: acquire_modules(
+                periods.iter().map(|p| p.iter().map(|(_, s)| s).collect()),
+                DEPTH,
+                num_modules,
+            ),
+        }
+    }
+}
+
+// the genotype of the rules of the first period, for representations other
+// than the tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Genome {
+    Linear {
+        routing: LinearProgram,
+        sequencing: LinearProgram,
+    },
+    // codons mapped through the installed grammar
+    Grammatical {
+        routing: Vec<u8>,
+        sequencing: Vec<u8>,
+    },
+}
+
+// the grammar of `REPRESENTATION=grammatical`, with its `<routing>` and
+// `<sequencing>` rules
+fn grammar(config: &Config) -> (&Grammar, usize, usize) {
+    let grammar = (config.grammar.as_ref()).expect("read with the representation");
+    let start = |name| grammar.start(name).expect("checked at startup");
+    (grammar, start("routing"), start("sequencing"))
+}
+
+impl Genome {
+    // a population of them for `REPRESENTATION`, none with trees
+    fn ramp(gpc: &GPContext<impl RngCore>, config: &Config) -> Option<Vec<Self>> {
+        let genomes = match config.representation {
+            Representation::Tree => return None,
+            Representation::Linear => {
+                let routing = gpc.ramp_linear::<RoutingContext>();
+                let sequencing = gpc.ramp_linear::<SequencingContext>();
+                (routing.into_iter().zip(sequencing))
+                    .map(|(routing, sequencing)| Self::Linear {
+                        routing,
+                        sequencing,
+                    })
+                    .collect()
+            }
+            Representation::Grammatical => {
+                let (grammar, r, s) = grammar(config);
+                let routing = gpc.ramp_grammatical::<RoutingContext>(grammar, r);
+                let sequencing = gpc.ramp_grammatical::<SequencingContext>(grammar, s);
+                (routing.into_iter().zip(sequencing))
+                    .map(|(routing, sequencing)| Self::Grammatical {
+                        routing,
+                        sequencing,
+                    })
+                    .collect()
+            }
+        };
+        Some(genomes)
+    }
+
+    // the rules it decodes into, which breeding keeps within `MAX_DEPTH`
+    fn decode<'a>(&self, config: &Config) -> Segment<'a> {
+        let max_depth = config.max_depth;
+        let (routing, sequencing) = match self {
+            Self::Linear {
                 routing,
                 sequencing,
-                result: None,
-            })
-            .collect()
+            } => (routing.decode(max_depth), sequencing.decode(max_depth)),
+            Self::Grammatical {
+                routing,
+                sequencing,
+            } => {
+                let (grammar, r, s) = grammar(config);
+                (
+                    grammar.decode(r, routing, max_depth),
+                    grammar.decode(s, sequencing, max_depth),
+                )
+            }
+        };
+        (
+            routing.expect("bred within the maximum depth"),
+            sequencing.expect("bred within the maximum depth"),
+        )
     }
 
-    pub fn crossover_with(&self, gpc: &GPContext<impl RngCore>, other: &Self) -> (Self, Self) {
-        let (r1, r2) = gpc.crossover(&self.routing, &other.routing);
-        let (s1, s2) = gpc.crossover(&self.sequencing, &other.sequencing);
-        (
+    fn crossover(
+        &self,
+        gpc: &GPContext<impl RngCore>,
+        other: &Self,
+        config: &Config,
+    ) -> Result<(Self, Self), BreedError> {
+        match (self, other) {
+            (
+                Self::Linear {
+                    routing: r1,
+                    sequencing: s1,
+                },
+                Self::Linear {
+                    routing: r2,
+                    sequencing: s2,
+                },
+            ) => {
+                let (r1, r2) =
+                    gpc.linear_crossover::<RoutingContext>(r1, r2, config.linear_length)?;
+                let (s1, s2) =
+                    gpc.linear_crossover::<SequencingContext>(s1, s2, config.linear_length)?;
+                Ok((
+                    Self::Linear {
+                        routing: r1,
+                        sequencing: s1,
+                    },
+                    Self::Linear {
+                        routing: r2,
+                        sequencing: s2,
+                    },
+                ))
+            }
+            (
+                Self::Grammatical {
+                    routing: r1,
+                    sequencing: s1,
+                },
+                Self::Grammatical {
+                    routing: r2,
+                    sequencing: s2,
+                },
+            ) => {
+                let (grammar, r, s) = grammar(config);
+                let (r1, r2) = gpc.grammatical_crossover::<RoutingContext>(
+                    grammar,
+                    r,
+                    (r1, r2),
+                    config.grammar_length,
+                )?;
+                let (s1, s2) = gpc.grammatical_crossover::<SequencingContext>(
+                    grammar,
+                    s,
+                    (s1, s2),
+                    config.grammar_length,
+                )?;
+                Ok((
+                    Self::Grammatical {
+                        routing: r1,
+                        sequencing: s1,
+                    },
+                    Self::Grammatical {
+                        routing: r2,
+                        sequencing: s2,
+                    },
+                ))
+            }
+            // a run breeds a single representation
+            _ => Ok((self.clone(), other.clone())),
+        }
+    }
+
+    fn headless_crossover(
+        &self,
+        gpc: &GPContext<impl RngCore>,
+        config: &Config,
+    ) -> Result<Self, BreedError> {
+        match self {
+            Self::Linear {
+                routing,
+                sequencing,
+            } => Ok(Self::Linear {
+                routing: gpc
+                    .linear_headless_crossover::<RoutingContext>(routing, config.linear_length)?,
+                sequencing: gpc.linear_headless_crossover::<SequencingContext>(
+                    sequencing,
+                    config.linear_length,
+                )?,
+            }),
+            Self::Grammatical {
+                routing,
+                sequencing,
+            } => {
+                let (grammar, r, s) = grammar(config);
+                Ok(Self::Grammatical {
+                    routing: gpc.grammatical_headless_crossover::<RoutingContext>(
+                        grammar,
+                        r,
+                        routing,
+                        config.grammar_length,
+                    )?,
+                    sequencing: gpc.grammatical_headless_crossover::<SequencingContext>(
+                        grammar,
+                        s,
+                        sequencing,
+                        config.grammar_length,
+                    )?,
+                })
+            }
+        }
+    }
+
+    fn mutate(&self, gpc: &GPContext<impl RngCore>, config: &Config) -> Result<Self, BreedError> {
+        match self {
+            Self::Linear {
+                routing,
+                sequencing,
+            } => Ok(Self::Linear {
+                routing: gpc.linear_mutation::<RoutingContext>(routing, config.linear_length)?,
+                sequencing: gpc
+                    .linear_mutation::<SequencingContext>(sequencing, config.linear_length)?,
+            }),
+            Self::Grammatical {
+                routing,
+                sequencing,
+            } => {
+                let (grammar, r, s) = grammar(config);
+                Ok(Self::Grammatical {
+                    routing: gpc.grammatical_mutation::<RoutingContext>(grammar, r, routing)?,
+                    sequencing: gpc
+                        .grammatical_mutation::<SequencingContext>(grammar, s, sequencing)?,
+                })
+            }
+        }
+    }
+
+    // the effective and total instructions, or codons read and total, of
+    // the routing and sequencing genomes
+    fn size(&self, config: &Config) -> [(usize, usize); 2] {
+        match self {
+            Self::Linear {
+                routing,
+                sequencing,
+            } => [
+                (
+                    routing.effective::<RoutingContext>().len(),
+                    routing.instructions.len(),
+                ),
+                (
+                    sequencing.effective::<SequencingContext>().len(),
+                    sequencing.instructions.len(),
+                ),
+            ],
+            Self::Grammatical {
+                routing,
+                sequencing,
+            } => {
+                let (grammar, r, s) = grammar(config);
+                let read = |rule, codons: &[u8]| {
+                    let derived = grammar.derive(rule, codons, config.max_depth);
+                    (derived.map_or(0, |(_, read)| read), codons.len())
+                };
+                [read(r, routing), read(s, sequencing)]
+            }
+        }
+    }
+}
+
+// what individuals are scored on and how, read from the environment once
+// rather than by every evaluation
+struct Evaluation<'p> {
+    problem: &'p Problem,
+    scenarios: Vec<Scenario>,
+    time_slot: Float,
+    objective: &'p dyn Objective,
+    aggregation: Aggregation,
+    penalize_non_finite: bool,
+    // the checkpoints of traced runs with `INCREMENTAL`
+    incremental: Option<usize>,
+}
+
+impl<'p> Evaluation<'p> {
+    fn new(
+        problem: &'p Problem,
+        scenarios: Vec<Scenario>,
+        time_slot: Float,
+        config: &'p Config,
+    ) -> Self {
+        Self {
+            problem,
+            scenarios,
+            time_slot,
+            objective: &*config.objective,
+            aggregation: config.scenario_agg,
+            penalize_non_finite: config.penalize_non_finite,
+            incremental: config.incremental.then_some(config.incremental_checkpoints),
+        }
+    }
+}
+
+// work done by evaluations, cache hits are free
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    // individuals simulated
+    evaluations: usize,
+    // individuals looked up in the fitness cache
+    lookups: usize,
+    // simulator events of the evaluations
+    events: usize,
+}
+
+// kept from one evaluation to the next: the simulation buffers and the traced
+// runs of recent individuals
+struct Scratch<'p> {
+    simulation: SimulationScratch,
+    traces: LruCache<String, Rc<Vec<Trace<'p>>>>,
+}
+
+impl Scratch<'_> {
+    // with the traces of the current population and its offspring
+    fn new(num_population: usize) -> Self {
+        Self {
+            simulation: SimulationScratch::default(),
+            traces: LruCache::new(
+                NonZeroUsize::new(2 * num_population).unwrap_or(NonZeroUsize::MIN),
+            ),
+        }
+    }
+}
+
+impl<'a> Individual<'a> {
+    pub fn ramp_half_and_half(gpc: &GPContext<impl RngCore>, config: &Config) -> Vec<Self> {
+        let individual = |(routing, sequencing), genome| Self {
+            routing,
+            sequencing,
+            segments: Vec::new(),
+            result: None,
+            shared: None,
+            rates: None,
+            genome,
+            parent: None,
+        };
+        let mut pop = match Genome::ramp(gpc, config) {
+            Some(genomes) => genomes
+                .into_iter()
+                .map(|genome| individual(genome.decode(config), Some(genome)))
+                .collect::<Vec<_>>(),
+            None => {
+                let r_pop = gpc.ramp_half_and_half();
+                let s_pop = gpc.ramp_half_and_half();
+                r_pop
+                    .into_iter()
+                    .zip(s_pop)
+                    .map(|first| individual(first, None))
+                    .collect()
+            }
+        };
+        // every later period drawn like the first
+        for _ in 1..config.segments {
+            let segments = gpc
+                .ramp_half_and_half()
+                .into_iter()
+                .zip(gpc.ramp_half_and_half());
+            for (i, segment) in pop.iter_mut().zip(segments) {
+                i.segments.push(segment);
+            }
+        }
+        pop
+    }
+
+    // the rules of period `k`, those of the first for an individual with
+    // fewer periods like a saved or migrated one
+    fn segment(&self, k: usize) -> (&RoutingProgram<'a>, &SequencingProgram<'a>) {
+        match k.checked_sub(1).and_then(|k| self.segments.get(k)) {
+            Some((routing, sequencing)) => (routing, sequencing),
+            None => (&self.routing, &self.sequencing),
+        }
+    }
+
+    // the rules of every period, for simulating them
+    fn periods(&self) -> Vec<Segment<'a>> {
+        let first = (self.routing.clone(), self.sequencing.clone());
+        [vec![first], self.segments.clone()].concat()
+    }
+
+    pub fn crossover_with(
+        &self,
+        gpc: &GPContext<impl RngCore>,
+        other: &Self,
+        config: &Config,
+    ) -> Result<(Self, Self), BreedError> {
+        // rules bred as trees, like saved or migrated ones, cross as trees
+        let ((r1, s1, g1), (r2, s2, g2)) = match (&self.genome, &other.genome) {
+            (Some(g1), Some(g2)) => {
+                let (g1, g2) = g1.crossover(gpc, g2, config)?;
+                let ((r1, s1), (r2, s2)) = (g1.decode(config), g2.decode(config));
+                ((r1, s1, Some(g1)), (r2, s2, Some(g2)))
+            }
+            _ => {
+                let (r1, r2) = gpc.crossover(&self.routing, &other.routing)?;
+                let (s1, s2) = gpc.crossover(&self.sequencing, &other.sequencing)?;
+                ((r1, s1, None), (r2, s2, None))
+            }
+        };
+        // the rules of a period only cross with those of the same period
+        let (mut segments1, mut segments2) = (Vec::new(), Vec::new());
+        for k in 1..=self.segments.len().max(other.segments.len()) {
+            let ((r1, s1), (r2, s2)) = (self.segment(k), other.segment(k));
+            let (r1, r2) = gpc.crossover(r1, r2)?;
+            let (s1, s2) = gpc.crossover(s1, s2)?;
+            segments1.push((r1, s1));
+            segments2.push((r2, s2));
+        }
+        Ok((
             Self {
                 routing: r1,
                 sequencing: s1,
+                segments: segments1,
                 result: None,
+                shared: None,
+                rates: None,
+                genome: g1,
+                parent: Some(self.structural_key()),
             },
             Self {
                 routing: r2,
                 sequencing: s2,
+                segments: segments2,
                 result: None,
+                shared: None,
+                rates: None,
+                genome: g2,
+                parent: Some(other.structural_key()),
             },
-        )
+        ))
     }
 
-    pub fn mutate(&self, gpc: &GPContext<impl RngCore>) -> Self {
-        Self {
-            routing: gpc.mutation(&self.routing),
-            sequencing: gpc.mutation(&self.sequencing),
+    // the rules of every period crossed with random ones
+    pub fn headless_crossover(
+        &self,
+        gpc: &GPContext<impl RngCore>,
+        config: &Config,
+    ) -> Result<Self, BreedError> {
+        let segments = self.segments.iter().map(|(routing, sequencing)| {
+            Ok((
+                gpc.headless_crossover(routing)?,
+                gpc.headless_crossover(sequencing)?,
+            ))
+        });
+        let genome = (self.genome.as_ref())
+            .map(|genome| genome.headless_crossover(gpc, config))
+            .transpose()?;
+        let (routing, sequencing) = match &genome {
+            Some(genome) => genome.decode(config),
+            None => (
+                gpc.headless_crossover(&self.routing)?,
+                gpc.headless_crossover(&self.sequencing)?,
+            ),
+        };
+        Ok(Self {
+            routing,
+            sequencing,
+            segments: segments.collect::<Result<_, BreedError>>()?,
+            genome,
+            result: None,
+            shared: None,
+            parent: Some(self.structural_key()),
+            ..self.clone()
+        })
+    }
+
+    // the rules of a single period, drawn uniformly, or with `MODULE_RATE` a
+    // subtree of them replaced by a module
+    pub fn mutate(
+        &self,
+        gpc: &GPContext<impl RngCore>,
+        modules: &Modules<'a>,
+        config: &Config,
+    ) -> Result<Self, BreedError> {
+        let mut mutated = Self {
+            result: None,
+            shared: None,
+            parent: Some(self.structural_key()),
+            ..self.clone()
+        };
+        let period = match self.segments.len() {
+            0 => 0,
+            n => gpc.rng.borrow_mut().gen_range(0..=n),
+        };
+        // the genome of the first period, modules being subtrees of the
+        // decoded rules
+        if let Some(genome) = self.genome.as_ref().filter(|_| period == 0) {
+            let genome = genome.mutate(gpc, config)?;
+            (mutated.routing, mutated.sequencing) = genome.decode(config);
+            mutated.genome = Some(genome);
+            return Ok(mutated);
+        }
+        let (routing, sequencing) = match period {
+            0 => (&mut mutated.routing, &mut mutated.sequencing),
+            k => {
+                let (routing, sequencing) = &mut mutated.segments[k - 1];
+                (routing, sequencing)
+            }
+        };
+        let grafting = (!modules.routing.is_empty() || !modules.sequencing.is_empty())
+            && gpc.rng.borrow_mut().gen_bool(config.module_rate);
+        if grafting {
+            *routing = gpc.module_mutation(routing, &modules.routing)?;
+            *sequencing = gpc.module_mutation(sequencing, &modules.sequencing)?;
+        } else {
+            *routing = gpc.mutation(routing)?;
+            *sequencing = gpc.mutation(sequencing)?;
+        }
+        Ok(mutated)
+    }
+
+    // rules saved as JSON lines with `routing` and `sequencing` expressions or
+    // base64 strings, like the LASTPOP and GP logs; other lines are skipped,
+    // as are rules deeper than `max_depth`
+    pub fn warm_start(path: &str, max_depth: usize) -> anyhow::Result<Vec<Self>> {
+        let mut pop = Vec::new();
+        let mut too_deep = 0;
+        for (line, text) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let Ok(saved) = serde_json::from_str::<Saved>(&text?) else {
+                continue;
+            };
+            let context = || format!("{path}:{}", line + 1);
+            let segments = saved.segments.iter().map(|(routing, sequencing)| {
+                Ok((
+                    program(routing).with_context(context)?,
+                    program(sequencing).with_context(context)?,
+                ))
+            });
+            let individual = Self {
+                routing: program(&saved.routing).with_context(context)?,
+                sequencing: program(&saved.sequencing).with_context(context)?,
+                segments: segments.collect::<anyhow::Result<_>>()?,
+                result: None,
+                shared: None,
+                rates: None,
+                genome: None,
+                parent: None,
+            };
+            let periods = individual.periods();
+            let depth = periods.iter().map(|(r, s)| r.depth().max(s.depth())).max();
+            if depth.unwrap_or(0) > max_depth {
+                too_deep += 1;
+            } else {
+                pop.push(individual);
+            }
+        }
+        log!(
+            GP,
+            "warm_start",
+            path = path,
+            loaded = pop.len(),
+            too_deep = too_deep
+        );
+        Ok(pop)
+    }
+
+    // every pair of a classical routing and sequencing rule
+    pub fn baseline() -> Vec<Self> {
+        baseline::routing_rules()
+            .into_iter()
+            .flat_map(|routing| {
+                baseline::sequencing_rules()
+                    .into_iter()
+                    .map(move |sequencing| Self {
+                        routing: routing.clone(),
+                        sequencing,
+                        segments: Vec::new(),
+                        result: None,
+                        shared: None,
+                        rates: None,
+                        genome: None,
+                        parent: None,
+                    })
+            })
+            .collect()
+    }
+
+    fn saved(&self) -> Saved {
+        Saved {
+            routing: self.routing.to_string(),
+            sequencing: self.sequencing.to_string(),
+            segments: (self.segments.iter())
+                .map(|(routing, sequencing)| (routing.to_string(), sequencing.to_string()))
+                .collect(),
+            result: self.result,
+        }
+    }
+
+    pub fn migrant(&self) -> Migrant {
+        Migrant {
+            routing: self.routing.base64(),
+            sequencing: self.sequencing.base64(),
+            fitness: self.result.unwrap().2,
+        }
+    }
+
+    pub fn from_migrant(migrant: &Migrant, max_depth: usize) -> Option<Self> {
+        let individual = Self {
+            routing: Program::from_base64(&migrant.routing).ok()?,
+            sequencing: Program::from_base64(&migrant.sequencing).ok()?,
+            segments: Vec::new(),
             result: None,
+            shared: None,
+            rates: None,
+            genome: None,
+            parent: None,
+        };
+        (individual
+            .routing
+            .depth()
+            .max(individual.sequencing.depth())
+            <= max_depth)
+            .then_some(individual)
+    }
+
+    // crossover and mutation rates
+    fn rates(&self, gpc: &GPContext<impl RngCore>) -> (f64, f64) {
+        self.rates
+            .unwrap_or((gpc.params.crossover_rate, gpc.params.mutation_rate))
+    }
+
+    // rates inherited from `rates` and perturbed log-normally by `tau`, kept
+    // within [0.01, 0.99] and adding up to at most 1
+    fn perturb_rates(
+        gpc: &GPContext<impl RngCore>,
+        (crossover, mutation): (f64, f64),
+        tau: f64,
+    ) -> (f64, f64) {
+        let mut rng = gpc.rng.borrow_mut();
+        let normal = Normal::new(0.0, tau).unwrap();
+        let mut perturb = |rate: f64| (rate * normal.sample(&mut *rng).exp()).clamp(0.01, 0.99);
+        let (crossover, mutation) = (perturb(crossover), perturb(mutation));
+        let total = (crossover + mutation).max(1.0);
+        (crossover / total, mutation / total)
+    }
+
+    pub fn selection_fitness(&self) -> f32 {
+        self.shared.unwrap_or(self.result.unwrap().2)
+    }
+
+    fn canonical(&self) -> Vec<Segment<'a>> {
+        let mut periods = self.periods();
+        for (routing, sequencing) in &mut periods {
+            routing.canonicalize();
+            sequencing.canonicalize();
+        }
+        periods
+    }
+
+    // mean node-overlap distance of both rules of every period, between the
+    // periods of two canonical individuals
+    fn canonical_distance(p1: &[Segment], p2: &[Segment]) -> f32 {
+        let n = p1.len().max(p2.len());
+        let distance = (0..n)
+            .map(|k| {
+                let (r1, s1) = p1.get(k).unwrap_or(&p1[0]);
+                let (r2, s2) = p2.get(k).unwrap_or(&p2[0]);
+                (r1.aligned_distance(r2) + s1.aligned_distance(s2)) / 2.0
+            })
+            .sum::<f32>();
+        distance / n as f32
+    }
+
+    // between every pair
+    fn distances(pop: &[Self]) -> Vec<Vec<f32>> {
+        let canonical = pop.iter().map(Self::canonical).collect::<Vec<_>>();
+        canonical
+            .iter()
+            .map(|p1| {
+                canonical
+                    .iter()
+                    .map(|p2| Self::canonical_distance(p1, p2))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // deterministic crowding: the offspring of every pair of `parents`, the
+    // individuals after the first, are matched with the parents so that the
+    // distances between them add up to the least, and each replaces its
+    // parent unless less fit. A parent picked again competes in the state
+    // the earlier offspring left it in. Returns the number of replacements
+    pub fn crowd(pop: &mut Vec<Self>, parents: &[(usize, usize)]) -> usize {
+        let offspring = pop.split_off(pop.len() - 2 * parents.len());
+        let mut replaced = 0;
+        for (&(p1, p2), children) in parents.iter().zip(offspring.chunks_exact(2)) {
+            let [c1, c2] = [&children[0], &children[1]].map(Self::canonical);
+            let (d1, d2) = (pop[p1].canonical(), pop[p2].canonical());
+            let straight = Self::canonical_distance(&d1, &c1) + Self::canonical_distance(&d2, &c2);
+            let crossed = Self::canonical_distance(&d1, &c2) + Self::canonical_distance(&d2, &c1);
+            let matches = match straight <= crossed {
+                true => [(p1, &children[0]), (p2, &children[1])],
+                false => [(p1, &children[1]), (p2, &children[0])],
+            };
+            for (parent, child) in matches {
+                if child.result.unwrap().2 <= pop[parent].result.unwrap().2 {
+                    pop[parent] = child.clone();
+                    replaced += 1;
+                }
+            }
+        }
+        replaced
+    }
+
+    // mean distance between two individuals of the population
+    pub fn diversity(pop: &[Self]) -> f32 {
+        let n = pop.len();
+        if n < 2 {
+            return 0.0;
+        }
+        let total: f32 = Self::distances(pop).iter().flatten().sum();
+        total / (n * (n - 1)) as f32
+    }
+
+    // sharing: the fitness (minimized) grows with the number of individuals
+    // closer than `radius`, each counted by `1 - distance / radius`
+    pub fn share_fitness(pop: &mut [Self], radius: f32) {
+        let distances = Self::distances(pop);
+        for (i, distances) in pop.iter_mut().zip(distances) {
+            let niche: f32 = distances
+                .into_iter()
+                .filter(|d| *d < radius)
+                .map(|d| 1.0 - d / radius)
+                .sum();
+            let fitness = i.result.unwrap().2;
+            i.shared = Some(if fitness >= 0.0 {
+                fitness * niche
+            } else {
+                fitness / niche
+            });
+        }
+    }
+
+    // folds the rules of every period, see `Program::fold`; returns the
+    // number of nodes removed
+    fn fold(&mut self, ranged: bool) -> usize {
+        let mut removed = self.routing.fold(ranged) + self.sequencing.fold(ranged);
+        for (routing, sequencing) in &mut self.segments {
+            removed += routing.fold(ranged) + sequencing.fold(ranged);
+        }
+        removed
+    }
+
+    // equal for structurally equivalent rule pairs, period by period
+    fn structural_key(&self) -> (u64, u64) {
+        let (mut routing, mut sequencing) = (
+            self.routing.structural_hash(),
+            self.sequencing.structural_hash(),
+        );
+        for (r, s) in &self.segments {
+            routing = routing.rotate_left(17) ^ r.structural_hash();
+            sequencing = sequencing.rotate_left(17) ^ s.structural_hash();
+        }
+        (routing, sequencing)
+    }
+
+    fn cache_key((routing, sequencing): (u64, u64), scenarios: &[Scenario]) -> String {
+        let set_key = Scenario::set_key(scenarios);
+        format!("{routing:016x}:{sequencing:016x}:{set_key}")
+    }
+
+    // improves the routes of its training run on the first scenario by local
+    // search, and credits it with the fitness they earn unless only logged
+    fn learn(
+        &mut self,
+        memetic: Memetic,
+        cache: &mut LruCache<String, (f32, usize, f32)>,
+        evaluation: &Evaluation,
+        gen: usize,
+    ) {
+        let Evaluation {
+            problem,
+            ref scenarios,
+            time_slot,
+            objective,
+            ..
+        } = *evaluation;
+        let mut improvement = Improvement::default();
+        let result = simulate_segments(
+            &mut SimulationScratch::default(),
+            problem,
+            &self.periods(),
+            scenarios[0],
+            time_slot,
+            |_, sim| improvement.add(local_search::improve(sim)),
+        );
+        let mut improved = result.clone();
+        improved.distance -= improvement.distance - improvement.improved;
+        let gain = objective.fitness(problem, &result) - objective.fitness(problem, &improved);
+        log!(
+            GP,
+            "memetic",
+            gen = gen,
+            distance = improvement.distance,
+            improved = improvement.improved,
+            gap = improvement.gap(),
+            moves = improvement.moves,
+            fitness_gain = gain
+        );
+        if memetic == Memetic::Log {
+            return;
+        }
+        let Some((distance, failed, fitness)) = self.result else {
+            return;
+        };
+        let saved = narrow(improvement.distance - improvement.improved);
+        let learned = (distance - saved, failed, fitness - gain);
+        self.result = Some(learned);
+        if memetic == Memetic::Lamarckian {
+            cache.put(Self::cache_key(self.structural_key(), scenarios), learned);
         }
     }
 
-    pub fn evaluate(
+    pub fn evaluate<'p>(
         &mut self,
         cache: &mut LruCache<String, (f32, usize, f32)>,
-        problem: &Problem,
-        time_slot: f32,
-    ) -> f32 {
+        scratch: &mut Scratch<'p>,
+        evaluation: &Evaluation<'p>,
+        counts: &mut Counts,
+    ) -> f32
+    where
+        'a: 'p,
+    {
         if let Some((_, _, fitness)) = self.result {
             return fitness;
         }
+        counts.lookups += 1;
 
-        let cache_key = format!("{}:{}", self.routing, self.sequencing);
-        let result = *cache.get_or_insert(cache_key, || {
-            let (dist, nb_fail) = Simulation::new(problem, &self.routing, &self.sequencing)
-                .simulate_until(time_slot, f32::MAX);
-            let fitness = fitness(problem, (dist, nb_fail));
-            (dist, nb_fail, fitness)
+        let Evaluation {
+            problem,
+            ref scenarios,
+            time_slot,
+            objective,
+            ..
+        } = *evaluation;
+        let key = |key| Self::cache_key(key, scenarios);
+        let cache_key = key(self.structural_key());
+        let result = *cache.get_or_insert(cache_key.clone(), || {
+            counts.evaluations += 1;
+            // traces replay a single pair of rules
+            let checkpoints = evaluation
+                .incremental
+                .filter(|_| Trace::supported(problem) && self.segments.is_empty());
+            let mut traces = checkpoints.map(|_| Vec::new());
+            let parent = traces
+                .as_ref()
+                .and(self.parent)
+                .and_then(|parent| scratch.traces.get(&key(parent)).cloned());
+            let results = scenarios
+                .iter()
+                .enumerate()
+                .map(|(index, scenario)| {
+                    let result =
+                        if let (Some(traces), Some(checkpoints)) = (&mut traces, checkpoints) {
+                            let (result, trace) = simulate_traced(
+                                problem,
+                                &self.routing,
+                                &self.sequencing,
+                                *scenario,
+                                time_slot,
+                                checkpoints,
+                                parent.as_ref().map(|parent| &parent[index]),
+                            );
+                            traces.push(trace);
+                            result
+                        } else {
+                            simulate_segments(
+                                &mut scratch.simulation,
+                                problem,
+                                &self.periods(),
+                                *scenario,
+                                time_slot,
+                                |_, _| (),
+                            )
+                        };
+                    counts.events += result.events;
+                    if result.non_finite > 0 {
+                        log!(
+                            GP,
+                            "non_finite_program",
+                            routing = self.routing.to_string(),
+                            sequencing = self.sequencing.to_string(),
+                            count = result.non_finite
+                        );
+                    }
+                    let fitness = if evaluation.penalize_non_finite && result.non_finite > 0 {
+                        f32::INFINITY
+                    } else {
+                        objective.fitness(problem, &result)
+                    };
+                    (narrow(result.distance), result.failed, fitness)
+                })
+                .collect::<Vec<_>>();
+            if let Some(traces) = traces {
+                scratch.traces.put(cache_key, Rc::new(traces));
+            }
+            evaluation.aggregation.aggregate(&results)
         });
 
         self.result = Some(result);
@@ -176,29 +1326,341 @@ impl<'a> Individual<'a> {
 fn select_parent<'a>(gpc: &GPContext<impl RngCore>, pop: &'a [Individual<'a>]) -> usize {
     rand::seq::index::sample(&mut *gpc.rng.borrow_mut(), pop.len(), 8)
         .into_iter()
-        .max_by_key(|i| OrderedFloat(pop[*i].result.unwrap().2))
+        .max_by_key(|i| OrderedFloat(pop[*i].selection_fitness()))
         .unwrap()
 }
 
-fn gp(problem: &Problem) -> anyhow::Result<()> {
-    let time_slot = problem.depot.close / *NUM_TIME_SLOT;
-    let train_time_slot = time_slot / *STRESS_FACTOR;
-    let training_problem = problem.clone_training(time_slot * (*TRAIN_FACTOR), *STRESS_FACTOR);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// a second Ctrl-C kills the process as usual
+extern "C" fn on_interrupt(signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+fn install_interrupt_handler() {
+    let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+// an individual as a line of a checkpoint, the rules as expressions like
+// those of LASTPOP so that `WARM_START` reads both
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    routing: String,
+    sequencing: String,
+    #[serde(default)]
+    segments: Vec<(String, String)>,
+    #[serde(default)]
+    result: Option<(f32, usize, f32)>,
+}
+
+#[derive(Serialize)]
+struct Checkpoint {
+    gen: usize,
+    evaluations: usize,
+}
+
+// a line with the generation and evaluations reached, then a line per
+// individual
+fn write_checkpoint(
+    writer: &mut impl Write,
+    gen: usize,
+    evaluations: usize,
+    pop: &[Individual],
+) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *writer, &Checkpoint { gen, evaluations })?;
+    writeln!(writer)?;
+    for i in pop {
+        serde_json::to_writer(&mut *writer, &i.saved())?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+// whether `seconds` of evolution and `evaluations` simulated individuals
+// reach `MAX_SECONDS` or `MAX_EVALUATIONS`; as it is only asked between
+// generations, a run overshoots a budget by up to the rest of a generation
+fn budget_exhausted(config: &Config, seconds: f64, evaluations: usize) -> bool {
+    config.max_seconds.is_some_and(|max| seconds >= max)
+        || config.max_evaluations.is_some_and(|max| evaluations >= max)
+}
+
+// the evaluated individuals of an interrupted run, best first
+fn save_checkpoint(
+    config: &Config,
+    gen: usize,
+    evaluations: usize,
+    pop: &mut Vec<Individual>,
+) -> anyhow::Result<()> {
+    pop.retain(|i| i.result.is_some());
+    pop.sort_unstable_by_key(|i| OrderedFloat(i.selection_fitness()));
+    for i in pop.iter().take(config.num_best_saved) {
+        let result = i.result.unwrap();
+        log!(
+            GP,
+            "interrupted_best",
+            gen = gen,
+            result = (result.0, result.1),
+            fitness = result.2,
+            routing = i.routing.to_string(),
+            sequencing = i.sequencing.to_string(),
+            routing_base64 = i.routing.base64(),
+            sequencing_base64 = i.sequencing.base64()
+        );
+    }
+    let mut writer = BufWriter::new(File::create(&config.checkpoint)?);
+    write_checkpoint(&mut writer, gen, evaluations, pop)?;
+    writer.flush()?;
+    log!(
+        GP,
+        "interrupted",
+        gen = gen,
+        evaluations = evaluations,
+        checkpoint = config.checkpoint
+    );
+    io::stdout().flush()?;
+    io::stderr().flush()?;
+    Ok(())
+}
+
+fn generation(
+    gen: usize,
+    pop: &[Individual],
+    result: &sim::SimulationResult,
+    evaluations_per_second: f64,
+) -> Generation {
+    let fitness = pop
+        .iter()
+        .filter_map(|i| i.result.map(|result| result.2))
+        .filter(|fitness| fitness.is_finite())
+        .collect::<Vec<_>>();
+    let nodes = pop
+        .iter()
+        .map(|i| i.routing.all_active_indices().len() + i.sequencing.all_active_indices().len())
+        .collect::<Vec<_>>();
+    Generation {
+        gen,
+        best: pop[0].result.unwrap().2,
+        mean: fitness.iter().sum::<f32>() / fitness.len() as f32,
+        distance: narrow(result.distance),
+        failed: result.failed,
+        diversity: Individual::diversity(pop),
+        population: pop.len(),
+        min_nodes: nodes.iter().copied().min().unwrap_or(0),
+        mean_nodes: nodes.iter().sum::<usize>() as f32 / nodes.len().max(1) as f32,
+        max_nodes: nodes.iter().copied().max().unwrap_or(0),
+        evaluations_per_second,
+        routing: pop[0].routing.to_string(),
+        sequencing: pop[0].sequencing.to_string(),
+    }
+}
+
+fn gp(problem: &Problem, config: &Config, web: Option<&WebDashboard>) -> anyhow::Result<()> {
+    install_interrupt_handler();
+    #[cfg(not(feature = "tui"))]
+    anyhow::ensure!(!config.tui, "TUI needs a build with --features tui");
+    #[cfg(feature = "tui")]
+    let mut dashboard = config
+        .tui
+        .then(|| Dashboard::new(config.num_gen, &INTERRUPTED))
+        .transpose()?;
+    let mut island = config
+        .island_coordinator
+        .as_deref()
+        .map(Island::connect)
+        .transpose()?;
+    let time_slot = problem.depot().close / config.num_time_slot;
+    // multi-day instances are trained on their whole horizon
+    let (mut training_problem, train_time_slot) = if problem.num_days > 1 {
+        (problem.clone(), time_slot)
+    } else {
+        training::apply(&config.training, problem, time_slot)
+    };
+    // the rules are tested on exact terminal values
+    training_problem.terminal_noise = TerminalNoise {
+        seed: config.scenario_seed,
+        ..config.terminal_noise
+    };
     let gpc = GPContext {
         rng: RefCell::new(SmallRng::from_entropy()),
-        num_population: *POP_SIZE,
-        max_depth: *MAX_DEPTH,
+        params: config.gp_params(),
     };
+    let scenarios = Scenario {
+        seed: config.scenario_seed,
+        travel_noise: config.travel_noise,
+        service_noise: config.service_noise,
+    }
+    .replications(config.num_scenarios);
+    let evaluation = Evaluation::new(&training_problem, scenarios, train_time_slot, config);
     let mut cache = LruCache::unbounded();
-    let mut pop = Individual::ramp_half_and_half(&gpc);
-    for gen in 1..=*NUM_GEN {
+    let mut scratch = Scratch::new(gpc.params.num_population);
+    let phenotype = (POP.enabled() && Trace::supported(&training_problem)).then(|| {
+        Phenotype::new(
+            &training_problem,
+            train_time_slot,
+            config.phenotype_situations,
+        )
+    });
+    let mut pop = Individual::ramp_half_and_half(&gpc, config);
+    if let Some(path) = &config.imitation {
+        // screened from a larger pool by how often the rules pick what the
+        // expert of the dataset did, much cheaper than simulating the pool
+        let samples = dataset::load(path).with_context(|| format!("IMITATION: {path}"))?;
+        for _ in 1..config.imitation_pool {
+            pop.extend(Individual::ramp_half_and_half(&gpc, config));
+        }
+        let mut scored = pop
+            .into_iter()
+            .map(|i| (dataset::agreement(&samples, &i.routing, &i.sequencing), i))
+            .collect::<Vec<_>>();
+        scored.sort_by_key(|(agreement, _)| Reverse(OrderedFloat(*agreement)));
+        let pool = scored.len();
+        scored.truncate(gpc.params.num_population);
+        log!(
+            GP,
+            "imitation",
+            samples = samples.len(),
+            pool = pool,
+            best = scored[0].0,
+            mean = scored.iter().map(|(agreement, _)| agreement).sum::<f64>() / scored.len() as f64
+        );
+        pop = scored.into_iter().map(|(_, i)| i).collect();
+    }
+    if let Some(path) = &config.warm_start {
+        let saved = Individual::warm_start(path, gpc.params.max_depth)?;
+        let num_saved = ((config.warm_start_share * pop.len() as f64) as usize).min(saved.len());
+        pop.splice(0..num_saved, saved.into_iter().take(num_saved));
+    }
+    if config.seed_baseline {
+        // in place of the last generated individuals, after any saved ones
+        let seeded = Individual::baseline();
+        let num_seeded = seeded.len().min(pop.len());
+        let start = pop.len() - num_seeded;
+        pop.splice(start.., seeded.into_iter().take(num_seeded));
+    }
+    let start = Instant::now();
+    let mut counts = Counts::default();
+    let mut learned = HashSet::new();
+    // the parents of every pair of offspring last bred
+    let mut parents = Vec::new();
+    let mut modules = Modules::default();
+    // every non-dominated trade-off of distance and failed requests so far
+    let mut archive = config.pareto.as_ref().map(|_| Archive::default());
+    for gen in 1..=config.num_gen {
+        let before = counts;
+        if config.fold {
+            // noisy terminals leave their ranges
+            let ranged = (config.terminal_noise.routing.iter())
+                .chain(&config.terminal_noise.sequencing)
+                .all(|level| *level == 0.0);
+            let removed = (pop.iter_mut())
+                .filter(|i| i.result.is_none())
+                .map(|i| i.fold(ranged))
+                .sum::<usize>();
+            log!(GP, "folding", gen = gen, removed = removed);
+        }
+        let evaluating = Instant::now();
         for i in pop.iter_mut() {
-            i.evaluate(&mut cache, &training_problem, train_time_slot);
+            if INTERRUPTED.load(Ordering::Relaxed) {
+                break;
+            }
+            i.evaluate(&mut cache, &mut scratch, &evaluation, &mut counts);
+        }
+        let mut evaluation_seconds = evaluating.elapsed().as_secs_f64();
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            return save_checkpoint(config, gen, counts.evaluations, &mut pop);
+        }
+        // budgets are checked between generations, the one that exhausts
+        // them is completed and logged as the last
+        let exhausted = budget_exhausted(config, start.elapsed().as_secs_f64(), counts.evaluations);
+        let last = gen == config.num_gen || exhausted;
+        if let Some(archive) = &mut archive {
+            // cloned only when they make it in
+            let candidates = (pop.iter())
+                .filter_map(|i| {
+                    i.result
+                        .map(|(distance, failed, _)| ((distance, failed), i))
+                })
+                .filter(|(point, _)| archive.admits(*point))
+                .map(|(point, i)| (point, i.clone()))
+                .collect::<Vec<_>>();
+            let added = archive.update(candidates);
+            log!(
+                GP,
+                "pareto",
+                gen = gen,
+                size = archive.members.len(),
+                added = added,
+                hypervolume = archive.hypervolume()
+            );
+        }
+        if config.crowding && !parents.is_empty() {
+            let replaced = Individual::crowd(&mut pop, &parents);
+            log!(GP, "crowding", gen = gen, replaced = replaced);
+        }
+        if config.deduplicate {
+            let mut seen = HashSet::new();
+            pop.retain(|i| seen.insert(i.structural_key()));
+        }
+        if let Some(radius) = config.fitness_sharing {
+            Individual::share_fitness(&mut pop, radius);
         }
 
-        pop.sort_unstable_by_key(|i| OrderedFloat(i.result.unwrap().2));
-        pop.truncate(gpc.num_population);
+        pop.sort_unstable_by_key(|i| OrderedFloat(i.selection_fitness()));
+        pop.truncate(gpc.params.num_population);
+        if let Some(memetic) = config.memetic {
+            // credited once, it would compound while the individual stays best
+            match learned.insert(pop[0].structural_key()) {
+                true => pop[0].learn(memetic, &mut cache, &evaluation, gen),
+                false => pop[0].learn(Memetic::Log, &mut cache, &evaluation, gen),
+            }
+        }
+        if let (Some(num_modules), 0) = (config.modules, gen % config.module_interval) {
+            // from the best tenth, which breeds most of the offspring
+            let elites = &pop[..(pop.len() / 10).max(2).min(pop.len())];
+            modules = Modules::acquire(elites, num_modules);
+            log!(
+                GP,
+                "modules",
+                gen = gen,
+                routing = modules
+                    .routing
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                sequencing = modules
+                    .sequencing
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            );
+        }
         let result = pop[0].result.unwrap();
+        if POP.enabled() {
+            for (rank, i) in pop.iter().enumerate() {
+                let (distance, failed, fitness) = i.result.unwrap();
+                log!(
+                    POP,
+                    "individual",
+                    gen = gen,
+                    rank = rank,
+                    routing_nodes = i.routing.all_active_indices().len(),
+                    sequencing_nodes = i.sequencing.all_active_indices().len(),
+                    fitness = fitness,
+                    distance = distance,
+                    failed = failed,
+                    phenotype = phenotype
+                        .as_ref()
+                        .map(|p| p.characterize(&i.routing, &i.sequencing))
+                );
+            }
+        }
 
         log!(
             GP,
@@ -206,84 +1668,887 @@ fn gp(problem: &Problem) -> anyhow::Result<()> {
             gen = gen,
             result = (result.0, result.1),
             fitness = result.2,
+            diversity = Individual::diversity(&pop),
+            rates = config.self_adaptive.map(|_| {
+                let n = pop.len() as f64;
+                let rates = pop.iter().map(|i| i.rates(&gpc));
+                rates.fold((0.0, 0.0), |(c, m), (ci, mi)| (c + ci / n, m + mi / n))
+            }),
+            genome = pop[0].genome.as_ref().map(|genome| genome.size(config)),
             routing = pop[0].routing.to_string(),
             sequencing = pop[0].sequencing.to_string()
         );
-        let mut sim = Simulation::new(problem, &pop[0].routing, &pop[0].sequencing);
-        let result = sim.simulate_until(time_slot, f32::MAX);
+        let mut legs = Vec::new();
+        let result = simulate_segments(
+            &mut SimulationScratch::default(),
+            problem,
+            &pop[0].periods(),
+            Scenario::default(),
+            time_slot,
+            |day, sim| {
+                if web.is_some() {
+                    for (vehicle, state) in sim.vehicles.iter().enumerate() {
+                        legs.extend(state.legs.iter().map(|leg| (day, vehicle, *leg)));
+                    }
+                }
+                if !last {
+                    return;
+                }
+                for vehicle in 0..sim.vehicles.len() {
+                    log!(
+                        LASTROUTE,
+                        "route_log",
+                        day = day,
+                        vehicle = vehicle,
+                        route = sim.vehicles[vehicle].route,
+                        dropped = sim.vehicles[vehicle].dropped,
+                        distance = sim.vehicles[vehicle].traveled(),
+                        legs = sim.vehicles[vehicle].legs
+                    );
+                }
+            },
+        );
         log!(
             GP,
             "full_result",
-            result = result,
-            fitness = fitness(problem, result)
+            result = (result.distance, result.failed),
+            lateness = result.lateness,
+            overtime = result.overtime,
+            rolled_over = result.rolled_over,
+            profit = problem.profit(result.revenue, result.distance),
+            fitness = config.objective.fitness(problem, &result)
+        );
+        log!(
+            GP,
+            "rule_profile",
+            profile = result.profile,
+            rule_share = result.profile.rule_share()
         );
+        // the value of information: the same rules knowing every request
+        // from the start
+        if last && !config.reveal {
+            let revealed = problem.reveal();
+            let known = simulate_segments(
+                &mut SimulationScratch::default(),
+                &revealed,
+                &pop[0].periods(),
+                Scenario::default(),
+                time_slot,
+                |_, _| (),
+            );
+            let (dynamic, fitness) = (
+                config.objective.fitness(problem, &result),
+                config.objective.fitness(&revealed, &known),
+            );
+            log!(
+                GP,
+                "static_result",
+                result = (known.distance, known.failed),
+                fitness = fitness,
+                gap = dynamic - fitness
+            );
+        }
+
+        // the best individuals voting together against the best alone
+        if let (true, Some((vote, k))) = (last, config.ensemble) {
+            let members = &pop[..k.min(pop.len())];
+            let ensemble = Arc::new(Ensemble {
+                routing: members
+                    .iter()
+                    .map(|i| Program::from_vec(i.routing.nodes.clone()))
+                    .collect(),
+                sequencing: members
+                    .iter()
+                    .map(|i| Program::from_vec(i.sequencing.nodes.clone()))
+                    .collect(),
+                vote,
+            });
+            let ensembled = Problem {
+                rules: Rules {
+                    routing: Some(ensemble.clone()),
+                    sequencing: Some(ensemble),
+                },
+                ..problem.clone()
+            };
+            let voted = simulate_days(
+                &ensembled,
+                &pop[0].routing,
+                &pop[0].sequencing,
+                Scenario::default(),
+                time_slot,
+                |_, _| (),
+            );
+            let fitness = config.objective.fitness(problem, &voted);
+            log!(
+                GP,
+                "ensemble_result",
+                members = members.len(),
+                result = (voted.distance, voted.failed),
+                fitness = fitness,
+                gain = config.objective.fitness(problem, &result) - fitness
+            );
+        }
 
         log!(
             GP,
             "base64",
             routing = pop[0].routing.base64(),
-            sequencing = pop[0].sequencing.base64()
+            sequencing = pop[0].sequencing.base64(),
+            segments = pop[0]
+                .segments
+                .iter()
+                .map(|(routing, sequencing)| (routing.base64(), sequencing.base64()))
+                .collect::<Vec<_>>()
         );
 
-        if gen == *NUM_GEN {
-            for vehicle in 0..problem.num_trucks {
-                log!(
-                    LASTROUTE,
-                    "route_log",
-                    vehicle = vehicle,
-                    route = sim.vehicles[vehicle].route,
-                    dropped = sim.vehicles[vehicle].dropped
-                );
-            }
+        if last {
             for i in pop.iter() {
                 log!(
                     LASTPOP,
                     "lastpop",
                     routing = i.routing.to_string(),
-                    sequencing = i.sequencing.to_string()
+                    sequencing = i.sequencing.to_string(),
+                    segments = i
+                        .segments
+                        .iter()
+                        .map(|(routing, sequencing)| (routing.to_string(), sequencing.to_string()))
+                        .collect::<Vec<_>>()
                 );
             }
         }
 
-        for _ in 0..gpc.num_population / 2 {
-            let p1 = select_parent(&gpc, &pop[0..gpc.num_population]);
-            let p2 = select_parent(&gpc, &pop[0..gpc.num_population]);
-
-            let x = gpc.rng.borrow_mut().gen_range(0.0..=1.0);
-            match x {
-                x if x <= *CROSSOVER_RATE => {
-                    let (c1, c2) = pop[p1].crossover_with(&gpc, &pop[p2]);
-                    pop.push(c1);
-                    pop.push(c2);
-                }
-                x if x <= *CROSSOVER_RATE + *MUTATION_RATE => {
-                    let m1 = pop[p1].mutate(&gpc);
-                    let m2 = pop[p2].mutate(&gpc);
-                    pop.push(m1);
-                    pop.push(m2);
-                }
-                _ => {
-                    pop.push(pop[p1].clone());
-                    pop.push(pop[p2].clone());
+        if let Some(island) = &mut island {
+            if gen % config.migration_interval == 0 || last {
+                let emigrants = pop
+                    .iter()
+                    .take(config.num_migrants)
+                    .map(Individual::migrant)
+                    .collect::<Vec<_>>();
+                // immigrants replace the worst individuals, rules this build
+                // cannot decode or deeper than allowed are skipped
+                let mut immigrants = island
+                    .exchange(&emigrants)?
+                    .iter()
+                    .filter_map(|migrant| Individual::from_migrant(migrant, gpc.params.max_depth))
+                    .collect::<Vec<_>>();
+                let evaluating = Instant::now();
+                for i in immigrants.iter_mut() {
+                    i.evaluate(&mut cache, &mut scratch, &evaluation, &mut counts);
                 }
+                evaluation_seconds += evaluating.elapsed().as_secs_f64();
+                log!(
+                    GP,
+                    "migration",
+                    gen = gen,
+                    sent = emigrants.len(),
+                    received = immigrants.len()
+                );
+                pop.truncate(pop.len().saturating_sub(immigrants.len()));
+                pop.extend(immigrants);
+                pop.sort_unstable_by_key(|i| OrderedFloat(i.selection_fitness()));
             }
         }
+
+        let evaluations_per_second =
+            (counts.evaluations - before.evaluations) as f64 / evaluation_seconds;
+        if let Some(web) = web {
+            web.send(
+                &generation(gen, &pop, &result, evaluations_per_second),
+                &legs,
+            )?;
+        }
+        #[cfg(feature = "tui")]
+        if let Some(dashboard) = &mut dashboard {
+            dashboard.update(generation(gen, &pop, &result, evaluations_per_second))?;
+        }
+
+        let breeding = Instant::now();
+        if !exhausted {
+            parents = breed(&gpc, &mut pop, &modules, config)?;
+        }
+        let breeding_seconds = breeding.elapsed().as_secs_f64();
+        let evaluations = counts.evaluations - before.evaluations;
+        let events = counts.events - before.events;
+        log!(
+            GP,
+            "gen_stats",
+            gen = gen,
+            evaluation_seconds = evaluation_seconds,
+            breeding_seconds = breeding_seconds,
+            evaluations = evaluations,
+            cache_hits = counts.lookups - before.lookups - evaluations,
+            cache_size = cache.len(),
+            events = events,
+            evaluations_per_second = evaluations as f64 / evaluation_seconds,
+            events_per_second = events as f64 / evaluation_seconds
+        );
+
+        if exhausted {
+            log!(
+                GP,
+                "budget_exhausted",
+                gen = gen,
+                seconds = start.elapsed().as_secs_f64(),
+                evaluations = counts.evaluations
+            );
+            break;
+        }
+    }
+    if let (Some(path), Some(archive)) = (&config.pareto, &archive) {
+        save_front(path, archive)?;
+    }
+    #[cfg(feature = "tui")]
+    if let Some(dashboard) = &mut dashboard {
+        dashboard.wait()?;
+    }
+    Ok(())
+}
+
+// the final Pareto front as JSON lines of the rules, like LASTPOP so that it
+// can warm start a run, with their objective values
+fn save_front(path: &str, archive: &Archive<Individual>) -> anyhow::Result<()> {
+    #[derive(Serialize)]
+    struct Member {
+        routing: String,
+        sequencing: String,
+        segments: Vec<(String, String)>,
+        distance: f32,
+        failed: usize,
+        fitness: f32,
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    for ((distance, failed), i) in &archive.members {
+        let member = Member {
+            routing: i.routing.to_string(),
+            sequencing: i.sequencing.to_string(),
+            segments: (i.segments.iter())
+                .map(|(routing, sequencing)| (routing.to_string(), sequencing.to_string()))
+                .collect(),
+            distance: *distance,
+            failed: *failed,
+            fitness: i.result.unwrap().2,
+        };
+        serde_json::to_writer(&mut writer, &member)?;
+        writeln!(writer)?;
+    }
+    Ok(writer.flush()?)
+}
+
+// appends `num_population` offspring of the sorted population, and returns
+// the parents of every pair of them
+fn breed<'a>(
+    gpc: &GPContext<impl RngCore>,
+    pop: &mut Vec<Individual<'a>>,
+    modules: &Modules<'a>,
+    config: &Config,
+) -> Result<Vec<(usize, usize)>, BreedError> {
+    // as many evaluations, none of them bred
+    if config.control == Some(Control::RandomSearch) {
+        pop.extend(Individual::ramp_half_and_half(gpc, config));
+        return Ok(Vec::new());
+    }
+    // fewer than `num_population` when duplicates were removed
+    let num_parents = pop.len();
+    let mut parents = Vec::new();
+    for _ in 0..gpc.params.num_population / 2 {
+        let p1 = select_parent(gpc, &pop[..num_parents]);
+        let p2 = select_parent(gpc, &pop[..num_parents]);
+        parents.push((p1, p2));
+        // the mean rates of the parents decide the operator
+        let ((c1, m1), (c2, m2)) = (pop[p1].rates(gpc), pop[p2].rates(gpc));
+        let (crossover_rate, mutation_rate) = ((c1 + c2) / 2.0, (m1 + m2) / 2.0);
+
+        let x = gpc.rng.borrow_mut().gen_range(0.0..=1.0);
+        match x {
+            x if x <= crossover_rate && config.control == Some(Control::HeadlessChicken) => {
+                let c1 = pop[p1].headless_crossover(gpc, config)?;
+                let c2 = pop[p2].headless_crossover(gpc, config)?;
+                pop.push(c1);
+                pop.push(c2);
+            }
+            x if x <= crossover_rate => {
+                let (c1, c2) = pop[p1].crossover_with(gpc, &pop[p2], config)?;
+                pop.push(c1);
+                pop.push(c2);
+            }
+            x if x <= crossover_rate + mutation_rate => {
+                let m1 = pop[p1].mutate(gpc, modules, config)?;
+                let m2 = pop[p2].mutate(gpc, modules, config)?;
+                pop.push(m1);
+                pop.push(m2);
+            }
+            _ => {
+                pop.push(pop[p1].clone());
+                pop.push(pop[p2].clone());
+            }
+        }
+        if let Some(tau) = config.self_adaptive {
+            let len = pop.len();
+            for child in &mut pop[len - 2..] {
+                child.rates = Some(Individual::perturb_rates(
+                    gpc,
+                    (crossover_rate, mutation_rate),
+                    tau,
+                ));
+            }
+        }
+    }
+    Ok(parents)
+}
+
+// throughput of rule evaluation, of a single simulation with the classical
+// rules and of GP generations, on seeded workloads
+fn bench(problem: &Problem, config: &Config) -> Report {
+    let seconds = config.bench_seconds;
+    let time_slot = problem.depot().close / config.num_time_slot;
+    let gpc = GPContext {
+        rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        params: config.gp_params(),
+    };
+    let mut pop = Individual::ramp_half_and_half(&gpc, config);
+    let vehicle_state = VehicleState::new(problem, 0);
+    let total_demand = problem.total_demand();
+    let program_evaluation = measure("program_evaluation", seconds, || {
+        let mut count = 0;
+        for request in &problem.requests {
+            let context = RoutingContext {
+                vehicle_state: &vehicle_state,
+                problem,
+                time: request.time,
+                request,
+                num_feasible: problem.num_vehicles(),
+                total_demand,
+            };
+            for i in &pop {
+                black_box(i.routing.calc(&context));
+                count += 1;
+            }
+        }
+        count
+    });
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let mut scratch = Scratch::new(gpc.params.num_population);
+    let simulation = measure("simulation", seconds, || {
+        black_box(simulate_days_with(
+            &mut scratch.simulation,
+            problem,
+            &routing,
+            &sequencing,
+            Scenario::default(),
+            time_slot,
+            |_, _| (),
+        ));
+        1
+    });
+    let evaluation = Evaluation::new(problem, vec![Scenario::default()], time_slot, config);
+    let mut cache = LruCache::unbounded();
+    let generation = measure("generation", seconds, || {
+        let mut counts = Counts::default();
+        for i in pop.iter_mut() {
+            i.evaluate(&mut cache, &mut scratch, &evaluation, &mut counts);
+        }
+        pop.sort_unstable_by_key(|i| OrderedFloat(i.selection_fitness()));
+        pop.truncate(gpc.params.num_population);
+        breed(&gpc, &mut pop, &Modules::default(), config)
+            .expect("the population is bred from valid rules");
+        counts.evaluations
+    });
+    Report {
+        measurements: vec![program_evaluation, simulation, generation],
+    }
+}
+
+// a rule as an expression or a base64 string
+fn program<C: ProgramContext>(str: &str) -> Result<Program<C>, DecodeError> {
+    str.parse()
+        .or_else(|err| Program::from_base64(str).map_err(|_| err))
+}
+
+// prints the rules given as `--routing` and `--sequencing` base64 strings, as
+// the GP logs them
+fn decode(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    fn describe<C: ProgramContext>(name: &str, base64: &str) -> anyhow::Result<()> {
+        let program = Program::<C>::from_base64(base64)
+            .with_context(|| format!("invalid {name} rule {base64:?}"))?;
+        println!("{name}: {program}");
+        println!("  depth: {}", program.depth());
+        println!("  nodes: {}", program.all_active_indices().len());
+        let terminals = program
+            .used_terminals()
+            .into_iter()
+            .map(Program::<C>::terminal_name);
+        println!("  terminals: {}", terminals.collect::<Vec<_>>().join(", "));
+        println!("  latex: {}", program.latex());
+        Ok(())
     }
+
+    let usage = "usage: cargo run -- decode [--routing base64] [--sequencing base64]";
+    let mut decoded = 0;
+    while let Some(flag) = args.next() {
+        let base64 = args.next().context(usage)?;
+        match flag.as_str() {
+            "--routing" => describe::<RoutingContext>("routing", &base64)?,
+            "--sequencing" => describe::<SequencingContext>("sequencing", &base64)?,
+            _ => anyhow::bail!(usage),
+        }
+        decoded += 1;
+    }
+    anyhow::ensure!(decoded > 0, usage);
     Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
     _ = dotenv::dotenv()?;
-    log!(MAIN, "start");
-    let path = args().nth(1).expect("usage: cargo run -- [problem path]");
-    let problem = Problem::load(&path, 1.0, 1300.0, 10)?;
+    let config = Config::from_env()?;
+    log!(MAIN, "start", config = ConfigSnapshot::new(&config));
+    let mut args = args().skip(1).peekable();
+    let command = args.next_if(|arg| {
+        [
+            "stats",
+            "convert",
+            "diff",
+            "replay",
+            "sweep",
+            "race",
+            "coordinate",
+            "bench",
+            "--dry-run",
+            "--dashboard",
+            "decode",
+            "explain",
+            "record",
+            "sensitivity",
+            "tightness",
+            "robustness",
+            "ablation",
+            "aggregate",
+        ]
+        .contains(&arg.as_str())
+    });
+    // rules are decoded without an instance
+    if command.as_deref() == Some("decode") {
+        return decode(args);
+    }
+    // nor are the logs of finished runs
+    if command.as_deref() == Some("aggregate") {
+        let dir = args
+            .next()
+            .context("usage: cargo run -- aggregate [runs directory]")?;
+        let runs = aggregate::load(&dir)?;
+        match config.aggregate_format.as_str() {
+            "csv" => println!("{}", Table(&runs)),
+            "json" => println!("{}", serde_json::to_string_pretty(&runs)?),
+            format => anyhow::bail!("AGGREGATE_FORMAT: unknown format {format:?}"),
+        }
+        for run in &runs {
+            if run.manifest.is_empty() {
+                eprintln!("{}: no LOG_MAIN start line", run.name);
+            }
+            if run.result.is_empty() {
+                eprintln!("{}: no LOG_GP full_result line", run.name);
+            }
+            if !run.mismatched.is_empty() {
+                eprintln!("{}: differs in {}", run.name, run.mismatched.join(", "));
+            }
+        }
+        return Ok(());
+    }
+    let path = args.next().expect(
+        "usage: cargo run -- [stats | convert [output path] | diff [route log] [route log] | replay [route log] | sweep [NAME=values]... | race [problem path | NAME=values]... | coordinate [address] | bench | --dry-run | --dashboard | (explain | sensitivity | tightness | robustness) [routing] [sequencing] | record [routing] [sequencing] [dataset path] | ablation [terminal | NAME=value]...] [problem path] | decode [--routing base64] [--sequencing base64] | aggregate [runs directory]",
+    );
+    // JSON instances describe their own depots and fleet, CSV ones use the
+    // benchmark fleet; either can be overridden per experiment
+    let instance = if path.ends_with(".json") {
+        Some(Instance::load(&path)?)
+    } else {
+        None
+    };
+    let fleet = instance.as_ref().map_or(Fleet::default(), |i| i.fleet);
+    let fleet = Fleet {
+        truck_speed: config.truck_speed.unwrap_or(fleet.truck_speed),
+        truck_capacity: config.truck_capacity.unwrap_or(fleet.truck_capacity),
+        num_trucks: config.num_trucks.unwrap_or(fleet.num_trucks),
+        ..fleet
+    };
+    log!(MAIN, "instance", path = path);
+    log!(MAIN, "fleet", fleet = fleet);
+    let mut problem = match instance {
+        Some(instance) => Instance { fleet, ..instance }.into_problem()?,
+        None => Problem::load(
+            &path,
+            fleet.truck_speed,
+            fleet.truck_capacity,
+            fleet.num_trucks,
+            config.num_depots,
+            config.num_satellites,
+        )?,
+    };
+    config.configure(&mut problem)?;
+    match command.as_deref() {
+        Some("stats") => {
+            println!(
+                "{}",
+                InstanceStats::new(&problem, config.num_time_slot as usize)
+            );
+            return Ok(());
+        }
+        Some("convert") => {
+            let output = args
+                .next()
+                .expect("usage: cargo run -- convert [problem path] [output path]");
+            Instance::from_problem(&problem).save(&output)?;
+            return Ok(());
+        }
+        Some("diff") => {
+            let mut log = || -> anyhow::Result<RouteLog> {
+                let path = args
+                    .next()
+                    .expect("usage: cargo run -- diff [problem path] [route log] [route log]");
+                Ok(RouteLog::load(&path)?)
+            };
+            let (a, b) = (log()?, log()?);
+            println!("{}", RouteDiff::new(&problem, &a, &b));
+            return Ok(());
+        }
+        Some("replay") => {
+            let log = args
+                .next()
+                .expect("usage: cargo run -- replay [problem path] [route log]");
+            println!("{}", Replay::new(&problem, &RouteLog::load(&log)?));
+            return Ok(());
+        }
+        Some("sweep") => {
+            let parameters = args
+                .map(|arg| Parameter::parse(&arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            let configs = sweep::configs(&parameters, config.sweep_samples, config.sweep_seed);
+            let mut results = Vec::new();
+            for swept in configs {
+                let runs = (0..config.sweep_runs)
+                    .map(|_| sweep::run(&path, &swept))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let result = SweepResult {
+                    config: swept,
+                    runs,
+                };
+                println!("{result}");
+                results.push(result);
+            }
+            if let Some(best) = results
+                .iter()
+                .min_by_key(|result| OrderedFloat(result.mean_fitness()))
+            {
+                println!("best: {best}");
+                // the runs are independent, so only unpaired tests apply
+                for result in results.iter().filter(|result| !std::ptr::eq(*result, best)) {
+                    if let Some(test) = mann_whitney(&best.fitness(), &result.fitness()) {
+                        println!(
+                            "{} against the best: {test}",
+                            sweep::describe(&result.config)
+                        );
+                    }
+                }
+            }
+            return Ok(());
+        }
+        Some("ablation") => {
+            // settings shared by every run, and the terminals disabled in turn
+            let (settings, terminals): (Vec<_>, Vec<_>) = args.partition(|arg| arg.contains('='));
+            let shared = settings
+                .iter()
+                .filter_map(|arg| arg.split_once('='))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<sweep::Config>();
+            let terminals = match terminals.is_empty() {
+                true => ablation::terminals(),
+                false => terminals,
+            };
+            for terminal in &terminals {
+                anyhow::ensure!(
+                    ablation::name(terminal).is_some(),
+                    "unknown terminal {terminal:?}, expected routing:index or sequencing:index"
+                );
+            }
+            let sweep = |terminal: &str| -> anyhow::Result<SweepResult> {
+                let mut swept = shared.clone();
+                swept.push(("ABLATE".to_string(), terminal.to_string()));
+                let runs = (0..config.sweep_runs)
+                    .map(|_| sweep::run(&path, &swept))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok(SweepResult {
+                    config: swept,
+                    runs,
+                })
+            };
+            let all = sweep("")?;
+            println!("all terminals: {all}");
+            let mut results = Vec::new();
+            for terminal in terminals {
+                let result = AblationResult {
+                    result: sweep(&terminal)?,
+                    terminal,
+                    baseline: all.fitness(),
+                };
+                println!("{result}");
+                results.push(result);
+            }
+            results.sort_by_key(|result| Reverse(OrderedFloat(result.degradation())));
+            println!("by degradation:");
+            for result in &results {
+                println!("{result}");
+            }
+            return Ok(());
+        }
+        Some("coordinate") => {
+            let address = args
+                .next()
+                .expect("usage: cargo run -- coordinate [problem path] [address]");
+            let listener = TcpListener::bind(&address)?;
+            log!(
+                MAIN,
+                "coordinator",
+                address = listener.local_addr()?.to_string()
+            );
+            island::coordinate(
+                listener,
+                |island, migrant| {
+                    fn expression<C: ProgramContext>(base64: &str) -> String {
+                        Program::<C>::from_base64(base64)
+                            .map_or_else(|err| err.to_string(), |program| program.to_string())
+                    }
+                    log!(
+                        GP,
+                        "global_best",
+                        island = island,
+                        fitness = migrant.fitness,
+                        routing = expression::<RoutingContext>(&migrant.routing),
+                        sequencing = expression::<SequencingContext>(&migrant.sequencing),
+                        routing_base64 = migrant.routing,
+                        sequencing_base64 = migrant.sequencing
+                    );
+                },
+                |island, err| {
+                    log!(
+                        MAIN,
+                        "island_error",
+                        island = island,
+                        error = err.to_string()
+                    );
+                },
+            )?;
+            return Ok(());
+        }
+        Some("bench") => {
+            let report = bench(&problem, &config);
+            print!("{report}");
+            if let Some(path) = &config.bench_save {
+                report.save(path)?;
+            }
+            if let Some(path) = &config.bench_baseline {
+                let regressions = report.regressions(&Report::load(path)?, config.bench_tolerance);
+                for (name, ratio) in &regressions {
+                    println!(
+                        "regression: {name} at {:.1}% of the baseline",
+                        ratio * 100.0
+                    );
+                }
+                anyhow::ensure!(
+                    regressions.is_empty(),
+                    "{} benchmarks regressed against {path}",
+                    regressions.len()
+                );
+            }
+            return Ok(());
+        }
+        Some(command @ ("tightness" | "robustness")) => {
+            let mut rule = || {
+                args.next().unwrap_or_else(|| {
+                    panic!("usage: cargo run -- {command} [problem path] [routing] [sequencing]")
+                })
+            };
+            let (routing, sequencing) = select(&rule(), &rule())?;
+            let time_slot = problem.depot().close / config.num_time_slot;
+            if command == "tightness" {
+                let tightness = Tightness::new(
+                    &problem,
+                    &routing,
+                    &sequencing,
+                    time_slot,
+                    &config.tightness_factors,
+                    &*config.objective,
+                );
+                println!("{tightness}");
+            } else {
+                let robustness = Robustness::new(
+                    &problem,
+                    &routing,
+                    &sequencing,
+                    time_slot,
+                    Perturb {
+                        seed: config.scenario_seed,
+                        ..config.perturb
+                    },
+                    config.robustness_copies,
+                    &*config.objective,
+                );
+                println!("{robustness}");
+            }
+            return Ok(());
+        }
+        Some(command @ ("explain" | "sensitivity" | "record")) => {
+            let mut rule = || {
+                args.next().unwrap_or_else(|| {
+                    panic!("usage: cargo run -- {command} [problem path] [routing] [sequencing]")
+                })
+            };
+            let (routing, sequencing) = match select(&rule(), &rule())? {
+                (Entry::Program(routing), Entry::Program(sequencing)) => (routing, sequencing),
+                _ => anyhow::bail!("{command} records program rules, not hand-written ones"),
+            };
+            anyhow::ensure!(
+                Trace::supported(&problem),
+                "{command} records a single day of program rules without LOOKAHEAD_K"
+            );
+            let time_slot = problem.depot().close / config.num_time_slot;
+            match command {
+                "explain" => {
+                    let explanation = Explanation::new(
+                        &problem,
+                        &routing,
+                        &sequencing,
+                        time_slot,
+                        config.explain_samples,
+                    );
+                    println!("{explanation}");
+                }
+                "record" => {
+                    let path = args.next().context(
+                        "usage: cargo run -- record [problem path] [routing] [sequencing] [dataset path]",
+                    )?;
+                    let samples = dataset::record(&problem, &routing, &sequencing, time_slot);
+                    dataset::save(&samples, &path)?;
+                    let overridden = samples.iter().filter(|s| s.chosen != s.rule_chosen);
+                    println!(
+                        "{} decisions recorded to {path}, {} of them overriding the rules",
+                        samples.len(),
+                        overridden.count()
+                    );
+                }
+                _ => {
+                    let sensitivity = Sensitivity::new(
+                        &problem,
+                        &routing,
+                        &sequencing,
+                        time_slot,
+                        config.sensitivity_points,
+                    );
+                    println!("{sensitivity}");
+                }
+            }
+            return Ok(());
+        }
+        Some("--dry-run") => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&DryRun::new(&problem, &config))?
+            );
+            let errors = validate(&problem, &config);
+            for error in &errors {
+                eprintln!("{error}");
+            }
+            anyhow::ensure!(errors.is_empty(), "{} invalid settings", errors.len());
+            return Ok(());
+        }
+        Some("race") => {
+            // the instances are raced on in turn, starting with the problem
+            let (parameters, others): (Vec<_>, Vec<_>) = args.partition(|arg| arg.contains('='));
+            let instances = [vec![path], others].concat();
+            let parameters = parameters
+                .iter()
+                .map(|arg| Parameter::parse(arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            let configs = sweep::configs(&parameters, config.sweep_samples, config.sweep_seed);
+            let mut race = Race::new(configs, config.race_alpha, config.race_min_stages);
+            for stage in 0..config.race_stages {
+                let instance = &instances[stage % instances.len()];
+                for c in race.alive() {
+                    let result = sweep::run(instance, &race.configs[c])?;
+                    race.results[c].push(result.fitness);
+                }
+                for c in race.eliminate() {
+                    println!(
+                        "stage {}: eliminated {} with mean fitness {}",
+                        stage + 1,
+                        sweep::describe(&race.configs[c]),
+                        race.mean(c)
+                    );
+                }
+                if race.alive().len() == 1 {
+                    break;
+                }
+            }
+            print!("{race}");
+            return Ok(());
+        }
+        _ => {}
+    }
     if HEU.enabled() {
         log!(MAIN, "heu_start");
-        heuristics(&problem)?;
+        heuristics(&problem, &config)?;
     }
-    if GP.enabled() {
+    // the GP runs for the page even without a GP log
+    let web = match command.as_deref() {
+        Some("--dashboard") => {
+            let web = WebDashboard::serve(&config.dashboard_address, &problem)
+                .with_context(|| format!("cannot serve on {}", config.dashboard_address))?;
+            eprintln!("dashboard at http://{}", config.dashboard_address);
+            Some(web)
+        }
+        _ => None,
+    };
+    if GP.enabled() || web.is_some() {
         log!(MAIN, "gp_start");
-        gp(&problem)?;
+        gp(&problem, &config, web.as_ref())?;
+    }
+    if web.is_some() && !INTERRUPTED.load(Ordering::Relaxed) {
+        eprintln!("finished, serving the last generation until interrupted");
+        while !INTERRUPTED.load(Ordering::Relaxed) {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
     }
     Ok(())
 }
+
+#[test]
+fn checkpoint_warm_start() {
+    let pop = Individual::baseline()
+        .into_iter()
+        .take(3)
+        .collect::<Vec<_>>();
+    let path = std::env::temp_dir().join(format!("vrpr-checkpoint-{}.jsonl", std::process::id()));
+    let mut writer = BufWriter::new(File::create(&path).unwrap());
+    write_checkpoint(&mut writer, 4, 120, &pop).unwrap();
+    drop(writer);
+    let loaded = Individual::warm_start(path.to_str().unwrap(), 6).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.len(), 3);
+    for (saved, loaded) in pop.iter().zip(&loaded) {
+        assert_eq!(saved.routing.to_string(), loaded.routing.to_string());
+        assert_eq!(saved.sequencing.to_string(), loaded.sequencing.to_string());
+    }
+}
+
+#[test]
+fn evaluation_budget() {
+    let mut config = Config::from_env().unwrap();
+    (config.max_seconds, config.max_evaluations) = (None, None);
+    assert!(!budget_exhausted(&config, 1e9, usize::MAX));
+    config.max_evaluations = Some(100);
+    assert!(!budget_exhausted(&config, 1e9, 99));
+    // a generation ending past the budget stops the run all the same
+    assert!(budget_exhausted(&config, 0.0, 100));
+    assert!(budget_exhausted(&config, 0.0, 164));
+    (config.max_seconds, config.max_evaluations) = (Some(2.0), None);
+    assert!(!budget_exhausted(&config, 1.9, 1000));
+    assert!(budget_exhausted(&config, 2.0, 0));
+}
@@ -0,0 +1,160 @@
+use crate::sim::{narrow, problem::Problem, Float, SimulationResult};
+
+// scores a simulated run, lower is better; `values` are the separate criteria
+// from the most to the least important, `fitness` folds them into the scalar
+// the GP selects on
+pub trait Objective: Send + Sync {
+    fn values(&self, problem: &Problem, result: &SimulationResult) -> Vec<f32>;
+    fn fitness(&self, problem: &Problem, result: &SimulationResult) -> f32;
+}
+
+// "weighted", "hierarchical", "service" or "profit"; a rolled over request
+// costs the `rollover_penalty` of `weighted` of a failure in all of them
+pub fn parse(str: &str, weighted: WeightedSum) -> Option<Box<dyn Objective>> {
+    let rollover_penalty = weighted.rollover_penalty;
+    Some(match str {
+        "weighted" => Box::new(weighted),
+        "hierarchical" => Box::new(Hierarchical { rollover_penalty }),
+        "service" => Box::new(ServiceLevel { rollover_penalty }),
+        "profit" => Box::new(Profit { rollover_penalty }),
+        _ => return None,
+    })
+}
+
+// the lateness penalty is a distance-equivalent cost, zero with hard windows
+fn cost(problem: &Problem, result: &SimulationResult) -> Float {
+    result.distance + problem.lateness_cost(result.lateness)
+}
+
+// the distance the whole fleet could drive over the horizon
+fn max_distance(problem: &Problem) -> Float {
+    problem.truck_speed
+        * problem.depot().close
+        * problem.num_trucks as Float
+        * problem.num_days as Float
+}
+
+fn failures(result: &SimulationResult, rollover_penalty: f32) -> f32 {
+    result.failed as f32 + rollover_penalty * result.rolled_over as f32
+}
+
+// mean per request as a share of the day
+fn per_request(problem: &Problem, time: Float) -> f32 {
+    narrow(time / problem.requests.len() as Float / problem.depot().close)
+}
+
+// `weight` blends distance and failures; the assignment-to-service wait and
+// the tardiness are added on top with their own weights
+pub struct WeightedSum {
+    pub weight: f32,
+    pub rollover_penalty: f32,
+    pub wait_weight: f32,
+    pub tardiness_weight: f32,
+}
+
+impl Objective for WeightedSum {
+    fn values(&self, problem: &Problem, result: &SimulationResult) -> Vec<f32> {
+        vec![
+            narrow(cost(problem, result) / max_distance(problem)),
+            failures(result, self.rollover_penalty) / problem.requests.len() as f32,
+            per_request(problem, result.assigned_wait),
+            per_request(problem, result.lateness),
+        ]
+    }
+
+    fn fitness(&self, problem: &Problem, result: &SimulationResult) -> f32 {
+        let values = self.values(problem, result);
+        values[0] * self.weight
+            + values[1] * (1.0 - self.weight)
+            + values[2] * self.wait_weight
+            + values[3] * self.tardiness_weight
+    }
+}
+
+// failures first, distance only breaks ties
+pub struct Hierarchical {
+    pub rollover_penalty: f32,
+}
+
+impl Objective for Hierarchical {
+    fn values(&self, problem: &Problem, result: &SimulationResult) -> Vec<f32> {
+        vec![
+            failures(result, self.rollover_penalty),
+            narrow(cost(problem, result)),
+        ]
+    }
+
+    // the normalized distance is squashed below 1 so it never outweighs a
+    // single failure
+    fn fitness(&self, problem: &Problem, result: &SimulationResult) -> f32 {
+        let values = self.values(problem, result);
+        let distance = values[1] / narrow(max_distance(problem));
+        values[0] + distance / (1.0 + distance)
+    }
+}
+
+// failures first, then tardiness and the assignment-to-service wait, distance
+// is ignored
+pub struct ServiceLevel {
+    pub rollover_penalty: f32,
+}
+
+impl Objective for ServiceLevel {
+    fn values(&self, problem: &Problem, result: &SimulationResult) -> Vec<f32> {
+        vec![
+            failures(result, self.rollover_penalty),
+            per_request(problem, result.lateness),
+            per_request(problem, result.assigned_wait),
+        ]
+    }
+
+    fn fitness(&self, problem: &Problem, result: &SimulationResult) -> f32 {
+        let values = self.values(problem, result);
+        let service = values[1] + values[2];
+        values[0] + service / (1.0 + service)
+    }
+}
+
+// failures only cost their lost revenue, rollovers the mean revenue of a
+// request
+pub struct Profit {
+    pub rollover_penalty: f32,
+}
+
+impl Objective for Profit {
+    fn values(&self, problem: &Problem, result: &SimulationResult) -> Vec<f32> {
+        let rolled_over = (self.rollover_penalty * result.rolled_over as f32) as Float;
+        let mean_revenue = problem.total_revenue() / problem.requests.len().max(1) as Float;
+        let rollover_cost = rolled_over * mean_revenue;
+        vec![-narrow(
+            problem.profit(result.revenue, cost(problem, result)) - rollover_cost,
+        )]
+    }
+
+    fn fitness(&self, problem: &Problem, result: &SimulationResult) -> f32 {
+        // an instance without revenue is only scored on its cost
+        1.0 + self.values(problem, result)[0] / narrow(problem.total_revenue()).max(f32::EPSILON)
+    }
+}
+
+#[test]
+fn profit_without_revenue() {
+    let (mut problem, _) = Problem::test_instance();
+    for request in problem.requests.iter_mut() {
+        request.revenue = 0.0;
+    }
+    let result = SimulationResult {
+        distance: 100.0,
+        ..Default::default()
+    };
+    let profit = Profit {
+        rollover_penalty: 1.0,
+    };
+    assert!(profit.fitness(&problem, &result).is_finite());
+    assert_eq!(profit.fitness(&problem, &SimulationResult::default()), 1.0);
+    let rolled_over = SimulationResult {
+        rolled_over: 2,
+        ..Default::default()
+    };
+    assert_eq!(profit.fitness(&problem, &rolled_over), 1.0);
+}
@@ -0,0 +1,90 @@
+// a distance and a number of failed requests, both minimized
+pub type Point = (f32, usize);
+
+fn dominates(a: Point, b: Point) -> bool {
+    a.0 <= b.0 && a.1 <= b.1 && a != b
+}
+
+// the non-dominated points found over a run, each with what reached it first
+pub struct Archive<T> {
+    pub members: Vec<(Point, T)>,
+    // the point hypervolumes are measured from, set past the worst of the
+    // first candidates so that later generations compare
+    reference: Option<Point>,
+}
+
+impl<T> Default for Archive<T> {
+    fn default() -> Self {
+        Self {
+            members: Vec::new(),
+            reference: None,
+        }
+    }
+}
+
+impl<T> Archive<T> {
+    // keeps the candidates no member dominates or equals, dropping the
+    // members they dominate; returns the number kept
+    pub fn update(&mut self, candidates: impl IntoIterator<Item = (Point, T)>) -> usize {
+        let candidates = candidates.into_iter().collect::<Vec<_>>();
+        if self.reference.is_none() && !candidates.is_empty() {
+            let worst = candidates
+                .iter()
+                .fold((0.0f32, 0), |(d, f), ((cd, cf), _)| {
+                    (d.max(*cd), f.max(*cf))
+                });
+            self.reference = Some((worst.0 * 1.1, worst.1 + 1));
+        }
+        let mut kept = 0;
+        for (point, member) in candidates {
+            if !self.admits(point) {
+                continue;
+            }
+            self.members.retain(|(other, _)| !dominates(point, *other));
+            self.members.push((point, member));
+            kept += 1;
+        }
+        self.members.sort_by(|a, b| a.0 .0.total_cmp(&b.0 .0));
+        kept
+    }
+
+    // that no member dominates or equals `point`
+    pub fn admits(&self, point: Point) -> bool {
+        (self.members.iter()).all(|(other, _)| *other != point && !dominates(*other, point))
+    }
+
+    // the area dominated by the members within the reference point
+    pub fn hypervolume(&self) -> f64 {
+        let Some((reference_distance, reference_failed)) = self.reference else {
+            return 0.0;
+        };
+        // by distance, so with ever fewer failed requests
+        let mut failed = reference_failed;
+        let mut volume = 0.0;
+        for &((distance, f), _) in &self.members {
+            if distance >= reference_distance || f >= failed {
+                continue;
+            }
+            volume += f64::from(reference_distance - distance) * (failed - f) as f64;
+            failed = f;
+        }
+        volume
+    }
+}
+
+#[test]
+fn pareto_archive() {
+    let mut archive = Archive::default();
+    // (20, 3) is dominated by (20, 0), later (12, 2) by (10, 2)
+    let kept = archive.update([((20.0, 0), 'a'), ((12.0, 2), 'b'), ((20.0, 3), 'c')]);
+    assert_eq!(kept, 2);
+    assert_eq!(archive.update([((10.0, 2), 'd'), ((10.0, 2), 'e')]), 1);
+    let members = archive.members.iter().map(|(_, m)| *m).collect::<String>();
+    assert_eq!(members, "da");
+    // against (22, 4): 12 x 2 for (10, 2), then 2 x 2 for (20, 0)
+    assert!((archive.hypervolume() - 28.0).abs() < 1e-4);
+    // a point beyond the reference is kept but adds nothing
+    assert_eq!(archive.update([((5.0, 5), 'f')]), 1);
+    assert!((archive.hypervolume() - 28.0).abs() < 1e-4);
+    assert_eq!(Archive::<()>::default().hypervolume(), 0.0);
+}
@@ -0,0 +1,21 @@
+use serde::Serialize;
+
+// what the dashboards show of one generation
+#[derive(Clone, Debug, Serialize)]
+pub struct Generation {
+    pub gen: usize,
+    pub best: f32,
+    // over the evaluated individuals with a finite fitness
+    pub mean: f32,
+    pub distance: f32,
+    pub failed: usize,
+    pub diversity: f32,
+    pub population: usize,
+    // nodes of both rules of an individual
+    pub min_nodes: usize,
+    pub mean_nodes: f32,
+    pub max_nodes: usize,
+    pub evaluations_per_second: f64,
+    pub routing: String,
+    pub sequencing: String,
+}
@@ -0,0 +1,167 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    significance::{ranks, wilcoxon},
+    sweep::{describe, Config},
+};
+
+// F-Race: every surviving configuration is run on the next instance of a
+// stream, and once `min_stages` instances are in, those the Friedman test
+// finds worse than the best one are dropped
+pub struct Race {
+    pub configs: Vec<Config>,
+    // fitness of every configuration on each instance it was run on
+    pub results: Vec<Vec<f32>>,
+    pub alive: Vec<bool>,
+    pub alpha: f32,
+    pub min_stages: usize,
+}
+
+// Abramowitz and Stegun 26.2.23, within 4.5e-4
+fn normal_quantile(p: f32) -> f32 {
+    let tail = if p < 0.5 { p } else { 1.0 - p };
+    let t = (-2.0 * tail.ln()).sqrt();
+    let x = t
+        - (2.515517 + 0.802853 * t + 0.010328 * t * t)
+            / (1.0 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t);
+    if p < 0.5 {
+        -x
+    } else {
+        x
+    }
+}
+
+// Wilson-Hilferty approximation
+fn chi_square_quantile(p: f32, df: f32) -> f32 {
+    let z = normal_quantile(p);
+    let a = 2.0 / (9.0 * df);
+    df * (1.0 - a + z * a.sqrt()).powi(3)
+}
+
+// Cornish-Fisher expansion around the normal quantile
+fn t_quantile(p: f32, df: f32) -> f32 {
+    let z = normal_quantile(p);
+    z + (z.powi(3) + z) / (4.0 * df)
+        + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * df * df)
+}
+
+impl Race {
+    pub fn new(configs: Vec<Config>, alpha: f32, min_stages: usize) -> Self {
+        Self {
+            results: vec![Vec::new(); configs.len()],
+            alive: vec![true; configs.len()],
+            configs,
+            alpha,
+            min_stages,
+        }
+    }
+
+    pub fn alive(&self) -> Vec<usize> {
+        (0..self.configs.len()).filter(|c| self.alive[*c]).collect()
+    }
+
+    pub fn stages(&self) -> usize {
+        self.alive().first().map_or(0, |c| self.results[*c].len())
+    }
+
+    pub fn mean(&self, config: usize) -> f32 {
+        let results = &self.results[config];
+        results.iter().sum::<f32>() / results.len() as f32
+    }
+
+    // drops the configurations that are significantly worse than the best
+    // one after the current stage, and returns them
+    pub fn eliminate(&mut self) -> Vec<usize> {
+        let alive = self.alive();
+        let (n, k) = (self.stages(), alive.len());
+        if n < self.min_stages || k < 2 {
+            return Vec::new();
+        }
+        let (nf, kf) = (n as f32, k as f32);
+        let blocks = (0..n)
+            .map(|stage| {
+                ranks(
+                    &alive
+                        .iter()
+                        .map(|c| self.results[*c][stage])
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let sums = (0..k)
+            .map(|j| blocks.iter().map(|ranks| ranks[j]).sum::<f32>())
+            .collect::<Vec<_>>();
+        let a: f32 = blocks.iter().flatten().map(|rank| rank * rank).sum();
+        let c = nf * kf * (kf + 1.0).powi(2) / 4.0;
+        if a - c <= 0.0 {
+            // every block ranks the configurations alike
+            return Vec::new();
+        }
+        let t = (kf - 1.0)
+            * sums
+                .iter()
+                .map(|sum| (sum - nf * (kf + 1.0) / 2.0).powi(2))
+                .sum::<f32>()
+            / (a - c);
+        if t <= chi_square_quantile(1.0 - self.alpha, kf - 1.0) {
+            return Vec::new();
+        }
+        // Conover's post-hoc comparison with the best rank sum
+        let df = (nf - 1.0) * (kf - 1.0);
+        let difference = t_quantile(1.0 - self.alpha / 2.0, df)
+            * (2.0 * nf * (a - c) / df * (1.0 - t / (nf * (kf - 1.0)))).sqrt();
+        let best = sums.iter().copied().fold(f32::INFINITY, f32::min);
+        let eliminated = (0..k)
+            .filter(|j| sums[*j] - best > difference)
+            .map(|j| alive[j])
+            .collect::<Vec<_>>();
+        for c in &eliminated {
+            self.alive[*c] = false;
+        }
+        eliminated
+    }
+}
+
+// the surviving configurations, best mean first, each tested against the best
+// one on the instances they both ran on
+impl Display for Race {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut alive = self.alive();
+        alive.sort_by(|a, b| self.mean(*a).total_cmp(&self.mean(*b)));
+        for &c in &alive {
+            write!(
+                f,
+                "{}: mean fitness {} over {} instances",
+                describe(&self.configs[c]),
+                self.mean(c),
+                self.results[c].len()
+            )?;
+            match wilcoxon(&self.results[alive[0]], &self.results[c]) {
+                Some(test) if c != alive[0] => writeln!(f, ", against the best: {test}")?,
+                _ => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn race_elimination() {
+    assert!((chi_square_quantile(0.95, 2.0) - 5.99).abs() < 0.1);
+    assert!((t_quantile(0.975, 10.0) - 2.228).abs() < 0.02);
+
+    let config = |value: &str| vec![("POP_SIZE".to_string(), value.to_string())];
+    let mut race = Race::new(vec![config("50"), config("100"), config("200")], 0.05, 5);
+    for stage in 0..8 {
+        // the first two take turns winning, the last one always loses
+        let turn = (stage % 2) as f32 * 0.2;
+        for (c, fitness) in [(0, 1.0 + turn), (1, 1.1), (2, 5.0)] {
+            race.results[c].push(fitness);
+        }
+        let eliminated = race.eliminate();
+        if stage < 4 {
+            assert!(eliminated.is_empty());
+        }
+    }
+    assert_eq!(race.alive(), [0, 1]);
+}
@@ -0,0 +1,91 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    objective::Objective,
+    sim::{
+        problem::Problem,
+        registry::{RoutingEntry, SequencingEntry},
+        scenario::Scenario,
+        simulate_rules,
+        training::{self, Perturb, TrainingTransform},
+        Float,
+    },
+};
+
+// a pair of rules run on perturbed copies of the instance, each drawn from
+// its own seed, against the instance as is
+pub struct Robustness {
+    pub original: f32,
+    // fitness and failed requests per copy
+    pub copies: Vec<(f32, usize)>,
+}
+
+impl Robustness {
+    pub fn new(
+        problem: &Problem,
+        routing: &RoutingEntry,
+        sequencing: &SequencingEntry,
+        time_slot: Float,
+        perturb: Perturb,
+        num_copies: usize,
+        objective: &dyn Objective,
+    ) -> Self {
+        let run = |problem: &Problem| {
+            let result = simulate_rules(
+                problem,
+                routing,
+                sequencing,
+                Scenario::default(),
+                time_slot,
+                |_, _| (),
+            );
+            (objective.fitness(problem, &result), result.failed)
+        };
+        let copies = (0..num_copies as u64)
+            .map(|copy| {
+                let perturb = Perturb {
+                    seed: perturb.seed + copy,
+                    ..perturb
+                };
+                let transforms: [Box<dyn TrainingTransform>; 1] = [Box::new(perturb)];
+                run(&training::apply(&transforms, problem, time_slot).0)
+            })
+            .collect();
+        Self {
+            original: run(problem).0,
+            copies,
+        }
+    }
+}
+
+impl Display for Robustness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let n = self.copies.len().max(1) as f32;
+        let mean = self.copies.iter().map(|c| c.0).sum::<f32>() / n;
+        let std = (self
+            .copies
+            .iter()
+            .map(|c| (c.0 - mean).powi(2))
+            .sum::<f32>()
+            / n)
+            .sqrt();
+        let worst = self
+            .copies
+            .iter()
+            .map(|c| c.0)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let failed = self.copies.iter().map(|c| c.1).sum::<usize>() as f32 / n;
+        writeln!(f, "original fitness: {}", self.original)?;
+        writeln!(
+            f,
+            "{} perturbed copies: mean {mean}, std {std}, worst {worst}, {failed:.1} failed on average",
+            self.copies.len()
+        )?;
+        write!(
+            f,
+            "degradation: {} on average, {} at worst",
+            mean - self.original,
+            worst - self.original
+        )
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use serde::Deserialize;
+
+use crate::sim::{
+    problem::{LoadError, Problem},
+    Float, Leg,
+};
+
+// a `route_log` line of the ROUTE or LASTROUTE logger
+#[derive(Clone, Debug, Deserialize)]
+pub struct LoggedRoute {
+    #[serde(default)]
+    pub day: usize,
+    pub vehicle: usize,
+    // request indices keyed by the truncated start of service
+    pub route: BTreeMap<i32, usize>,
+    #[serde(default)]
+    pub dropped: BTreeMap<i32, usize>,
+    #[serde(default)]
+    pub distance: Float,
+    #[serde(default)]
+    pub legs: Vec<Leg>,
+}
+
+// the routes of one simulation by day and vehicle; when a log holds several
+// simulations, the last one wins
+#[derive(Clone, Debug, Default)]
+pub struct RouteLog {
+    pub routes: BTreeMap<(usize, usize), LoggedRoute>,
+}
+
+impl RouteLog {
+    // lines that are not route logs are skipped
+    pub fn parse(reader: impl BufRead) -> Result<Self, LoadError> {
+        let mut log = Self::default();
+        for line in reader.lines() {
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(&line?) else {
+                continue;
+            };
+            if value.get("_").and_then(|name| name.as_str()) != Some("route_log") {
+                continue;
+            }
+            let route = serde_json::from_value::<LoggedRoute>(value)?;
+            log.routes.insert((route.day, route.vehicle), route);
+        }
+        Ok(log)
+    }
+
+    pub fn load(path: &str) -> Result<Self, LoadError> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    // where each customer was served
+    fn stops(&self, problem: &Problem) -> BTreeMap<usize, Stop> {
+        let num_depots = problem.depots.len() + problem.satellites.len();
+        let mut stops = BTreeMap::new();
+        for (&(day, vehicle), route) in &self.routes {
+            let customers = route.route.values().filter(|idx| **idx >= num_depots);
+            for (position, &idx) in customers.enumerate() {
+                stops.insert(idx, (day, vehicle, position));
+            }
+        }
+        stops
+    }
+}
+
+// day, vehicle and position in the route
+pub type Stop = (usize, usize, usize);
+
+pub struct VehicleDiff {
+    pub day: usize,
+    pub vehicle: usize,
+    pub distance: (Float, Float),
+    pub served: (usize, usize),
+}
+
+// what changed from run `a` to run `b` on the same instance
+pub struct RouteDiff {
+    pub vehicles: Vec<VehicleDiff>,
+    // request, where it was served in `a` and in `b`
+    pub moved: Vec<(usize, Stop, Stop)>,
+    pub reordered: usize,
+    pub newly_served: Vec<usize>,
+    pub newly_dropped: Vec<usize>,
+}
+
+impl RouteDiff {
+    pub fn new(problem: &Problem, a: &RouteLog, b: &RouteLog) -> Self {
+        let (stops_a, stops_b) = (a.stops(problem), b.stops(problem));
+        let keys = a.routes.keys().chain(b.routes.keys());
+        let vehicles = keys
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|&(day, vehicle)| {
+                let of = |log: &RouteLog, stops: &BTreeMap<_, _>| {
+                    let served = stops_of(stops, day, vehicle);
+                    let distance = log.routes.get(&(day, vehicle)).map_or(0.0, |r| r.distance);
+                    (distance, served)
+                };
+                let (distance_a, served_a) = of(a, &stops_a);
+                let (distance_b, served_b) = of(b, &stops_b);
+                VehicleDiff {
+                    day,
+                    vehicle,
+                    distance: (distance_a, distance_b),
+                    served: (served_a, served_b),
+                }
+            })
+            .collect();
+        let mut moved = Vec::new();
+        let mut reordered = 0;
+        for (&idx, &from) in &stops_a {
+            match stops_b.get(&idx) {
+                Some(&to) if (to.0, to.1) != (from.0, from.1) => moved.push((idx, from, to)),
+                Some(&to) if to.2 != from.2 => reordered += 1,
+                _ => {}
+            }
+        }
+        Self {
+            vehicles,
+            moved,
+            reordered,
+            newly_served: stops_b
+                .keys()
+                .filter(|idx| !stops_a.contains_key(idx))
+                .copied()
+                .collect(),
+            newly_dropped: stops_a
+                .keys()
+                .filter(|idx| !stops_b.contains_key(idx))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+fn stops_of(stops: &BTreeMap<usize, Stop>, day: usize, vehicle: usize) -> usize {
+    stops
+        .values()
+        .filter(|(d, v, _)| (*d, *v) == (day, vehicle))
+        .count()
+}
+
+impl Display for RouteDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let multi_day = self.vehicles.iter().any(|v| v.day > 0);
+        let label = |day: usize, vehicle: usize| {
+            if multi_day {
+                format!("day {day} vehicle {vehicle}")
+            } else {
+                format!("vehicle {vehicle}")
+            }
+        };
+        for v in &self.vehicles {
+            let (a, b) = v.distance;
+            writeln!(
+                f,
+                "{}: distance {a:.1} -> {b:.1} ({:+.1}), served {} -> {}",
+                label(v.day, v.vehicle),
+                b - a,
+                v.served.0,
+                v.served.1
+            )?;
+        }
+        writeln!(
+            f,
+            "{} requests changed vehicle, {} changed position on the same vehicle",
+            self.moved.len(),
+            self.reordered
+        )?;
+        for (idx, from, to) in &self.moved {
+            writeln!(
+                f,
+                "  request {idx}: {} #{} -> {} #{}",
+                label(from.0, from.1),
+                from.2,
+                label(to.0, to.1),
+                to.2
+            )?;
+        }
+        writeln!(f, "newly served: {:?}", self.newly_served)?;
+        write!(f, "no longer served: {:?}", self.newly_dropped)
+    }
+}
+
+// the logged legs played back on the instance: distances and service times
+// are recomputed and checked against the log, as are time windows, vehicle
+// capacities and the continuity of truck routes; only single-day runs without
+// travel or service noise replay exactly
+pub struct Replay {
+    pub distance: Float,
+    pub logged_distance: Float,
+    pub served: usize,
+    pub failed: usize,
+    pub lateness: Float,
+    pub violations: Vec<String>,
+}
+
+fn close(a: Float, b: Float) -> bool {
+    (a - b).abs() <= 1e-3 * a.abs().max(b.abs()).max(1.0)
+}
+
+impl Replay {
+    pub fn new(problem: &Problem, log: &RouteLog) -> Self {
+        let mut replay = Self {
+            distance: 0.0,
+            logged_distance: 0.0,
+            served: 0,
+            failed: 0,
+            lateness: 0.0,
+            violations: Vec::new(),
+        };
+        let nodes = problem
+            .depots
+            .iter()
+            .chain(&problem.satellites)
+            .chain(&problem.requests)
+            .map(|node| (node.idx, node))
+            .collect::<HashMap<_, _>>();
+        let mut served = HashSet::new();
+        for (&(day, vehicle), route) in &log.routes {
+            if day > 0 {
+                replay
+                    .violations
+                    .push(format!("vehicle {vehicle}: day {day} is not replayed"));
+                continue;
+            }
+            let mut violation = |message: String| {
+                replay
+                    .violations
+                    .push(format!("vehicle {vehicle}: {message}"))
+            };
+            if route.legs.is_empty() && !route.route.is_empty() {
+                violation("no legs logged".to_string());
+                continue;
+            }
+            let drone = vehicle >= problem.num_trucks;
+            let (speed, capacity) = if drone {
+                (problem.drone_speed, problem.drone_capacity)
+            } else {
+                problem.vehicle_class()
+            };
+            let mut at = problem.home_depot(vehicle % problem.num_trucks).idx;
+            let (mut distance, mut load) = (0.0, 0.0);
+            for leg in &route.legs {
+                let (Some(from), Some(to)) = (nodes.get(&leg.from), nodes.get(&leg.to)) else {
+                    violation(format!("unknown node {} or {}", leg.from, leg.to));
+                    continue;
+                };
+                // drones are carried along between their sorties
+                if !drone && leg.from != at {
+                    violation(format!("leaves {} while at {at}", leg.from));
+                }
+                at = leg.to;
+                distance += problem.distances.distance(from, to);
+                let mut start = leg.departed + problem.distances.travel_time(from, to, speed);
+                if !leg.reload || problem.is_depot(to) {
+                    start = start.max(to.open);
+                }
+                if !close(start, leg.start) {
+                    violation(format!(
+                        "starts {} at {}, expected {start}",
+                        to.idx, leg.start
+                    ));
+                }
+                if leg.reload {
+                    if leg.end + 1e-3 < leg.start {
+                        violation(format!("finishes reloading at {} before arriving", to.idx));
+                    }
+                    load = 0.0;
+                    continue;
+                }
+                // no-shows take no time
+                if leg.end == leg.start && to.service_time > 0.0 {
+                    continue;
+                }
+                if !close(leg.end, leg.start + to.service_time) {
+                    violation(format!("serves {} for {}", to.idx, leg.end - leg.start));
+                }
+                if leg.start > problem.latest_start(to) + 1e-3 {
+                    violation(format!("serves {} after its window", to.idx));
+                }
+                replay.lateness += (leg.start - to.close).max(0.0);
+                load += to.demand.abs();
+                if load > capacity + 1e-3 {
+                    violation(format!("carries {load} at {}", to.idx));
+                }
+                if !served.insert(to.idx) {
+                    violation(format!("serves {} again", to.idx));
+                }
+            }
+            if !close(distance, route.distance) {
+                violation(format!(
+                    "logged distance {}, replayed {distance}",
+                    route.distance
+                ));
+            }
+            replay.distance += distance;
+            replay.logged_distance += route.distance;
+        }
+        replay.served = served.len();
+        replay.failed = problem.requests.len() - served.len();
+        replay
+    }
+}
+
+impl Display for Replay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "distance: {:.3} replayed, {:.3} logged",
+            self.distance, self.logged_distance
+        )?;
+        writeln!(
+            f,
+            "served: {}, failed: {}, lateness: {:.1}",
+            self.served, self.failed, self.lateness
+        )?;
+        for violation in &self.violations {
+            writeln!(f, "  {violation}")?;
+        }
+        match self.violations.len() {
+            0 => write!(f, "the log matches the simulator"),
+            n => write!(f, "{n} mismatches"),
+        }
+    }
+}
+
+#[test]
+fn route_diff() {
+    let (problem, _) = Problem::test_instance();
+    let log = |lines: &str| RouteLog::parse(lines.as_bytes()).unwrap();
+    let a = log(concat!(
+        r#"{"__":"ROUTE","_":"route_log","vehicle":0,"route":{"5":3,"9":4,"20":0},"distance":10.0}"#,
+        "\n",
+        r#"{"__":"ROUTE","_":"route_log","vehicle":1,"route":{"7":5},"distance":4.0}"#,
+    ));
+    let b = log(concat!(
+        r#"{"__":"MAIN","_":"start"}"#,
+        "\n",
+        r#"{"__":"ROUTE","_":"route_log","vehicle":0,"route":{"5":4,"9":3},"distance":8.0}"#,
+        "\n",
+        r#"{"__":"ROUTE","_":"route_log","vehicle":1,"route":{"7":6},"distance":5.0}"#,
+    ));
+    let diff = RouteDiff::new(&problem, &a, &b);
+    assert_eq!(diff.vehicles[0].distance, (10.0, 8.0));
+    assert_eq!(diff.vehicles[0].served, (2, 2));
+    assert!(diff.moved.is_empty());
+    assert_eq!(diff.reordered, 2);
+    assert_eq!(diff.newly_served, vec![6]);
+    assert_eq!(diff.newly_dropped, vec![5]);
+}
+
+#[test]
+fn replay_legs() {
+    let (problem, _) = Problem::test_instance();
+    let (depot, request) = (problem.depot(), &problem.requests[0]);
+    let distance = problem.distances.distance(depot, request);
+    let start = distance.max(request.open);
+    let end = start + request.service_time;
+    let log = |start: Float| {
+        let legs = serde_json::to_string(&[
+            Leg {
+                from: depot.idx,
+                to: request.idx,
+                departed: 0.0,
+                start,
+                end: start + request.service_time,
+                reload: false,
+            },
+            Leg {
+                from: request.idx,
+                to: depot.idx,
+                departed: end,
+                start: end + distance,
+                end: end + distance,
+                reload: true,
+            },
+        ])
+        .unwrap();
+        let line = format!(
+            r#"{{"_":"route_log","vehicle":0,"route":{{}},"distance":{},"legs":{legs}}}"#,
+            2.0 * distance
+        );
+        RouteLog::parse(line.as_bytes()).unwrap()
+    };
+    let replay = Replay::new(&problem, &log(start));
+    assert!(replay.violations.is_empty());
+    assert_eq!(replay.served, 1);
+    assert_eq!(replay.failed, problem.requests.len() - 1);
+    assert_eq!(Replay::new(&problem, &log(start + 5.0)).violations.len(), 1);
+}
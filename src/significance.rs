@@ -0,0 +1,149 @@
+use std::{
+    f32::consts::SQRT_2,
+    fmt::{self, Display, Formatter},
+};
+
+// ranks from 1, ties share their mean rank
+pub fn ranks(values: &[f32]) -> Vec<f32> {
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let end = (start..order.len())
+            .find(|i| values[order[*i]] != values[order[start]])
+            .unwrap_or(order.len());
+        let rank = (start + end + 1) as f32 / 2.0;
+        for i in &order[start..end] {
+            ranks[*i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+// the sum of t^3 - t over the groups of `t` tied values, which shrinks the
+// variance of rank statistics
+fn ties(values: &[f32]) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted
+        .chunk_by(|a, b| a == b)
+        .map(|group| (group.len() as f32).powi(3) - group.len() as f32)
+        .sum()
+}
+
+// Abramowitz and Stegun 7.1.26, within 1.5e-7
+fn normal_cdf(z: f32) -> f32 {
+    let x = z.abs() / SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let polynomial =
+        ((((1.0614054 * t - 1.4531521) * t + 1.4214138) * t - 0.28449672) * t + 0.2548296) * t;
+    let erf = 1.0 - polynomial * (-x * x).exp();
+    if z < 0.0 {
+        0.5 * (1.0 - erf)
+    } else {
+        0.5 * (1.0 + erf)
+    }
+}
+
+// a two-sided rank test of whether one sample of fitness tends to be lower
+// than the other, by the normal approximation, which is rough below about 10
+// values; the rank-biserial effect size is in [-1, 1] and positive when the
+// first sample is lower
+#[derive(Clone, Copy, Debug)]
+pub struct RankTest {
+    pub name: &'static str,
+    pub z: f32,
+    pub p: f32,
+    pub effect: f32,
+}
+
+impl RankTest {
+    fn new(name: &'static str, z: f32, effect: f32) -> Self {
+        Self {
+            name,
+            z,
+            p: (2.0 * (1.0 - normal_cdf(z.abs()))).min(1.0),
+            effect,
+        }
+    }
+}
+
+// Mann-Whitney U test of independent runs, none when either sample is empty
+// or all values are tied
+pub fn mann_whitney(a: &[f32], b: &[f32]) -> Option<RankTest> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (na, nb) = (a.len() as f32, b.len() as f32);
+    let n = na + nb;
+    let all = [a, b].concat();
+    let rank_sum: f32 = ranks(&all)[..a.len()].iter().sum();
+    // the pairs in which `a` is higher, ties counting half
+    let u = rank_sum - na * (na + 1.0) / 2.0;
+    let variance = na * nb / 12.0 * (n + 1.0 - ties(&all) / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return None;
+    }
+    let z = (u - na * nb / 2.0) / variance.sqrt();
+    Some(RankTest::new("Mann-Whitney", z, 1.0 - 2.0 * u / (na * nb)))
+}
+
+// Wilcoxon signed-rank test of runs paired by instance or seed, none when no
+// pair differs
+pub fn wilcoxon(a: &[f32], b: &[f32]) -> Option<RankTest> {
+    let differences = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| a - b)
+        .filter(|d| *d != 0.0)
+        .collect::<Vec<_>>();
+    if differences.is_empty() {
+        return None;
+    }
+    let magnitudes = differences.iter().map(|d| d.abs()).collect::<Vec<_>>();
+    let ranks = ranks(&magnitudes);
+    // the rank sum of the pairs in which `a` is higher
+    let higher: f32 = (0..differences.len())
+        .filter(|i| differences[*i] > 0.0)
+        .map(|i| ranks[i])
+        .sum();
+    let n = differences.len() as f32;
+    let total = n * (n + 1.0) / 2.0;
+    let variance = n * (n + 1.0) * (2.0 * n + 1.0) / 24.0 - ties(&magnitudes) / 48.0;
+    let z = (higher - total / 2.0) / variance.sqrt();
+    Some(RankTest::new("Wilcoxon", z, 1.0 - 2.0 * higher / total))
+}
+
+impl Display for RankTest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} p={:.4} (z {:.2}, effect {:+.2})",
+            self.name, self.p, self.z, self.effect
+        )
+    }
+}
+
+#[test]
+fn rank_tests() {
+    assert_eq!(ranks(&[3.0, 1.0, 3.0, 2.0]), [3.5, 1.0, 3.5, 2.0]);
+    assert!((normal_cdf(1.96) - 0.975).abs() < 1e-4);
+
+    let lower = [1.0, 2.0, 3.0, 4.0, 5.0];
+    let higher = [6.0, 7.0, 8.0, 9.0, 10.0];
+    let test = mann_whitney(&lower, &higher).unwrap();
+    assert!((test.z + 2.611).abs() < 1e-3);
+    assert!((test.p - 0.009).abs() < 1e-3);
+    assert_eq!(test.effect, 1.0);
+    assert_eq!(mann_whitney(&higher, &lower).unwrap().effect, -1.0);
+    assert!(mann_whitney(&[1.0, 1.0], &[1.0]).is_none());
+
+    let before = [2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 14.0, 16.0];
+    let after = [3.0, 6.0, 9.0, 12.0, 15.0, 18.0, 21.0, 24.0];
+    let test = wilcoxon(&before, &after).unwrap();
+    assert!((test.p - 0.0117).abs() < 1e-3);
+    assert_eq!(test.effect, 1.0);
+    assert!(wilcoxon(&before, &before).is_none());
+}
@@ -0,0 +1,178 @@
+use std::{cell::Cell, collections::HashMap};
+
+use ordered_float::OrderedFloat;
+
+use crate::gp::program::{Node, Program, ProgramContext};
+
+use super::{
+    ctx::{RoutingProgram, SequencingProgram},
+    problem::{splitmix, Problem, Request},
+    trace::Record,
+    widen, Float, RoutingRule, SequencingRule, VehicleState,
+};
+
+// classical dispatching rules as program trees, lower values are preferred
+// like for evolved rules
+fn tree<C: ProgramContext>(nodes: Vec<Node>) -> Program<C> {
+    Program::from_vec(nodes.into_iter().map(u8::from).collect())
+}
+
+// time to reach the request, the `C` routing heuristic
+pub fn nearest_vehicle<'a>() -> RoutingProgram<'a> {
+    Program::terminal(3)
+}
+
+// capacity left after the queued requests, the `WIQ` heuristic
+pub fn work_in_queue<'a>() -> RoutingProgram<'a> {
+    Program::terminal(1)
+}
+
+pub fn shortest_queue<'a>() -> RoutingProgram<'a> {
+    Program::terminal(0)
+}
+
+// time until the vehicle is done with its current stop
+pub fn earliest_available<'a>() -> RoutingProgram<'a> {
+    Program::terminal(15)
+}
+
+// the later of the drive there and the time waited since the window opened,
+// the `C` sequencing heuristic
+pub fn cost<'a>() -> SequencingProgram<'a> {
+    tree(vec![
+        Node::Internal(5),
+        Node::Terminal(0),
+        Node::Terminal(4),
+    ])
+}
+
+// share of the queued demand, the `W` heuristic
+pub fn demand<'a>() -> SequencingProgram<'a> {
+    Program::terminal(3)
+}
+
+// earliest due date: the drive there divided by the share of the time left
+// before the window closes that it takes, i.e. that time left
+pub fn earliest_due_date<'a>() -> SequencingProgram<'a> {
+    tree(vec![
+        Node::Internal(3),
+        Node::Terminal(0),
+        Node::Internal(1),
+        Node::Null,
+        Node::Null,
+        Node::Const(1.0),
+        Node::Terminal(2),
+    ])
+}
+
+pub fn first_released<'a>() -> SequencingProgram<'a> {
+    Program::terminal(5)
+}
+
+pub fn routing_rules<'a>() -> Vec<RoutingProgram<'a>> {
+    vec![
+        nearest_vehicle(),
+        work_in_queue(),
+        shortest_queue(),
+        earliest_available(),
+    ]
+}
+
+pub fn sequencing_rules<'a>() -> Vec<SequencingProgram<'a>> {
+    vec![cost(), demand(), earliest_due_date(), first_released()]
+}
+
+// deliberately weak rules, a floor any evolved rule should clear: a feasible
+// vehicle drawn at random, from `seed` and the decision so that runs repeat
+pub struct Random {
+    pub seed: u64,
+}
+
+impl RoutingRule for Random {
+    fn rank_vehicles(
+        &self,
+        problem: &Problem,
+        time: Float,
+        vehicles: &[VehicleState],
+        request: &Request,
+        _: &Cell<usize>,
+        _: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let key = [request.idx as u64, widen(time).to_bits()];
+        let state = key
+            .iter()
+            .fold(self.seed, |state, part| splitmix(state ^ part));
+        let mut ranked = (0..vehicles.len())
+            .filter(|v| vehicles[*v].can_route(problem, request, time))
+            .map(|v| (v, splitmix(state ^ v as u64)))
+            .collect::<Vec<_>>();
+        ranked.sort_by_key(|(_, draw)| *draw);
+        // a value of 0 never defers the request
+        ranked.into_iter().map(|(v, _)| (v, 0.0)).collect()
+    }
+}
+
+// the queued requests in the order they were queued in
+pub struct Fifo;
+
+impl SequencingRule for Fifo {
+    fn rank_queue(
+        &self,
+        _: &Problem,
+        _: Float,
+        _: usize,
+        vehicle_state: &VehicleState,
+        _: &mut HashMap<usize, (OrderedFloat<Float>, usize)>,
+        _: &Cell<usize>,
+        _: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let mut ranked = (vehicle_state.queue().iter().enumerate())
+            .map(|(i, (_, queued))| (i, *queued))
+            .collect::<Vec<_>>();
+        ranked.sort_by_key(|&(i, queued)| (OrderedFloat(queued), i));
+        ranked
+    }
+}
+
+#[test]
+fn baseline_rules_are_valid() {
+    for rule in routing_rules() {
+        rule.validate().unwrap();
+    }
+    for rule in sequencing_rules() {
+        rule.validate().unwrap();
+    }
+    assert_eq!(earliest_due_date().to_string(), "div(TERM0, sub(1, TERM2))");
+}
+
+#[test]
+fn weak_baselines() {
+    use std::sync::Arc;
+
+    use super::{registry::Rules, scenario::Scenario, simulate_days};
+
+    let (problem, time_slot) = Problem::test_instance();
+    let run = |problem: &Problem| {
+        let result = simulate_days(
+            problem,
+            &nearest_vehicle(),
+            &cost(),
+            Scenario::default(),
+            time_slot,
+            |_, _| (),
+        );
+        (result.distance, result.failed)
+    };
+    let weak = |seed| Problem {
+        rules: Rules {
+            routing: Some(Arc::new(Random { seed })),
+            sequencing: Some(Arc::new(Fifo)),
+        },
+        ..problem.clone()
+    };
+    let classical = run(&problem);
+    let random = run(&weak(0));
+    assert_eq!(random, run(&weak(0)));
+    assert_ne!(random, run(&weak(1)));
+    assert!(random.0 > classical.0 && random.1 >= classical.1);
+}
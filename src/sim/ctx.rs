@@ -1,15 +1,19 @@
-use std::fmt::{self, Formatter};
+use std::{
+    borrow::Cow,
+    fmt::{self, Formatter},
+};
 
 use smallvec::SmallVec;
 
-use crate::gp::program::{Program, ProgramContext, MAX_PROGRAM_NODE_CHILDREN};
+use crate::gp::program::{Interval, Program, ProgramContext, MAX_PROGRAM_NODE_CHILDREN, UNBOUNDED};
 
 use super::{
+    narrow,
     problem::{Problem, Request},
-    VehicleState,
+    Float, VehicleState,
 };
 
-fn safe_div(x: f32, y: f32) -> f32 {
+fn safe_div(x: Float, y: Float) -> Float {
     if y.abs() < 1e-4 {
         1.0
     } else {
@@ -20,45 +24,154 @@ fn safe_div(x: f32, y: f32) -> f32 {
 pub struct RoutingContext<'a> {
     pub vehicle_state: &'a VehicleState<'a>,
     pub problem: &'a Problem,
-    pub time: f32,
+    pub time: Float,
     pub request: &'a Request,
+    // vehicles the request could be assigned to
+    pub num_feasible: usize,
+    // `Problem::total_demand`, computed once per decision
+    pub total_demand: Float,
 }
 
 pub struct SequencingContext<'a> {
     pub vehicle_state: &'a VehicleState<'a>,
     pub problem: &'a Problem,
-    pub time: f32,
+    pub time: Float,
     pub request: &'a Request,
-    pub ready_time: f32,
+    pub ready_time: Float,
+    pub total_demand: Float,
 }
 
+// every internal there is; the function set picks those the GP generates,
+// by default the binary ones
+pub const INTERNAL_NAMES: [&str; 8] = ["sum", "sub", "mul", "div", "min", "max", "neg", "abs"];
+const BINARY_INTERNALS: [usize; 6] = [0, 1, 2, 3, 4, 5];
+
 fn common_num_internal() -> usize {
-    6
+    INTERNAL_NAMES.len()
+}
+
+// `neg` and `abs` take a single operand
+pub fn common_internal_num_children(index: usize) -> usize {
+    match index {
+        6 | 7 => 1,
+        _ => 2,
+    }
 }
 
-fn common_internal_num_children(_: usize) -> usize {
-    2
+// sum, mul, min and max
+fn common_is_commutative(index: usize) -> bool {
+    matches!(index, 0 | 2 | 4 | 5)
 }
 
 fn common_format_terminal(index: usize, f: &mut Formatter<'_>) -> fmt::Result {
-    write!(
-        f,
-        "{}",
-        match index {
-            0 => "sum",
-            1 => "sub",
-            2 => "mul",
-            3 => "div",
-            4 => "min",
-            5 => "max",
-            _ => unreachable!(),
+    write!(f, "{}", INTERNAL_NAMES[index])
+}
+
+fn common_latex_internal(index: usize, children: &[(String, bool)]) -> (String, bool) {
+    let paren = |(child, additive): &(String, bool)| match additive {
+        true => format!("\\left({child}\\right)"),
+        false => child.clone(),
+    };
+    let (x, y) = match children {
+        [x] => return common_latex_unary(index, x, paren(x)),
+        [x, y, ..] => (x, y),
+        _ => unreachable!(),
+    };
+    match index {
+        0 => (format!("{} + {}", x.0, y.0), true),
+        1 => (format!("{} - {}", x.0, paren(y)), true),
+        2 => (format!("{} \\cdot {}", paren(x), paren(y)), false),
+        3 => (format!("\\frac{{{}}}{{{}}}", x.0, y.0), false),
+        4 => (format!("\\min\\left({}, {}\\right)", x.0, y.0), false),
+        5 => (format!("\\max\\left({}, {}\\right)", x.0, y.0), false),
+        _ => unreachable!(),
+    }
+}
+
+fn common_latex_unary(index: usize, x: &(String, bool), paren: String) -> (String, bool) {
+    match index {
+        6 => (format!("-{paren}"), true),
+        7 => (format!("\\left|{}\\right|", x.0), false),
+        _ => unreachable!(),
+    }
+}
+
+// short names of the terminals by index, in papers and reports
+pub const ROUTING_NAMES: [&str; 18] = [
+    "NIQ", "RC", "DMQ", "TC", "DEM", "DH", "CR", "RR", "FS", "SL", "TUC", "DRN", "DW", "DNQ", "NB",
+    "BT", "ST", "FV",
+];
+pub const SEQUENCING_NAMES: [&str; 12] = [
+    "TC", "TIQ", "SR", "DEM", "WT", "REL", "CR", "FS", "DH", "RL", "DQ", "WO",
+];
+
+// the bounds of `common_internal` over operands within `children`; the
+// operands are finite, so 0 times an unbounded one is still 0
+fn common_internal_range(index: usize, children: &[Interval]) -> Interval {
+    let ((a, b), (c, d)) = match children {
+        [(a, b)] => {
+            return match index {
+                6 => (-b, -a),
+                7 if *a >= 0.0 => (*a, *b),
+                7 if *b <= 0.0 => (-b, -a),
+                7 => (0.0, (-a).max(*b)),
+                _ => unreachable!(),
+            }
+        }
+        [x, y, ..] => (*x, *y),
+        _ => unreachable!(),
+    };
+    let hull = |values: [f32; 4]| {
+        values
+            .into_iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), value| {
+                (lo.min(value), hi.max(value))
+            })
+    };
+    match index {
+        0 => (a + c, b + d),
+        1 => (a - d, b - c),
+        2 => hull([a * c, a * d, b * c, b * d].map(|v| if v.is_nan() { 0.0 } else { v })),
+        // divisors too close to 0 give 1
+        3 if c > -1e-4 && d < 1e-4 => (1.0, 1.0),
+        3 if d <= -1e-4 || c >= 1e-4 => {
+            let quotients = [a / c, a / d, b / c, b / d];
+            match quotients.iter().any(|q| q.is_nan()) {
+                true => UNBOUNDED,
+                false => hull(quotients),
+            }
         }
-    )
+        3 => UNBOUNDED,
+        4 => (a.min(c), b.min(d)),
+        5 => (a.max(c), b.max(d)),
+        _ => unreachable!(),
+    }
+}
+
+// the operand of a `min` or `max` the other never beats
+fn common_dominant_child(index: usize, children: &[Interval]) -> Option<usize> {
+    let [(a, b), (c, d)] = *children else {
+        return None;
+    };
+    match index {
+        4 if b <= c => Some(0),
+        4 if d <= a => Some(1),
+        5 if a >= d => Some(0),
+        5 if c >= b => Some(1),
+        _ => None,
+    }
 }
 
-fn common_internal(idx: usize, child_values: SmallVec<[f32; MAX_PROGRAM_NODE_CHILDREN]>) -> f32 {
+pub fn common_internal(
+    idx: usize,
+    child_values: SmallVec<[f32; MAX_PROGRAM_NODE_CHILDREN]>,
+) -> f32 {
     let x = child_values[0];
-    let y = child_values[1];
+    let y = match idx {
+        6 => return -x,
+        7 => return x.abs(),
+        _ => child_values[1],
+    };
     match idx {
         0 => x + y,
         1 => x - y,
@@ -80,6 +193,29 @@ pub type RoutingProgram<'a> = Program<RoutingContext<'a>>;
 pub type SequencingProgram<'a> = Program<SequencingContext<'a>>;
 
 impl<'a> ProgramContext for RoutingContext<'a> {
+    const RULE: &'static str = "routing";
+
+    fn generated_internals() -> Cow<'static, [usize]> {
+        Cow::Borrowed(&BINARY_INTERNALS)
+    }
+
+    // shares, risks and flags, and times and distances
+    fn terminal_range(index: usize) -> Interval {
+        match index {
+            0 | 4 | 6 | 9 | 11 | 14 | 17 => (0.0, 1.0),
+            2 | 3 | 5 | 12 | 13 | 15 => (0.0, f32::INFINITY),
+            _ => UNBOUNDED,
+        }
+    }
+
+    fn internal_range(index: usize, children: &[Interval]) -> Interval {
+        common_internal_range(index, children)
+    }
+
+    fn dominant_child(index: usize, children: &[Interval]) -> Option<usize> {
+        common_dominant_child(index, children)
+    }
+
     fn internal(
         &self,
         idx: usize,
@@ -92,6 +228,10 @@ impl<'a> ProgramContext for RoutingContext<'a> {
         common_internal_num_children(index)
     }
 
+    fn is_commutative(index: usize) -> bool {
+        common_is_commutative(index)
+    }
+
     fn num_internals() -> usize {
         common_num_internal()
     }
@@ -100,42 +240,119 @@ impl<'a> ProgramContext for RoutingContext<'a> {
         common_format_terminal(index, f)
     }
 
+    fn latex_terminal(index: usize) -> String {
+        format!("\\mathit{{{}}}", ROUTING_NAMES[index])
+    }
+
+    fn latex_internal(index: usize, children: &[(String, bool)]) -> (String, bool) {
+        common_latex_internal(index, children)
+    }
+
     fn terminal(&self, idx: usize) -> f32 {
-        match idx {
-            0 => self.vehicle_state.queue.len() as f32 / self.problem.requests.len() as f32,
+        narrow(match idx {
+            0 => self.vehicle_state.queue.len() as Float / self.problem.requests.len() as Float,
             1 => {
-                (self.problem.truck_capacity
-                    - self
-                        .vehicle_state
-                        .queue
-                        .iter()
-                        .map(|r| r.0.demand)
-                        .sum::<f32>())
-                    / self.problem.total_demand()
+                (self.problem.truck_capacity - self.vehicle_state.queued_demand())
+                    / self.total_demand
             }
             2 => {
-                let (x, y) = self.vehicle_state.median_queue_pos();
-                let (rx, ry) = (self.request.x, self.request.y);
-                ((x - rx) * (x - rx) + (y - ry) * (y - ry)).sqrt()
+                let median = self.vehicle_state.median_queue_pos();
+                let request = (self.request.x, self.request.y);
+                self.problem.coordinates.distance(median, request)
                     / self.problem.truck_speed
-                    / self.problem.depot.close
+                    / self.problem.depot().close
             }
             3 => {
                 self.vehicle_state
                     .raw_time_cost(self.problem, self.request, self.time)
-                    / self.problem.depot.close
+                    / self.problem.depot().close
+            }
+            4 => self.request.demand / self.total_demand,
+            5 => {
+                self.vehicle_state.home_distance(self.request)
+                    / self.vehicle_state.speed
+                    / self.problem.depot().close
+            }
+            6 => {
+                let arrival = self.time
+                    + self
+                        .vehicle_state
+                        .raw_time_cost(self.problem, self.request, self.time);
+                self.problem.cancel_risk(self.request, arrival)
+            }
+            7 => match self.vehicle_state.range {
+                max if max.is_finite() => self.vehicle_state.remaining_range(self.problem) / max,
+                _ => 1.0,
+            },
+            8 => self.vehicle_state.free_space(self.problem) / self.vehicle_state.capacity,
+            9 => self.vehicle_state.queue_slack(self.problem),
+            10 => (self.request.close - self.time) / self.problem.depot().close,
+            11 => self.vehicle_state.is_drone() as u8 as Float,
+            12 => {
+                (self.vehicle_state.home_dock_free - self.time).max(0.0)
+                    / self.problem.depot().close
             }
-            4 => self.request.demand / self.problem.total_demand(),
+            // the current stop stands in for an empty queue
+            13 => {
+                let distance = self
+                    .vehicle_state
+                    .nearest_queued(self.request)
+                    .unwrap_or_else(|| self.vehicle_state.distance_to(self.request));
+                distance / self.problem.truck_speed / self.problem.depot().close
+            }
+            14 => match self.vehicle_state.queue.len() {
+                0 => 0.0,
+                n => {
+                    self.vehicle_state
+                        .queued_within(self.request, self.problem.neighbor_radius)
+                        as Float
+                        / n as Float
+                }
+            },
+            15 => (self.vehicle_state.busy_until - self.time).max(0.0) / self.problem.depot().close,
+            // time left before the window closes after driving there, negative
+            // when late
+            16 => {
+                let arrival = self.time
+                    + self
+                        .vehicle_state
+                        .raw_time_cost(self.problem, self.request, self.time);
+                (self.request.close - arrival) / self.problem.depot().close
+            }
+            17 => self.num_feasible as Float / self.problem.num_vehicles() as Float,
             _ => unreachable!(),
-        }
+        })
     }
 
     fn num_terminals() -> usize {
-        5
+        18
     }
 }
 
 impl<'a> ProgramContext for SequencingContext<'a> {
+    const RULE: &'static str = "sequencing";
+
+    fn generated_internals() -> Cow<'static, [usize]> {
+        Cow::Borrowed(&BINARY_INTERNALS)
+    }
+
+    // shares, risks and flags, and times and distances
+    fn terminal_range(index: usize) -> Interval {
+        match index {
+            3 | 6 => (0.0, 1.0),
+            0 | 5 | 8 | 10 | 11 => (0.0, f32::INFINITY),
+            _ => UNBOUNDED,
+        }
+    }
+
+    fn internal_range(index: usize, children: &[Interval]) -> Interval {
+        common_internal_range(index, children)
+    }
+
+    fn dominant_child(index: usize, children: &[Interval]) -> Option<usize> {
+        common_dominant_child(index, children)
+    }
+
     fn internal(
         &self,
         idx: usize,
@@ -148,6 +365,10 @@ impl<'a> ProgramContext for SequencingContext<'a> {
         common_internal_num_children(index)
     }
 
+    fn is_commutative(index: usize) -> bool {
+        common_is_commutative(index)
+    }
+
     fn num_internals() -> usize {
         common_num_internal()
     }
@@ -156,24 +377,141 @@ impl<'a> ProgramContext for SequencingContext<'a> {
         common_format_terminal(index, f)
     }
 
+    fn latex_terminal(index: usize) -> String {
+        format!("\\mathit{{{}}}", SEQUENCING_NAMES[index])
+    }
+
+    fn latex_internal(index: usize, children: &[(String, bool)]) -> (String, bool) {
+        common_latex_internal(index, children)
+    }
+
     fn terminal(&self, idx: usize) -> f32 {
         let raw_time_cost = self
             .vehicle_state
             .raw_time_cost(self.problem, self.request, self.time);
         let time_until_close = self.request.close - self.vehicle_state.busy_until;
         let wait_time = self.time - self.request.open;
-        match idx {
-            0 => raw_time_cost / self.problem.depot.close,
-            1 => (self.time - self.ready_time) / self.problem.depot.close,
+        narrow(match idx {
+            0 => raw_time_cost / self.problem.depot().close,
+            1 => (self.time - self.ready_time) / self.problem.depot().close,
             2 => safe_div(time_until_close - raw_time_cost, time_until_close),
-            3 => self.request.demand / self.problem.total_demand(),
-            4 => wait_time / self.problem.depot.close,
-            5 => self.request.time / self.problem.depot.close,
+            3 => self.request.demand / self.total_demand,
+            4 => wait_time / self.problem.depot().close,
+            5 => self.request.time / self.problem.depot().close,
+            6 => self
+                .problem
+                .cancel_risk(self.request, self.time + raw_time_cost),
+            7 => self.vehicle_state.free_space(self.problem) / self.vehicle_state.capacity,
+            8 => {
+                self.vehicle_state.home_distance(self.request)
+                    / self.vehicle_state.speed
+                    / self.problem.depot().close
+            }
+            // the room left for pickups or the goods left on board
+            9 => {
+                let left = if self.request.pickup {
+                    self.vehicle_state.free_space(self.problem)
+                } else {
+                    self.vehicle_state.total_demand
+                };
+                (left - self.request.demand) / self.vehicle_state.capacity
+            }
+            10 => safe_div(self.request.demand, self.vehicle_state.queued_demand()),
+            // waiting for the window to open after driving there
+            11 => {
+                (self.request.open - self.time - raw_time_cost).max(0.0)
+                    / self.problem.depot().close
+            }
             _ => unreachable!(),
-        }
+        })
     }
 
     fn num_terminals() -> usize {
-        6
+        12
+    }
+}
+
+#[test]
+fn terminals_fit_encoding() {
+    use crate::gp::program::Node;
+
+    for last in [
+        RoutingContext::num_terminals() - 1,
+        SequencingContext::num_terminals() - 1,
+    ] {
+        let byte = u8::from(Node::Terminal(last));
+        assert!(matches!(Node::from(byte), Node::Terminal(index) if index == last));
     }
 }
+
+#[test]
+fn latex_names() {
+    assert_eq!(ROUTING_NAMES.len(), RoutingContext::num_terminals());
+    assert_eq!(SEQUENCING_NAMES.len(), SequencingContext::num_terminals());
+    let program = "mul(sub(TERM0, sum(TERM3, -0.5)), div(TERM4, max(TERM1, 1)))"
+        .parse::<SequencingProgram>()
+        .unwrap();
+    assert_eq!(
+        program.latex(),
+        "\\left(\\mathit{TC} - \\left(\\mathit{DEM} + \\left(-0.5\\right)\\right)\\right) \\cdot \\frac{\\mathit{WT}}{\\max\\left(\\mathit{TIQ}, 1\\right)}"
+    );
+}
+
+#[test]
+fn interval_folding() {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    let fold = |expression: &str, ranged| {
+        let mut program = expression.parse::<RoutingProgram>().unwrap();
+        let removed = program.fold(ranged);
+        (program.to_string(), removed)
+    };
+    assert_eq!(fold("mul(sum(1, 0.5), TERM1)", false).0, "mul(1.5, TERM1)");
+    assert_eq!(fold("mul(sub(TERM1, 0), 0)", false), ("0".to_string(), 4));
+    // a share is never above 1, nor a distance below 0
+    assert_eq!(fold("max(TERM4, 2)", true).0, "2");
+    assert_eq!(fold("max(TERM4, 2)", false).0, "max(TERM4, 2)");
+    assert_eq!(fold("min(TERM3, sub(0, TERM0))", true).0, "sub(0, TERM0)");
+    // 1/3 is no constant, nor is the quotient bounded near 0
+    assert_eq!(fold("div(1, 3)", false).0, "div(1, 3)");
+    assert_eq!(fold("div(TERM0, TERM4)", true).0, "div(TERM0, TERM4)");
+
+    // the folded program agrees with the original within the ranges
+    let mut rng = SmallRng::seed_from_u64(0);
+    let original = "max(mul(TERM0, min(TERM9, 3)), sum(div(TERM2, 0.5), min(TERM4, -1)))"
+        .parse::<RoutingProgram>()
+        .unwrap();
+    let mut folded = original.clone();
+    assert!(folded.fold(true) > 0);
+    for _ in 0..100 {
+        let terminals = (0..RoutingContext::num_terminals())
+            .map(|index| {
+                let (lo, hi) = RoutingContext::terminal_range(index);
+                rng.gen_range(lo.max(-10.0)..=hi.min(10.0))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            original.calc_terminals(&terminals, common_internal),
+            folded.calc_terminals(&terminals, common_internal)
+        );
+    }
+}
+
+#[test]
+fn unary_internals() {
+    let program = "abs(neg(sub(TERM0, TERM1)))"
+        .parse::<SequencingProgram>()
+        .unwrap();
+    assert_eq!(program.calc_terminals(&[1.0, 3.0], common_internal), 2.0);
+    assert_eq!(
+        program.latex(),
+        "\\left|-\\left(\\mathit{TC} - \\mathit{TIQ}\\right)\\right|"
+    );
+    // a time is never negative, nor is its absolute value below 0
+    let mut folded = "max(abs(neg(TERM0)), -1)"
+        .parse::<SequencingProgram>()
+        .unwrap();
+    folded.fold(true);
+    assert_eq!(folded.to_string(), "abs(neg(TERM0))");
+    assert!(!SequencingContext::generated_internals().contains(&6));
+}
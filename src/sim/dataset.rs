@@ -0,0 +1,184 @@
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use crate::gp::program::ProgramContext;
+
+use super::{
+    ctx::{common_internal, RoutingContext, RoutingProgram, SequencingContext, SequencingProgram},
+    problem::Problem,
+    protect,
+    scenario::Scenario,
+    trace::{simulate_recorded, Decision, Record},
+    Float,
+};
+
+// a vehicle or request offered to a rule, with its rule value and the full
+// terminal vector it was computed from
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alternative {
+    pub id: usize,
+    pub value: Float,
+    pub terminals: Vec<f32>,
+}
+
+// one decision of a run, for offline analysis and imitation: `chosen` is the
+// candidate the simulation went with and `rule_chosen` the one the rule
+// picked, which differ when an insertion dispatcher or a sequencing search
+// overrode it; `runner_up` is the best rule value among the others
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sample {
+    pub routing: bool,
+    pub time: Float,
+    pub subject: usize,
+    pub chosen: usize,
+    pub rule_chosen: usize,
+    pub runner_up: Option<Float>,
+    pub deferred: bool,
+    pub candidates: Vec<Alternative>,
+}
+
+impl Sample {
+    fn new(
+        record: &Record,
+        decision: &Decision,
+        routing: &RoutingProgram,
+        sequencing: &SequencingProgram,
+    ) -> Self {
+        let non_finite = Cell::new(0);
+        let candidates = decision
+            .candidates
+            .iter()
+            .map(|&(offset, _, id)| {
+                let (terminals, value) = if decision.routing {
+                    let terminals = record.terminals(offset, RoutingContext::num_terminals());
+                    (
+                        terminals,
+                        routing.calc_terminals(terminals, common_internal),
+                    )
+                } else {
+                    let terminals = record.terminals(offset, SequencingContext::num_terminals());
+                    (
+                        terminals,
+                        sequencing.calc_terminals(terminals, common_internal),
+                    )
+                };
+                Alternative {
+                    id,
+                    value: protect(value, &non_finite),
+                    terminals: terminals.to_vec(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let runner_up = (0..candidates.len())
+            .filter(|i| *i != decision.taken)
+            .map(|i| OrderedFloat(candidates[i].value))
+            .min()
+            .map(|value| value.0);
+        Self {
+            routing: decision.routing,
+            time: decision.time,
+            subject: decision.subject,
+            chosen: decision.taken,
+            rule_chosen: decision.chosen,
+            runner_up,
+            deferred: decision.deferred,
+            candidates,
+        }
+    }
+}
+
+// every decision of a run of `problem`, which has to be `Trace::supported`
+// so that the recorded values alone explain each decision: a single day,
+// without rollouts, decided by the programs
+pub fn record(
+    problem: &Problem,
+    routing: &RoutingProgram,
+    sequencing: &SequencingProgram,
+    time_slot: Float,
+) -> Vec<Sample> {
+    let (_, record) =
+        simulate_recorded(problem, routing, sequencing, Scenario::default(), time_slot);
+    record
+        .decisions()
+        .iter()
+        .map(|decision| Sample::new(&record, decision, routing, sequencing))
+        .collect()
+}
+
+// one JSON object per line
+pub fn save(samples: &[Sample], path: &str) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for sample in samples {
+        serde_json::to_writer(&mut writer, sample)?;
+        writeln!(writer)?;
+    }
+    Ok(writer.flush()?)
+}
+
+pub fn load(path: &str) -> anyhow::Result<Vec<Sample>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+// the share of the decisions where a pair of rules, evaluated on the recorded
+// terminals, picks the candidate the run went with; ties go to the candidate
+// offered first since the tie-break keys are not recorded
+pub fn agreement(
+    samples: &[Sample],
+    routing: &RoutingProgram,
+    sequencing: &SequencingProgram,
+) -> f64 {
+    let non_finite = Cell::new(0);
+    let agreed = samples
+        .iter()
+        .filter(|sample| {
+            let values = sample.candidates.iter().map(|candidate| {
+                let value = match sample.routing {
+                    true => routing.calc_terminals(&candidate.terminals, common_internal),
+                    false => sequencing.calc_terminals(&candidate.terminals, common_internal),
+                };
+                OrderedFloat(protect(value, &non_finite))
+            });
+            let values = values.collect::<Vec<_>>();
+            (0..values.len()).min_by_key(|i| values[*i]) == Some(sample.chosen)
+        })
+        .count();
+    agreed as f64 / samples.len().max(1) as f64
+}
+
+#[test]
+fn recorded_samples() {
+    use super::{baseline, insertion::Insertion};
+
+    let (mut problem, time_slot) = Problem::test_instance();
+    let routing = baseline::nearest_vehicle();
+    let sequencing = baseline::cost();
+    let samples = record(&problem, &routing, &sequencing, time_slot);
+    assert!(samples.len() >= problem.requests.len());
+    // the rules agree with themselves but for ties broken otherwise
+    assert!(agreement(&samples, &routing, &sequencing) > 0.9);
+    let other = agreement(&samples, &baseline::work_in_queue(), &sequencing);
+    assert!(other < agreement(&samples, &routing, &sequencing));
+    for sample in &samples {
+        assert_eq!(sample.chosen, sample.rule_chosen);
+        let chosen = sample.candidates[sample.chosen].value;
+        assert!(sample.runner_up.is_none_or(|next| chosen <= next));
+        let len = match sample.routing {
+            true => RoutingContext::num_terminals(),
+            false => SequencingContext::num_terminals(),
+        };
+        assert_eq!(sample.candidates[0].terminals.len(), len);
+    }
+    // an insertion dispatcher overrides some of the rule's picks
+    problem.insertion = Some(Insertion::Cheapest);
+    let samples = record(&problem, &routing, &sequencing, time_slot);
+    assert!(samples.iter().any(|s| s.chosen != s.rule_chosen));
+}
@@ -0,0 +1,150 @@
+use std::{fs::read_to_string, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use super::{problem::Request, Float};
+
+pub trait DistanceProvider: Send + Sync {
+    fn distance(&self, from: &Request, to: &Request) -> Float;
+
+    fn travel_time(&self, from: &Request, to: &Request, speed: Float) -> Float {
+        self.distance(from, to) / speed
+    }
+}
+
+const EARTH_RADIUS_KM: Float = 6371.0;
+
+// with `Geographic`, `Request::x` is the latitude and `Request::y` the
+// longitude, both in degrees, and distances are in kilometers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinateSystem {
+    #[default]
+    Cartesian,
+    Geographic,
+}
+
+impl CoordinateSystem {
+    pub fn parse(str: &str) -> Option<Self> {
+        match str {
+            "cartesian" | "euclidean" => Some(Self::Cartesian),
+            "geographic" | "geo" | "latlon" => Some(Self::Geographic),
+            _ => None,
+        }
+    }
+
+    pub fn distance(&self, from: (Float, Float), to: (Float, Float)) -> Float {
+        match self {
+            Self::Cartesian => {
+                let (x, y) = (from.0 - to.0, from.1 - to.1);
+                (x * x + y * y).sqrt()
+            }
+            Self::Geographic => {
+                let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+                let dlat = lat2 - lat1;
+                let dlon = (to.1 - from.1).to_radians();
+                let a = (dlat / 2.0).sin().powi(2)
+                    + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+                2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+            }
+        }
+    }
+
+    pub fn provider(&self) -> Arc<dyn DistanceProvider> {
+        match self {
+            Self::Cartesian => Arc::new(Euclidean),
+            Self::Geographic => Arc::new(Haversine),
+        }
+    }
+}
+
+pub struct Euclidean;
+
+impl DistanceProvider for Euclidean {
+    fn distance(&self, from: &Request, to: &Request) -> Float {
+        CoordinateSystem::Cartesian.distance((from.x, from.y), (to.x, to.y))
+    }
+}
+
+pub struct Haversine;
+
+impl DistanceProvider for Haversine {
+    fn distance(&self, from: &Request, to: &Request) -> Float {
+        CoordinateSystem::Geographic.distance((from.x, from.y), (to.x, to.y))
+    }
+}
+
+// square matrices indexed by `Request::idx`, i.e. by instance row (depots
+// included); when no time matrix is given, travel time is distance / speed
+pub struct Matrix {
+    size: usize,
+    distances: Vec<Float>,
+    times: Option<Vec<Float>>,
+}
+
+impl Matrix {
+    pub fn parse(str: &str, size: usize) -> anyhow::Result<Vec<Float>> {
+        let mut values = Vec::with_capacity(size * size);
+        for (row, line) in str.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+            let len = values.len();
+            for tok in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                if !tok.is_empty() {
+                    values.push(tok.parse::<Float>()?);
+                }
+            }
+            anyhow::ensure!(
+                values.len() - len == size,
+                "matrix row {row} has {} entries, expected {size}",
+                values.len() - len
+            );
+        }
+        anyhow::ensure!(
+            values.len() == size * size,
+            "matrix has {} rows, expected {size}",
+            values.len() / size
+        );
+        Ok(values)
+    }
+
+    pub fn load(distances: &str, times: Option<&str>, size: usize) -> anyhow::Result<Self> {
+        Ok(Self {
+            size,
+            distances: Self::parse(&read_to_string(distances)?, size)?,
+            times: match times {
+                Some(path) => Some(Self::parse(&read_to_string(path)?, size)?),
+                None => None,
+            },
+        })
+    }
+}
+
+impl DistanceProvider for Matrix {
+    fn distance(&self, from: &Request, to: &Request) -> Float {
+        self.distances[from.idx * self.size + to.idx]
+    }
+
+    fn travel_time(&self, from: &Request, to: &Request, speed: Float) -> Float {
+        match &self.times {
+            Some(times) => times[from.idx * self.size + to.idx],
+            None => self.distance(from, to) / speed,
+        }
+    }
+}
+
+#[test]
+fn parse_matrix() {
+    assert_eq!(
+        Matrix::parse("0,1.5\n2 0\n\n", 2).unwrap(),
+        &[0.0, 1.5, 2.0, 0.0]
+    );
+    assert!(Matrix::parse("0,1\n2\n", 2).is_err());
+    assert!(Matrix::parse("0,1\n", 2).is_err());
+}
+
+#[test]
+fn haversine() {
+    let geo = CoordinateSystem::Geographic;
+    assert!((geo.distance((0.0, 0.0), (0.0, 1.0)) - 111.195).abs() < 0.01);
+    assert!((geo.distance((48.8566, 2.3522), (51.5074, -0.1278)) - 343.5).abs() < 1.0);
+    assert_eq!(geo.distance((10.0, 20.0), (10.0, 20.0)), 0.0);
+}
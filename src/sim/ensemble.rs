@@ -0,0 +1,148 @@
+use std::{cell::Cell, collections::HashMap};
+
+use ordered_float::OrderedFloat;
+
+use super::{
+    ctx::{RoutingProgram, SequencingProgram},
+    problem::{Problem, Request},
+    trace::Record,
+    Float, RoutingRule, SequencingRule, VehicleState,
+};
+
+// how the rankings of the members are combined: by the sum of the places
+// each member ranks a candidate at (a Borda count), or by the number of
+// members ranking it first, the Borda count breaking ties
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Vote {
+    Borda,
+    Majority,
+}
+
+// several pairs of rules, usually the best of a population, dispatching
+// together in place of the simulated rules; the first member breaks the ties
+// left by the vote
+#[derive(Clone)]
+pub struct Ensemble {
+    pub routing: Vec<RoutingProgram<'static>>,
+    pub sequencing: Vec<SequencingProgram<'static>>,
+    pub vote: Vote,
+}
+
+impl Ensemble {
+    // "borda:k" or "majority:k", with `k` members at least 2
+    pub fn parse(str: &str) -> Option<(Vote, usize)> {
+        let (vote, k) = str.split_once(':')?;
+        let vote = match vote {
+            "borda" => Vote::Borda,
+            "majority" => Vote::Majority,
+            _ => None?,
+        };
+        Some((vote, k.parse().ok().filter(|k| *k >= 2)?))
+    }
+
+    // the candidates of every member's ranking, best first, with the mean of
+    // their member values
+    fn aggregate(&self, rankings: &[Vec<(usize, Float)>]) -> Vec<(usize, Float)> {
+        let mut votes = HashMap::<usize, (usize, usize, Float, usize)>::new();
+        for (member, ranking) in rankings.iter().enumerate() {
+            for (place, &(candidate, value)) in ranking.iter().enumerate() {
+                let (first, borda, sum, tie) = votes.entry(candidate).or_insert((0, 0, 0.0, 0));
+                *first += (place == 0) as usize;
+                *borda += place;
+                *sum += value;
+                if member == 0 {
+                    *tie = place;
+                }
+            }
+        }
+        let mut ranked = votes.into_iter().collect::<Vec<_>>();
+        ranked.sort_by_key(|&(_, (first, borda, _, tie))| match self.vote {
+            Vote::Borda => (0, borda, tie),
+            Vote::Majority => (usize::MAX - first, borda, tie),
+        });
+        let n = rankings.len().max(1) as Float;
+        ranked
+            .into_iter()
+            .map(|(candidate, (_, _, sum, _))| (candidate, sum / n))
+            .collect()
+    }
+}
+
+// the members are not recorded, a traced run replays a single rule
+impl RoutingRule for Ensemble {
+    fn rank_vehicles(
+        &self,
+        problem: &Problem,
+        time: Float,
+        vehicles: &[VehicleState],
+        request: &Request,
+        non_finite: &Cell<usize>,
+        _: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let rankings = self
+            .routing
+            .iter()
+            .map(|member| member.rank_vehicles(problem, time, vehicles, request, non_finite, None))
+            .collect::<Vec<_>>();
+        self.aggregate(&rankings)
+    }
+}
+
+// every member keeps its own values of the queue, evaluated anew for every
+// pick rather than cached for the vehicle
+impl SequencingRule for Ensemble {
+    fn rank_queue(
+        &self,
+        problem: &Problem,
+        time: Float,
+        vehicle: usize,
+        vehicle_state: &VehicleState,
+        _: &mut HashMap<usize, (OrderedFloat<Float>, usize)>,
+        non_finite: &Cell<usize>,
+        _: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let rankings = self
+            .sequencing
+            .iter()
+            .map(|member| {
+                let mut cache = HashMap::new();
+                member.rank_queue(
+                    problem,
+                    time,
+                    vehicle,
+                    vehicle_state,
+                    &mut cache,
+                    non_finite,
+                    None,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.aggregate(&rankings)
+    }
+}
+
+#[test]
+fn ensemble_votes() {
+    use super::baseline;
+
+    let ensemble = |vote| Ensemble {
+        routing: vec![baseline::nearest_vehicle(); 3],
+        sequencing: vec![baseline::cost(); 3],
+        vote,
+    };
+    // a and b split the first places two to one, c is always second
+    let rankings = [
+        vec![(0, 1.0), (2, 2.0), (1, 3.0)],
+        vec![(1, 1.0), (2, 2.0), (0, 3.0)],
+        vec![(0, 1.0), (2, 2.0), (1, 3.0)],
+    ];
+    let order = |vote| {
+        let ranked = ensemble(vote).aggregate(&rankings);
+        ranked.into_iter().map(|(c, _)| c).collect::<Vec<_>>()
+    };
+    assert_eq!(order(Vote::Majority), [0, 1, 2]);
+    assert_eq!(order(Vote::Borda), [0, 2, 1]);
+    assert_eq!(ensemble(Vote::Borda).aggregate(&rankings)[1], (2, 2.0));
+    assert_eq!(Ensemble::parse("majority:5"), Some((Vote::Majority, 5)));
+    assert_eq!(Ensemble::parse("borda:1"), None);
+}
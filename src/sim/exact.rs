@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use ordered_float::OrderedFloat;
+
+use super::{
+    problem::{Problem, Request},
+    Float,
+};
+
+// queues up to this long are small enough to be ordered exactly
+pub const MAX_EXACT_QUEUE: usize = 10;
+
+// partial plans kept by the beam search of longer queues when none is set
+pub const DEFAULT_BEAM_WIDTH: usize = 16;
+
+// an order of a vehicle queue, with the requests that cannot start within
+// their window left out first and the distance driven second
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    // indices into the queue in the order they are served
+    pub order: Vec<usize>,
+    pub skipped: usize,
+    pub distance: Float,
+}
+
+impl Plan {
+    pub fn key(&self) -> (usize, OrderedFloat<Float>) {
+        (self.skipped, OrderedFloat(self.distance))
+    }
+}
+
+// the best partial plan of a state, from the state it extends by serving
+// or skipping one more request
+#[derive(Clone, Copy)]
+struct Label {
+    skipped: usize,
+    distance: Float,
+    parent: (usize, usize),
+    served: bool,
+}
+
+impl Label {
+    fn key(&self) -> (usize, OrderedFloat<Float>) {
+        (self.skipped, OrderedFloat(self.distance))
+    }
+}
+
+// Held-Karp over the subsets of the queue that are served or skipped and the
+// last stop, the start when it is the queue length. Like in the simulation,
+// where the whole queue is dispatched at once, every leg is timed from
+// `time`, so a window may only be reached from the stops close enough to it
+pub fn solve(
+    problem: &Problem,
+    from: &Request,
+    speed: Float,
+    time: Float,
+    queue: &[&Request],
+) -> Plan {
+    let n = queue.len();
+    assert!(n <= MAX_EXACT_QUEUE, "exact plans are for small queues");
+    let full = (1usize << n) - 1;
+    let index = |set: usize, at: usize| set * (n + 1) + at;
+    let mut states: Vec<Option<Label>> = vec![None; index(full, n) + 1];
+    states[index(0, n)] = Some(Label {
+        skipped: 0,
+        distance: 0.0,
+        parent: (0, n),
+        served: false,
+    });
+    for set in 0..full {
+        for at in 0..=n {
+            let Some(label) = states[index(set, at)] else {
+                continue;
+            };
+            let stop = if at == n { from } else { queue[at] };
+            for (next, request) in queue.iter().enumerate() {
+                if set & 1 << next != 0 {
+                    continue;
+                }
+                let skip = Label {
+                    skipped: label.skipped + 1,
+                    parent: (set, at),
+                    served: false,
+                    ..label
+                };
+                improve(&mut states[index(set | 1 << next, at)], skip);
+                let start = time + problem.distances.travel_time(stop, request, speed);
+                if start <= problem.latest_start(request) {
+                    let serve = Label {
+                        distance: label.distance + problem.distances.distance(stop, request),
+                        parent: (set, at),
+                        served: true,
+                        ..label
+                    };
+                    improve(&mut states[index(set | 1 << next, next)], serve);
+                }
+            }
+        }
+    }
+    let (mut at, best) = (0..=n)
+        .filter_map(|at| Some((at, states[index(full, at)]?)))
+        .min_by_key(|(_, label)| label.key())
+        .expect("skipping everything is always a plan");
+    let mut order = Vec::new();
+    let mut set = full;
+    while set != 0 {
+        let label = states[index(set, at)].expect("the parent of a state is reached");
+        if label.served {
+            order.push(at);
+        }
+        (set, at) = label.parent;
+    }
+    order.reverse();
+    Plan {
+        order,
+        skipped: best.skipped,
+        distance: best.distance,
+    }
+}
+
+// the best plan that serves `first` first, or skips it when it cannot be
+// reached in time
+pub fn solve_from(
+    problem: &Problem,
+    from: &Request,
+    speed: Float,
+    time: Float,
+    queue: &[&Request],
+    first: usize,
+) -> Plan {
+    let mut rest = queue.to_vec();
+    let request = rest.remove(first);
+    let start = time + problem.distances.travel_time(from, request, speed);
+    let (mut plan, lead) = if start <= problem.latest_start(request) {
+        let mut plan = solve(problem, request, speed, time, &rest);
+        plan.distance += problem.distances.distance(from, request);
+        (plan, Some(first))
+    } else {
+        let mut plan = solve(problem, from, speed, time, &rest);
+        plan.skipped += 1;
+        (plan, None)
+    };
+    plan.order = lead
+        .into_iter()
+        .chain(
+            plan.order
+                .iter()
+                .map(|i| if *i < first { *i } else { i + 1 }),
+        )
+        .collect();
+    plan
+}
+
+// an exact plan of a short queue, a beam search of `width` partial plans of
+// a longer one
+pub fn plan(
+    problem: &Problem,
+    from: &Request,
+    speed: Float,
+    time: Float,
+    queue: &[&Request],
+    width: usize,
+) -> Plan {
+    match queue.len() <= MAX_EXACT_QUEUE {
+        true => solve(problem, from, speed, time, queue),
+        false => beam(problem, from, speed, time, queue, width),
+    }
+}
+
+// extends the `width` shortest partial plans serving as many requests by one
+// more request at a time, with the same timing as `solve`; a partial plan
+// ends by skipping everything left
+pub fn beam(
+    problem: &Problem,
+    from: &Request,
+    speed: Float,
+    time: Float,
+    queue: &[&Request],
+    width: usize,
+) -> Plan {
+    let n = queue.len();
+    let mut best = Plan {
+        order: Vec::new(),
+        skipped: n,
+        distance: 0.0,
+    };
+    let mut beam = vec![(Vec::new(), vec![false; n], 0.0)];
+    while !beam.is_empty() {
+        // the shortest way to each set of served requests and last stop
+        let mut next: HashMap<(Vec<bool>, usize), (Vec<usize>, Float)> = HashMap::new();
+        for (order, served, distance) in &beam {
+            let stop = order.last().map_or(from, |&at| queue[at]);
+            for (at, request) in queue.iter().enumerate() {
+                let start = time + problem.distances.travel_time(stop, request, speed);
+                if served[at] || start > problem.latest_start(request) {
+                    continue;
+                }
+                let distance = distance + problem.distances.distance(stop, request);
+                let mut served = served.clone();
+                served[at] = true;
+                let entry = next
+                    .entry((served, at))
+                    .or_insert_with(|| (Vec::new(), Float::INFINITY));
+                if distance < entry.1 {
+                    let mut order = order.clone();
+                    order.push(at);
+                    *entry = (order, distance);
+                }
+            }
+        }
+        beam = next
+            .into_iter()
+            .map(|((served, _), (order, distance))| (order, served, distance))
+            .collect();
+        beam.sort_by_key(|(order, _, distance)| (OrderedFloat(*distance), order.clone()));
+        beam.truncate(width.max(1));
+        if let Some((order, _, distance)) = beam.first() {
+            best = Plan {
+                order: order.clone(),
+                skipped: n - order.len(),
+                distance: *distance,
+            };
+        }
+    }
+    best
+}
+
+fn improve(state: &mut Option<Label>, label: Label) {
+    if state.is_none_or(|other| label.key() < other.key()) {
+        *state = Some(label);
+    }
+}
+
+#[test]
+fn exact_plans() {
+    let request = |idx, x, open, close| Request {
+        idx,
+        x,
+        y: 0.0,
+        demand: 1.0,
+        open,
+        close,
+        service_time: 0.0,
+        time: 0.0,
+        pickup: false,
+        revenue: 1.0,
+    };
+    let depot = request(0, 0.0, 0.0, 1000.0);
+    let problem = Problem::new(vec![depot], vec![], vec![], 1.0, 10.0, 1);
+    let (a, b, c) = (
+        request(1, 10.0, 0.0, 100.0),
+        request(2, 20.0, 0.0, 100.0),
+        request(3, -5.0, 0.0, 100.0),
+    );
+    // the near side first, then straight out
+    let plan = solve(&problem, &depot, 1.0, 0.0, &[&a, &b, &c]);
+    assert_eq!((plan.order.clone(), plan.skipped), (vec![2, 0, 1], 0));
+    assert_eq!(plan.distance, 30.0);
+    let rule = solve_from(&problem, &depot, 1.0, 0.0, &[&a, &b, &c], 1);
+    assert_eq!((rule.order, rule.distance), (vec![1, 0, 2], 45.0));
+    let rule = solve_from(&problem, &depot, 1.0, 0.0, &[&a, &b, &c], 0);
+    assert_eq!((rule.order, rule.distance), (vec![0, 1, 2], 45.0));
+    // legs are timed from the dispatch, the middle one cannot be reached from
+    // the near side in time anymore
+    let a = request(1, 10.0, 0.0, 10.0);
+    let plan = solve(&problem, &depot, 1.0, 0.0, &[&a, &b, &c]);
+    assert_eq!((plan.order, plan.distance), (vec![2, 1, 0], 40.0));
+    // and one that cannot be reached from anywhere is skipped
+    let b = request(2, 20.0, 0.0, 5.0);
+    let plan = solve(&problem, &depot, 1.0, 0.0, &[&a, &b, &c]);
+    assert_eq!((plan.order, plan.skipped), (vec![0, 2], 1));
+    // a beam wide enough to keep every partial plan is exact
+    let plan = solve(&problem, &depot, 1.0, 0.0, &[&a, &b, &c]);
+    assert_eq!(beam(&problem, &depot, 1.0, 0.0, &[&a, &b, &c], 16), plan);
+    let a = request(1, 10.0, 0.0, 100.0);
+    let b = request(2, 20.0, 0.0, 100.0);
+    let beamed = beam(&problem, &depot, 1.0, 0.0, &[&a, &b, &c], 1);
+    assert_eq!((beamed.order, beamed.distance), (vec![2, 0, 1], 30.0));
+}
@@ -0,0 +1,196 @@
+use std::{
+    cell::Cell,
+    fmt::{self, Display, Formatter},
+};
+
+use ordered_float::OrderedFloat;
+
+use crate::gp::program::ProgramContext;
+
+use super::{
+    ctx::{
+        common_internal, RoutingContext, RoutingProgram, SequencingContext, SequencingProgram,
+        ROUTING_NAMES, SEQUENCING_NAMES,
+    },
+    problem::Problem,
+    protect,
+    scenario::Scenario,
+    trace::{simulate_recorded, Decision, Record},
+    Float,
+};
+
+// the vehicle or request offered to a rule, with its value and the terminal
+// values it was computed from
+struct Candidate {
+    id: usize,
+    value: Float,
+    terminals: Vec<f32>,
+}
+
+struct Explained {
+    routing: bool,
+    time: Float,
+    subject: usize,
+    deferred: bool,
+    num_candidates: usize,
+    chosen: Candidate,
+    runner_up: Option<Candidate>,
+}
+
+// decisions of the rules sampled evenly over a run, with what they were made
+// on and how close the runner-up came, to see what an evolved policy learned
+pub struct Explanation {
+    pub num_decisions: usize,
+    decisions: Vec<Explained>,
+    // the terminals each rule reads, the only ones shown
+    routing_terminals: Vec<usize>,
+    sequencing_terminals: Vec<usize>,
+}
+
+impl Explained {
+    fn new(
+        record: &Record,
+        decision: &Decision,
+        routing: &RoutingProgram,
+        sequencing: &SequencingProgram,
+    ) -> Self {
+        let non_finite = Cell::new(0);
+        let candidate = |index: usize| {
+            let (offset, _, id) = decision.candidates[index];
+            let (terminals, value) = if decision.routing {
+                let terminals = record.terminals(offset, RoutingContext::num_terminals());
+                (
+                    terminals,
+                    routing.calc_terminals(terminals, common_internal),
+                )
+            } else {
+                let terminals = record.terminals(offset, SequencingContext::num_terminals());
+                (
+                    terminals,
+                    sequencing.calc_terminals(terminals, common_internal),
+                )
+            };
+            Candidate {
+                id,
+                value: protect(value, &non_finite),
+                terminals: terminals.to_vec(),
+            }
+        };
+        let chosen = candidate(decision.chosen);
+        // the next best by the same order the rules decide by
+        let runner_up = (0..decision.candidates.len())
+            .filter(|i| *i != decision.chosen)
+            .map(|i| (candidate(i), decision.candidates[i].1))
+            .min_by_key(|(c, tie)| (OrderedFloat(c.value), OrderedFloat(*tie)))
+            .map(|(c, _)| c);
+        Self {
+            routing: decision.routing,
+            time: decision.time,
+            subject: decision.subject,
+            deferred: decision.deferred,
+            num_candidates: decision.candidates.len(),
+            chosen,
+            runner_up,
+        }
+    }
+}
+
+impl Explanation {
+    // `problem` has to be `Trace::supported` by
+    pub fn new(
+        problem: &Problem,
+        routing: &RoutingProgram,
+        sequencing: &SequencingProgram,
+        time_slot: Float,
+        num_samples: usize,
+    ) -> Self {
+        let (_, record) =
+            simulate_recorded(problem, routing, sequencing, Scenario::default(), time_slot);
+        let decisions = record.decisions();
+        let num_samples = num_samples.min(decisions.len());
+        Self {
+            num_decisions: decisions.len(),
+            decisions: (0..num_samples)
+                .map(|i| &decisions[i * decisions.len() / num_samples])
+                .map(|decision| Explained::new(&record, decision, routing, sequencing))
+                .collect(),
+            routing_terminals: routing.used_terminals(),
+            sequencing_terminals: sequencing.used_terminals(),
+        }
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} decisions",
+            self.decisions.len(),
+            self.num_decisions
+        )?;
+        for decision in &self.decisions {
+            let (names, terminals, subject, id): (&[&str], _, _, _) = match decision.routing {
+                true => (
+                    &ROUTING_NAMES,
+                    &self.routing_terminals,
+                    "request",
+                    "vehicle",
+                ),
+                false => (
+                    &SEQUENCING_NAMES,
+                    &self.sequencing_terminals,
+                    "vehicle",
+                    "request",
+                ),
+            };
+            let Explained {
+                chosen, runner_up, ..
+            } = decision;
+            writeln!(f)?;
+            write!(
+                f,
+                "\nt={:.1} {subject} {}: {id} {} at {:.4}",
+                decision.time, decision.subject, chosen.id, chosen.value
+            )?;
+            match runner_up {
+                Some(next) => write!(
+                    f,
+                    ", {id} {} next at {:.4} (margin {:.4}) of {} candidates",
+                    next.id,
+                    next.value,
+                    next.value - chosen.value,
+                    decision.num_candidates
+                )?,
+                None => write!(f, ", the only candidate")?,
+            }
+            if decision.deferred {
+                write!(f, ", deferred")?;
+            }
+            for &i in terminals {
+                let name = names[i];
+                write!(f, "\n  TERM{i:<2} {name:<4} {:>9.4}", chosen.terminals[i])?;
+                if let Some(next) = runner_up {
+                    write!(f, " {:>9.4}", next.terminals[i])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn explained_decisions() {
+    use super::baseline;
+
+    let (problem, time_slot) = Problem::test_instance();
+    let routing = baseline::nearest_vehicle();
+    let sequencing = baseline::cost();
+    let explanation = Explanation::new(&problem, &routing, &sequencing, time_slot, 20);
+    assert_eq!(explanation.decisions.len(), 20);
+    assert!(explanation.num_decisions >= problem.requests.len());
+    for decision in &explanation.decisions {
+        if let Some(next) = &decision.runner_up {
+            assert!(decision.chosen.value <= next.value);
+        }
+    }
+}
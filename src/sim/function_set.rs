@@ -0,0 +1,81 @@
+use std::fs;
+
+use anyhow::{ensure, Context};
+use serde::Deserialize;
+
+use crate::gp::{program::ProgramContext, FunctionSet};
+
+use super::ctx::{
+    RoutingContext, SequencingContext, INTERNAL_NAMES, ROUTING_NAMES, SEQUENCING_NAMES,
+};
+
+// the subset of the built-in internals and terminals to generate rules from,
+// as a JSON object with the internals by name and the terminals of each rule
+// by short name, e.g. {"internals": ["sum", "neg"], "routing": ["TC", "NIQ"]};
+// what is left out stays as by default. Only a selection: every node keeps
+// the arity and encoding it has in `ctx`, where new ones have to be added
+pub fn parse(json: &str) -> anyhow::Result<FunctionSet> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Declaration {
+        internals: Option<Vec<String>>,
+        routing: Option<Vec<String>>,
+        sequencing: Option<Vec<String>>,
+    }
+    let declaration = serde_json::from_str::<Declaration>(json)?;
+    let indices = |names: Vec<String>, all: &[&str], kind| {
+        let mut indices = names
+            .iter()
+            .map(|name| {
+                all.iter()
+                    .position(|n| n == name)
+                    .with_context(|| format!("unknown {kind} {name:?}, there are {all:?}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        indices.sort_unstable();
+        indices.dedup();
+        ensure!(
+            !indices.is_empty(),
+            "the function set needs at least one {kind}"
+        );
+        Ok::<_, anyhow::Error>(indices)
+    };
+    let mut set = FunctionSet::default();
+    if let Some(names) = declaration.internals {
+        set.internals = Some(indices(names, &INTERNAL_NAMES, "internal")?);
+    }
+    if let Some(names) = declaration.routing {
+        let routing = indices(names, &ROUTING_NAMES, "routing terminal")?;
+        set.terminals.insert(RoutingContext::RULE, routing);
+    }
+    if let Some(names) = declaration.sequencing {
+        let sequencing = indices(names, &SEQUENCING_NAMES, "sequencing terminal")?;
+        set.terminals.insert(SequencingContext::RULE, sequencing);
+    }
+    Ok(set)
+}
+
+pub fn load(path: &str) -> anyhow::Result<FunctionSet> {
+    parse(&fs::read_to_string(path)?).with_context(|| format!("function set {path}"))
+}
+
+#[test]
+fn declared_function_set() {
+    let set =
+        parse(r#"{"internals": ["max", "neg", "sum"], "routing": ["TC", "NIQ", "TC"]}"#).unwrap();
+    assert_eq!(set.internals::<RoutingContext>(), [0, 5, 6].as_slice());
+    assert_eq!(set.terminals::<RoutingContext>(), [0, 3].as_slice());
+    let all = (0..SEQUENCING_NAMES.len()).collect::<Vec<_>>();
+    assert_eq!(set.terminals::<SequencingContext>(), all.as_slice());
+    // the binary internals unless declared
+    let default = parse("{}").unwrap();
+    assert_eq!(
+        default.internals::<SequencingContext>(),
+        [0, 1, 2, 3, 4, 5].as_slice()
+    );
+    assert!(parse(r#"{"internals": ["pow"]}"#).is_err());
+    assert!(parse(r#"{"internals": {"neg": 1}}"#).is_err());
+    assert!(parse(r#"{"sequencing": ["NIQ"]}"#).is_err());
+    assert!(parse(r#"{"routing": []}"#).is_err());
+    assert!(parse(r#"{"terminals": []}"#).is_err());
+}
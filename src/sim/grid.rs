@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use super::{distance::CoordinateSystem, problem::Request, Float};
+
+// uniform grid over the queued requests of a vehicle, so neighborhood queries
+// only look at the cells around a point; geographic coordinates use a single
+// cell since a degree of longitude has no fixed length
+// request index and coordinates
+type Point = (usize, Float, Float);
+
+#[derive(Clone)]
+pub struct Grid {
+    coordinates: CoordinateSystem,
+    cell: Float,
+    len: usize,
+    cells: HashMap<(i32, i32), Vec<Point>>,
+}
+
+impl Grid {
+    pub fn new(coordinates: CoordinateSystem, cell: Float) -> Self {
+        let cell = match coordinates {
+            CoordinateSystem::Cartesian if cell > 0.0 => cell,
+            _ => Float::INFINITY,
+        };
+        Self {
+            coordinates,
+            cell,
+            len: 0,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn key(&self, (x, y): (Float, Float)) -> (i32, i32) {
+        match self.cell {
+            cell if cell.is_finite() => ((x / cell).floor() as i32, (y / cell).floor() as i32),
+            _ => (0, 0),
+        }
+    }
+
+    pub fn insert(&mut self, request: &Request) {
+        let key = self.key((request.x, request.y));
+        self.cells
+            .entry(key)
+            .or_default()
+            .push((request.idx, request.x, request.y));
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, request: &Request) {
+        let key = self.key((request.x, request.y));
+        if let Some(cell) = self.cells.get_mut(&key) {
+            if let Some(i) = cell.iter().position(|(idx, ..)| *idx == request.idx) {
+                cell.swap_remove(i);
+                self.len -= 1;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.len = 0;
+    }
+
+    // an empty grid keeping the allocation of `self`
+    pub fn reset(mut self, coordinates: CoordinateSystem, cell: Float) -> Self {
+        self.cells.clear();
+        Self {
+            cells: self.cells,
+            ..Self::new(coordinates, cell)
+        }
+    }
+
+    // the cells at Chebyshev distance `ring` from `center`
+    fn ring(&self, (cx, cy): (i32, i32), ring: i32) -> impl Iterator<Item = &Point> {
+        (-ring..=ring)
+            .flat_map(move |dx| (-ring..=ring).map(move |dy| (dx, dy)))
+            .filter(move |(dx, dy)| dx.abs().max(dy.abs()) == ring)
+            .filter_map(move |(dx, dy)| self.cells.get(&(cx + dx, cy + dy)))
+            .flatten()
+    }
+
+    // distance to the closest point, rings are searched outwards until no
+    // unseen point can be closer: those in ring `r` are at least `r - 1`
+    // cells away
+    pub fn nearest(&self, point: (Float, Float)) -> Option<Float> {
+        let center = self.key(point);
+        let (mut best, mut seen) = (Float::INFINITY, 0);
+        for ring in 0.. {
+            if seen == self.len || best <= (ring - 1) as Float * self.cell {
+                break;
+            }
+            for &(_, x, y) in self.ring(center, ring) {
+                best = best.min(self.coordinates.distance(point, (x, y)));
+                seen += 1;
+            }
+        }
+        (seen > 0).then_some(best)
+    }
+
+    // number of points at most `radius` away
+    pub fn within(&self, point: (Float, Float), radius: Float) -> usize {
+        let center = self.key(point);
+        let rings = match self.cell {
+            cell if cell.is_finite() => (radius / cell).ceil() as i32,
+            _ => 0,
+        };
+        (0..=rings)
+            .flat_map(|ring| self.ring(center, ring))
+            .filter(|(_, x, y)| self.coordinates.distance(point, (*x, *y)) <= radius)
+            .count()
+    }
+}
+
+#[test]
+fn grid_queries() {
+    let mut grid = Grid::new(CoordinateSystem::Cartesian, 10.0);
+    let request = |idx, x, y| Request {
+        idx,
+        x,
+        y,
+        demand: 0.0,
+        open: 0.0,
+        close: 0.0,
+        service_time: 0.0,
+        time: 0.0,
+        pickup: false,
+        revenue: 0.0,
+    };
+    let far = request(1, 55.0, 5.0);
+    grid.insert(&request(0, 3.0, 4.0));
+    grid.insert(&far);
+    grid.insert(&request(2, -8.0, 0.0));
+    assert_eq!(grid.nearest((0.0, 0.0)), Some(5.0));
+    assert_eq!(grid.nearest((60.0, 5.0)), Some(5.0));
+    assert_eq!(grid.within((0.0, 0.0), 8.0), 2);
+    grid.remove(&far);
+    assert_eq!(grid.within((50.0, 0.0), 20.0), 0);
+    grid.clear();
+    assert_eq!(grid.nearest((0.0, 0.0)), None);
+}
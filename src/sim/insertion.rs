@@ -0,0 +1,138 @@
+use super::{
+    problem::{Problem, Request},
+    Float,
+};
+
+// classical insertion dispatchers, in place of the routing and sequencing
+// rules: every vehicle keeps a planned order of its queue, and a request goes
+// to the position of least added distance that keeps every planned window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Insertion {
+    // each request in turn into its cheapest position over all vehicles
+    Cheapest,
+    // of a batch of requests, first the one that loses the most by not going
+    // to its best vehicle, summed over its `k - 1` next best vehicles
+    Regret(usize),
+}
+
+impl Insertion {
+    // "cheapest" or "regret:k", with `k` at least 2
+    pub fn parse(str: &str) -> Option<Self> {
+        match str.split_once(':') {
+            Some(("regret", k)) => k.parse().ok().filter(|k| *k >= 2).map(Self::Regret),
+            Some(_) => None,
+            None => match str {
+                "cheapest" => Some(Self::Cheapest),
+                _ => None,
+            },
+        }
+    }
+}
+
+// whether every stop of `plan` is reached in its window, driven from `from`
+// at `time`
+fn on_time(
+    problem: &Problem,
+    from: &Request,
+    time: Float,
+    speed: Float,
+    plan: &[&Request],
+) -> bool {
+    let (mut at, mut time) = (from, time);
+    for stop in plan {
+        time = (time + problem.distances.travel_time(at, stop, speed)).max(stop.open);
+        if time > problem.latest_start(stop) {
+            return false;
+        }
+        time += stop.service_time;
+        at = stop;
+    }
+    true
+}
+
+// the position in `plan` where `request` adds the least distance, and that
+// distance
+pub fn cheapest_position(
+    problem: &Problem,
+    from: &Request,
+    time: Float,
+    speed: Float,
+    plan: &[&Request],
+    request: &Request,
+) -> Option<(usize, Float)> {
+    let distance = |a: &Request, b: &Request| problem.distances.distance(a, b);
+    let mut best: Option<(usize, Float)> = None;
+    let mut candidate = plan.to_vec();
+    candidate.insert(0, request);
+    for position in 0..=plan.len() {
+        if position > 0 {
+            candidate.swap(position - 1, position);
+        }
+        let before = if position == 0 {
+            from
+        } else {
+            plan[position - 1]
+        };
+        let added = distance(before, request)
+            + plan.get(position).map_or(0.0, |after| {
+                distance(request, after) - distance(before, after)
+            });
+        if best.is_some_and(|(_, cost)| cost <= added) {
+            continue;
+        }
+        if on_time(problem, from, time, speed, &candidate) {
+            best = Some((position, added));
+        }
+    }
+    best
+}
+
+// what a request loses by not going to its best vehicle, from the added
+// distances of its feasible vehicles sorted cheapest first; one with fewer
+// than `k` vehicles left comes first, the fewer the sooner
+pub fn regret(costs: &[Float], k: usize) -> Float {
+    match costs.len() {
+        n if n < k => Float::MAX / (n + 1) as Float,
+        _ => costs[1..k].iter().map(|cost| cost - costs[0]).sum(),
+    }
+}
+
+#[test]
+fn insertion_positions() {
+    let request = |idx, x, close| Request {
+        idx,
+        x,
+        y: 0.0,
+        demand: 1.0,
+        open: 0.0,
+        close,
+        service_time: 0.0,
+        time: 0.0,
+        pickup: false,
+        revenue: 1.0,
+    };
+    let depot = request(0, 0.0, 1000.0);
+    let problem = Problem::new(vec![depot], vec![], vec![], 1.0, 10.0, 1);
+    let (a, b) = (request(1, 10.0, 100.0), request(2, 20.0, 100.0));
+    let between = request(3, 15.0, 100.0);
+    assert_eq!(
+        cheapest_position(&problem, &depot, 0.0, 1.0, &[&a, &b], &between),
+        Some((1, 0.0))
+    );
+    // `a` is missed when the cheaper detour comes first
+    let a = request(1, 10.0, 10.0);
+    let behind = request(4, -5.0, 100.0);
+    assert_eq!(
+        cheapest_position(&problem, &depot, 0.0, 1.0, &[&a], &behind),
+        Some((1, 15.0))
+    );
+    let late = request(5, 50.0, 20.0);
+    assert_eq!(
+        cheapest_position(&problem, &depot, 0.0, 1.0, &[&a], &late),
+        None
+    );
+    assert_eq!(Insertion::parse("regret:3"), Some(Insertion::Regret(3)));
+    assert_eq!(Insertion::parse("regret:1"), None);
+    assert_eq!(regret(&[1.0, 3.0, 6.0], 3), 7.0);
+    assert!(regret(&[1.0], 2) > regret(&[1.0, 2.0], 3));
+}
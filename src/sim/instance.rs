@@ -0,0 +1,265 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    distance::CoordinateSystem,
+    problem::{LoadError, Problem, Request},
+    Float,
+};
+
+// self-describing instance file, an alternative to the positional CSV columns
+// and the fleet given on the command line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Instance {
+    pub fleet: Fleet,
+    #[serde(default)]
+    pub coordinates: CoordinateSystem,
+    pub depots: Vec<Node>,
+    #[serde(default)]
+    pub satellites: Vec<Node>,
+    pub requests: Vec<Node>,
+}
+
+// the vehicles besides the trucks, and the docks, take the defaults of a
+// CSV instance when left out
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Fleet {
+    pub truck_speed: Float,
+    pub truck_capacity: Float,
+    pub num_trucks: usize,
+    #[serde(default)]
+    pub satellite_vehicle_speed: Option<Float>,
+    #[serde(default)]
+    pub satellite_vehicle_capacity: Option<Float>,
+    #[serde(default)]
+    pub num_drones: Option<usize>,
+    #[serde(default)]
+    pub drone_speed: Option<Float>,
+    #[serde(default)]
+    pub drone_capacity: Option<Float>,
+    #[serde(default)]
+    pub drone_range: Option<Float>,
+    #[serde(default)]
+    pub reload_time: Option<Float>,
+    #[serde(default)]
+    pub num_docks: Option<usize>,
+}
+
+// the fleet of the benchmark CSV instances
+impl Default for Fleet {
+    fn default() -> Self {
+        Self {
+            truck_speed: 1.0,
+            truck_capacity: 1300.0,
+            num_trucks: 10,
+            satellite_vehicle_speed: None,
+            satellite_vehicle_capacity: None,
+            num_drones: None,
+            drone_speed: None,
+            drone_capacity: None,
+            drone_range: None,
+            reload_time: None,
+            num_docks: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Node {
+    pub x: Float,
+    pub y: Float,
+    #[serde(default)]
+    pub demand: Float,
+    pub open: Float,
+    pub close: Float,
+    #[serde(default = "default_service_time")]
+    pub service_time: Float,
+    // release time
+    #[serde(default)]
+    pub time: Float,
+    #[serde(default)]
+    pub pickup: bool,
+    // the demand when missing
+    #[serde(default)]
+    pub revenue: Option<Float>,
+}
+
+fn default_service_time() -> Float {
+    10.0
+}
+
+impl Node {
+    fn from_request(request: &Request) -> Self {
+        Self {
+            x: request.x,
+            y: request.y,
+            demand: request.demand,
+            open: request.open,
+            close: request.close,
+            service_time: request.service_time,
+            time: request.time,
+            pickup: request.pickup,
+            revenue: Some(request.revenue),
+        }
+    }
+
+    fn to_request(self, idx: usize) -> Request {
+        Request {
+            idx,
+            x: self.x,
+            y: self.y,
+            demand: self.demand,
+            open: self.open,
+            close: self.close,
+            service_time: self.service_time,
+            time: self.time,
+            pickup: self.pickup,
+            revenue: self.revenue.unwrap_or(self.demand),
+        }
+    }
+}
+
+impl Instance {
+    pub fn from_problem(problem: &Problem) -> Self {
+        let nodes = |requests: &[Request]| requests.iter().map(Node::from_request).collect();
+        Self {
+            fleet: Fleet {
+                truck_speed: problem.truck_speed,
+                truck_capacity: problem.truck_capacity,
+                num_trucks: problem.num_trucks,
+                satellite_vehicle_speed: Some(problem.satellite_vehicle_speed),
+                satellite_vehicle_capacity: Some(problem.satellite_vehicle_capacity),
+                num_drones: Some(problem.num_drones),
+                drone_speed: Some(problem.drone_speed),
+                drone_capacity: Some(problem.drone_capacity),
+                drone_range: Some(problem.drone_range),
+                reload_time: Some(problem.reload_time),
+                num_docks: Some(problem.num_docks),
+            },
+            coordinates: problem.coordinates,
+            depots: nodes(&problem.depots),
+            satellites: nodes(&problem.satellites),
+            requests: nodes(&problem.requests),
+        }
+    }
+
+    // nodes are indexed depots first, then satellites, then requests, like
+    // the rows of a CSV instance
+    pub fn into_problem(self) -> Result<Problem, LoadError> {
+        if self.depots.is_empty() {
+            return Err(LoadError::NoDepot);
+        }
+        let mut idx = 0;
+        let mut requests = |nodes: Vec<Node>| {
+            nodes
+                .into_iter()
+                .map(|node| {
+                    idx += 1;
+                    node.to_request(idx - 1)
+                })
+                .collect::<Vec<_>>()
+        };
+        let depots = requests(self.depots);
+        let satellites = requests(self.satellites);
+        let requests = requests(self.requests);
+        let fleet = self.fleet;
+        let mut problem = Problem::new(
+            depots,
+            satellites,
+            requests,
+            fleet.truck_speed,
+            fleet.truck_capacity,
+            fleet.num_trucks,
+        );
+        let set = |value: &mut Float, fleet: Option<Float>| *value = fleet.unwrap_or(*value);
+        set(
+            &mut problem.satellite_vehicle_speed,
+            fleet.satellite_vehicle_speed,
+        );
+        set(
+            &mut problem.satellite_vehicle_capacity,
+            fleet.satellite_vehicle_capacity,
+        );
+        set(&mut problem.drone_speed, fleet.drone_speed);
+        set(&mut problem.drone_capacity, fleet.drone_capacity);
+        set(&mut problem.drone_range, fleet.drone_range);
+        set(&mut problem.reload_time, fleet.reload_time);
+        problem.num_drones = fleet.num_drones.unwrap_or(problem.num_drones);
+        problem.num_docks = fleet.num_docks.unwrap_or(problem.num_docks);
+        problem.coordinates = self.coordinates;
+        problem.distances = self.coordinates.provider();
+        Ok(problem)
+    }
+
+    pub fn load(path: &str) -> Result<Self, LoadError> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), LoadError> {
+        Ok(serde_json::to_writer_pretty(
+            BufWriter::new(File::create(path)?),
+            self,
+        )?)
+    }
+}
+
+impl From<Problem> for Instance {
+    fn from(problem: Problem) -> Self {
+        Self::from_problem(&problem)
+    }
+}
+
+impl TryFrom<Instance> for Problem {
+    type Error = LoadError;
+
+    fn try_from(instance: Instance) -> Result<Self, Self::Error> {
+        instance.into_problem()
+    }
+}
+
+#[test]
+fn round_trip() {
+    let (problem, _) = Problem::test_instance();
+    let json = serde_json::to_string(&Instance::from_problem(&problem)).unwrap();
+    let loaded = serde_json::from_str::<Instance>(&json)
+        .unwrap()
+        .into_problem()
+        .unwrap();
+    assert_eq!(loaded.requests.len(), problem.requests.len());
+    assert_eq!(loaded.requests[5].idx, problem.requests[5].idx);
+    assert_eq!(loaded.requests[5].close, problem.requests[5].close);
+    assert_eq!(loaded.num_trucks, 10);
+
+    // the whole fleet is carried, and what is left out keeps its default
+    let mut mixed = problem.clone();
+    mixed.num_drones = 3;
+    mixed.drone_range = 45.0;
+    mixed.satellite_vehicle_capacity = 200.0;
+    mixed.reload_time = 5.0;
+    mixed.num_docks = 2;
+    let json = serde_json::to_string(&Instance::from_problem(&mixed)).unwrap();
+    let loaded = serde_json::from_str::<Problem>(&json).unwrap();
+    assert_eq!(loaded.num_drones, 3);
+    assert_eq!(loaded.drone_range, 45.0);
+    assert_eq!(loaded.satellite_vehicle_capacity, 200.0);
+    assert_eq!(loaded.reload_time, 5.0);
+    assert_eq!(loaded.num_docks, 2);
+    let json = r#"{"fleet": {"truck_speed": 1, "truck_capacity": 100, "num_trucks": 2,
+        "num_drones": 1}, "depots": [{"x": 0, "y": 0, "open": 0, "close": 100}],
+        "requests": []}"#;
+    let loaded = serde_json::from_str::<Instance>(json)
+        .unwrap()
+        .into_problem()
+        .unwrap();
+    assert_eq!(loaded.num_drones, 1);
+    assert_eq!(loaded.drone_capacity, 2.0);
+    assert_eq!(loaded.num_docks, usize::MAX);
+
+    let loaded =
+        serde_json::from_str::<Problem>(&serde_json::to_string(&problem).unwrap()).unwrap();
+    assert_eq!(loaded.requests[5].idx, problem.requests[5].idx);
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::{
+    problem::{Problem, Request},
+    Float, Leg, Simulation, VehicleState,
+};
+
+// what becomes of the improvement found for the best individual: only logged,
+// or its fitness also credited to the individual (Baldwinian) or to its rules
+// wherever they turn up again (Lamarckian)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Memetic {
+    Log,
+    Baldwinian,
+    Lamarckian,
+}
+
+impl Memetic {
+    pub fn parse(str: &str) -> Option<Self> {
+        match str {
+            "log" => Some(Self::Log),
+            "baldwinian" => Some(Self::Baldwinian),
+            "lamarckian" => Some(Self::Lamarckian),
+            _ => None,
+        }
+    }
+}
+
+// moves shorter than this are rounding noise
+const EPSILON: Float = 1e-6;
+
+// the distance of the simulated routes and after local search
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Improvement {
+    pub distance: Float,
+    pub improved: Float,
+    pub moves: usize,
+}
+
+impl Improvement {
+    pub fn add(&mut self, other: Improvement) {
+        self.distance += other.distance;
+        self.improved += other.improved;
+        self.moves += other.moves;
+    }
+
+    // share of the distance saved
+    pub fn gap(&self) -> Float {
+        match self.distance > 0.0 {
+            true => (self.distance - self.improved) / self.distance,
+            false => 0.0,
+        }
+    }
+}
+
+// the customers served between two visits of the dock, left at `departed`
+// and due back no later than the simulated return so the following trips
+// keep their times
+#[derive(Clone)]
+struct Trip<'a> {
+    from: &'a Request,
+    departed: Float,
+    // with the time spent at each, none for no-shows
+    stops: Vec<(&'a Request, Float)>,
+    back: Option<(&'a Request, Float)>,
+}
+
+impl Trip<'_> {
+    // none when a window is missed or the return is late
+    fn distance(&self, problem: &Problem, speed: Float) -> Option<Float> {
+        let (mut at, mut time, mut distance) = (self.from, self.departed, 0.0);
+        for &(stop, service) in &self.stops {
+            time = (time + problem.distances.travel_time(at, stop, speed)).max(stop.open);
+            if time > problem.latest_start(stop) + EPSILON {
+                return None;
+            }
+            time += service;
+            distance += problem.distances.distance(at, stop);
+            at = stop;
+        }
+        if let Some((dock, arrival)) = self.back {
+            time += problem.distances.travel_time(at, dock, speed);
+            if time > arrival + EPSILON {
+                return None;
+            }
+            distance += problem.distances.distance(at, dock);
+        }
+        Some(distance)
+    }
+
+    fn load(&self) -> Float {
+        self.stops.iter().map(|(stop, _)| stop.demand.abs()).sum()
+    }
+}
+
+// the trips of a truck from its legs, none with drones, whose sorties hang on
+// the stops of their carrier, and for trucks that took a break, whose waits
+// are not replayed
+fn trips<'a>(
+    problem: &Problem,
+    nodes: &HashMap<usize, &'a Request>,
+    state: &VehicleState<'a>,
+) -> Option<Vec<Trip<'a>>> {
+    if problem.num_vehicles() > problem.num_trucks || !state.breaks.is_empty() {
+        return None;
+    }
+    let mut trips = Vec::new();
+    let mut trip: Option<Trip> = None;
+    for &Leg {
+        from,
+        to,
+        departed,
+        start,
+        end,
+        reload,
+    } in &state.legs
+    {
+        let current = trip.get_or_insert_with(|| Trip {
+            from: nodes[&from],
+            departed,
+            stops: Vec::new(),
+            back: None,
+        });
+        if reload {
+            current.back = Some((nodes[&to], start));
+            trips.extend(trip.take());
+        } else {
+            current.stops.push((nodes[&to], end - start));
+        }
+    }
+    trips.extend(trip);
+    Some(trips)
+}
+
+// 2-opt and Or-opt within each trip of a truck and relocation between its
+// trips, taking the first improving move until none is left
+pub fn improve_vehicle(problem: &Problem, state: &VehicleState) -> Improvement {
+    let nodes = problem
+        .depots
+        .iter()
+        .chain(&problem.satellites)
+        .chain(&problem.requests)
+        .map(|node| (node.idx, node))
+        .collect::<HashMap<_, _>>();
+    let mut improvement = Improvement {
+        distance: state.traveled(),
+        improved: state.traveled(),
+        moves: 0,
+    };
+    let Some(mut trips) = trips(problem, &nodes, state) else {
+        return improvement;
+    };
+    let (speed, capacity) = (state.speed, state.capacity);
+    let mut distances = trips
+        .iter()
+        .map(|trip| trip.distance(problem, speed))
+        .collect::<Option<Vec<_>>>();
+    // the simulated routes should replay, leave them alone otherwise
+    let Some(distances) = distances.as_mut() else {
+        return improvement;
+    };
+    while let Some((moved, saved)) = first_improvement(problem, speed, capacity, &trips, distances)
+    {
+        for (index, trip, distance) in moved {
+            trips[index] = trip;
+            distances[index] = distance;
+        }
+        improvement.improved -= saved;
+        improvement.moves += 1;
+    }
+    improvement
+}
+
+type Moved<'a> = Vec<(usize, Trip<'a>, Float)>;
+
+fn first_improvement<'a>(
+    problem: &Problem,
+    speed: Float,
+    capacity: Float,
+    trips: &[Trip<'a>],
+    distances: &[Float],
+) -> Option<(Moved<'a>, Float)> {
+    let better = |index: usize, trip: Trip<'a>| {
+        let distance = trip.distance(problem, speed)?;
+        (distance < distances[index] - EPSILON).then_some((index, trip, distance))
+    };
+    for (index, trip) in trips.iter().enumerate() {
+        let n = trip.stops.len();
+        // 2-opt: reverse a segment
+        for i in 0..n {
+            for j in i + 1..n {
+                let mut candidate = trip.clone();
+                candidate.stops[i..=j].reverse();
+                if let Some((index, trip, distance)) = better(index, candidate) {
+                    let saved = distances[index] - distance;
+                    return Some((vec![(index, trip, distance)], saved));
+                }
+            }
+        }
+        // Or-opt: move a segment of up to 3 stops elsewhere in the trip
+        for length in 1..=3.min(n) {
+            for i in 0..=n - length {
+                for j in 0..=n - length {
+                    if i == j {
+                        continue;
+                    }
+                    let mut candidate = trip.clone();
+                    let segment = candidate.stops.drain(i..i + length).collect::<Vec<_>>();
+                    candidate.stops.splice(j..j, segment);
+                    if let Some((index, trip, distance)) = better(index, candidate) {
+                        let saved = distances[index] - distance;
+                        return Some((vec![(index, trip, distance)], saved));
+                    }
+                }
+            }
+        }
+    }
+    // relocate: move a stop to another trip of the truck
+    for (a, from) in trips.iter().enumerate() {
+        for i in 0..from.stops.len() {
+            let mut source = from.clone();
+            let stop = source.stops.remove(i);
+            let Some(source_distance) = source.distance(problem, speed) else {
+                continue;
+            };
+            for (b, to) in trips.iter().enumerate() {
+                if a == b || to.load() + stop.0.demand.abs() > capacity + EPSILON {
+                    continue;
+                }
+                for j in 0..=to.stops.len() {
+                    let mut target = to.clone();
+                    target.stops.insert(j, stop);
+                    let Some(target_distance) = target.distance(problem, speed) else {
+                        continue;
+                    };
+                    let saved = distances[a] + distances[b] - source_distance - target_distance;
+                    if saved > EPSILON {
+                        let moved =
+                            vec![(a, source, source_distance), (b, target, target_distance)];
+                        return Some((moved, saved));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// every truck of a simulated day
+pub fn improve(sim: &Simulation) -> Improvement {
+    let mut improvement = Improvement::default();
+    for state in &sim.vehicles {
+        improvement.add(improve_vehicle(sim.problem, state));
+    }
+    improvement
+}
+
+#[test]
+fn local_search() {
+    let request = |idx, x| Request {
+        idx,
+        x,
+        y: 0.0,
+        demand: 1.0,
+        open: 0.0,
+        close: 1000.0,
+        service_time: 0.0,
+        time: 0.0,
+        pickup: false,
+        revenue: 1.0,
+    };
+    let requests = vec![request(1, 10.0), request(2, -5.0), request(3, 20.0)];
+    let problem = Problem::new(vec![request(0, 0.0)], vec![], requests, 1.0, 10.0, 1);
+    let mut state = VehicleState::new(&problem, 0);
+    // out to 10, back past the depot to -5, then out to 20 and home
+    let leg = |from, to, departed, start, reload| Leg {
+        from,
+        to,
+        departed,
+        start,
+        end: start,
+        reload,
+    };
+    state.legs = vec![
+        leg(0, 1, 0.0, 10.0, false),
+        leg(1, 2, 10.0, 25.0, false),
+        leg(2, 3, 25.0, 50.0, false),
+        leg(3, 0, 50.0, 70.0, true),
+    ];
+    state.traveled = 70.0;
+    let improvement = improve_vehicle(&problem, &state);
+    assert_eq!((improvement.distance, improvement.improved), (70.0, 50.0));
+    assert!(improvement.moves > 0);
+    // a window that only the simulated order meets keeps it
+    let mut requests = problem.requests.clone();
+    requests[0].close = 10.0;
+    requests[1].close = 25.0;
+    let problem = Problem::new(vec![request(0, 0.0)], vec![], requests, 1.0, 10.0, 1);
+    let improvement = improve_vehicle(&problem, &state);
+    assert_eq!(improvement.improved, 70.0);
+}
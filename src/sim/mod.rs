@@ -1,38 +1,123 @@
 use std::{
+    cell::{Cell, OnceCell},
     cmp::Reverse,
-    collections::{BTreeMap, BinaryHeap, HashMap},
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    time::Instant,
 };
 
 use ordered_float::OrderedFloat;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use crate::{log, ROUTE, ROUTEEVAL, SIM};
 
 use self::{
     ctx::{RoutingContext, RoutingProgram, SequencingContext, SequencingProgram},
-    problem::{Problem, Request},
+    distance::DistanceProvider,
+    grid::Grid,
+    insertion::Insertion,
+    problem::{Ablation, EpochPolicy, Problem, Request, TerminalNoise},
+    registry::{Entry, RoutingEntry, SequencingEntry},
+    rolling::Rolling,
+    scenario::Scenario,
+    trace::{Record, Trace},
 };
 
+pub mod baseline;
 pub mod ctx;
+pub mod dataset;
+pub mod distance;
+pub mod ensemble;
+pub mod exact;
+pub mod explain;
+pub mod function_set;
+pub mod grid;
+pub mod insertion;
+pub mod instance;
+pub mod local_search;
+pub mod phenotype;
 pub mod problem;
+pub mod registry;
+pub mod rolling;
+pub mod scenario;
+pub mod sensitivity;
+pub mod stats;
+pub mod trace;
+pub mod training;
 
+// the numeric type of the simulation, rule programs and fitness stay in `f32`
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+#[cfg(not(feature = "f64"))]
+pub fn narrow(value: Float) -> f32 {
+    value
+}
+
+#[cfg(feature = "f64")]
+pub fn narrow(value: Float) -> f32 {
+    value as f32
+}
+
+#[cfg(not(feature = "f64"))]
+pub fn widen(value: Float) -> f64 {
+    value.into()
+}
+
+#[cfg(feature = "f64")]
+pub fn widen(value: Float) -> f64 {
+    value
+}
+
+#[derive(Clone)]
 pub enum Event<'a> {
-    Requests(Vec<&'a Request>, f32),
+    Requests(Vec<&'a Request>, Float),
     VehicleFinish {
         vehicle: usize,
         request: &'a Request,
-        time: f32,
+        time: Float,
+    },
+    Cancel {
+        request: &'a Request,
+        time: Float,
+    },
+    BreakEnd {
+        vehicle: usize,
+        time: Float,
+    },
+    ShiftEnd {
+        vehicle: usize,
+        time: Float,
+    },
+    Reassign(Float),
+    Transfer {
+        satellite: usize,
+        amount: Float,
+        time: Float,
+    },
+    Recover {
+        vehicle: usize,
+        time: Float,
     },
 }
 
 impl Event<'_> {
-    pub fn time(&self) -> f32 {
+    pub fn time(&self) -> Float {
         match self {
             Self::Requests(_, time) => *time,
             Self::VehicleFinish { time, .. } => *time,
+            Self::Cancel { time, .. } => *time,
+            Self::BreakEnd { time, .. } => *time,
+            Self::ShiftEnd { time, .. } => *time,
+            Self::Reassign(time) => *time,
+            Self::Transfer { time, .. } => *time,
+            Self::Recover { time, .. } => *time,
         }
     }
 
-    pub fn time_ordered(&self) -> OrderedFloat<f32> {
+    pub fn time_ordered(&self) -> OrderedFloat<Float> {
         OrderedFloat(self.time())
     }
 }
@@ -56,63 +141,340 @@ impl Ord for Event<'_> {
     }
 }
 
+#[derive(Clone, Copy)]
+struct QueueSummary {
+    demand: Float,
+    median: (Float, Float),
+}
+
+#[derive(Clone)]
 pub struct VehicleState<'a> {
+    distances: &'a dyn DistanceProvider,
+    home: &'a Request,
+    // where the vehicle reloads: its home depot, or for a drone the current
+    // stop of its carrier truck
+    dock: &'a Request,
+    carrier: Option<usize>,
+    speed: Float,
+    capacity: Float,
+    // range between recharges, the whole day for trucks and a single sortie
+    // for drones
+    range: Float,
+    recharged_at: Float,
+    // earliest time a loading dock at home is available
+    home_dock_free: Float,
     cur_request: &'a Request,
-    queue: Vec<(&'a Request, f32)>,
-    // total_queued_demand: f32,
-    total_demand: f32,
-    busy_until: f32,
+    queue: Vec<(&'a Request, Float)>,
+    // spatial index over `queue`
+    grid: Grid,
+    // queue invariants shared by the evaluation of every candidate, computed
+    // when first needed after the queue changed
+    summary: OnceCell<QueueSummary>,
+    // total_queued_demand: Float,
+    total_demand: Float,
+    pickup_load: Float,
+    busy_until: Float,
+    departed_at: Option<Float>,
+    traveled: Float,
+    trips: usize,
+    // demand served on each trip
+    pub trip_loads: Vec<Float>,
+    served: usize,
+    depot_returns: usize,
+    lateness: Float,
+    busy_time: Float,
+    busy_end: Float,
+    last_break_end: Option<Float>,
+    pub breaks: Vec<(Float, Float)>,
     pub route: BTreeMap<i32, usize>,
     pub dropped: BTreeMap<i32, usize>,
+    // unlike `route`, every move in order
+    pub legs: Vec<Leg>,
+    num_dropped: usize,
 }
 
 impl<'a> VehicleState<'a> {
-    pub fn new(problem: &'a Problem) -> Self {
+    // vehicles after the trucks are drones, carried by the trucks round-robin
+    pub fn new(problem: &'a Problem, vehicle: usize) -> Self {
+        let carrier = vehicle
+            .checked_sub(problem.num_trucks)
+            .map(|drone| drone % problem.num_trucks);
+        let home = problem.home_depot(carrier.unwrap_or(vehicle));
+        let (speed, capacity, range) = match carrier {
+            Some(_) => (
+                problem.drone_speed,
+                problem.drone_capacity,
+                problem.drone_range,
+            ),
+            None => {
+                let (speed, capacity) = problem.vehicle_class();
+                (speed, capacity, problem.max_travel_distance)
+            }
+        };
         Self {
-            cur_request: &problem.depot,
+            distances: &*problem.distances,
+            home,
+            dock: home,
+            carrier,
+            speed,
+            capacity,
+            range,
+            recharged_at: 0.0,
+            home_dock_free: 0.0,
+            cur_request: home,
             queue: Vec::new(),
-            total_demand: problem.truck_capacity,
+            grid: Grid::new(problem.coordinates, problem.neighbor_radius),
+            summary: OnceCell::new(),
+            total_demand: capacity,
+            pickup_load: 0.0,
             // total_queued_demand: 0.0,
             busy_until: 0.0,
+            departed_at: None,
+            traveled: 0.0,
+            trips: 0,
+            trip_loads: Vec::new(),
+            served: 0,
+            depot_returns: 0,
+            lateness: 0.0,
+            busy_time: 0.0,
+            busy_end: 0.0,
+            last_break_end: None,
+            breaks: Vec::new(),
             route: Default::default(),
             dropped: Default::default(),
+            legs: Vec::new(),
+            num_dropped: 0,
         }
     }
 
-    pub fn time_cost(&self, problem: &'a Problem, req: &'a Request, time: f32) -> f32 {
-        (self.distance_to(req) / problem.truck_speed).max(req.open - time)
+    fn with_scratch(problem: &'a Problem, vehicle: usize, scratch: VehicleScratch) -> Self {
+        let mut state = Self::new(problem, vehicle);
+        state.queue = recycle(scratch.queue);
+        if let Some(grid) = scratch.grid {
+            state.grid = grid.reset(problem.coordinates, problem.neighbor_radius);
+        }
+        state.trip_loads = recycle(scratch.trip_loads);
+        state.breaks = recycle(scratch.breaks);
+        state.legs = recycle(scratch.legs);
+        state
     }
 
-    pub fn raw_time_cost(&self, problem: &'a Problem, req: &'a Request, _: f32) -> f32 {
-        self.distance_to(req) / problem.truck_speed
+    fn into_scratch(self) -> VehicleScratch {
+        VehicleScratch {
+            queue: recycle(self.queue),
+            grid: Some(self.grid),
+            trip_loads: self.trip_loads,
+            breaks: self.breaks,
+            legs: self.legs,
+        }
     }
 
-    pub fn time_until_open(&self, req: &'a Request, time: f32) -> f32 {
+    pub fn time_cost(&self, problem: &'a Problem, req: &'a Request, time: Float) -> Float {
+        self.raw_time_cost(problem, req, time).max(req.open - time)
+    }
+
+    pub fn raw_time_cost(&self, _: &'a Problem, req: &'a Request, _: Float) -> Float {
+        self.distances
+            .travel_time(self.cur_request, req, self.speed)
+    }
+
+    pub fn is_drone(&self) -> bool {
+        self.carrier.is_some()
+    }
+
+    pub fn at_dock(&self) -> bool {
+        self.cur_request.idx == self.dock.idx
+    }
+
+    // latest time the vehicle may be back at its home depot: the depot close,
+    // or the end of the maximum route duration counted from its first departure
+    pub fn deadline(&self, problem: &'a Problem, time: Float) -> Float {
+        let start = self.departed_at.unwrap_or(time);
+        self.home
+            .close
+            .min(problem.shift_end)
+            .min(start + problem.max_route_duration)
+    }
+
+    // start of the current uninterrupted work period
+    fn work_start(&self, time: Float) -> Float {
+        self.last_break_end.or(self.departed_at).unwrap_or(time)
+    }
+
+    pub fn break_due(&self, problem: &'a Problem, time: Float) -> bool {
+        !self.is_drone()
+            && self.departed_at.is_some()
+            && time - self.work_start(time) >= problem.break_after
+    }
+
+    // with open routes the vehicle only has to finish serving `req` in time
+    pub fn can_return_home(&self, problem: &'a Problem, req: &'a Request, time: Float) -> bool {
+        let start = time + self.time_cost(problem, req, time);
+        let mut back = start + req.service_time;
+        if !problem.open_routes || self.is_drone() {
+            back += self.distances.travel_time(req, self.dock, self.speed);
+        }
+        // a break falling due on the way is taken before the vehicle is home
+        if back - self.work_start(time) > problem.break_after {
+            back += problem.break_duration;
+        }
+        back <= self.deadline(problem, time)
+    }
+
+    pub fn remaining_range(&self, _: &'a Problem) -> Float {
+        self.range - (self.traveled - self.recharged_at)
+    }
+
+    pub fn within_range(&self, problem: &'a Problem, req: &'a Request) -> bool {
+        let back = if problem.open_routes && !self.is_drone() {
+            0.0
+        } else {
+            self.home_distance(req)
+        };
+        if self.fits(problem, req) {
+            return self.distance_to(req) + back <= self.remaining_range(problem);
+        }
+        // a reload is due first, by the dock where drones also recharge
+        let (to_dock, from_dock) = (self.distance_to(self.dock), self.home_distance(req));
+        match self.is_drone() {
+            true => to_dock <= self.remaining_range(problem) && from_dock + back <= self.range,
+            false => to_dock + from_dock + back <= self.remaining_range(problem),
+        }
+    }
+
+    // a truck on its last trip only takes what it still carries
+    pub fn can_take(&self, problem: &'a Problem, req: &'a Request) -> bool {
+        self.is_drone()
+            || self.trips < problem.max_trips
+            || (!self.at_dock() && self.fits(problem, req))
+    }
+
+    // whether the vehicle may be offered `req` at all
+    pub fn can_route(&self, problem: &'a Problem, req: &'a Request, time: Float) -> bool {
+        let cost = self.raw_time_cost(problem, req, time);
+        req.demand <= self.capacity
+            && self.queue.len() < problem.max_queue_len
+            && self.can_take(problem, req)
+            && time + cost <= problem.latest_start(req)
+            && self.is_feasible(problem, req, time)
+    }
+
+    pub fn is_feasible(&self, problem: &'a Problem, req: &'a Request, time: Float) -> bool {
+        self.can_return_home(problem, req, time) && self.within_range(problem, req)
+    }
+
+    pub fn time_until_open(&self, req: &'a Request, time: Float) -> Float {
         time - req.time
     }
 
-    pub fn distance_to(&self, request: &'a Request) -> f32 {
-        Self::dist(
-            self.cur_request.x - request.x,
-            self.cur_request.y - request.y,
-        )
+    pub fn distance_to(&self, request: &'a Request) -> Float {
+        self.distances.distance(self.cur_request, request)
+    }
+
+    pub fn home_distance(&self, request: &'a Request) -> Float {
+        self.distances.distance(request, self.dock)
+    }
+
+    // space left for pickups, which share the truck with goods still to be
+    // delivered
+    pub fn free_space(&self, _: &'a Problem) -> Float {
+        self.capacity - self.total_demand - self.pickup_load
+    }
+
+    pub fn fits(&self, problem: &'a Problem, req: &'a Request) -> bool {
+        if req.pickup {
+            req.demand <= self.free_space(problem)
+        } else {
+            req.demand <= self.total_demand
+        }
+    }
+
+    // unloads pickups and loads delivery goods, leaving room for the pickups
+    // already queued; at most `stock` is loaded, the loaded amount is returned
+    pub fn reload(&mut self, _: &'a Problem, stock: Float) -> Float {
+        let queued_pickups = self
+            .queue
+            .iter()
+            .filter(|r| r.0.pickup)
+            .map(|r| r.0.demand)
+            .sum::<Float>();
+        let before = self.total_demand;
+        let target = (self.capacity - queued_pickups).max(0.0);
+        self.total_demand = target.min(before + stock);
+        self.pickup_load = 0.0;
+        self.total_demand - before
     }
 
-    fn dist(x: f32, y: f32) -> f32 {
-        (x * x + y * y).sqrt()
+    pub fn queue_slack(&self, problem: &'a Problem) -> Float {
+        match problem.max_queue_len {
+            usize::MAX => 1.0,
+            max => max.saturating_sub(self.queue.len()) as Float / max as Float,
+        }
     }
 
-    pub fn enqueue(&mut self, request: &'a Request, time: f32) {
+    pub fn enqueue(&mut self, request: &'a Request, time: Float) {
         self.queue.push((request, time));
+        self.grid.insert(request);
+        self.summary.take();
         // self.total_queued_demand += request.demand;
     }
 
-    pub fn median(x: impl Iterator<Item = f32>) -> f32 {
+    pub fn dequeue(&mut self, index: usize) -> (&'a Request, Float) {
+        let queued = self.queue.swap_remove(index);
+        self.grid.remove(queued.0);
+        self.summary.take();
+        queued
+    }
+
+    // takes a queued request back from the vehicle, to be offered again or
+    // failed
+    pub fn drop_queued(&mut self, index: usize, time: Float) -> &'a Request {
+        let (request, _) = self.dequeue(index);
+        self.dropped.insert(time as _, request.idx);
+        self.num_dropped += 1;
+        request
+    }
+
+    pub fn queue(&self) -> &[(&'a Request, Float)] {
+        &self.queue
+    }
+
+    pub fn traveled(&self) -> Float {
+        self.traveled
+    }
+
+    pub fn take_queue(&mut self) -> Vec<(&'a Request, Float)> {
+        self.grid.clear();
+        self.summary.take();
+        std::mem::take(&mut self.queue)
+    }
+
+    // whether `request` was queued
+    pub fn remove_queued(&mut self, request: &'a Request) -> bool {
+        match self.queue.iter().position(|(r, _)| r.idx == request.idx) {
+            Some(index) => {
+                self.dequeue(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // distance from the request to the closest queued one
+    pub fn nearest_queued(&self, request: &'a Request) -> Option<Float> {
+        self.grid.nearest((request.x, request.y))
+    }
+
+    pub fn queued_within(&self, request: &'a Request, radius: Float) -> usize {
+        self.grid.within((request.x, request.y), radius)
+    }
+
+    pub fn median(x: impl Iterator<Item = Float>) -> Float {
         // match x.len() {
         //     0 => 0.0,
-        //     n => x.iter().copied().sum::<f32>() / n as f32,
+        //     n => x.iter().copied().sum::<Float>() / n as Float,
         // }
-        let mut x: Vec<f32> = x.collect();
+        let mut x: Vec<Float> = x.collect();
         x.sort_unstable_by_key(|f| OrderedFloat::from(*f));
         match x.len() {
             0 => 0.0,
@@ -121,197 +483,1285 @@ impl<'a> VehicleState<'a> {
         }
     }
 
-    pub fn median_queue_pos(&self) -> (f32, f32) {
-        let x = self.queue.iter().map(|r| r.0.x);
-        let y = self.queue.iter().map(|r| r.0.y);
-        (Self::median(x), Self::median(y))
+    fn summary(&self) -> &QueueSummary {
+        self.summary.get_or_init(|| {
+            let x = self.queue.iter().map(|r| r.0.x);
+            let y = self.queue.iter().map(|r| r.0.y);
+            QueueSummary {
+                demand: self.queue.iter().map(|r| r.0.demand).sum(),
+                median: (Self::median(x), Self::median(y)),
+            }
+        })
+    }
+
+    pub fn queued_demand(&self) -> Float {
+        self.summary().demand
+    }
+
+    pub fn median_queue_pos(&self) -> (Float, Float) {
+        self.summary().median
     }
 }
 
-trait RoutingRule {
-    fn route_request(
+// the routing and sequencing rules of a period of the day
+pub type Segment<'a> = (RoutingProgram<'a>, SequencingProgram<'a>);
+
+pub enum Dispatch {
+    Vehicle(usize),
+    // postpone to the next decision epoch, the vehicle is used instead when
+    // the request cannot wait that long
+    Defer(usize),
+    Reject,
+}
+
+impl Dispatch {
+    // from the vehicles ranked by the routing rule, which defers a request
+    // by the sign of the value of its best vehicle
+    fn ranked(problem: &Problem, ranked: &[(usize, Float)]) -> Self {
+        match ranked.first() {
+            None => Self::Reject,
+            Some(&(vehicle, value)) if problem.defers(value) => Self::Defer(vehicle),
+            Some(&(vehicle, _)) => Self::Vehicle(vehicle),
+        }
+    }
+}
+
+// a non-finite rule value ranks its candidate last instead of aborting the
+// run, and is counted in `non_finite`
+fn protect(value: f32, non_finite: &Cell<usize>) -> Float {
+    if value.is_finite() {
+        value as Float
+    } else {
+        non_finite.set(non_finite.get() + 1);
+        Float::INFINITY
+    }
+}
+
+// a routing rule, evolved or hand-written; `Registry` selects them by name
+// and `Rules` simulates the hand-written ones in place of the programs, which
+// is why the rules stay object safe
+pub trait RoutingRule {
+    // feasible vehicles with their rule value, best first; a best value
+    // below zero defers the request with `defer` on, and non-finite values are
+    // counted in `non_finite`. Only programs fill `record`, which traced runs
+    // replay programs from
+    fn rank_vehicles(
         &self,
         problem: &Problem,
-        time: f32,
+        time: Float,
         vehicles: &[VehicleState],
         request: &Request,
-    ) -> Option<usize>;
+        non_finite: &Cell<usize>,
+        record: Option<&mut Record>,
+    ) -> Vec<(usize, Float)>;
 }
 
-trait SequencingRule {
-    fn sequence_request(
+pub trait SequencingRule {
+    // indices of the queued requests with their rule value, best first;
+    // `cache` keeps values over the picks of a vehicle at a time, by request
+    #[allow(clippy::too_many_arguments)]
+    fn rank_queue(
         &self,
         problem: &Problem,
-        time: f32,
-        vehicle: &VehicleState,
-        cache: &mut HashMap<usize, OrderedFloat<f32>>,
-    ) -> Option<usize>;
+        time: Float,
+        vehicle: usize,
+        vehicle_state: &VehicleState,
+        cache: &mut HashMap<usize, (OrderedFloat<Float>, usize)>,
+        non_finite: &Cell<usize>,
+        record: Option<&mut Record>,
+    ) -> Vec<(usize, Float)>;
 }
 
 impl<'a> RoutingRule for RoutingProgram<'a> {
-    fn route_request(
+    fn rank_vehicles(
         &self,
         problem: &Problem,
-        time: f32,
+        time: Float,
         vehicles: &[VehicleState],
         request: &Request,
-    ) -> Option<usize> {
-        (0..vehicles.len())
-            .filter(|vehicle| {
-                let cost = vehicles[*vehicle].raw_time_cost(problem, request, time);
-                time + cost <= request.close
-            })
-            .min_by_key(|vehicle| {
-                let value = self.calc(&RoutingContext {
+        non_finite: &Cell<usize>,
+        mut record: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let feasible = (0..vehicles.len())
+            .filter(|vehicle| vehicles[*vehicle].can_route(problem, request, time))
+            .collect::<Vec<_>>();
+        let total_demand = problem.total_demand();
+        let mut ranked = feasible
+            .iter()
+            .map(|&vehicle| {
+                let context = RoutingContext {
                     problem,
                     time,
-                    vehicle_state: &vehicles[*vehicle],
+                    vehicle_state: &vehicles[vehicle],
                     request,
-                });
-                assert!(value.is_finite());
+                    num_feasible: feasible.len(),
+                    total_demand,
+                };
+                let mut terminals = RoutingProgram::terminals(&context);
+                Ablation::apply(problem.ablation.routing, &mut terminals);
+                let noise = &problem.terminal_noise;
+                let key = [widen(time).to_bits(), request.idx as u64, vehicle as u64];
+                TerminalNoise::apply(&noise.routing, noise.seed, key, &mut terminals);
+                let value = protect(self.calc_with(&context, &terminals), non_finite);
                 log!(
                     ROUTEEVAL,
                     "routing_evaluation",
                     value = value,
                     vehicle = vehicle
                 );
-                (
-                    OrderedFloat(value),
-                    // vehicles[*vehicle].queue.len(),
-                )
+                let offset = record
+                    .as_mut()
+                    .map_or(0, |record| record.push_terminals(&terminals));
+                let distance = vehicles[vehicle].distance_to(request);
+                let tie = problem.tie_break.key(vehicle, distance, request.idx);
+                (vehicle, value, tie, offset)
             })
+            .collect::<Vec<_>>();
+        let key = |(_, value, tie, _): &(usize, Float, Float, usize)| {
+            (OrderedFloat(*value), OrderedFloat(*tie))
+        };
+        if let Some(record) = record {
+            if let Some(best) = (0..ranked.len()).min_by_key(|i| key(&ranked[*i])) {
+                let candidates = ranked.iter().map(|c| (c.3, c.2, c.0)).collect();
+                let deferred = problem.defers(ranked[best].1);
+                record.push_routing(time, request.idx, candidates, best, deferred);
+            }
+        }
+        // stable, so ties left by the tie-breaker keep the lowest vehicle
+        // index first
+        ranked.sort_by_key(key);
+        ranked
+            .into_iter()
+            .map(|(vehicle, value, ..)| (vehicle, value))
+            .collect()
     }
 }
 
 impl<'a> SequencingRule for SequencingProgram<'a> {
-    fn sequence_request(
+    fn rank_queue(
         &self,
         problem: &Problem,
-        time: f32,
+        time: Float,
+        vehicle: usize,
         vehicle_state: &VehicleState,
-        cache: &mut HashMap<usize, OrderedFloat<f32>>,
-    ) -> Option<usize> {
-        (0..vehicle_state.queue.len()).min_by_key(|i| {
-            let request_idx = vehicle_state.queue[*i].0.idx;
-            *cache.entry(request_idx).or_insert_with(|| {
-                let value = self.calc(&SequencingContext {
-                    problem,
-                    time,
-                    vehicle_state,
-                    request: vehicle_state.queue[*i].0,
-                    ready_time: vehicle_state.queue[*i].1,
+        cache: &mut HashMap<usize, (OrderedFloat<Float>, usize)>,
+        non_finite: &Cell<usize>,
+        mut record: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let total_demand = problem.total_demand();
+        let ranked = (0..vehicle_state.queue.len())
+            .map(|i| {
+                let (request, ready_time) = vehicle_state.queue[i];
+                let (value, offset) = *cache.entry(request.idx).or_insert_with(|| {
+                    let context = SequencingContext {
+                        problem,
+                        time,
+                        vehicle_state,
+                        request,
+                        ready_time,
+                        total_demand,
+                    };
+                    let mut terminals = SequencingProgram::terminals(&context);
+                    Ablation::apply(problem.ablation.sequencing, &mut terminals);
+                    let noise = &problem.terminal_noise;
+                    let key = [widen(time).to_bits(), request.idx as u64, vehicle as u64];
+                    TerminalNoise::apply(&noise.sequencing, noise.seed, key, &mut terminals);
+                    let value = protect(self.calc_with(&context, &terminals), non_finite);
+                    let offset = record
+                        .as_mut()
+                        .map_or(0, |record| record.push_terminals(&terminals));
+                    (OrderedFloat(value), offset)
                 });
-                assert!(value.is_finite());
-                OrderedFloat(value)
+                let distance = vehicle_state.distance_to(request);
+                let tie = problem.tie_break.key(i, distance, request.idx);
+                (value, OrderedFloat(tie), offset, request.idx)
             })
-        })
+            .collect::<Vec<_>>();
+        // stable, so ties left by the tie-breaker keep the queue order
+        let mut order = (0..ranked.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| (ranked[*i].0, ranked[*i].1));
+        if let (Some(record), Some(&chosen)) = (record, order.first()) {
+            let candidates = ranked.iter().map(|c| (c.2, c.1 .0, c.3)).collect();
+            record.push_sequencing(time, vehicle, candidates, chosen);
+        }
+        order.into_iter().map(|i| (i, ranked[i].0 .0)).collect()
     }
 }
 
+// one move of a vehicle as planned at `departed`, `reload` when it ends at a
+// depot or, for a drone, back on its carrier
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Leg {
+    pub from: usize,
+    pub to: usize,
+    pub departed: Float,
+    pub start: Float,
+    pub end: Float,
+    pub reload: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct VehicleResult {
+    pub distance: Float,
+    pub served: usize,
+    pub depot_returns: usize,
+    pub lateness: Float,
+    // share of the day spent away from an idle wait at the depot
+    pub utilization: Float,
+    pub busy_time: Float,
+    pub idle_time: Float,
+    pub trips: usize,
+    // mean share of the capacity served per trip
+    pub load_factor: Float,
+    // queued requests given back to the fleet or failed
+    pub dropped: usize,
+}
+
+// how often the rules were evaluated, how often each of their nodes by index
+// then, and with `Problem::profile` the time spent evaluating them against
+// the rest of the run; rollouts of `lookahead` count as the rest of the run
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RuleProfile {
+    pub routing_evaluations: usize,
+    pub sequencing_evaluations: usize,
+    pub routing_terminals: Vec<usize>,
+    pub routing_internals: Vec<usize>,
+    pub sequencing_terminals: Vec<usize>,
+    pub sequencing_internals: Vec<usize>,
+    pub rule_seconds: f64,
+    pub event_seconds: f64,
+}
+
+impl RuleProfile {
+    pub fn add(&mut self, other: &RuleProfile) {
+        let add = |total: &mut Vec<usize>, other: &[usize]| {
+            total.resize(total.len().max(other.len()), 0);
+            for (total, other) in total.iter_mut().zip(other) {
+                *total += other;
+            }
+        };
+        self.routing_evaluations += other.routing_evaluations;
+        self.sequencing_evaluations += other.sequencing_evaluations;
+        add(&mut self.routing_terminals, &other.routing_terminals);
+        add(&mut self.routing_internals, &other.routing_internals);
+        add(&mut self.sequencing_terminals, &other.sequencing_terminals);
+        add(&mut self.sequencing_internals, &other.sequencing_internals);
+        self.rule_seconds += other.rule_seconds;
+        self.event_seconds += other.event_seconds;
+    }
+
+    // every node of the rules is evaluated once per evaluation; rules other
+    // than programs have no nodes to count
+    pub fn count_nodes(
+        &mut self,
+        routing: Option<&RoutingProgram>,
+        sequencing: Option<&SequencingProgram>,
+    ) {
+        let times = |counts: Vec<usize>, evaluations: usize| {
+            counts
+                .into_iter()
+                .map(|count| count * evaluations)
+                .collect()
+        };
+        if let Some(routing) = routing {
+            let (terminals, internals) = routing.node_counts();
+            self.routing_terminals = times(terminals, self.routing_evaluations);
+            self.routing_internals = times(internals, self.routing_evaluations);
+        }
+        if let Some(sequencing) = sequencing {
+            let (terminals, internals) = sequencing.node_counts();
+            self.sequencing_terminals = times(terminals, self.sequencing_evaluations);
+            self.sequencing_internals = times(internals, self.sequencing_evaluations);
+        }
+    }
+
+    // share of the run spent evaluating the rules
+    pub fn rule_share(&self) -> f64 {
+        match self.rule_seconds + self.event_seconds {
+            total if total > 0.0 => self.rule_seconds / total,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SimulationResult {
+    pub distance: Float,
+    pub failed: usize,
+    pub lateness: Float,
+    pub max_lateness: Float,
+    // time from the release of a request to the start of its service, and
+    // from its first assignment to a vehicle
+    pub wait: Float,
+    pub assigned_wait: Float,
+    pub utilization: Float,
+    pub depot_returns: usize,
+    pub overtime: Float,
+    pub revenue: Float,
+    pub cancelled: usize,
+    pub no_show: usize,
+    // requests carried over to the next day, once for every day they wait
+    pub rolled_over: usize,
+    // rule evaluations that were not finite and ranked their candidate last
+    pub non_finite: usize,
+    // events run by the simulator
+    pub events: usize,
+    // the worst failure rate and mean wait over any `rolling_window`, 0
+    // without one
+    pub peak_failure_rate: Float,
+    pub peak_wait: Float,
+    // decisions on a queue short enough for `exact_queue`, those where the
+    // rule picked as well as the exact order, and the skipped requests and
+    // distance its picks cost over it
+    pub exact_decisions: usize,
+    pub exact_agreed: usize,
+    pub sequencing_skipped: usize,
+    pub sequencing_loss: Float,
+    pub profile: RuleProfile,
+    pub vehicles: Vec<VehicleResult>,
+}
+
+impl SimulationResult {
+    // totals over consecutive days, the failures are those of the last day
+    // and utilizations are averaged
+    pub fn combine(days: &[SimulationResult]) -> Self {
+        let mut total = Self::default();
+        let n = days.len().max(1) as Float;
+        for day in days {
+            total.distance += day.distance;
+            total.failed = day.failed;
+            total.lateness += day.lateness;
+            total.max_lateness = total.max_lateness.max(day.max_lateness);
+            total.wait += day.wait;
+            total.assigned_wait += day.assigned_wait;
+            total.utilization += day.utilization / n;
+            total.depot_returns += day.depot_returns;
+            total.overtime += day.overtime;
+            total.revenue += day.revenue;
+            total.cancelled += day.cancelled;
+            total.no_show += day.no_show;
+            total.rolled_over += day.rolled_over;
+            total.non_finite += day.non_finite;
+            total.events += day.events;
+            total.peak_failure_rate = total.peak_failure_rate.max(day.peak_failure_rate);
+            total.peak_wait = total.peak_wait.max(day.peak_wait);
+            total.exact_decisions += day.exact_decisions;
+            total.exact_agreed += day.exact_agreed;
+            total.sequencing_skipped += day.sequencing_skipped;
+            total.sequencing_loss += day.sequencing_loss;
+            total.profile.add(&day.profile);
+            total.vehicles.resize(
+                day.vehicles.len().max(total.vehicles.len()),
+                Default::default(),
+            );
+            for (total, day) in total.vehicles.iter_mut().zip(day.vehicles.iter()) {
+                total.distance += day.distance;
+                total.served += day.served;
+                total.depot_returns += day.depot_returns;
+                total.lateness += day.lateness;
+                total.utilization += day.utilization / n;
+                total.busy_time += day.busy_time;
+                total.idle_time += day.idle_time;
+                total.trips += day.trips;
+                total.load_factor += day.load_factor / n;
+                total.dropped += day.dropped;
+            }
+        }
+        total
+    }
+}
+
+// runs the days of `problem` back to back with the fleet reset at the depot
+// every morning; requests failed on a day are released again at the start of
+// the next one and only the failures of the last day are final
+pub fn simulate_days(
+    problem: &Problem,
+    routing_rule: &RoutingProgram,
+    sequencing_rule: &SequencingProgram,
+    scenario: Scenario,
+    time_slot: Float,
+    on_day: impl FnMut(usize, &Simulation),
+) -> SimulationResult {
+    simulate_days_with(
+        &mut SimulationScratch::default(),
+        problem,
+        routing_rule,
+        sequencing_rule,
+        scenario,
+        time_slot,
+        on_day,
+    )
+}
+
+// like `simulate_days`, with the buffers of `scratch` and those of every day
+// left in it for the next call
+pub fn simulate_days_with(
+    scratch: &mut SimulationScratch,
+    problem: &Problem,
+    routing_rule: &RoutingProgram,
+    sequencing_rule: &SequencingProgram,
+    scenario: Scenario,
+    time_slot: Float,
+    on_day: impl FnMut(usize, &Simulation),
+) -> SimulationResult {
+    let first = (
+        Entry::Program(routing_rule.clone()),
+        Entry::Program(sequencing_rule.clone()),
+    );
+    run_days(scratch, problem, &first, &[], scenario, time_slot, on_day)
+}
+
+// like `simulate_days` with rules of the registry, programs or not
+pub fn simulate_rules(
+    problem: &Problem,
+    routing_rule: &RoutingEntry,
+    sequencing_rule: &SequencingEntry,
+    scenario: Scenario,
+    time_slot: Float,
+    on_day: impl FnMut(usize, &Simulation),
+) -> SimulationResult {
+    let first = (routing_rule.clone(), sequencing_rule.clone());
+    run_days(
+        &mut SimulationScratch::default(),
+        problem,
+        &first,
+        &[],
+        scenario,
+        time_slot,
+        on_day,
+    )
+}
+
+// like `simulate_days_with` for a piecewise policy, with the rules of each
+// period of the day in turn
+pub fn simulate_segments(
+    scratch: &mut SimulationScratch,
+    problem: &Problem,
+    segments: &[Segment],
+    scenario: Scenario,
+    time_slot: Float,
+    on_day: impl FnMut(usize, &Simulation),
+) -> SimulationResult {
+    let (routing_rule, sequencing_rule) = &segments[0];
+    let first = (
+        Entry::Program(routing_rule.clone()),
+        Entry::Program(sequencing_rule.clone()),
+    );
+    let segments = &segments[1..];
+    run_days(
+        scratch, problem, &first, segments, scenario, time_slot, on_day,
+    )
+}
+
+// the days of `simulate_days` with `first` opening every day and the rules
+// of `segments` deciding its later periods
+fn run_days(
+    scratch: &mut SimulationScratch,
+    problem: &Problem,
+    (routing_rule, sequencing_rule): &(RoutingEntry, SequencingEntry),
+    segments: &[Segment],
+    scenario: Scenario,
+    time_slot: Float,
+    mut on_day: impl FnMut(usize, &Simulation),
+) -> SimulationResult {
+    let mut days = Vec::new();
+    let mut rolled_over = Vec::new();
+    for day in 0..problem.num_days {
+        let day_problem;
+        let problem = if problem.num_days == 1 {
+            problem
+        } else {
+            day_problem = problem.day(day, &rolled_over);
+            &day_problem
+        };
+        let scenario = Scenario {
+            seed: scenario.seed + day as u64,
+            ..scenario
+        };
+        let mut sim = Simulation::with_rules(
+            problem,
+            routing_rule,
+            sequencing_rule,
+            scenario,
+            std::mem::take(scratch),
+        );
+        sim.set_segments(segments);
+        let mut result = sim.simulate_until(time_slot, Float::MAX);
+        on_day(day, &sim);
+        if day + 1 < problem.num_days {
+            rolled_over = sim.failed.iter().map(|request| **request).collect();
+            result.rolled_over = rolled_over.len();
+            result.failed = 0;
+        }
+        *scratch = sim.into_scratch();
+        days.push(result);
+    }
+    SimulationResult::combine(&days)
+}
+
+// seconds since `start`, 0 without profiling
+fn elapsed(start: Option<Instant>) -> f64 {
+    start.map_or(0.0, |start| start.elapsed().as_secs_f64())
+}
+
+// an empty vector with the allocation of `v`, for elements that only differ
+// in their lifetime; this relies on std collecting a `vec::IntoIter` into a
+// vector of a same-sized element in place, which it does but does not
+// promise, so `recycled_buffers` checks it
+fn recycle<T, U>(mut v: Vec<T>) -> Vec<U> {
+    v.clear();
+    v.into_iter().map(|_| unreachable!()).collect()
+}
+
+fn refill<T: Clone>(mut v: Vec<T>, len: usize, value: T) -> Vec<T> {
+    v.clear();
+    v.resize(len, value);
+    v
+}
+
+#[derive(Default)]
+struct VehicleScratch {
+    queue: Vec<(&'static Request, Float)>,
+    grid: Option<Grid>,
+    trip_loads: Vec<Float>,
+    breaks: Vec<(Float, Float)>,
+    legs: Vec<Leg>,
+}
+
+// buffers of a finished simulation, reset and reused by the next one instead
+// of allocating them again for every evaluation
+#[derive(Default)]
+pub struct SimulationScratch {
+    vehicles: Vec<VehicleScratch>,
+    events: Vec<Reverse<Event<'static>>>,
+    cancelled: HashSet<usize>,
+    pool: Vec<(&'static Request, Float)>,
+    stock: Vec<Float>,
+    in_transit: Vec<Float>,
+    line_haul: Vec<Float>,
+    docks: Vec<Vec<Float>>,
+    failed: Vec<&'static Request>,
+    assigned_at: HashMap<usize, Float>,
+}
+
+#[derive(Clone)]
 pub struct Simulation<'a> {
     problem: &'a Problem,
-    routing_rule: &'a RoutingProgram<'a>,
-    sequencing_rule: &'a SequencingProgram<'a>,
-    time: f32,
+    routing_rule: RoutingEntry<'a>,
+    sequencing_rule: SequencingEntry<'a>,
+    scenario: Scenario,
+    rng: SmallRng,
+    time: Float,
     pub vehicles: Vec<VehicleState<'a>>,
     events: BinaryHeap<Reverse<Event<'a>>>,
+    cancelled: HashSet<usize>,
+    // requests no vehicle could take yet or deferred by the routing rule,
+    // re-offered every time slot
+    pool: Vec<(&'a Request, Float)>,
+    pool_offer_at: Float,
+    time_slot: Float,
+    in_rollout: bool,
+    // per satellite stock and amount on its way, and the time each line-haul
+    // truck is back at the depot
+    stock: Vec<Float>,
+    in_transit: Vec<Float>,
+    line_haul: Vec<Float>,
+    // time each loading dock of every depot and satellite becomes free
+    docks: Vec<Vec<Float>>,
+    pub total_distance: Float,
+    pub total_failed: usize,
+    pub failed: Vec<&'a Request>,
+    pub num_cancelled: usize,
+    pub num_no_show: usize,
+    pub num_events: usize,
+    pub total_lateness: Float,
+    pub max_lateness: Float,
+    pub total_wait: Float,
+    pub total_assigned_wait: Float,
+    assigned_at: HashMap<usize, Float>,
+    pub total_overtime: Float,
+    pub total_revenue: Float,
+    pub non_finite: Cell<usize>,
+    pub exact_decisions: usize,
+    pub exact_agreed: usize,
+    pub sequencing_skipped: usize,
+    pub sequencing_loss: Float,
+    // the rules of the later periods of a piecewise policy, the day split
+    // evenly between them and the simulated rules, which open it
+    segments: Vec<Segment<'a>>,
+    // rule evaluations of every period and the time spent in them and in
+    // `run_events`
+    routing_evaluations: Vec<usize>,
+    sequencing_evaluations: Vec<usize>,
+    rule_seconds: f64,
+    run_seconds: f64,
+    // the order each vehicle serves its queue in with `insertion`
+    plans: Vec<Vec<&'a Request>>,
+    // windowed metrics, sampled before every batch of requests
+    pub rolling: Option<Rolling>,
+    // the decisions and checkpoints of a traced run
+    trace: Option<Trace<'a>>,
 }
 
 impl<'a> Simulation<'a> {
     pub fn new(
         problem: &'a Problem,
-        routing_rule: &'a RoutingProgram<'a>,
-        sequencing_rule: &'a SequencingProgram<'a>,
+        routing_rule: &RoutingProgram<'a>,
+        sequencing_rule: &SequencingProgram<'a>,
     ) -> Self {
-        Self {
+        Self::with_scenario(problem, routing_rule, sequencing_rule, Scenario::default())
+    }
+
+    pub fn with_scenario(
+        problem: &'a Problem,
+        routing_rule: &RoutingProgram<'a>,
+        sequencing_rule: &SequencingProgram<'a>,
+        scenario: Scenario,
+    ) -> Self {
+        Self::with_scratch(
             problem,
             routing_rule,
             sequencing_rule,
+            scenario,
+            SimulationScratch::default(),
+        )
+    }
+
+    pub fn with_scratch(
+        problem: &'a Problem,
+        routing_rule: &RoutingProgram<'a>,
+        sequencing_rule: &SequencingProgram<'a>,
+        scenario: Scenario,
+        scratch: SimulationScratch,
+    ) -> Self {
+        Self::with_rules(
+            problem,
+            &Entry::Program(routing_rule.clone()),
+            &Entry::Program(sequencing_rule.clone()),
+            scenario,
+            scratch,
+        )
+    }
+
+    pub fn with_rules(
+        problem: &'a Problem,
+        routing_rule: &RoutingEntry<'a>,
+        sequencing_rule: &SequencingEntry<'a>,
+        scenario: Scenario,
+        scratch: SimulationScratch,
+    ) -> Self {
+        let mut vehicles = scratch.vehicles;
+        vehicles.resize_with(problem.num_vehicles(), Default::default);
+        let mut cancelled = scratch.cancelled;
+        cancelled.clear();
+        let mut assigned_at = scratch.assigned_at;
+        assigned_at.clear();
+        // more docks than vehicles never make anyone wait
+        let num_docks = problem.num_docks.min(problem.num_vehicles());
+        let mut docks = scratch.docks;
+        docks.resize_with(problem.depots.len() + problem.satellites.len(), Vec::new);
+        Self {
+            problem,
+            routing_rule: routing_rule.clone(),
+            sequencing_rule: sequencing_rule.clone(),
+            scenario,
+            rng: SmallRng::seed_from_u64(scenario.seed),
             time: 0.0,
-            vehicles: (0..problem.num_trucks)
-                .map(|_| VehicleState::new(problem))
+            vehicles: vehicles
+                .into_iter()
+                .enumerate()
+                .map(|(vehicle, scratch)| VehicleState::with_scratch(problem, vehicle, scratch))
                 .collect(),
-            events: BinaryHeap::new(),
+            events: BinaryHeap::from(recycle(scratch.events)),
+            cancelled,
+            pool: recycle(scratch.pool),
+            pool_offer_at: Float::NEG_INFINITY,
+            time_slot: Float::INFINITY,
+            in_rollout: false,
+            stock: refill(
+                scratch.stock,
+                problem.satellites.len(),
+                problem.satellite_capacity,
+            ),
+            in_transit: refill(scratch.in_transit, problem.satellites.len(), 0.0),
+            line_haul: refill(scratch.line_haul, problem.num_line_haul, 0.0),
+            docks: docks
+                .into_iter()
+                .map(|docks| refill(docks, num_docks, 0.0))
+                .collect(),
+            total_distance: 0.0,
+            total_failed: 0,
+            failed: recycle(scratch.failed),
+            num_cancelled: 0,
+            num_no_show: 0,
+            num_events: 0,
+            total_lateness: 0.0,
+            max_lateness: 0.0,
+            total_wait: 0.0,
+            total_assigned_wait: 0.0,
+            assigned_at,
+            total_overtime: 0.0,
+            total_revenue: 0.0,
+            non_finite: Cell::new(0),
+            exact_decisions: 0,
+            exact_agreed: 0,
+            sequencing_skipped: 0,
+            sequencing_loss: 0.0,
+            segments: Vec::new(),
+            routing_evaluations: vec![0],
+            sequencing_evaluations: vec![0],
+            rule_seconds: 0.0,
+            run_seconds: 0.0,
+            plans: vec![Vec::new(); problem.num_vehicles()],
+            rolling: (problem.rolling_window > 0.0).then(|| Rolling::new(problem.rolling_window)),
+            trace: None,
         }
     }
 
-    pub fn simulate_until(&mut self, time_slot: f32, time_max: f32) -> (f32, usize) {
-        let mut batched_requests = HashMap::<i32, Vec<&'a Request>>::new();
-        for request in self.problem.requests.iter() {
-            let timeslot_idx = (request.time / time_slot).ceil() as i32;
-            batched_requests
-                .entry(timeslot_idx)
-                .or_default()
-                .push(request);
+    pub fn into_scratch(self) -> SimulationScratch {
+        SimulationScratch {
+            vehicles: self
+                .vehicles
+                .into_iter()
+                .map(VehicleState::into_scratch)
+                .collect(),
+            events: recycle(self.events.into_vec()),
+            cancelled: self.cancelled,
+            pool: recycle(self.pool),
+            stock: self.stock,
+            in_transit: self.in_transit,
+            line_haul: self.line_haul,
+            docks: self.docks,
+            failed: recycle(self.failed),
+            assigned_at: self.assigned_at,
         }
+    }
 
-        for (idx, requests) in batched_requests {
-            self.events
-                .push(Reverse(Event::Requests(requests, idx as f32 * time_slot)));
+    pub fn simulate_until(&mut self, time_slot: Float, time_max: Float) -> SimulationResult {
+        self.start(time_slot);
+        self.finish(time_max)
+    }
+
+    // the events known before the first one is run
+    fn start(&mut self, time_slot: Float) {
+        self.time_slot = time_slot;
+        for (time, requests) in self.epochs(time_slot) {
+            self.events.push(Reverse(Event::Requests(requests, time)));
+        }
+
+        if self.problem.reassign_interval.is_finite() {
+            let time = self.problem.reassign_interval;
+            self.events.push(Reverse(Event::Reassign(time)));
+        }
+
+        if self.problem.shift_end.is_finite() {
+            for vehicle in 0..self.vehicles.len() {
+                let time = self.problem.shift_end;
+                self.events.push(Reverse(Event::ShiftEnd { vehicle, time }));
+            }
+        }
+
+        if self.problem.cancel_rate > 0.0 {
+            for request in self.problem.requests.iter() {
+                if self.rng.gen_bool(widen(self.problem.cancel_rate)) {
+                    // a request released after its window closes goes at once
+                    let close = request.close.max(request.time);
+                    let time = self.rng.gen_range(request.time..=close);
+                    self.events.push(Reverse(Event::Cancel { request, time }));
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, time_max: Float) -> SimulationResult {
+        let start = self.problem.profile.then(Instant::now);
+        self.run_events(time_max);
+        self.run_seconds += elapsed(start);
+
+        for (request, _) in std::mem::take(&mut self.pool) {
+            self.fail(request);
+        }
+        self.sample_rolling();
+        for vehicle in 0..self.vehicles.len() {
+            let home = self.vehicles[vehicle].home;
+            // open routes end where the last service finished, drones land on
+            // their carrier which brings them home
+            let end = if self.vehicles[vehicle].is_drone() {
+                if self.vehicles[vehicle].at_dock() {
+                    self.vehicles[vehicle].busy_until
+                } else {
+                    self.recover(vehicle)
+                }
+            } else if self.problem.open_routes {
+                self.vehicles[vehicle].busy_until
+            } else {
+                self.route_vehicle_to(vehicle, home)
+            };
+            let overtime = (end - home.close).max(0.0);
+            self.total_overtime += overtime;
+            log!(
+                ROUTE,
+                "route_log",
+                vehicle = vehicle,
+                route = self.vehicles[vehicle].route,
+                dropped = self.vehicles[vehicle].dropped,
+                breaks = self.vehicles[vehicle].breaks,
+                trips = self.vehicles[vehicle].trip_loads,
+                overtime = overtime,
+                distance = self.vehicles[vehicle].traveled,
+                legs = self.vehicles[vehicle].legs
+            );
+        }
+
+        self.result()
+    }
+
+    // the batches of requests revealed together and when
+    fn epochs(&self, time_slot: Float) -> Vec<(Float, Vec<&'a Request>)> {
+        let slot_end = |request: &Request| (request.time / time_slot).ceil() * time_slot;
+        let mut requests = self.problem.requests.iter().collect::<Vec<_>>();
+        requests.sort_by_key(|request| OrderedFloat(request.time));
+        let mut epochs = Vec::new();
+        match self.problem.epochs {
+            EpochPolicy::Slot => {
+                let mut batched_requests = HashMap::<i32, Vec<&'a Request>>::new();
+                for request in self.problem.requests.iter() {
+                    let timeslot_idx = (request.time / time_slot).ceil() as i32;
+                    batched_requests
+                        .entry(timeslot_idx)
+                        .or_default()
+                        .push(request);
+                }
+                for (idx, requests) in batched_requests {
+                    epochs.push((idx as Float * time_slot, requests));
+                }
+            }
+            EpochPolicy::Immediate => {
+                for request in requests {
+                    epochs.push((request.time, vec![request]));
+                }
+            }
+            EpochPolicy::Count(k) => {
+                for batch in requests.chunks(k) {
+                    epochs.push((batch[batch.len() - 1].time, batch.to_vec()));
+                }
+            }
+            EpochPolicy::Hybrid(k) => {
+                let mut batch = Vec::<&'a Request>::new();
+                for request in requests {
+                    if let Some(first) = batch.first() {
+                        if slot_end(request) > slot_end(first) {
+                            epochs.push((slot_end(first), std::mem::take(&mut batch)));
+                        }
+                    }
+                    batch.push(request);
+                    if batch.len() == k {
+                        epochs.push((request.time, std::mem::take(&mut batch)));
+                    }
+                }
+                if let Some(first) = batch.first() {
+                    epochs.push((slot_end(first), batch));
+                }
+            }
         }
+        epochs
+    }
 
-        let mut total_distance = 0f32;
-        let mut total_failed = 0usize;
-        while let Some(Reverse(event)) = self.events.pop() {
+    fn run_events(&mut self, time_max: Float) {
+        loop {
+            // traced runs resume before a batch of requests is revealed
+            if let Some(Reverse(Event::Requests(_, time))) = self.events.peek() {
+                let time = *time;
+                if let Some(mut trace) = self.trace.take() {
+                    if trace.checkpoint_due(time) {
+                        trace.checkpoint(self.clone(), time);
+                    }
+                    self.trace = Some(trace);
+                }
+            }
+            let Some(Reverse(event)) = self.events.pop() else {
+                break;
+            };
             if event.time() > time_max {
                 self.events.push(Reverse(event));
                 break;
             }
 
             self.time = event.time();
+            self.num_events += 1;
             log!(SIM, "sim_time", time = self.time);
             match event {
                 Event::Requests(requests, _) => {
-                    for request in requests {
-                        self.handle_request(request, &mut total_failed);
+                    self.sample_rolling();
+                    for (request, ready_time) in std::mem::take(&mut self.pool) {
+                        self.offer_request(request, ready_time);
                     }
+                    self.handle_requests(requests);
                 }
                 Event::VehicleFinish {
                     vehicle, request, ..
                 } => self.handle_vehicle_finish(vehicle, request),
+                Event::Cancel { request, .. } => self.handle_cancel(request),
+                Event::BreakEnd { vehicle, .. } => {
+                    log!(SIM, "vehicle_break_end", vehicle = vehicle);
+                }
+                Event::Reassign(_) => self.handle_reassign(),
+                Event::ShiftEnd { vehicle, .. } => self.handle_shift_end(vehicle),
+                Event::Transfer {
+                    satellite, amount, ..
+                } => self.handle_transfer(satellite, amount),
+                Event::Recover { vehicle, .. } => self.handle_recover(vehicle),
             }
-            for vehicle in 0..self.problem.num_trucks {
-                self.update_vehicle_queue(vehicle, &mut total_failed, &mut total_distance);
+            for vehicle in 0..self.vehicles.len() {
+                self.update_vehicle_queue(vehicle);
             }
         }
+    }
 
-        for vehicle in 0..self.problem.num_trucks {
-            self.route_vehicle_to(vehicle, &self.problem.depot, &mut total_distance);
-        }
-        for vehicle in 0..self.problem.num_trucks {
-            log!(
-                ROUTE,
-                "route_log",
-                vehicle = vehicle,
-                route = self.vehicles[vehicle].route,
-                dropped = self.vehicles[vehicle].dropped
-            );
+    // with regret insertion, the request that loses the most by not getting
+    // its best vehicle goes first, as long as the batch lasts
+    fn handle_requests(&mut self, mut requests: Vec<&'a Request>) {
+        let Some(Insertion::Regret(k)) = self.problem.insertion else {
+            for request in requests {
+                self.handle_request(request);
+            }
+            return;
+        };
+        while !requests.is_empty() {
+            let regrets = requests
+                .iter()
+                .map(|request| {
+                    let costs = self.insertions(request);
+                    insertion::regret(&costs.iter().map(|c| c.2).collect::<Vec<_>>(), k)
+                })
+                .collect::<Vec<_>>();
+            let next = (0..requests.len())
+                .max_by_key(|i| (OrderedFloat(regrets[*i]), Reverse(*i)))
+                .expect("the batch is not empty");
+            let request = requests.remove(next);
+            self.handle_request(request);
         }
+    }
 
-        (total_distance, total_failed)
+    // the queue of a vehicle in its planned order, with requests queued
+    // around the plan (reassigned or taken back) after it
+    fn planned(&self, vehicle: usize) -> Vec<&'a Request> {
+        let queue = &self.vehicles[vehicle].queue;
+        let queued = |request: &Request| queue.iter().any(|(r, _)| r.idx == request.idx);
+        let plan = &self.plans[vehicle];
+        let planned = |request: &Request| plan.iter().any(|r| r.idx == request.idx);
+        plan.iter()
+            .copied()
+            .filter(|request| queued(request))
+            .chain(queue.iter().map(|(r, _)| *r).filter(|r| !planned(r)))
+            .collect()
     }
 
-    fn handle_request(&mut self, request: &'a Request, total_failed: &mut usize) {
-        if let Some(vehicle) =
-            self.routing_rule
-                .route_request(self.problem, self.time, &self.vehicles, request)
-        {
-            self.vehicles[vehicle].enqueue(request, self.time);
+    // the vehicles that can take `request` with its cheapest position in
+    // their plan and the distance it adds, cheapest first
+    fn insertions(&self, request: &'a Request) -> Vec<(usize, usize, Float)> {
+        let mut insertions = (0..self.vehicles.len())
+            .filter_map(|vehicle| {
+                let state = &self.vehicles[vehicle];
+                if !state.can_route(self.problem, request, self.time) {
+                    return None;
+                }
+                let (from, speed) = (state.cur_request, state.speed);
+                let time = self.time.max(state.busy_until);
+                let plan = self.planned(vehicle);
+                let (position, cost) =
+                    insertion::cheapest_position(self.problem, from, time, speed, &plan, request)?;
+                Some((vehicle, position, cost))
+            })
+            .collect::<Vec<_>>();
+        // stable, ties keep the lowest vehicle index first
+        insertions.sort_by_key(|(_, _, cost)| OrderedFloat(*cost));
+        insertions
+    }
+
+    fn handle_request(&mut self, request: &'a Request) {
+        self.offer_request(request, self.time);
+    }
+
+    fn offer_request(&mut self, request: &'a Request, ready_time: Float) {
+        // cancelled before its release
+        if self.cancelled.contains(&request.idx) {
+            self.num_cancelled += 1;
+            log!(SIM, "request_cancelled", request = request.idx);
+            return;
+        }
+
+        let dispatch = if self.problem.insertion.is_some() {
+            // a traced run records what the routing rule would have done
+            let ranked = match self.trace.is_some() {
+                true => self.rank_vehicles(request, true),
+                false => Vec::new(),
+            };
+            match self.insertions(request).first() {
+                Some(&(vehicle, position, _)) => {
+                    if let (Some(trace), false) = (self.trace.as_mut(), ranked.is_empty()) {
+                        trace.record.take(vehicle);
+                    }
+                    let mut plan = self.planned(vehicle);
+                    plan.insert(position, request);
+                    self.plans[vehicle] = plan;
+                    Dispatch::Vehicle(vehicle)
+                }
+                None => Dispatch::Reject,
+            }
+        } else if self.problem.lookahead_k > 1 && !self.in_rollout {
+            let ranked = self.rank_vehicles(request, false);
+            match self.lookahead(request, ready_time, ranked) {
+                Some(vehicle) => Dispatch::Vehicle(vehicle),
+                None => Dispatch::Reject,
+            }
+        } else {
+            Dispatch::ranked(self.problem, &self.rank_vehicles(request, true))
+        };
+        let dispatch = match dispatch {
+            Dispatch::Defer(vehicle) => {
+                let next_epoch = self.next_slot();
+                let cost = self.vehicles[vehicle].raw_time_cost(self.problem, request, next_epoch);
+                if next_epoch + cost <= self.problem.latest_start(request) {
+                    log!(SIM, "request_deferred", request = request.idx);
+                    self.park(request, ready_time);
+                    return;
+                }
+                Dispatch::Vehicle(vehicle)
+            }
+            dispatch => dispatch,
+        };
+        if let Dispatch::Vehicle(vehicle) = dispatch {
+            self.vehicles[vehicle].enqueue(request, ready_time);
+            self.assigned_at.entry(request.idx).or_insert(self.time);
             log!(
                 SIM,
                 "vehicle_assigned",
                 vehicle = vehicle,
                 request = request.idx
             );
+        } else if self.problem.pool_unassigned && self.time < self.problem.latest_start(request) {
+            log!(SIM, "request_pooled", request = request.idx);
+            self.park(request, ready_time);
         } else {
             // self.vehicles[vehicle]
             //     .dropped
             //     .insert(start_time as _, request.idx);
-            *total_failed += 1;
+            self.fail(request);
             log!(SIM, "vehicle_skipped", request = request.idx);
         }
     }
 
+    fn fail(&mut self, request: &'a Request) {
+        self.total_failed += 1;
+        self.failed.push(request);
+        if let Some(rolling) = &mut self.rolling {
+            rolling.failed(self.time);
+        }
+    }
+
+    fn sample_rolling(&mut self) {
+        if let Some(rolling) = &mut self.rolling {
+            let sample = rolling.sample(self.time);
+            log!(
+                SIM,
+                "rolling",
+                time = sample.time,
+                outcomes = sample.outcomes,
+                failure_rate = sample.failure_rate,
+                mean_wait = sample.mean_wait
+            );
+        }
+    }
+
+    fn set_segments(&mut self, segments: &[Segment<'a>]) {
+        self.segments = segments.to_vec();
+        self.routing_evaluations = vec![0; segments.len() + 1];
+        self.sequencing_evaluations = vec![0; segments.len() + 1];
+    }
+
+    // the period of the day whose rules decide now
+    fn period(&self) -> usize {
+        let n = self.segments.len() + 1;
+        ((self.time / self.problem.depot().close * n as Float).max(0.0) as usize).min(n - 1)
+    }
+
+    // the routing rule over the feasible vehicles, `record`ed in a traced run
+    fn rank_vehicles(&mut self, request: &Request, record: bool) -> Vec<(usize, Float)> {
+        let start = self.problem.profile.then(Instant::now);
+        let period = self.period();
+        let rule: &dyn RoutingRule = match (&self.problem.rules.routing, period) {
+            (Some(rule), _) => &**rule,
+            (None, 0) => &self.routing_rule,
+            (None, period) => &self.segments[period - 1].0,
+        };
+        let ranked = rule.rank_vehicles(
+            self.problem,
+            self.time,
+            &self.vehicles,
+            request,
+            &self.non_finite,
+            self.trace
+                .as_mut()
+                .filter(|_| record)
+                .map(|trace| &mut trace.record),
+        );
+        self.rule_seconds += elapsed(start);
+        self.routing_evaluations[period] += ranked.len();
+        ranked
+    }
+
+    // the sequencing rule over the queue of `vehicle`, evaluated once per
+    // request into `cache`
+    fn sequence_request(
+        &mut self,
+        vehicle: usize,
+        cache: &mut HashMap<usize, (OrderedFloat<Float>, usize)>,
+    ) -> Option<usize> {
+        let (start, cached) = (self.problem.profile.then(Instant::now), cache.len());
+        let period = self.period();
+        let rule: &dyn SequencingRule = match (&self.problem.rules.sequencing, period) {
+            (Some(rule), _) => &**rule,
+            (None, 0) => &self.sequencing_rule,
+            (None, period) => &self.segments[period - 1].1,
+        };
+        let ranked = rule.rank_queue(
+            self.problem,
+            self.time,
+            vehicle,
+            &self.vehicles[vehicle],
+            cache,
+            &self.non_finite,
+            self.trace.as_mut().map(|trace| &mut trace.record),
+        );
+        self.rule_seconds += elapsed(start);
+        self.sequencing_evaluations[period] += cache.len() - cached;
+        ranked.first().map(|&(index, _)| index)
+    }
+
+    fn profile(&self) -> RuleProfile {
+        let mut profile = RuleProfile {
+            rule_seconds: self.rule_seconds,
+            event_seconds: (self.run_seconds - self.rule_seconds).max(0.0),
+            ..Default::default()
+        };
+        let first = (self.routing_rule.program(), self.sequencing_rule.program());
+        let rules =
+            std::iter::once(first).chain((self.segments.iter()).map(|(r, s)| (Some(r), Some(s))));
+        for (period, (routing, sequencing)) in rules.enumerate() {
+            let mut part = RuleProfile {
+                routing_evaluations: self.routing_evaluations[period],
+                sequencing_evaluations: self.sequencing_evaluations[period],
+                ..Default::default()
+            };
+            part.count_nodes(routing, sequencing);
+            profile.add(&part);
+        }
+        profile
+    }
+
+    pub fn result(&self) -> SimulationResult {
+        let day = self.problem.depot().close;
+        let vehicles = self
+            .vehicles
+            .iter()
+            .map(|state| VehicleResult {
+                distance: state.traveled,
+                served: state.served,
+                depot_returns: state.depot_returns,
+                lateness: state.lateness,
+                utilization: state.busy_time / day,
+                busy_time: state.busy_time,
+                idle_time: (day - state.busy_time).max(0.0),
+                trips: state.trip_loads.len(),
+                load_factor: match state.trip_loads.len() {
+                    0 => 0.0,
+                    n => state.trip_loads.iter().sum::<Float>() / (n as Float * state.capacity),
+                },
+                dropped: state.num_dropped,
+            })
+            .collect::<Vec<_>>();
+        let (peak_failure_rate, peak_wait) =
+            self.rolling.as_ref().map_or((0.0, 0.0), Rolling::peak);
+        SimulationResult {
+            distance: self.total_distance,
+            failed: self.total_failed,
+            lateness: self.total_lateness,
+            max_lateness: self.max_lateness,
+            wait: self.total_wait,
+            assigned_wait: self.total_assigned_wait,
+            utilization: vehicles.iter().map(|v| v.utilization).sum::<Float>()
+                / vehicles.len().max(1) as Float,
+            depot_returns: vehicles.iter().map(|v| v.depot_returns).sum(),
+            overtime: self.total_overtime,
+            revenue: self.total_revenue,
+            cancelled: self.num_cancelled,
+            no_show: self.num_no_show,
+            rolled_over: 0,
+            non_finite: self.non_finite.get(),
+            events: self.num_events,
+            peak_failure_rate,
+            peak_wait,
+            exact_decisions: self.exact_decisions,
+            exact_agreed: self.exact_agreed,
+            sequencing_skipped: self.sequencing_skipped,
+            sequencing_loss: self.sequencing_loss,
+            profile: self.profile(),
+            vehicles,
+        }
+    }
+
+    fn next_slot(&self) -> Float {
+        ((self.time / self.time_slot).floor() + 1.0) * self.time_slot
+    }
+
+    // an empty batch at the next slot re-offers the pool
+    fn park(&mut self, request: &'a Request, ready_time: Float) {
+        self.pool.push((request, ready_time));
+        if self.pool_offer_at <= self.time {
+            self.pool_offer_at = self.next_slot();
+            self.events
+                .push(Reverse(Event::Requests(Vec::new(), self.pool_offer_at)));
+        }
+    }
+
+    // tries the assignment to each of the best `lookahead_k` vehicles on a
+    // copy of the simulation run for at most `lookahead_horizon` minutes; the
+    // copy only knows the requests revealed so far, draws its own noise and
+    // logs nothing
+    fn lookahead(
+        &self,
+        request: &'a Request,
+        ready_time: Float,
+        ranked: Vec<(usize, Float)>,
+    ) -> Option<usize> {
+        ranked
+            .into_iter()
+            .map(|(vehicle, _)| vehicle)
+            .take(self.problem.lookahead_k)
+            .min_by_key(|vehicle| {
+                let mut sim = self.clone();
+                sim.in_rollout = true;
+                sim.rng = SmallRng::seed_from_u64(
+                    self.scenario.seed ^ ((request.idx as u64) << 16) ^ *vehicle as u64,
+                );
+                sim.events.retain(|Reverse(event)| {
+                    !matches!(event, Event::Requests(..) | Event::Cancel { .. })
+                });
+                sim.vehicles[*vehicle].enqueue(request, ready_time);
+                log::muted(|| {
+                    for vehicle in 0..sim.vehicles.len() {
+                        sim.update_vehicle_queue(vehicle);
+                    }
+                    sim.run_events(self.time + self.problem.lookahead_horizon);
+                });
+                (
+                    sim.total_failed - self.total_failed,
+                    OrderedFloat(sim.total_distance - self.total_distance),
+                )
+            })
+    }
+
     fn handle_vehicle_finish(&mut self, vehicle: usize, request: &'a Request) {
         log!(
             SIM,
@@ -319,65 +1769,445 @@ impl<'a> Simulation<'a> {
             vehicle = vehicle,
             request = request.idx
         );
+        // the goods are only taken from the stock once the vehicle is there
+        if self.problem.is_depot(request) {
+            self.reload(vehicle, request);
+        }
     }
 
-    fn update_vehicle_queue(
-        &mut self,
-        vehicle: usize,
-        total_failed: &mut usize,
-        total_distance: &mut f32,
-    ) {
+    // a request already dispatched to a vehicle is committed and can no longer
+    // be cancelled, it may still be a no-show on arrival
+    fn handle_cancel(&mut self, request: &'a Request) {
+        self.cancelled.insert(request.idx);
+        let pooled = self.pool.len();
+        self.pool.retain(|(r, _)| r.idx != request.idx);
+        let mut removed = self.pool.len() < pooled;
+        for vehicle in self.vehicles.iter_mut() {
+            removed |= vehicle.remove_queued(request);
+        }
+        if removed {
+            self.num_cancelled += 1;
+            log!(SIM, "request_cancelled", request = request.idx);
+        }
+    }
+
+    // queued requests are not committed yet, so they are all taken back and
+    // routed again as if they had just arrived, keeping their ready time
+    fn handle_reassign(&mut self) {
+        log!(SIM, "reassign", time = self.time);
+        let queued = self
+            .vehicles
+            .iter_mut()
+            .flat_map(|vehicle| vehicle.take_queue())
+            .collect::<Vec<_>>();
+        for (request, ready_time) in queued {
+            self.offer_request(request, ready_time);
+        }
+
+        // stop once nothing is left to re-assign
+        let pending = !self.events.is_empty() || self.vehicles.iter().any(|v| !v.queue.is_empty());
+        let next = self.time + self.problem.reassign_interval;
+        if pending && next <= self.problem.depot().close {
+            self.events.push(Reverse(Event::Reassign(next)));
+        }
+    }
+
+    // the queue of a vehicle whose shift is over is offered to the others
+    fn handle_shift_end(&mut self, vehicle: usize) {
+        log!(SIM, "vehicle_shift_end", vehicle = vehicle);
+        let queue = self.vehicles[vehicle].take_queue();
+        for (request, _) in queue {
+            self.handle_request(request);
+        }
+    }
+
+    fn handle_transfer(&mut self, satellite: usize, amount: Float) {
+        self.stock[satellite] += amount;
+        self.in_transit[satellite] -= amount;
+        log!(
+            SIM,
+            "satellite_replenished",
+            satellite = satellite,
+            stock = self.stock[satellite]
+        );
+    }
+
+    // sends idle line-haul trucks to the satellites missing the most stock,
+    // either a full load or whatever keeps a satellite from running dry
+    fn replenish(&mut self) {
+        let depot = self.problem.depot();
+        for truck in 0..self.line_haul.len() {
+            if self.line_haul[truck] > self.time || self.time >= depot.close {
+                continue;
+            }
+            let deficit =
+                |s: usize| self.problem.satellite_capacity - self.stock[s] - self.in_transit[s];
+            let Some(satellite) = (0..self.stock.len())
+                .max_by_key(|s| OrderedFloat(deficit(*s)))
+                .filter(|s| {
+                    deficit(*s) >= self.problem.line_haul_capacity
+                        || (deficit(*s) > 0.0
+                            && self.stock[*s] + self.in_transit[*s]
+                                < self.problem.satellite_vehicle_capacity)
+                })
+            else {
+                return;
+            };
+            let amount = deficit(satellite).min(self.problem.line_haul_capacity);
+            let target = &self.problem.satellites[satellite];
+            let distance = self.problem.distances.distance(depot, target);
+            let travel =
+                self.problem
+                    .distances
+                    .travel_time(depot, target, self.problem.truck_speed);
+            let time = self.time + travel;
+            self.total_distance += 2.0 * distance;
+            self.in_transit[satellite] += amount;
+            self.line_haul[truck] = time + target.service_time + travel;
+            self.events.push(Reverse(Event::Transfer {
+                satellite,
+                amount,
+                time,
+            }));
+            log!(
+                SIM,
+                "line_haul_dispatched",
+                truck = truck,
+                satellite = satellite,
+                amount = amount
+            );
+        }
+    }
+
+    fn reload(&mut self, vehicle: usize, depot: &'a Request) {
+        // a drone away from the depots takes the goods off its carrier
+        if let (false, Some(carrier)) =
+            (self.problem.is_depot(depot), self.vehicles[vehicle].carrier)
+        {
+            let stock = self.vehicles[carrier].total_demand;
+            let loaded = self.vehicles[vehicle].reload(self.problem, stock);
+            self.vehicles[carrier].total_demand -= loaded;
+            return;
+        }
+        match self.problem.satellite(depot) {
+            Some(satellite) => {
+                let loaded = self.vehicles[vehicle].reload(self.problem, self.stock[satellite]);
+                self.stock[satellite] -= loaded;
+                self.replenish();
+            }
+            None => {
+                self.vehicles[vehicle].reload(self.problem, Float::INFINITY);
+            }
+        }
+    }
+
+    // flies a drone back to its carrier's current stop, where it recharges
+    // and reloads on arrival
+    fn recover(&mut self, vehicle: usize) -> Float {
+        let state = &mut self.vehicles[vehicle];
+        let dock = state.dock;
+        let distance = state.distance_to(dock);
+        self.total_distance += distance;
+        state.traveled += distance;
+        let time = self.time
+            + state
+                .distances
+                .travel_time(state.cur_request, dock, state.speed)
+                * self.scenario.travel_noise.sample(&mut self.rng);
+        state.busy_time += time - self.time;
+        state.busy_end = state.busy_end.max(time);
+        state.legs.push(Leg {
+            from: state.cur_request.idx,
+            to: dock.idx,
+            departed: self.time,
+            start: time,
+            end: time,
+            reload: true,
+        });
+        state.cur_request = dock;
+        state.busy_until = time;
+        self.events.push(Reverse(Event::Recover { vehicle, time }));
+        log!(
+            SIM,
+            "drone_recovering",
+            vehicle = vehicle,
+            busy_until = time
+        );
+        time
+    }
+
+    // a carrier that moved on while the drone was flying is followed to its
+    // next stop
+    fn handle_recover(&mut self, vehicle: usize) {
+        let state = &mut self.vehicles[vehicle];
+        if !state.at_dock() {
+            self.recover(vehicle);
+            return;
+        }
+        state.recharged_at = state.traveled;
+        let dock = state.dock;
+        self.reload(vehicle, dock);
+        log!(SIM, "drone_recovered", vehicle = vehicle);
+    }
+
+    fn take_break(&mut self, vehicle: usize) {
+        let state = &mut self.vehicles[vehicle];
+        let time = self.time + self.problem.break_duration;
+        state.breaks.push((self.time, time));
+        state.busy_until = time;
+        state.last_break_end = Some(time);
+        self.events.push(Reverse(Event::BreakEnd { vehicle, time }));
+        log!(SIM, "vehicle_break", vehicle = vehicle, busy_until = time);
+    }
+
+    fn update_vehicle_queue(&mut self, vehicle: usize) {
         if self.time < self.vehicles[vehicle].busy_until {
             return;
         }
 
-        let mut cache = HashMap::<usize, OrderedFloat<f32>>::new();
+        let state = &self.vehicles[vehicle];
+        if state.is_drone() && state.queue.is_empty() && !state.at_dock() {
+            self.recover(vehicle);
+            return;
+        }
+        if !state.queue.is_empty() && state.break_due(self.problem, self.time) {
+            self.take_break(vehicle);
+            return;
+        }
 
-        while let Some(index) = self.sequencing_rule.sequence_request(
-            self.problem,
-            self.time,
-            &self.vehicles[vehicle],
-            &mut cache,
-        ) {
-            let queue = &mut self.vehicles[vehicle].queue;
-            let request = queue[index].0;
-            if request.demand > self.vehicles[vehicle].total_demand {
+        let mut cache = HashMap::new();
+
+        while let Some(index) = self.sequence_request(vehicle, &mut cache) {
+            let index = match (self.problem.insertion, self.problem.sequencing_search) {
+                (Some(_), _) => self.sequence_planned(vehicle).unwrap_or(index),
+                (None, 0) => self.sequence_exactly(vehicle, index),
+                (None, width) => self.sequence_by_search(vehicle, width).unwrap_or(index),
+            };
+            if let Some(trace) = self.trace.as_mut() {
+                trace.record.take(self.vehicles[vehicle].queue[index].0.idx);
+            }
+            let state = &self.vehicles[vehicle];
+            let request = state.queue[index].0;
+            let at = state.cur_request;
+            if !state.can_take(self.problem, request) {
+                self.vehicles[vehicle].drop_queued(index, self.time);
+                self.handle_request(request);
+                continue;
+            }
+            if !state.fits(self.problem, request) && state.at_dock() {
+                // already at the depot, reloading is instant
+                self.reload(vehicle, at);
+                if !self.vehicles[vehicle].fits(self.problem, request) {
+                    // wait at the satellite for the inbound transfer
+                    if let Some(satellite) = self.problem.satellite(at) {
+                        if self.in_transit[satellite] > 0.0 {
+                            return;
+                        }
+                    }
+                    self.vehicles[vehicle].drop_queued(index, self.time);
+                    self.fail(request);
+                    log!(SIM, "vehicle_skipped", request = request.idx);
+                    continue;
+                }
+            }
+            let state = &mut self.vehicles[vehicle];
+            if !state.fits(self.problem, request) {
                 // return to depot
-                self.route_vehicle_to(vehicle, &self.problem.depot, total_distance);
+                if state.is_drone() {
+                    self.recover(vehicle);
+                } else {
+                    let home = state.home;
+                    state.depot_returns += 1;
+                    self.route_vehicle_to(vehicle, home);
+                }
                 return;
             }
 
-            self.vehicles[vehicle].queue.swap_remove(index);
             let start_time =
                 self.time + self.vehicles[vehicle].time_cost(self.problem, request, self.time);
-            if start_time > request.close {
-                self.handle_request(request, total_failed);
+            if start_time > self.problem.latest_start(request)
+                || !self.vehicles[vehicle].is_feasible(self.problem, request, self.time)
+            {
+                self.vehicles[vehicle].drop_queued(index, self.time);
+                self.handle_request(request);
                 continue;
             }
 
-            self.route_vehicle_to(vehicle, request, total_distance);
+            self.vehicles[vehicle].dequeue(index);
+            self.route_vehicle_to(vehicle, request);
         }
     }
 
-    fn route_vehicle_to(&mut self, vehicle: usize, request: &'a Request, total_distance: &mut f32) {
+    // the first request of an exact plan of a short enough queue when it beats
+    // the rule's `chosen`, counting what the rule loses; drones and queues
+    // that need a reload on the way are left to the rule
+    fn sequence_exactly(&mut self, vehicle: usize, chosen: usize) -> usize {
+        let state = &self.vehicles[vehicle];
+        let len = state.queue.len();
+        let (pickups, deliveries) =
+            state
+                .queue
+                .iter()
+                .fold((0.0, 0.0), |(p, d), (r, _)| match r.pickup {
+                    true => (p + r.demand, d),
+                    false => (p, d + r.demand),
+                });
+        if len < 2
+            || len > self.problem.exact_queue
+            || state.is_drone()
+            || pickups > state.free_space(self.problem)
+            || deliveries > state.total_demand
+        {
+            return chosen;
+        }
+        let queue = state.queue.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        let (time, from, speed) = (self.time, state.cur_request, state.speed);
+        let plan = exact::solve(self.problem, from, speed, time, &queue);
+        let Some(&best) = plan.order.first() else {
+            return chosen;
+        };
+        self.exact_decisions += 1;
+        let rule = match best == chosen {
+            true => plan.clone(),
+            false => exact::solve_from(self.problem, from, speed, time, &queue, chosen),
+        };
+        log!(
+            SIM,
+            "exact_sequencing",
+            vehicle = vehicle,
+            queue = len,
+            rule = queue[chosen].idx,
+            exact = queue[best].idx,
+            skipped = rule.skipped - plan.skipped,
+            loss = rule.distance - plan.distance
+        );
+        if rule.key() <= plan.key() {
+            self.exact_agreed += 1;
+            return chosen;
+        }
+        self.sequencing_skipped += rule.skipped - plan.skipped;
+        self.sequencing_loss += rule.distance - plan.distance;
+        best
+    }
+
+    // the queue index of the first request of the vehicle's plan
+    fn sequence_planned(&self, vehicle: usize) -> Option<usize> {
+        let first = *self.planned(vehicle).first()?;
+        let queue = &self.vehicles[vehicle].queue;
+        queue.iter().position(|(r, _)| r.idx == first.idx)
+    }
+
+    // the first request of a searched plan of the whole queue, none when no
+    // request can be reached in time and the rule may as well pick
+    fn sequence_by_search(&self, vehicle: usize, width: usize) -> Option<usize> {
+        let state = &self.vehicles[vehicle];
+        let queue = state.queue.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        let (from, speed) = (state.cur_request, state.speed);
+        let plan = exact::plan(self.problem, from, speed, self.time, &queue, width);
+        plan.order.first().copied()
+    }
+
+    // returns the arrival time, i.e. the start of service
+    fn route_vehicle_to(&mut self, vehicle: usize, request: &'a Request) -> Float {
         let state = &mut self.vehicles[vehicle];
+        if !self.problem.is_depot(request) && state.departed_at.is_none() {
+            state.departed_at = Some(self.time);
+        }
+        if !self.problem.is_depot(request) && !state.is_drone() && state.at_dock() {
+            state.trips += 1;
+            state.trip_loads.push(0.0);
+            log!(
+                SIM,
+                "vehicle_trip_start",
+                vehicle = vehicle,
+                trip = state.trips
+            );
+        }
         let distance = state.distance_to(request);
-        *total_distance += distance;
-        let time = (self.time + distance / self.problem.truck_speed).max(request.open)
-            + request.service_time;
-        if request.idx == 0 {
-            state.total_demand = self.problem.truck_capacity;
+        self.total_distance += distance;
+        state.traveled += distance;
+        // the dispatcher plans with nominal travel times, only the realized
+        // leg is perturbed
+        let travel_time = state
+            .distances
+            .travel_time(state.cur_request, request, state.speed)
+            * self.scenario.travel_noise.sample(&mut self.rng);
+        let start_time = (self.time + travel_time).max(request.open);
+        if !self.problem.is_depot(request) {
+            let lateness = (start_time - request.close).max(0.0);
+            self.total_lateness += lateness;
+            self.max_lateness = self.max_lateness.max(lateness);
+            state.lateness += lateness;
+            self.total_wait += start_time - request.time;
+            let assigned_at = self.assigned_at.get(&request.idx).copied();
+            self.total_assigned_wait += start_time - assigned_at.unwrap_or(self.time);
+            if let Some(rolling) = &mut self.rolling {
+                rolling.served(self.time, start_time - request.time);
+            }
+        }
+        let no_show = !self.problem.is_depot(request)
+            && self.problem.no_show_rate > 0.0
+            && self.rng.gen_bool(widen(self.problem.no_show_rate));
+        let time = if no_show {
+            self.num_no_show += 1;
+            log!(SIM, "request_no_show", request = request.idx);
+            start_time
+        } else if self.problem.is_depot(request) {
+            let reload =
+                self.problem.reload_time * self.scenario.service_noise.sample(&mut self.rng);
+            // first come first served on the earliest free dock
+            match self.docks[request.idx]
+                .iter_mut()
+                .min_by_key(|free| OrderedFloat(**free))
+            {
+                Some(dock) => {
+                    let begin = dock.max(start_time);
+                    if begin > start_time {
+                        log!(
+                            SIM,
+                            "vehicle_dock_wait",
+                            vehicle = vehicle,
+                            wait = begin - start_time
+                        );
+                    }
+                    *dock = begin + reload;
+                    begin + reload
+                }
+                None => start_time + reload,
+            }
         } else {
-            state.total_demand -= request.demand;
+            start_time + request.service_time * self.scenario.service_noise.sample(&mut self.rng)
+        };
+        // legs may be planned before the previous one is over, only count
+        // the time not already covered
+        state.busy_time += (time - self.time.max(state.busy_end)).max(0.0);
+        state.busy_end = state.busy_end.max(time);
+        if !self.problem.is_depot(request) && !no_show {
+            state.served += 1;
+            self.total_revenue += request.revenue;
+            if let Some(load) = state.trip_loads.last_mut() {
+                *load += request.demand;
+            }
+            if request.pickup {
+                state.pickup_load += request.demand;
+            } else {
+                state.total_demand -= request.demand;
+            }
         }
         self.events.push(Reverse(Event::VehicleFinish {
             vehicle,
             request,
             time,
         }));
-        state
-            .route
-            .insert((time - request.service_time) as _, request.idx);
+        state.route.insert(start_time as _, request.idx);
+        state.legs.push(Leg {
+            from: state.cur_request.idx,
+            to: request.idx,
+            departed: self.time,
+            start: start_time,
+            end: time,
+            reload: self.problem.is_depot(request),
+        });
         state.cur_request = request;
         state.busy_until = time;
         log!(
@@ -386,5 +2216,620 @@ impl<'a> Simulation<'a> {
             request = request.idx,
             busy_until = time
         );
+        if self.problem.is_depot(request) {
+            let free = self.docks[request.idx]
+                .iter()
+                .copied()
+                .min_by_key(|free| OrderedFloat(*free))
+                .unwrap_or(0.0);
+            for state in self.vehicles.iter_mut() {
+                if state.home.idx == request.idx {
+                    state.home_dock_free = free;
+                }
+            }
+        }
+        // drones riding the truck move along, the others are recovered at its
+        // new stop
+        for drone in self.vehicles.iter_mut() {
+            if drone.carrier == Some(vehicle) {
+                if drone.at_dock() && drone.busy_until <= self.time {
+                    drone.cur_request = request;
+                }
+                drone.dock = request;
+            }
+        }
+        start_time
+    }
+}
+
+#[test]
+fn combine_days() {
+    let day = |failed, utilization| SimulationResult {
+        distance: 10.0,
+        failed,
+        utilization,
+        vehicles: vec![VehicleResult {
+            served: 2,
+            utilization,
+            trips: 1,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let total = SimulationResult::combine(&[day(0, 0.25), day(3, 0.75)]);
+    assert_eq!(total.distance, 20.0);
+    assert_eq!(total.failed, 3);
+    assert_eq!(total.utilization, 0.5);
+    assert_eq!(total.vehicles[0].served, 4);
+    assert_eq!(total.vehicles[0].trips, 2);
+}
+
+#[test]
+fn scratch_reuse() {
+    let (problem, time_slot) = Problem::test_instance();
+    let mut small = problem.clone();
+    small.num_trucks = 4;
+    let mut scratch = SimulationScratch::default();
+    for (problem, routing) in [
+        (&problem, baseline::nearest_vehicle()),
+        (&small, baseline::work_in_queue()),
+        (&problem, baseline::nearest_vehicle()),
+    ] {
+        let sequencing = baseline::cost();
+        let run = |scratch| {
+            let result = simulate_days_with(
+                scratch,
+                problem,
+                &routing,
+                &sequencing,
+                Scenario::default(),
+                time_slot,
+                |_, _| (),
+            );
+            serde_json::to_string(&result).unwrap()
+        };
+        assert_eq!(run(&mut scratch), run(&mut SimulationScratch::default()));
+    }
+}
+
+#[test]
+fn recycled_buffers() {
+    let v = Vec::<&u32>::with_capacity(64);
+    let pointer = v.as_ptr() as usize;
+    let recycled = recycle::<&u32, &'static u32>(v);
+    assert_eq!(recycled.as_ptr() as usize, pointer);
+    assert!(recycled.capacity() >= 64);
+    // and through a simulation, whose events outlive it in the scratch
+    let (problem, time_slot) = Problem::test_instance();
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let mut scratch = SimulationScratch::default();
+    simulate_days_with(
+        &mut scratch,
+        &problem,
+        &routing,
+        &sequencing,
+        Scenario::default(),
+        time_slot,
+        |_, _| (),
+    );
+    let (events, capacity) = (scratch.events.as_ptr() as usize, scratch.events.capacity());
+    assert!(capacity > 0);
+    simulate_days_with(
+        &mut scratch,
+        &problem,
+        &routing,
+        &sequencing,
+        Scenario::default(),
+        time_slot,
+        |_, _| (),
+    );
+    assert_eq!(scratch.events.as_ptr() as usize, events);
+    assert!(scratch.events.capacity() >= capacity);
+}
+
+#[test]
+fn satellite_reload() {
+    let problem = Problem::load("datasets/100/h100c102.csv", 1.0, 1300.0, 10, 1, 1).unwrap();
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let scratch = SimulationScratch::default();
+    let mut sim = Simulation::with_scratch(
+        &problem,
+        &routing,
+        &sequencing,
+        Scenario::default(),
+        scratch,
+    );
+    let capacity = problem.satellite_vehicle_capacity;
+    assert_eq!(sim.vehicles[0].capacity, capacity);
+    sim.vehicles[0].cur_request = &problem.requests[0];
+    sim.vehicles[0].total_demand = 0.0;
+    // the stock is only handed out once the vehicle is back
+    let arrival = sim.route_vehicle_to(0, &problem.satellites[0]);
+    assert_eq!(sim.stock[0], problem.satellite_capacity);
+    sim.run_events(arrival + problem.reload_time);
+    assert_eq!(sim.stock[0], problem.satellite_capacity - capacity);
+    assert_eq!(sim.vehicles[0].total_demand, capacity);
+}
+
+#[test]
+fn drone_recovery() {
+    let (mut problem, _) = Problem::test_instance();
+    problem.num_drones = 1;
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let scratch = SimulationScratch::default();
+    let mut sim = Simulation::with_scratch(
+        &problem,
+        &routing,
+        &sequencing,
+        Scenario::default(),
+        scratch,
+    );
+    let (carrier, drone) = (0, problem.num_trucks);
+    let stops = &problem.requests;
+    sim.route_vehicle_to(carrier, &stops[0]);
+    sim.vehicles[drone].cur_request = &stops[1];
+    sim.vehicles[drone].total_demand = 0.0;
+    sim.recover(drone);
+    // the carrier moves on while the drone flies back to it
+    sim.route_vehicle_to(carrier, &stops[2]);
+    let load = sim.vehicles[carrier].total_demand;
+    sim.run_events(Float::MAX);
+    let state = &sim.vehicles[drone];
+    let legs = state.legs.iter().map(|leg| (leg.from, leg.to));
+    let stop = |i: usize| stops[i].idx;
+    assert!(legs.eq([(stop(1), stop(0)), (stop(0), stop(2))]));
+    // the goods come off the carrier
+    assert_eq!(state.total_demand, problem.drone_capacity);
+    assert_eq!(
+        sim.vehicles[carrier].total_demand,
+        load - problem.drone_capacity
+    );
+}
+
+#[test]
+fn reload_with_pickups() {
+    let (problem, _) = Problem::test_instance();
+    let capacity = problem.truck_capacity;
+    let mut pickup = problem.requests[1];
+    (pickup.pickup, pickup.demand) = (true, 50.0);
+    let mut state = VehicleState::new(&problem, 0);
+    state.enqueue(&pickup, 0.0);
+    state.total_demand = 0.0;
+    // the goods loaded leave room for the queued pickup
+    assert_eq!(state.reload(&problem, Float::INFINITY), capacity - 50.0);
+    assert!(state.fits(&problem, &pickup));
+    // a larger pickup has to wait for deliveries to free the room
+    let mut larger = pickup;
+    larger.demand = 60.0;
+    assert!(!state.fits(&problem, &larger));
+    state.total_demand -= 10.0;
+    assert!(state.fits(&problem, &larger));
+    // a satellite only hands out its stock
+    state.total_demand = 0.0;
+    assert_eq!(state.reload(&problem, 20.0), 20.0);
+}
+
+#[test]
+fn range_with_reload() {
+    let (mut problem, _) = Problem::test_instance();
+    let (from, to) = (problem.requests[1], problem.requests[2]);
+    let distances = problem.distances.clone();
+    let depot = problem.depot();
+    // just enough for the trip and the way back, not for a detour by the depot
+    problem.max_travel_distance = distances.distance(&from, &to) + distances.distance(&to, depot);
+    let mut state = VehicleState::new(&problem, 0);
+    state.cur_request = &from;
+    assert!(state.within_range(&problem, &to));
+    state.total_demand = 0.0;
+    assert!(!state.within_range(&problem, &to));
+}
+
+#[test]
+fn cancellations() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    problem.cancel_rate = 1.0;
+    problem.pool_unassigned = true;
+    // released after their windows close, cancelled at release
+    for request in problem.requests.iter_mut().step_by(5) {
+        request.time = request.close + 1.0;
+    }
+    let result = simulate_days(
+        &problem,
+        &baseline::nearest_vehicle(),
+        &baseline::cost(),
+        Scenario::default(),
+        time_slot,
+        |_, _| (),
+    );
+    // every request is cancelled unless it was dispatched first
+    let served = result.vehicles.iter().map(|v| v.served).sum::<usize>();
+    assert!(result.cancelled > 0);
+    assert_eq!(
+        result.cancelled + served + result.failed,
+        problem.requests.len()
+    );
+}
+
+#[test]
+fn soft_time_windows() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    problem.num_trucks = 3;
+    let run = |problem: &Problem| {
+        simulate_days(
+            problem,
+            &baseline::nearest_vehicle(),
+            &baseline::cost(),
+            Scenario::default(),
+            time_slot,
+            |_, _| (),
+        )
+    };
+    let hard = run(&problem);
+    assert_eq!(hard.lateness, 0.0);
+    // late service within the grace period saves requests that failed
+    problem.time_windows = problem::TimeWindowMode::Soft {
+        penalty: 1.0,
+        max_lateness: 60.0,
+    };
+    let soft = run(&problem);
+    assert!(soft.lateness > 0.0);
+    assert!(0.0 < soft.max_lateness && soft.max_lateness <= 60.0);
+    assert!(soft.failed < hard.failed);
+    let late = soft.vehicles.iter().map(|v| v.lateness).sum::<Float>();
+    assert!((late - soft.lateness).abs() < 1e-2);
+}
+
+#[test]
+fn driver_breaks() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    problem.break_after = 120.0;
+    problem.break_duration = 30.0;
+    problem.shift_end = 0.75 * problem.depot().close;
+    let mut breaks = 0;
+    let result = simulate_days(
+        &problem,
+        &baseline::nearest_vehicle(),
+        &baseline::cost(),
+        Scenario::default(),
+        time_slot,
+        |_, sim| {
+            for vehicle in &sim.vehicles {
+                // every rest is complete
+                for (start, end) in &vehicle.breaks {
+                    assert!((end - start - problem.break_duration).abs() < 1e-3);
+                }
+                breaks += vehicle.breaks.len();
+                // served in time to be home by the end of the shift
+                let Some(last) = vehicle.legs.iter().rev().find(|leg| !leg.reload) else {
+                    continue;
+                };
+                let stop = (problem.requests.iter())
+                    .find(|r| r.idx == last.to)
+                    .unwrap();
+                let home = vehicle
+                    .distances
+                    .travel_time(stop, vehicle.home, vehicle.speed);
+                assert!(last.end + home <= problem.shift_end + 1e-3);
+            }
+        },
+    );
+    assert!(breaks > 0);
+    let served = result.vehicles.iter().map(|v| v.served).sum::<usize>();
+    assert_eq!(served + result.failed, problem.requests.len());
+    // which keeps vehicles from serving as late as they would
+    let shift_end = problem.shift_end;
+    problem.shift_end = Float::INFINITY;
+    let mut later = false;
+    simulate_days(
+        &problem,
+        &baseline::nearest_vehicle(),
+        &baseline::cost(),
+        Scenario::default(),
+        time_slot,
+        |_, sim| {
+            let legs = sim.vehicles.iter().flat_map(|v| &v.legs);
+            later |= legs
+                .filter(|leg| !leg.reload)
+                .any(|leg| leg.end > shift_end);
+        },
+    );
+    assert!(later);
+}
+
+#[test]
+fn open_routes() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    let last_legs = |problem: &Problem| {
+        let mut last = Vec::new();
+        simulate_days(
+            problem,
+            &baseline::nearest_vehicle(),
+            &baseline::cost(),
+            Scenario::default(),
+            time_slot,
+            |_, sim| {
+                let used = sim.vehicles.iter().filter(|v| v.served > 0);
+                last.extend(used.map(|v| *v.legs.last().unwrap()));
+            },
+        );
+        last
+    };
+    assert!(last_legs(&problem).iter().all(|leg| leg.reload));
+    // the routes end at the last customer
+    problem.open_routes = true;
+    let open = last_legs(&problem);
+    assert!(!open.is_empty() && open.iter().all(|leg| !leg.reload));
+    // which only has to be served in time, however far from home
+    let closed = Problem {
+        open_routes: false,
+        ..problem.clone()
+    };
+    let mut state = VehicleState::new(&problem, 0);
+    let request = &problem.requests[0];
+    let back = state
+        .distances
+        .travel_time(request, state.home, state.speed);
+    let time = problem.depot().close - request.service_time - back / 2.0;
+    state.cur_request = request;
+    assert!(state.can_return_home(&problem, request, time));
+    assert!(!state.can_return_home(&closed, request, time));
+}
+
+#[test]
+fn queue_cap() {
+    let (mut problem, _) = Problem::test_instance();
+    problem.max_queue_len = 2;
+    let mut state = VehicleState::new(&problem, 0);
+    let request = (problem.requests.iter())
+        .find(|r| state.can_route(&problem, r, r.time))
+        .unwrap();
+    assert_eq!(state.queue_slack(&problem), 1.0);
+    state.enqueue(request, request.time);
+    assert_eq!(state.queue_slack(&problem), 0.5);
+    assert!(state.can_route(&problem, request, request.time));
+    // a full vehicle is not offered any more requests
+    state.enqueue(request, request.time);
+    assert_eq!(state.queue_slack(&problem), 0.0);
+    assert!(!state.can_route(&problem, request, request.time));
+    state.dequeue(0);
+    assert!(state.can_route(&problem, request, request.time));
+    // without a cap the slack stays full
+    problem.max_queue_len = usize::MAX;
+    let state = VehicleState::new(&problem, 0);
+    assert_eq!(state.queue_slack(&problem), 1.0);
+}
+
+#[test]
+fn reassign() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    problem.reassign_interval = 60.0;
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let mut sim = Simulation::new(&problem, &routing, &sequencing);
+    let request = (problem.requests.iter())
+        .find(|r| sim.vehicles[0].can_route(&problem, r, 0.0))
+        .unwrap();
+    // queued on a vehicle that is no longer the nearest
+    sim.vehicles[1].enqueue(request, 0.0);
+    sim.vehicles[2].cur_request = request;
+    sim.handle_reassign();
+    assert!(sim.vehicles[1].queue.is_empty());
+    assert_eq!(sim.vehicles[2].queue[0].0.idx, request.idx);
+    // and again while anything is queued
+    assert!(matches!(
+        sim.events.peek(),
+        Some(Reverse(Event::Reassign(time))) if *time == 60.0
+    ));
+    let result = simulate_days(
+        &problem,
+        &routing,
+        &sequencing,
+        Scenario::default(),
+        time_slot,
+        |_, _| (),
+    );
+    let served = result.vehicles.iter().map(|v| v.served).sum::<usize>();
+    assert_eq!(served + result.failed, problem.requests.len());
+}
+
+#[test]
+fn pool_unassigned() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    problem.max_queue_len = 1;
+    problem.pool_unassigned = true;
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let mut sim = Simulation::new(&problem, &routing, &sequencing);
+    sim.time_slot = time_slot;
+    let (first, request) = (&problem.requests[0], &problem.requests[1]);
+    assert!(sim.vehicles[0].can_route(&problem, request, 0.0));
+    for vehicle in sim.vehicles.iter_mut() {
+        vehicle.enqueue(first, 0.0);
+    }
+    // no vehicle has room, so the request waits for the next slot
+    sim.offer_request(request, 0.0);
+    assert_eq!(sim.total_failed, 0);
+    assert_eq!(sim.pool.len(), 1);
+    for vehicle in sim.vehicles.iter_mut() {
+        vehicle.take_queue();
+    }
+    sim.run_events(time_slot);
+    assert!(sim.pool.is_empty());
+    assert!(sim.assigned_at.contains_key(&request.idx));
+    assert_eq!(sim.total_failed, 0);
+    // without the pool it fails at once
+    problem.pool_unassigned = false;
+    let mut sim = Simulation::new(&problem, &routing, &sequencing);
+    for vehicle in sim.vehicles.iter_mut() {
+        vehicle.enqueue(first, 0.0);
+    }
+    sim.offer_request(request, 0.0);
+    assert!(sim.pool.is_empty());
+    assert_eq!(sim.total_failed, 1);
+}
+
+#[test]
+fn epoch_policies() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    let n = problem.requests.len();
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    // the release times of each batch
+    let mut epochs = |policy| {
+        problem.epochs = policy;
+        let sim = Simulation::new(&problem, &routing, &sequencing);
+        let epochs = (sim.epochs(time_slot).into_iter())
+            .map(|(time, batch)| (time, batch.iter().map(|r| r.time).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        let batched = epochs.iter().map(|(_, batch)| batch.len()).sum::<usize>();
+        assert_eq!(batched, n);
+        // nothing is revealed before its release
+        for (time, batch) in &epochs {
+            assert!(batch.iter().all(|release| release <= time));
+        }
+        epochs
+    };
+    let slot = |time: Float| (time / time_slot).ceil();
+    for (time, batch) in epochs(EpochPolicy::Slot) {
+        assert!(batch
+            .iter()
+            .all(|release| slot(*release) * time_slot == time));
+    }
+    let immediate = epochs(EpochPolicy::Immediate);
+    assert!(immediate.iter().all(|(time, batch)| batch == &[*time]));
+    let count = epochs(EpochPolicy::Count(7));
+    assert_eq!(count.len(), n.div_ceil(7));
+    assert!(count.iter().all(|(_, batch)| batch.len() <= 7));
+    // a batch of the hybrid is cut at the count or at the end of its slot
+    let hybrid = epochs(EpochPolicy::Hybrid(7));
+    assert!(hybrid.len() > count.len());
+    for (_, batch) in hybrid {
+        assert!(batch.len() <= 7);
+        assert!(batch.iter().all(|release| slot(*release) == slot(batch[0])));
     }
 }
+
+#[test]
+fn dock_capacity() {
+    let (mut problem, _) = Problem::test_instance();
+    problem.reload_time = 10.0;
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let mut reloads = |num_docks| {
+        problem.num_docks = num_docks;
+        let mut sim = Simulation::new(&problem, &routing, &sequencing);
+        let depot = problem.depot();
+        for vehicle in 0..2 {
+            sim.vehicles[vehicle].cur_request = &problem.requests[0];
+            sim.route_vehicle_to(vehicle, depot);
+        }
+        let end = |vehicle: usize| sim.vehicles[vehicle].legs.last().unwrap().end;
+        (end(0), end(1))
+    };
+    // both reload at once
+    let (first, second) = reloads(usize::MAX);
+    assert_eq!(first, second);
+    // the second truck in waits for the only dock
+    let (first, second) = reloads(1);
+    assert_eq!(second, first + 10.0);
+}
+
+#[test]
+fn deferral() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    problem.defer = true;
+    let committing = Problem {
+        defer: false,
+        ..problem.clone()
+    };
+    // minus the time to reach the request, which defers whenever it can wait
+    let routing = "neg(TERM3)".parse::<RoutingProgram>().unwrap();
+    let sequencing = baseline::cost();
+    let simulation = |problem| {
+        let mut sim = Simulation::new(problem, &routing, &sequencing);
+        sim.time_slot = time_slot;
+        sim
+    };
+    let mut sim = simulation(&problem);
+    let request = (problem.requests.iter())
+        .find(|r| {
+            r.time + 2.0 * time_slot < r.close && sim.vehicles[0].can_route(&problem, r, r.time)
+        })
+        .unwrap();
+    sim.time = request.time;
+    sim.offer_request(request, request.time);
+    assert_eq!(sim.pool.len(), 1);
+    assert!(!sim.assigned_at.contains_key(&request.idx));
+    // parked until waiting any longer would make it late
+    sim.run_events(Float::MAX);
+    let assigned = sim.assigned_at[&request.idx];
+    assert!(assigned >= request.time + time_slot);
+    assert_eq!(sim.total_failed, 0);
+    let served = sim.vehicles.iter().flat_map(|v| &v.legs);
+    assert!(served
+        .clone()
+        .any(|leg| leg.to == request.idx && leg.end <= request.close + request.service_time));
+    // committed at once when it cannot wait for the next slot
+    let mut sim = simulation(&problem);
+    let cost = sim.vehicles[0].raw_time_cost(&problem, request, request.time);
+    sim.time = problem.latest_start(request) - cost - 1.0;
+    assert!(sim.next_slot() + cost > problem.latest_start(request));
+    sim.offer_request(request, request.time);
+    assert!(sim.pool.is_empty());
+    assert!(sim.assigned_at.contains_key(&request.idx));
+    // and always without `defer`
+    let mut sim = simulation(&committing);
+    sim.time = request.time;
+    sim.offer_request(request, request.time);
+    assert!(sim.pool.is_empty());
+    assert!(sim.assigned_at.contains_key(&request.idx));
+}
+
+#[test]
+fn rule_profile() {
+    let (mut problem, time_slot) = Problem::test_instance();
+    problem.profile = true;
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let result = simulate_days(
+        &problem,
+        &routing,
+        &sequencing,
+        Scenario::default(),
+        time_slot,
+        |_, _| (),
+    );
+    let profile = &result.profile;
+    // every served request was ranked on at least one vehicle
+    assert!(profile.routing_evaluations >= problem.requests.len() - result.failed);
+    assert!(profile.sequencing_evaluations > 0);
+    let (terminals, internals) = sequencing.node_counts();
+    assert_eq!(
+        profile.sequencing_terminals.iter().sum::<usize>(),
+        terminals.iter().sum::<usize>() * profile.sequencing_evaluations
+    );
+    assert_eq!(profile.sequencing_internals.len(), internals.len());
+    assert!(profile.rule_seconds > 0.0 && profile.event_seconds > 0.0);
+    let total = SimulationResult::combine(&[result.clone(), result.clone()]).profile;
+    assert_eq!(total.routing_evaluations, 2 * profile.routing_evaluations);
+}
+
+#[test]
+fn piecewise_policy() {
+    let (problem, time_slot) = Problem::test_instance();
+    let (routing, sequencing) = (baseline::nearest_vehicle(), baseline::cost());
+    let run = |segments: &[Segment]| {
+        let result = simulate_segments(
+            &mut SimulationScratch::default(),
+            &problem,
+            segments,
+            Scenario::default(),
+            time_slot,
+            |_, _| (),
+        );
+        serde_json::to_string(&result).unwrap()
+    };
+    let single = (routing.clone(), sequencing.clone());
+    let other = (baseline::work_in_queue(), baseline::earliest_due_date());
+    // the same rules in every period are a single policy
+    assert_eq!(
+        run(&[single.clone(), single.clone(), single.clone()]),
+        run(std::slice::from_ref(&single))
+    );
+    assert_ne!(run(&[single.clone(), other]), run(&[single]));
+}
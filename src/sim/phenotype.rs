@@ -0,0 +1,127 @@
+use std::cell::Cell;
+
+use ordered_float::OrderedFloat;
+
+use crate::gp::program::{Program, ProgramContext};
+
+use super::{
+    baseline,
+    ctx::{common_internal, RoutingContext, RoutingProgram, SequencingContext, SequencingProgram},
+    problem::Problem,
+    protect,
+    scenario::Scenario,
+    trace::simulate_recorded,
+    Float,
+};
+
+// one decision of the reference rules: the terminal values and tie order of
+// each candidate, and the rank the reference rule gave it
+struct Situation {
+    routing: bool,
+    candidates: Vec<(Vec<f32>, Float)>,
+    ranks: Vec<usize>,
+}
+
+// the behavior of a pair of rules as the reference rank of the candidate they
+// pick in a fixed sample of decisions, so that rules deciding alike are close
+// whatever they look like
+pub struct Phenotype {
+    situations: Vec<Situation>,
+}
+
+impl Phenotype {
+    // decisions sampled evenly from a run of the nearest vehicle and cost
+    // baselines, which `problem` has to be `Trace::supported` by; only those
+    // with more than one candidate tell rules apart
+    pub fn new(problem: &Problem, time_slot: Float, num_situations: usize) -> Self {
+        let routing = baseline::nearest_vehicle();
+        let sequencing = baseline::cost();
+        let (_, record) = simulate_recorded(
+            problem,
+            &routing,
+            &sequencing,
+            Scenario::default(),
+            time_slot,
+        );
+        let decisions = record
+            .decisions()
+            .iter()
+            .filter(|decision| decision.candidates.len() > 1)
+            .collect::<Vec<_>>();
+        let num_situations = num_situations.min(decisions.len());
+        let situations = (0..num_situations)
+            .map(|i| decisions[i * decisions.len() / num_situations])
+            .map(|decision| {
+                let len = match decision.routing {
+                    true => RoutingContext::num_terminals(),
+                    false => SequencingContext::num_terminals(),
+                };
+                let candidates = decision
+                    .candidates
+                    .iter()
+                    .map(|(offset, tie, _)| (record.terminals(*offset, len).to_vec(), *tie))
+                    .collect::<Vec<_>>();
+                let ranks = match decision.routing {
+                    true => rank(&routing, &candidates),
+                    false => rank(&sequencing, &candidates),
+                };
+                Situation {
+                    routing: decision.routing,
+                    candidates,
+                    ranks,
+                }
+            })
+            .collect();
+        Self { situations }
+    }
+
+    pub fn characterize(
+        &self,
+        routing: &RoutingProgram,
+        sequencing: &SequencingProgram,
+    ) -> Vec<usize> {
+        self.situations
+            .iter()
+            .map(|situation| {
+                let ranks = match situation.routing {
+                    true => rank(routing, &situation.candidates),
+                    false => rank(sequencing, &situation.candidates),
+                };
+                let chosen = ranks.iter().position(|rank| *rank == 0).unwrap_or(0);
+                situation.ranks[chosen]
+            })
+            .collect()
+    }
+}
+
+// the rank of every candidate under `program`, 0 for the one it picks, by
+// the lowest value and then the tie order like the simulation
+fn rank<C: ProgramContext>(program: &Program<C>, candidates: &[(Vec<f32>, Float)]) -> Vec<usize> {
+    let non_finite = Cell::new(0);
+    let mut order = (0..candidates.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| {
+        let (terminals, tie) = &candidates[*i];
+        let value = protect(
+            program.calc_terminals(terminals, common_internal),
+            &non_finite,
+        );
+        (OrderedFloat(value), OrderedFloat(*tie))
+    });
+    let mut ranks = vec![0; candidates.len()];
+    for (rank, i) in order.into_iter().enumerate() {
+        ranks[i] = rank;
+    }
+    ranks
+}
+
+#[test]
+fn phenotypes() {
+    let (problem, time_slot) = Problem::test_instance();
+    let phenotype = Phenotype::new(&problem, time_slot, 30);
+    assert_eq!(phenotype.situations.len(), 30);
+    // the reference rules pick their own best candidate everywhere
+    let reference = phenotype.characterize(&baseline::nearest_vehicle(), &baseline::cost());
+    assert!(reference.iter().all(|rank| *rank == 0));
+    let other = phenotype.characterize(&baseline::work_in_queue(), &baseline::cost());
+    assert!(other.iter().any(|rank| *rank > 0));
+}
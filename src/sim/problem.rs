@@ -1,98 +1,769 @@
 use std::{
     fs::File,
     io::{BufRead, BufReader},
+    sync::Arc,
 };
 
-#[derive(Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+use super::{
+    distance::{CoordinateSystem, DistanceProvider, Euclidean},
+    insertion::Insertion,
+    instance::Instance,
+    registry::Rules,
+    Float,
+};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Request {
     pub idx: usize,
-    pub x: f32,
-    pub y: f32,
-    pub demand: f32,
-    pub open: f32,
-    pub close: f32,
-    pub service_time: f32,
-    pub time: f32,
+    pub x: Float,
+    pub y: Float,
+    pub demand: Float,
+    pub open: Float,
+    pub close: Float,
+    pub service_time: Float,
+    pub time: Float,
+    // backhaul: `demand` is loaded at the customer and brought back to the
+    // depot instead of being delivered
+    pub pickup: bool,
+    // earned when the request is served, from the optional `revenue` column
+    // and equal to the demand otherwise
+    pub revenue: Float,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("line {line}: expected at least {expected} columns, found {found}")]
+    MissingColumns {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("line {line}: column `{column}` is not a number: {value:?}")]
+    NotANumber {
+        line: usize,
+        column: String,
+        value: String,
+    },
+    #[error("line {line}: column `{column}` must not be negative, found {value}")]
+    Negative {
+        line: usize,
+        column: String,
+        value: Float,
+    },
+    #[error("line {line}: the time window opens at {open} after it closes at {close}")]
+    InvertedWindow {
+        line: usize,
+        open: Float,
+        close: Float,
+    },
+    #[error("at least one depot is required")]
+    NoDepot,
+    #[error("the depots and satellites need {expected} rows, found {found}")]
+    TooFewRows { found: usize, expected: usize },
+}
+
+// columns of the instance files, the revenue is optional
+const COLUMNS: [&str; 9] = [
+    "x",
+    "y",
+    "demand",
+    "open",
+    "close",
+    "servicetime",
+    "drone_serve",
+    "time",
+    "revenue",
+];
+
+// rows after the header, line numbers in errors count the header as line 1;
+// blank lines are skipped and the demand may be negative for pickups
+pub fn parse_requests(reader: impl BufRead) -> Result<Vec<Request>, LoadError> {
+    let mut lines = reader.lines();
+    let header = match lines.next() {
+        Some(header) => header?,
+        None => return Ok(Vec::new()),
+    };
+    let names = header.split(',').map(str::trim).collect::<Vec<_>>();
+    let column = |i: usize| {
+        names
+            .get(i)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&COLUMNS[i])
+            .to_string()
+    };
+    let mut requests = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line_no = i + 2;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let args = line
+            .split(',')
+            .enumerate()
+            .map(|(i, tok)| {
+                tok.trim()
+                    .parse::<Float>()
+                    .map_err(|_| LoadError::NotANumber {
+                        line: line_no,
+                        column: column(i.min(COLUMNS.len() - 1)),
+                        value: tok.to_string(),
+                    })
+            })
+            .collect::<Result<Vec<Float>, _>>()?;
+        if args.len() < COLUMNS.len() - 1 {
+            return Err(LoadError::MissingColumns {
+                line: line_no,
+                expected: COLUMNS.len() - 1,
+                found: args.len(),
+            });
+        }
+        for i in [3, 4, 7, 8] {
+            match args.get(i) {
+                Some(value) if *value < 0.0 => {
+                    return Err(LoadError::Negative {
+                        line: line_no,
+                        column: column(i),
+                        value: *value,
+                    })
+                }
+                _ => {}
+            }
+        }
+        if args[3] > args[4] {
+            return Err(LoadError::InvertedWindow {
+                line: line_no,
+                open: args[3],
+                close: args[4],
+            });
+        }
+        requests.push(Request {
+            idx: requests.len(),
+            x: args[0],
+            y: args[1],
+            // a negative demand marks a pickup of that amount
+            demand: args[2].abs(),
+            open: args[3],
+            close: args[4],
+            service_time: 10.0,
+            time: args[7],
+            pickup: args[2] < 0.0,
+            revenue: args.get(8).copied().unwrap_or(args[2].abs()),
+        });
+    }
+    Ok(requests)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeWindowMode {
+    Hard,
+    // service may start up to `max_lateness` after close, each minute late
+    // costs `penalty` distance units
+    Soft { penalty: Float, max_lateness: Float },
+}
+
+impl TimeWindowMode {
+    pub fn parse(str: &str, penalty: Float, max_lateness: Float) -> Option<Self> {
+        match str {
+            "hard" => Some(Self::Hard),
+            "soft" => Some(Self::Soft {
+                penalty,
+                max_lateness,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// when the requests released so far are revealed to the routing rule
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EpochPolicy {
+    // at the end of the time slot they are released in
+    Slot,
+    // one by one at their release time
+    Immediate,
+    // every `k` releases
+    Count(usize),
+    // every `k` releases or at the end of the time slot, whichever is first
+    Hybrid(usize),
+}
+
+impl EpochPolicy {
+    // "slot", "immediate", "count:k" or "hybrid:k"
+    pub fn parse(str: &str) -> Option<Self> {
+        match str.split_once(':') {
+            Some((kind, k)) => {
+                let k = k.parse().ok().filter(|k| *k > 0)?;
+                match kind {
+                    "count" => Some(Self::Count(k)),
+                    "hybrid" => Some(Self::Hybrid(k)),
+                    _ => None,
+                }
+            }
+            None => match str {
+                "slot" => Some(Self::Slot),
+                "immediate" => Some(Self::Immediate),
+                _ => None,
+            },
+        }
+    }
+}
+
+// terminals the rules read as 0, so that a run learns without them; bit `i`
+// stands for terminal `i`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Ablation {
+    pub routing: u32,
+    pub sequencing: u32,
+}
+
+impl Ablation {
+    // comma-separated "routing:index" and "sequencing:index"
+    pub fn parse(str: &str) -> Option<Self> {
+        let mut ablation = Self::default();
+        for terminal in str.split(',').filter(|s| !s.is_empty()) {
+            let (rule, index) = terminal.split_once(':')?;
+            let bit = 1u32.checked_shl(index.parse().ok()?)?;
+            match rule {
+                "routing" => ablation.routing |= bit,
+                "sequencing" => ablation.sequencing |= bit,
+                _ => return None,
+            }
+        }
+        Some(ablation)
+    }
+
+    pub fn apply(mask: u32, terminals: &mut [f32]) {
+        if mask == 0 {
+            return;
+        }
+        for (i, terminal) in terminals.iter_mut().enumerate() {
+            if mask >> i & 1 == 1 {
+                *terminal = 0.0;
+            }
+        }
+    }
+}
+
+// relative normal noise on the terminals the rules read, by terminal index;
+// drawn from `seed` and the decision, so that an evaluation is repeatable
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TerminalNoise {
+    pub routing: [f32; 32],
+    pub sequencing: [f32; 32],
+    pub seed: u64,
+}
+
+impl TerminalNoise {
+    // comma-separated "routing:index:level" and "sequencing:index:level",
+    // with "*" as the index for every terminal of the rule
+    pub fn parse(str: &str) -> Option<Self> {
+        let mut noise = Self::default();
+        for terminal in str.split(',').filter(|s| !s.is_empty()) {
+            let mut parts = terminal.trim().split(':');
+            let (rule, index, level) = (parts.next()?, parts.next()?, parts.next()?);
+            let level = level.parse::<f32>().ok().filter(|l| *l >= 0.0)?;
+            let levels = match rule {
+                "routing" => &mut noise.routing,
+                "sequencing" => &mut noise.sequencing,
+                _ => return None,
+            };
+            match index {
+                "*" => levels.fill(level),
+                index => *levels.get_mut(index.parse::<usize>().ok()?)? = level,
+            }
+        }
+        Some(noise)
+    }
+
+    // `key` identifies the decision and the candidate
+    pub fn apply(levels: &[f32; 32], seed: u64, key: [u64; 3], terminals: &mut [f32]) {
+        if levels.iter().all(|level| *level == 0.0) {
+            return;
+        }
+        let mut state = key.iter().fold(seed, |state, part| splitmix(state ^ part));
+        let mut uniform = || {
+            state = splitmix(state);
+            // in (0, 1]
+            ((state >> 11) + 1) as f64 / (1u64 << 53) as f64
+        };
+        for (terminal, level) in terminals.iter_mut().zip(levels) {
+            if *level > 0.0 {
+                // Box-Muller
+                let (u, v) = (uniform(), uniform());
+                let z = (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+                *terminal *= 1.0 + level * z as f32;
+            }
+        }
+    }
+}
+
+pub(super) fn splitmix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// orders candidates the routing or sequencing rule values the same
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TieBreak {
+    // the lowest vehicle index or queue position
+    Index,
+    // the closest to the vehicle's current stop
+    Distance,
+    // a fixed pseudo-random order per seed
+    Random(u64),
+}
+
+impl TieBreak {
+    // "index", "distance" or "random:seed"
+    pub fn parse(str: &str) -> Option<Self> {
+        match str.split_once(':') {
+            Some(("random", seed)) => seed.parse().ok().map(Self::Random),
+            Some(_) => None,
+            None => match str {
+                "index" => Some(Self::Index),
+                "distance" => Some(Self::Distance),
+                "random" => Some(Self::Random(0)),
+                _ => None,
+            },
+        }
+    }
+
+    // secondary sort key of candidate `index` for `request`, lower first
+    pub fn key(&self, index: usize, distance: Float, request: usize) -> Float {
+        match *self {
+            Self::Index => index as Float,
+            Self::Distance => distance,
+            Self::Random(seed) => {
+                // the top 24 bits, exact in an f32 and so in an f64 too
+                let z = splitmix(seed ^ ((request as u64) << 32) ^ index as u64);
+                (z >> 40) as Float
+            }
+        }
+    }
 }
 
-#[derive(Clone)]
+// (de)serialized as an `Instance`: the nodes, fleet and coordinates, while the
+// simulation settings are left to the environment like for a CSV instance
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "Instance", try_from = "Instance")]
 pub struct Problem {
-    pub depot: Request,
+    // vehicle `v` is homed to `depots[v % depots.len()]`; the first depot's
+    // time window defines the planning horizon
+    pub depots: Vec<Request>,
+    // requests are released over `num_days` days of `day_length`, those
+    // failed on a day roll over to the next one
+    pub num_days: usize,
+    pub day_length: Float,
+    // two-echelon instances: vehicles are homed to the satellites, which hold
+    // up to `satellite_capacity` of stock replenished from the first depot by
+    // `num_line_haul` trucks of `line_haul_capacity`; the vehicles serving the
+    // customers from there are a smaller class of their own
+    pub satellites: Vec<Request>,
+    pub satellite_capacity: Float,
+    pub num_line_haul: usize,
+    pub line_haul_capacity: Float,
+    pub satellite_vehicle_speed: Float,
+    pub satellite_vehicle_capacity: Float,
     pub requests: Vec<Request>,
-    pub truck_speed: f32,
-    pub truck_capacity: f32,
+    pub truck_speed: Float,
+    pub truck_capacity: Float,
     pub num_trucks: usize,
+    // drones fly one sortie at a time from their carrier truck and back,
+    // within `drone_range`
+    pub num_drones: usize,
+    pub drone_speed: Float,
+    pub drone_capacity: Float,
+    pub drone_range: Float,
+    pub coordinates: CoordinateSystem,
+    pub distances: Arc<dyn DistanceProvider>,
+    // probability that a request is cancelled at a uniformly random time
+    // between its release and its close
+    pub cancel_rate: Float,
+    // probability that the customer is absent when the vehicle arrives
+    pub no_show_rate: Float,
+    pub time_windows: TimeWindowMode,
+    // a vehicle must be back home at most this long after its first departure
+    pub max_route_duration: Float,
+    // range of every vehicle over the whole day, depot visits do not refuel
+    pub max_travel_distance: Float,
+    // drivers rest `break_duration` after `break_after` of uninterrupted work
+    // and all vehicles must be home by `shift_end`
+    pub break_after: Float,
+    pub break_duration: Float,
+    pub shift_end: Float,
+    // vehicles finish at their last customer instead of returning home
+    pub open_routes: bool,
+    pub cost_per_distance: Float,
+    pub max_queue_len: usize,
+    // time spent at the depot on every return, and the number of trips a
+    // truck may leave it for
+    pub reload_time: Float,
+    pub max_trips: usize,
+    // simultaneous reloads at every depot and satellite, at least 1
+    pub num_docks: usize,
+    // period of the re-assignment of queued, not yet dispatched requests
+    pub reassign_interval: Float,
+    // park unroutable requests and retry them until their window expires
+    // instead of failing them right away
+    pub pool_unassigned: bool,
+    // rollout routing over the best `lookahead_k` vehicles, off when <= 1
+    pub lookahead_k: usize,
+    pub lookahead_horizon: Float,
+    // let the routing rule defer requests, see `defers`
+    pub defer: bool,
+    pub epochs: EpochPolicy,
+    pub tie_break: TieBreak,
+    pub ablation: Ablation,
+    // with training evaluations only
+    pub terminal_noise: TerminalNoise,
+    // radius of the neighborhood routing terminals
+    pub neighbor_radius: Float,
+    // length of the window of the rolling metrics, off when 0
+    pub rolling_window: Float,
+    // queues of at most this many requests are served in an exactly optimal
+    // order instead of the sequencing rule's, off when below 2
+    pub exact_queue: usize,
+    // every queue is served in the order of an exact plan, or one from a beam
+    // search this wide past `MAX_EXACT_QUEUE`, instead of the sequencing
+    // rule's, off when 0
+    pub sequencing_search: usize,
+    // insertion dispatching in place of both rules
+    pub insertion: Option<Insertion>,
+    // rules simulated in place of the programs
+    pub rules: Rules,
+    // time the rule evaluations and the rest of the run, which makes the
+    // results differ from run to run
+    pub profile: bool,
 }
 
 impl Problem {
+    // the instance the tests run on, with the time slot they dispatch at
+    #[cfg(test)]
+    pub fn test_instance() -> (Self, Float) {
+        let problem = Self::load("datasets/100/h100c102.csv", 1.0, 1300.0, 10, 1, 0).unwrap();
+        let time_slot = problem.depot().close / 10.0;
+        (problem, time_slot)
+    }
+
     pub fn load(
         csv: &str,
-        truck_speed: f32,
-        truck_capacity: f32,
+        truck_speed: Float,
+        truck_capacity: Float,
         num_trucks: usize,
-    ) -> anyhow::Result<Problem> {
-        let file = BufReader::new(File::open(csv)?);
-        let mut requests = Vec::new();
-        let lines = file.lines().skip(1);
-        for (idx, line) in lines.enumerate() {
-            let args = line?
-                .split(',')
-                .map(|tok| tok.parse::<f32>())
-                .collect::<Result<Vec<f32>, _>>()?;
-            let req = Request {
-                idx,
-                x: args[0],
-                y: args[1],
-                demand: args[2],
-                open: args[3],
-                close: args[4],
-                service_time: 10.0,
-                time: args[7],
-            };
-            requests.push(req);
+        num_depots: usize,
+        num_satellites: usize,
+    ) -> Result<Problem, LoadError> {
+        let mut requests = parse_requests(BufReader::new(File::open(csv)?))?;
+        if num_depots == 0 {
+            return Err(LoadError::NoDepot);
         }
-        let depot = requests.remove(0);
-        Ok(Self {
-            depot,
+        if requests.len() < num_depots + num_satellites {
+            return Err(LoadError::TooFewRows {
+                found: requests.len(),
+                expected: num_depots + num_satellites,
+            });
+        }
+        let depots = requests.drain(0..num_depots).collect();
+        let satellites = requests.drain(0..num_satellites).collect();
+        Ok(Self::new(
+            depots,
+            satellites,
             requests,
             truck_speed,
             truck_capacity,
             num_trucks,
-        })
+        ))
     }
 
-    pub fn clone_training(&self, time_limit: f32, stress_factor: f32) -> Self {
-        let mut requests = Vec::new();
-        let mut current_index = 0;
-        let mut turn = 0.0f32;
-        for mut req in self.requests.iter().cloned() {
-            req.x *= stress_factor;
-            req.y *= stress_factor;
-            req.service_time *= stress_factor;
-            if req.time > time_limit {
-                let time_req = self.requests[current_index];
-                req.time = time_limit * turn + (time_req.time + time_req.open * 1.5) / 2.5;
-                req.open = time_limit * turn + time_req.open;
-                req.close = time_limit * turn + time_req.close;
-
-                current_index += 1;
-                if self.requests[current_index].time > time_limit {
-                    current_index = 0;
-                    turn += 1.0;
-                }
-            }
-            requests.push(req);
-        }
+    // every other setting starts at its default, which reproduces the
+    // original problem
+    pub fn new(
+        depots: Vec<Request>,
+        satellites: Vec<Request>,
+        requests: Vec<Request>,
+        truck_speed: Float,
+        truck_capacity: Float,
+        num_trucks: usize,
+    ) -> Self {
         Self {
-            depot: self.depot,
+            num_days: 1,
+            day_length: depots[0].close,
+            depots,
+            satellites,
+            satellite_capacity: 4.0 * truck_capacity,
+            num_line_haul: 2,
+            line_haul_capacity: 2.0 * truck_capacity,
+            satellite_vehicle_speed: truck_speed,
+            satellite_vehicle_capacity: 0.5 * truck_capacity,
             requests,
-            truck_speed: self.truck_speed,
-            num_trucks: self.num_trucks,
-            truck_capacity: self.truck_capacity,
+            truck_speed,
+            truck_capacity,
+            num_trucks,
+            num_drones: 0,
+            drone_speed: 2.0 * truck_speed,
+            drone_capacity: 0.02 * truck_capacity,
+            drone_range: 60.0,
+            coordinates: CoordinateSystem::Cartesian,
+            distances: Arc::new(Euclidean),
+            cancel_rate: 0.0,
+            no_show_rate: 0.0,
+            time_windows: TimeWindowMode::Hard,
+            max_route_duration: Float::INFINITY,
+            max_travel_distance: Float::INFINITY,
+            break_after: Float::INFINITY,
+            break_duration: 0.0,
+            shift_end: Float::INFINITY,
+            open_routes: false,
+            cost_per_distance: 1.0,
+            max_queue_len: usize::MAX,
+            reload_time: 10.0,
+            max_trips: usize::MAX,
+            num_docks: usize::MAX,
+            reassign_interval: Float::INFINITY,
+            pool_unassigned: false,
+            lookahead_k: 1,
+            lookahead_horizon: Float::INFINITY,
+            defer: false,
+            epochs: EpochPolicy::Slot,
+            tie_break: TieBreak::Index,
+            ablation: Ablation::default(),
+            terminal_noise: TerminalNoise::default(),
+            neighbor_radius: 10.0,
+            rolling_window: 0.0,
+            exact_queue: 0,
+            sequencing_search: 0,
+            insertion: None,
+            rules: Rules::default(),
+            profile: false,
+        }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.depots.len() + self.satellites.len() + self.requests.len()
+    }
+
+    // the requests released on `day` with times relative to its morning, the
+    // last day also takes everything released later; requests rolled over from
+    // the day before are known from the start and keep their window
+    pub fn day(&self, day: usize, rolled_over: &[Request]) -> Self {
+        let start = day as Float * self.day_length;
+        let end = if day + 1 == self.num_days {
+            Float::INFINITY
+        } else {
+            start + self.day_length
+        };
+        let released = self
+            .requests
+            .iter()
+            .filter(|r| (day == 0 || r.time >= start) && r.time < end)
+            .map(|r| Request {
+                time: r.time - start,
+                open: r.open - start,
+                close: r.close - start,
+                ..*r
+            });
+        let mut problem = self.clone();
+        problem.requests = rolled_over
+            .iter()
+            .map(|r| Request { time: 0.0, ..*r })
+            .chain(released)
+            .collect();
+        problem
+    }
+
+    // the static counterpart of the instance: every request is known from the
+    // start of the day it is released on
+    pub fn reveal(&self) -> Self {
+        let mut problem = self.clone();
+        for request in &mut problem.requests {
+            let day = (request.time / self.day_length)
+                .floor()
+                .min((self.num_days - 1) as Float);
+            request.time = day.max(0.0) * self.day_length;
         }
+        problem
+    }
+
+    pub fn num_vehicles(&self) -> usize {
+        self.num_trucks + self.num_drones
     }
 
-    pub fn total_demand(&self) -> f32 {
+    pub fn depot(&self) -> &Request {
+        &self.depots[0]
+    }
+
+    pub fn home_depot(&self, vehicle: usize) -> &Request {
+        match self.satellites.len() {
+            0 => &self.depots[vehicle % self.depots.len()],
+            n => &self.satellites[vehicle % n],
+        }
+    }
+
+    // speed and capacity of the vehicles other than drones, those homed to
+    // satellites when there are any
+    pub fn vehicle_class(&self) -> (Float, Float) {
+        match self.satellites.is_empty() {
+            true => (self.truck_speed, self.truck_capacity),
+            false => (
+                self.satellite_vehicle_speed,
+                self.satellite_vehicle_capacity,
+            ),
+        }
+    }
+
+    // satellites count as depots, vehicles reload there too
+    pub fn is_depot(&self, request: &Request) -> bool {
+        request.idx < self.depots.len() + self.satellites.len()
+    }
+
+    pub fn satellite(&self, request: &Request) -> Option<usize> {
+        request
+            .idx
+            .checked_sub(self.depots.len())
+            .filter(|s| *s < self.satellites.len())
+    }
+
+    pub fn latest_start(&self, request: &Request) -> Float {
+        match self.time_windows {
+            TimeWindowMode::Hard => request.close,
+            TimeWindowMode::Soft { max_lateness, .. } => request.close + max_lateness,
+        }
+    }
+
+    // a negative value for the best vehicle postpones the request, so that
+    // when to defer is evolved with the routing rule; the baselines never go
+    // below zero and are not affected
+    pub fn defers(&self, value: Float) -> bool {
+        self.defer && value < 0.0
+    }
+
+    pub fn lateness_cost(&self, total_lateness: Float) -> Float {
+        match self.time_windows {
+            TimeWindowMode::Hard => 0.0,
+            TimeWindowMode::Soft { penalty, .. } => penalty * total_lateness,
+        }
+    }
+
+    // estimated probability that a visit arriving at `arrival` is wasted,
+    // either because the request was cancelled first or the customer is absent
+    pub fn cancel_risk(&self, request: &Request, arrival: Float) -> Float {
+        let window = (request.close - request.time).max(1e-4);
+        let cancel = self.cancel_rate * ((arrival - request.time) / window).clamp(0.0, 1.0);
+        cancel + (1.0 - cancel) * self.no_show_rate
+    }
+
+    pub fn total_revenue(&self) -> Float {
+        self.requests.iter().map(|r| r.revenue).sum()
+    }
+
+    pub fn profit(&self, revenue: Float, distance: Float) -> Float {
+        revenue - self.cost_per_distance * distance
+    }
+
+    pub fn total_demand(&self) -> Float {
         self.requests.iter().map(|r| r.demand).sum()
     }
 }
+
+#[test]
+fn parse_errors() {
+    let header = "x,y,demand,open,close,servicetime,drone_serve,time\n";
+    let parse = |rows: &str| parse_requests(format!("{header}{rows}").as_bytes());
+    assert_eq!(parse("1,2,-3,0,10,5,0,1\n\n").unwrap()[0].demand, 3.0);
+    assert!(matches!(
+        parse("1,2,3,0,10\n"),
+        Err(LoadError::MissingColumns {
+            line: 2,
+            found: 5,
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse("1,2,3,0,10,5,0,1\n1,2,x,0,10,5,0,1\n"),
+        Err(LoadError::NotANumber { line: 3, column, .. }) if column == "demand"
+    ));
+    assert!(matches!(
+        parse("1,2,3,20,10,5,0,1\n"),
+        Err(LoadError::InvertedWindow { line: 2, .. })
+    ));
+    assert!(matches!(
+        parse("1,2,3,0,10,5,0,-1\n"),
+        Err(LoadError::Negative { line: 2, .. })
+    ));
+}
+
+#[test]
+fn reveal_requests() {
+    let header = "x,y,demand,open,close,servicetime,drone_serve,time\n";
+    let rows = "0,0,0,0,100,0,0,0\n1,1,1,0,100,0,0,30\n2,2,1,120,180,0,0,130\n";
+    let requests = parse_requests(format!("{header}{rows}").as_bytes()).unwrap();
+    let mut problem = Problem::new(
+        requests[..1].to_vec(),
+        vec![],
+        requests[1..].to_vec(),
+        1.0,
+        10.0,
+        1,
+    );
+    assert_eq!(problem.reveal().requests[0].time, 0.0);
+    problem.num_days = 2;
+    let revealed = problem.reveal();
+    let times = revealed.requests.iter().map(|r| r.time).collect::<Vec<_>>();
+    assert_eq!(times, vec![0.0, 100.0]);
+}
+
+#[test]
+fn terminal_noise() {
+    let noise = TerminalNoise::parse("routing:*:0.1,sequencing:2:0.5").unwrap();
+    assert!(noise.routing.iter().all(|level| *level == 0.1));
+    assert_eq!(noise.sequencing[..3], [0.0, 0.0, 0.5]);
+    assert!(TerminalNoise::parse("sequencing:40:0.1").is_none());
+    let noisy = |key| {
+        let mut terminals = [1.0, 2.0, 3.0];
+        TerminalNoise::apply(&noise.sequencing, 7, key, &mut terminals);
+        terminals
+    };
+    assert_eq!(noisy([1, 2, 3]), noisy([1, 2, 3]));
+    assert_ne!(noisy([1, 2, 3]), noisy([1, 2, 4]));
+    assert_eq!(noisy([1, 2, 3])[..2], [1.0, 2.0]);
+}
+
+#[test]
+fn tie_breaks() {
+    // three tied candidates, the last the closest
+    let distances = [3.0, 2.0, 1.0];
+    let order = |tie_break: TieBreak, request| {
+        let mut candidates = vec![0, 1, 2];
+        candidates.sort_by(|&a, &b| {
+            let key = |c: usize| tie_break.key(c, distances[c], request);
+            key(a).total_cmp(&key(b))
+        });
+        candidates
+    };
+    assert_eq!(order(TieBreak::Index, 0), [0, 1, 2]);
+    assert_eq!(order(TieBreak::Distance, 0), [2, 1, 0]);
+    // a seed always gives the same order, which some seed makes differ
+    // from both
+    let random = TieBreak::parse("random:7").unwrap();
+    assert_eq!(order(random, 5), order(TieBreak::Random(7), 5));
+    assert!((0..16).any(|seed| {
+        let order = order(TieBreak::Random(seed), 5);
+        order != [0, 1, 2] && order != [2, 1, 0]
+    }));
+    assert_ne!(
+        (0..4).map(|r| order(random, r)).collect::<Vec<_>>(),
+        (0..4)
+            .map(|r| order(TieBreak::Random(8), r))
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,219 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use ordered_float::OrderedFloat;
+
+use super::{
+    baseline,
+    ctx::{RoutingProgram, SequencingProgram},
+    problem::{Problem, Request},
+    trace::Record,
+    Float, RoutingRule, SequencingRule, VehicleState,
+};
+
+// rules simulated in place of the programs, hand-written or voting ones
+#[derive(Clone, Default)]
+pub struct Rules {
+    pub routing: Option<Arc<dyn RoutingRule>>,
+    pub sequencing: Option<Arc<dyn SequencingRule>>,
+}
+
+impl Rules {
+    pub fn is_empty(&self) -> bool {
+        self.routing.is_none() && self.sequencing.is_none()
+    }
+}
+
+// a rule selectable by name: a program tree, which the GP and the traced
+// tools work with, or a hand-written rule
+pub enum Entry<P, R: ?Sized> {
+    Program(P),
+    Custom(Arc<R>),
+}
+
+pub type RoutingEntry<'a> = Entry<RoutingProgram<'a>, dyn RoutingRule>;
+pub type SequencingEntry<'a> = Entry<SequencingProgram<'a>, dyn SequencingRule>;
+
+impl<P: Clone, R: ?Sized> Clone for Entry<P, R> {
+    fn clone(&self) -> Self {
+        match self {
+            Entry::Program(program) => Entry::Program(program.clone()),
+            Entry::Custom(rule) => Entry::Custom(rule.clone()),
+        }
+    }
+}
+
+impl<P, R: ?Sized> Entry<P, R> {
+    pub fn program(&self) -> Option<&P> {
+        match self {
+            Entry::Program(program) => Some(program),
+            Entry::Custom(_) => None,
+        }
+    }
+}
+
+impl<'a> RoutingRule for RoutingEntry<'a> {
+    fn rank_vehicles(
+        &self,
+        problem: &Problem,
+        time: Float,
+        vehicles: &[VehicleState],
+        request: &Request,
+        non_finite: &Cell<usize>,
+        record: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let rule: &dyn RoutingRule = match self {
+            Entry::Program(program) => program,
+            Entry::Custom(rule) => &**rule,
+        };
+        rule.rank_vehicles(problem, time, vehicles, request, non_finite, record)
+    }
+}
+
+impl<'a> SequencingRule for SequencingEntry<'a> {
+    fn rank_queue(
+        &self,
+        problem: &Problem,
+        time: Float,
+        vehicle: usize,
+        vehicle_state: &VehicleState,
+        cache: &mut HashMap<usize, (OrderedFloat<Float>, usize)>,
+        non_finite: &Cell<usize>,
+        record: Option<&mut Record>,
+    ) -> Vec<(usize, Float)> {
+        let rule: &dyn SequencingRule = match self {
+            Entry::Program(program) => program,
+            Entry::Custom(rule) => &**rule,
+        };
+        rule.rank_queue(
+            problem,
+            time,
+            vehicle,
+            vehicle_state,
+            cache,
+            non_finite,
+            record,
+        )
+    }
+}
+
+// the rules known by name, the classical ones, the weak `random` and
+// `fifo` and any registered after
+pub struct Registry {
+    routing: BTreeMap<String, RoutingEntry<'static>>,
+    sequencing: BTreeMap<String, SequencingEntry<'static>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self {
+            routing: BTreeMap::new(),
+            sequencing: BTreeMap::new(),
+        };
+        for (name, program) in [
+            ("nearest_vehicle", baseline::nearest_vehicle()),
+            ("work_in_queue", baseline::work_in_queue()),
+            ("shortest_queue", baseline::shortest_queue()),
+            ("earliest_available", baseline::earliest_available()),
+        ] {
+            registry.register_routing(name, Entry::Program(program));
+        }
+        for (name, program) in [
+            ("cost", baseline::cost()),
+            ("demand", baseline::demand()),
+            ("earliest_due_date", baseline::earliest_due_date()),
+            ("first_released", baseline::first_released()),
+        ] {
+            registry.register_sequencing(name, Entry::Program(program));
+        }
+        registry.register_routing(
+            "random",
+            Entry::Custom(Arc::new(baseline::Random { seed: 0 })),
+        );
+        registry.register_sequencing("fifo", Entry::Custom(Arc::new(baseline::Fifo)));
+        registry
+    }
+}
+
+impl Registry {
+    // replaces any rule of the same name
+    pub fn register_routing(&mut self, name: &str, rule: RoutingEntry<'static>) {
+        self.routing.insert(name.to_string(), rule);
+    }
+
+    pub fn register_sequencing(&mut self, name: &str, rule: SequencingEntry<'static>) {
+        self.sequencing.insert(name.to_string(), rule);
+    }
+
+    pub fn routing_names(&self) -> impl Iterator<Item = &str> {
+        self.routing.keys().map(String::as_str)
+    }
+
+    pub fn sequencing_names(&self) -> impl Iterator<Item = &str> {
+        self.sequencing.keys().map(String::as_str)
+    }
+
+    // the rules named, none for an unknown name
+    pub fn select_routing(&self, name: &str) -> Option<RoutingEntry<'static>> {
+        self.routing.get(name).cloned()
+    }
+
+    pub fn select_sequencing(&self, name: &str) -> Option<SequencingEntry<'static>> {
+        self.sequencing.get(name).cloned()
+    }
+}
+
+#[test]
+fn custom_rules() {
+    use super::{scenario::Scenario, simulate_rules};
+
+    // the farthest feasible vehicle, a rule no program of the baselines is
+    struct Farthest;
+
+    impl RoutingRule for Farthest {
+        fn rank_vehicles(
+            &self,
+            problem: &Problem,
+            time: Float,
+            vehicles: &[VehicleState],
+            request: &Request,
+            _: &Cell<usize>,
+            _: Option<&mut Record>,
+        ) -> Vec<(usize, Float)> {
+            let mut ranked = (0..vehicles.len())
+                .filter(|v| vehicles[*v].can_route(problem, request, time))
+                .map(|v| (v, -vehicles[v].distance_to(request)))
+                .collect::<Vec<_>>();
+            ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+            ranked
+        }
+    }
+
+    let (problem, time_slot) = Problem::test_instance();
+    let mut registry = Registry::default();
+    registry.register_routing("farthest", Entry::Custom(Arc::new(Farthest)));
+    assert!(registry.routing_names().any(|name| name == "farthest"));
+    assert_eq!(registry.sequencing_names().count(), 5);
+
+    let run = |routing| {
+        let routing = registry.select_routing(routing).unwrap();
+        let sequencing = registry.select_sequencing("cost").unwrap();
+        let result = simulate_rules(
+            &problem,
+            &routing,
+            &sequencing,
+            Scenario::default(),
+            time_slot,
+            |_, _| (),
+        );
+        (routing.program().is_some(), result.distance)
+    };
+    let (program, nearest) = run("nearest_vehicle");
+    let (custom, farthest) = run("farthest");
+    assert!(program && !custom);
+    assert!(farthest > nearest);
+    assert!(registry.select_routing("unknown").is_none());
+}
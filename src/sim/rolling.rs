@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::Float;
+
+// the outcomes of the last `window` time units of a run at one time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RollingSample {
+    pub time: Float,
+    pub outcomes: usize,
+    pub failure_rate: Float,
+    pub mean_wait: Float,
+}
+
+// failures and waits of the requests served or failed in a sliding window,
+// sampled every batch of requests so that a burst shows when it hurts
+// instead of only in the totals
+#[derive(Clone, Debug)]
+pub struct Rolling {
+    window: Float,
+    // when each request was dispatched to its service or failed, with its
+    // wait if it was served
+    outcomes: VecDeque<(Float, Option<Float>)>,
+    samples: Vec<RollingSample>,
+}
+
+impl Rolling {
+    pub fn new(window: Float) -> Self {
+        Self {
+            window,
+            outcomes: VecDeque::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn served(&mut self, time: Float, wait: Float) {
+        self.outcomes.push_back((time, Some(wait)));
+    }
+
+    pub fn failed(&mut self, time: Float) {
+        self.outcomes.push_back((time, None));
+    }
+
+    pub fn sample(&mut self, time: Float) -> RollingSample {
+        while let Some((start, _)) = self.outcomes.front() {
+            if *start > time - self.window {
+                break;
+            }
+            self.outcomes.pop_front();
+        }
+        let waits = self.outcomes.iter().filter_map(|(_, wait)| *wait);
+        let served = waits.clone().count();
+        let outcomes = self.outcomes.len();
+        let sample = RollingSample {
+            time,
+            outcomes,
+            failure_rate: match outcomes {
+                0 => 0.0,
+                n => (n - served) as Float / n as Float,
+            },
+            mean_wait: match served {
+                0 => 0.0,
+                n => waits.sum::<Float>() / n as Float,
+            },
+        };
+        self.samples.push(sample);
+        sample
+    }
+
+    pub fn samples(&self) -> &[RollingSample] {
+        &self.samples
+    }
+
+    // the highest failure rate and mean wait of any sample
+    pub fn peak(&self) -> (Float, Float) {
+        self.samples
+            .iter()
+            .fold((0.0, 0.0), |(rate, wait), sample| {
+                (rate.max(sample.failure_rate), wait.max(sample.mean_wait))
+            })
+    }
+}
+
+#[test]
+fn rolling_window() {
+    let mut rolling = Rolling::new(10.0);
+    rolling.served(1.0, 4.0);
+    rolling.failed(2.0);
+    rolling.served(8.0, 2.0);
+    let sample = rolling.sample(10.0);
+    assert_eq!(sample.outcomes, 3);
+    assert!((sample.failure_rate - 1.0 / 3.0).abs() < 1e-6);
+    assert_eq!(sample.mean_wait, 3.0);
+    // the first two have left the window
+    let sample = rolling.sample(12.0);
+    assert_eq!((sample.outcomes, sample.failure_rate), (1, 0.0));
+    assert_eq!(sample.mean_wait, 2.0);
+    assert_eq!(rolling.sample(30.0).outcomes, 0);
+    assert!((rolling.peak().0 - 1.0 / 3.0).abs() < 1e-6);
+    assert_eq!(rolling.peak().1, 3.0);
+}
@@ -0,0 +1,190 @@
+use std::cmp::Reverse;
+
+use ordered_float::OrderedFloat;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+use super::Float;
+
+// multiplicative perturbation applied to a nominal value, the sampled
+// factor is never negative
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Noise {
+    #[default]
+    None,
+    Uniform(Float),
+    Normal(Float),
+    LogNormal(Float),
+}
+
+impl Noise {
+    // "none", "uniform:0.2", "normal:0.1", "lognormal:0.1"
+    pub fn parse(str: &str) -> Option<Self> {
+        let (kind, param) = match str.split_once(':') {
+            Some((kind, param)) => (kind, param.parse::<Float>().ok()?),
+            None => (str, 0.0),
+        };
+        if param < 0.0 {
+            return None;
+        }
+        Some(match kind {
+            "none" => Self::None,
+            "uniform" => Self::Uniform(param),
+            "normal" => Self::Normal(param),
+            "lognormal" => Self::LogNormal(param),
+            _ => return None,
+        })
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, Self::None)
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> Float {
+        match *self {
+            Self::None => 1.0,
+            Self::Uniform(a) if a > 0.0 => (1.0 + rng.gen_range(-a..=a)).max(0.0),
+            Self::Normal(s) if s > 0.0 => {
+                let n = Normal::new(1.0, s).expect("invalid normal noise");
+                n.sample(rng).max(0.0)
+            }
+            // mean-preserving: E[exp(N(-s^2/2, s))] = 1
+            Self::LogNormal(s) if s > 0.0 => {
+                let n = Normal::new(-s * s / 2.0, s).expect("invalid lognormal noise");
+                n.sample(rng).exp()
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+// the sources of randomness of one simulated day; the default scenario is
+// the deterministic problem
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Scenario {
+    pub seed: u64,
+    pub travel_noise: Noise,
+    pub service_noise: Noise,
+}
+
+impl Scenario {
+    // replication `k` is seeded with `seed + k`, so every individual is
+    // evaluated on the same days
+    pub fn replications(&self, count: usize) -> Vec<Self> {
+        (0..count.max(1) as u64)
+            .map(|k| Self {
+                seed: self.seed + k,
+                ..*self
+            })
+            .collect()
+    }
+
+    // identifies a set of replications, results are only comparable within
+    // the same set
+    pub fn set_key(scenarios: &[Self]) -> String {
+        scenarios
+            .iter()
+            .map(|s| format!("{}/{:?}/{:?}", s.seed, s.travel_noise, s.service_noise))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregation {
+    Mean,
+    // mean fitness plus `k` standard deviations
+    MeanStd(f32),
+    // conditional value-at-risk, the mean over the worst `alpha` share of
+    // the scenarios, at least one
+    CVaR(f32),
+    Worst,
+}
+
+impl Aggregation {
+    // "mean", "meanstd:k", "cvar:alpha" or "worst"
+    pub fn parse(str: &str) -> Option<Self> {
+        match str.split_once(':') {
+            Some(("meanstd", k)) => k.parse().ok().map(Self::MeanStd),
+            Some(("cvar", alpha)) => alpha
+                .parse()
+                .ok()
+                .filter(|alpha| *alpha > 0.0 && *alpha <= 1.0)
+                .map(Self::CVaR),
+            Some(_) => None,
+            None => match str {
+                "mean" => Some(Self::Mean),
+                "worst" => Some(Self::Worst),
+                _ => None,
+            },
+        }
+    }
+
+    // aggregates (distance, num_fail, fitness) over scenarios, lower fitness
+    // is better
+    pub fn aggregate(&self, results: &[(f32, usize, f32)]) -> (f32, usize, f32) {
+        assert!(!results.is_empty());
+        match self {
+            Self::Mean => {
+                let n = results.len() as f32;
+                let dist = results.iter().map(|r| r.0).sum::<f32>() / n;
+                let num_fail = results.iter().map(|r| r.1 as f32).sum::<f32>() / n;
+                let fitness = results.iter().map(|r| r.2).sum::<f32>() / n;
+                (dist, num_fail.round() as usize, fitness)
+            }
+            Self::MeanStd(k) => {
+                let (dist, num_fail, mean) = Self::Mean.aggregate(results);
+                let n = results.len() as f32;
+                let var = results.iter().map(|r| (r.2 - mean).powi(2)).sum::<f32>() / n;
+                (dist, num_fail, mean + k * var.sqrt())
+            }
+            Self::CVaR(alpha) => {
+                let mut sorted = results.to_vec();
+                sorted.sort_by_key(|r| Reverse(OrderedFloat(r.2)));
+                let tail = ((alpha * sorted.len() as f32).ceil() as usize).max(1);
+                Self::Mean.aggregate(&sorted[..tail.min(sorted.len())])
+            }
+            Self::Worst => *results.iter().max_by_key(|r| OrderedFloat(r.2)).unwrap(),
+        }
+    }
+}
+
+#[test]
+fn parse_noise() {
+    assert_eq!(Noise::parse("none"), Some(Noise::None));
+    assert_eq!(Noise::parse("normal:0.1"), Some(Noise::Normal(0.1)));
+    assert_eq!(Noise::parse("uniform:0.25"), Some(Noise::Uniform(0.25)));
+    assert_eq!(Noise::parse("normal:-1"), None);
+    assert_eq!(Noise::parse("gamma:1"), None);
+    let scenario = Scenario {
+        seed: 7,
+        ..Default::default()
+    };
+    assert_eq!(scenario.replications(0).len(), 1);
+    assert_eq!(scenario.replications(5)[4].seed, 11);
+}
+
+#[test]
+fn aggregate_mean_std() {
+    assert_eq!(
+        Aggregation::parse("meanstd:2"),
+        Some(Aggregation::MeanStd(2.0))
+    );
+    assert_eq!(Aggregation::parse("meanstd:x"), None);
+    let results = [(10.0, 1, 1.0), (20.0, 3, 3.0)];
+    assert_eq!(
+        Aggregation::MeanStd(2.0).aggregate(&results),
+        (15.0, 2, 4.0)
+    );
+}
+
+#[test]
+fn aggregate_cvar() {
+    assert_eq!(Aggregation::parse("cvar:0.1"), Some(Aggregation::CVaR(0.1)));
+    assert_eq!(Aggregation::parse("cvar:0"), None);
+    let results = (1..=10)
+        .map(|k| (k as f32, k, k as f32))
+        .collect::<Vec<_>>();
+    assert_eq!(Aggregation::CVaR(0.1).aggregate(&results), (10.0, 10, 10.0));
+    assert_eq!(Aggregation::CVaR(0.2).aggregate(&results), (9.5, 10, 9.5));
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::gp::program::{Program, ProgramContext};
+
+use super::{
+    ctx::{
+        common_internal, RoutingContext, RoutingProgram, SequencingContext, SequencingProgram,
+        ROUTING_NAMES, SEQUENCING_NAMES,
+    },
+    problem::Problem,
+    scenario::Scenario,
+    trace::{simulate_recorded, Record},
+    Float,
+};
+
+// the response of a rule to one terminal swept over the range it took in a
+// run, the other terminals kept at the values of each recorded candidate
+pub struct Curve {
+    pub routing: bool,
+    pub terminal: usize,
+    // input, then the mean, min and max of the finite rule values
+    pub points: Vec<(f32, Float, Float, Float)>,
+}
+
+pub struct Sensitivity {
+    pub curves: Vec<Curve>,
+}
+
+// the terminal values of every candidate the rule was asked about
+fn candidates(record: &Record, routing: bool, num_terminals: usize) -> Vec<&[f32]> {
+    record
+        .decisions()
+        .iter()
+        .filter(|decision| decision.routing == routing)
+        .flat_map(|decision| decision.candidates.iter().map(|c| c.0))
+        // sequencing values are shared by the decisions of a queue
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|offset| record.terminals(offset, num_terminals))
+        .collect()
+}
+
+fn curves<C: ProgramContext>(
+    program: &Program<C>,
+    routing: bool,
+    candidates: &[&[f32]],
+    num_points: usize,
+) -> Vec<Curve> {
+    let mut curves = Vec::new();
+    if candidates.is_empty() {
+        return curves;
+    }
+    let mut terminals = Vec::new();
+    for terminal in program.used_terminals() {
+        let observed = candidates.iter().map(|c| c[terminal]);
+        let min = observed.clone().fold(f32::INFINITY, f32::min);
+        let max = observed.fold(f32::NEG_INFINITY, f32::max);
+        let points = (0..num_points)
+            .map(|i| {
+                let input = match num_points {
+                    1 => min,
+                    n => min + (max - min) * i as f32 / (n - 1) as f32,
+                };
+                let (mut sum, mut n) = (0.0, 0);
+                let (mut low, mut high) = (Float::INFINITY, Float::NEG_INFINITY);
+                for candidate in candidates {
+                    terminals.clear();
+                    terminals.extend_from_slice(candidate);
+                    terminals[terminal] = input;
+                    let value = program.calc_terminals(&terminals, common_internal) as Float;
+                    if value.is_finite() {
+                        sum += value;
+                        n += 1;
+                        low = low.min(value);
+                        high = high.max(value);
+                    }
+                }
+                (input, sum / n.max(1) as Float, low, high)
+            })
+            .collect();
+        curves.push(Curve {
+            routing,
+            terminal,
+            points,
+        });
+    }
+    curves
+}
+
+impl Sensitivity {
+    // `problem` has to be `Trace::supported` by
+    pub fn new(
+        problem: &Problem,
+        routing: &RoutingProgram,
+        sequencing: &SequencingProgram,
+        time_slot: Float,
+        num_points: usize,
+    ) -> Self {
+        let (_, record) =
+            simulate_recorded(problem, routing, sequencing, Scenario::default(), time_slot);
+        let routing_candidates = candidates(&record, true, RoutingContext::num_terminals());
+        let sequencing_candidates = candidates(&record, false, SequencingContext::num_terminals());
+        let mut all = curves(routing, true, &routing_candidates, num_points);
+        all.extend(curves(
+            sequencing,
+            false,
+            &sequencing_candidates,
+            num_points,
+        ));
+        Self { curves: all }
+    }
+}
+
+// CSV, one row per point
+impl Display for Sensitivity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "rule,terminal,name,input,mean,min,max")?;
+        for curve in &self.curves {
+            let (rule, name) = match curve.routing {
+                true => ("routing", ROUTING_NAMES[curve.terminal]),
+                false => ("sequencing", SEQUENCING_NAMES[curve.terminal]),
+            };
+            for (input, mean, min, max) in &curve.points {
+                write!(
+                    f,
+                    "\n{rule},{},{name},{input},{mean},{min},{max}",
+                    curve.terminal
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn response_curves() {
+    use super::baseline;
+
+    let (problem, time_slot) = Problem::test_instance();
+    let routing = baseline::nearest_vehicle();
+    // the value is the swept terminal itself
+    let sequencing = SequencingProgram::terminal(4);
+    let sensitivity = Sensitivity::new(&problem, &routing, &sequencing, time_slot, 5);
+    let curve = sensitivity.curves.iter().find(|c| !c.routing).unwrap();
+    assert_eq!(curve.terminal, 4);
+    assert_eq!(curve.points.len(), 5);
+    for (input, mean, min, max) in &curve.points {
+        assert_eq!((*min, *max), (*input as Float, *input as Float));
+        assert!((mean - *input as Float).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::{problem::Problem, Float};
+
+// summary of an instance, to sanity-check it before long runs
+pub struct InstanceStats {
+    pub num_requests: usize,
+    pub num_pickups: usize,
+    pub delivered: Float,
+    pub picked_up: Float,
+    pub fleet_capacity: Float,
+    pub window_width: (Float, Float, Float),
+    pub depot_distance: (Float, Float, Float),
+    pub bounds: ((Float, Float), (Float, Float)),
+    // requests released in each of `num_bins` equal parts of the day
+    pub arrivals: Vec<usize>,
+}
+
+// min, mean and max
+fn spread(values: impl Iterator<Item = Float>) -> (Float, Float, Float) {
+    let (mut min, mut max, mut sum, mut n) = (Float::INFINITY, Float::NEG_INFINITY, 0.0, 0);
+    for value in values {
+        min = min.min(value);
+        max = max.max(value);
+        sum += value;
+        n += 1;
+    }
+    match n {
+        0 => (0.0, 0.0, 0.0),
+        n => (min, sum / n as Float, max),
+    }
+}
+
+impl InstanceStats {
+    pub fn new(problem: &Problem, num_bins: usize) -> Self {
+        let requests = &problem.requests;
+        let demand = |pickup: bool| {
+            requests
+                .iter()
+                .filter(|r| r.pickup == pickup)
+                .fold(0.0, |sum, r| sum + r.demand)
+        };
+        let (min_x, _, max_x) = spread(requests.iter().map(|r| r.x));
+        let (min_y, _, max_y) = spread(requests.iter().map(|r| r.y));
+        let horizon = problem.day_length * problem.num_days as Float;
+        let num_bins = num_bins.max(1);
+        let mut arrivals = vec![0; num_bins];
+        for request in requests {
+            let bin = (request.time / horizon * num_bins as Float) as usize;
+            arrivals[bin.min(num_bins - 1)] += 1;
+        }
+        Self {
+            num_requests: requests.len(),
+            num_pickups: requests.iter().filter(|r| r.pickup).count(),
+            delivered: demand(false),
+            picked_up: demand(true),
+            fleet_capacity: problem.truck_capacity * problem.num_trucks as Float,
+            window_width: spread(requests.iter().map(|r| r.close - r.open)),
+            depot_distance: spread(
+                requests
+                    .iter()
+                    .map(|r| problem.distances.distance(problem.depot(), r)),
+            ),
+            bounds: ((min_x, min_y), (max_x, max_y)),
+            arrivals,
+        }
+    }
+}
+
+impl Display for InstanceStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (w_min, w_mean, w_max) = self.window_width;
+        let (d_min, d_mean, d_max) = self.depot_distance;
+        let ((x0, y0), (x1, y1)) = self.bounds;
+        writeln!(
+            f,
+            "requests: {} ({} pickups)",
+            self.num_requests, self.num_pickups
+        )?;
+        writeln!(
+            f,
+            "demand: {} delivered, {} picked up, fleet capacity {} ({:.2} loads)",
+            self.delivered,
+            self.picked_up,
+            self.fleet_capacity,
+            self.delivered.max(self.picked_up) / self.fleet_capacity
+        )?;
+        writeln!(
+            f,
+            "time window width: min {w_min:.1}, mean {w_mean:.1}, max {w_max:.1}"
+        )?;
+        writeln!(
+            f,
+            "distance to depot: min {d_min:.1}, mean {d_mean:.1}, max {d_max:.1}"
+        )?;
+        writeln!(f, "bounds: ({x0:.1}, {y0:.1}) - ({x1:.1}, {y1:.1})")?;
+        write!(f, "arrivals per period: {:?}", self.arrivals)
+    }
+}
+
+#[test]
+fn instance_stats() {
+    let (problem, _) = Problem::test_instance();
+    let stats = InstanceStats::new(&problem, 4);
+    assert_eq!(stats.num_requests, problem.requests.len());
+    assert_eq!(stats.arrivals.iter().sum::<usize>(), stats.num_requests);
+    assert_eq!(stats.fleet_capacity, 13000.0);
+}
@@ -0,0 +1,371 @@
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+use ordered_float::OrderedFloat;
+
+use crate::gp::program::ProgramContext;
+
+use super::{
+    ctx::{common_internal, RoutingContext, RoutingProgram, SequencingContext, SequencingProgram},
+    problem::Problem,
+    protect,
+    registry::Entry,
+    scenario::Scenario,
+    Float, Simulation, SimulationResult,
+};
+
+// one call of a rule at `time` for a request to route or a vehicle to
+// sequence: where the terminal values of every candidate start in
+// `Record::terminals` with their tie-break key and the vehicle or request it
+// is, in the order they were offered, and the candidate that won
+#[derive(Clone, Debug)]
+pub struct Decision {
+    pub routing: bool,
+    pub time: Float,
+    pub subject: usize,
+    pub candidates: Vec<(usize, Float, usize)>,
+    pub chosen: usize,
+    // the candidate the simulation went with, other than `chosen` when an
+    // insertion dispatcher or a sequencing search overrode the rule
+    pub taken: usize,
+    // the best routing value was negative with `defer` on
+    pub deferred: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Record {
+    terminals: Vec<f32>,
+    decisions: Vec<Decision>,
+}
+
+impl Record {
+    // the offset the decision refers to `terminals` by
+    pub fn push_terminals(&mut self, terminals: &[f32]) -> usize {
+        self.terminals.extend_from_slice(terminals);
+        self.terminals.len() - terminals.len()
+    }
+
+    pub fn push_routing(
+        &mut self,
+        time: Float,
+        request: usize,
+        candidates: Vec<(usize, Float, usize)>,
+        chosen: usize,
+        deferred: bool,
+    ) {
+        self.decisions.push(Decision {
+            routing: true,
+            time,
+            subject: request,
+            candidates,
+            chosen,
+            taken: chosen,
+            deferred,
+        });
+    }
+
+    pub fn push_sequencing(
+        &mut self,
+        time: Float,
+        vehicle: usize,
+        candidates: Vec<(usize, Float, usize)>,
+        chosen: usize,
+    ) {
+        self.decisions.push(Decision {
+            routing: false,
+            time,
+            subject: vehicle,
+            candidates,
+            chosen,
+            taken: chosen,
+            deferred: false,
+        });
+    }
+
+    // the last decision went with the candidate `id` instead, if offered
+    pub fn take(&mut self, id: usize) {
+        if let Some(decision) = self.decisions.last_mut() {
+            if let Some(index) = decision.candidates.iter().position(|c| c.2 == id) {
+                decision.taken = index;
+            }
+        }
+    }
+
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    // the values of `len` terminals from `offset` on
+    pub fn terminals(&self, offset: usize, len: usize) -> &[f32] {
+        &self.terminals[offset..offset + len]
+    }
+}
+
+// the simulation right before the requests revealed at `time`, after the
+// first `decisions` decisions which used the first `terminals` values
+#[derive(Clone)]
+struct Checkpoint<'a> {
+    time: Float,
+    decisions: usize,
+    terminals: usize,
+    simulation: Rc<Simulation<'a>>,
+}
+
+// the decisions of a run with checkpoints of its state, so that a run of other
+// rules starts from the last checkpoint before they decide differently
+#[derive(Clone)]
+pub struct Trace<'a> {
+    pub record: Record,
+    checkpoints: Vec<Checkpoint<'a>>,
+    // simulated time between checkpoints
+    spacing: Float,
+    result: Option<SimulationResult>,
+}
+
+enum Resume {
+    // every decision is the same and so is the result, up to the rule values
+    // that were not finite
+    Finished(usize),
+    // the index of the checkpoint and the rule values that were not finite
+    // before it
+    Checkpoint(usize, usize),
+}
+
+impl<'a> Trace<'a> {
+    fn new(problem: &Problem, checkpoints: usize) -> Self {
+        Self {
+            record: Record::default(),
+            checkpoints: Vec::new(),
+            spacing: problem.depot().close / checkpoints.max(1) as Float,
+            result: None,
+        }
+    }
+
+    // the decisions only depend on the recorded values within a single day
+    // simulated without rollouts, by the programs
+    pub fn supported(problem: &Problem) -> bool {
+        problem.num_days == 1 && problem.lookahead_k <= 1 && problem.rules.is_empty()
+    }
+
+    pub fn checkpoint_due(&self, time: Float) -> bool {
+        match self.checkpoints.last() {
+            // a later state after the same decisions resumes sooner
+            Some(last) => {
+                last.decisions == self.record.decisions.len() || time >= last.time + self.spacing
+            }
+            None => true,
+        }
+    }
+
+    pub fn checkpoint(&mut self, simulation: Simulation<'a>, time: Float) {
+        let checkpoint = Checkpoint {
+            time,
+            decisions: self.record.decisions.len(),
+            terminals: self.record.terminals.len(),
+            simulation: Rc::new(simulation),
+        };
+        match self.checkpoints.last_mut() {
+            Some(last) if last.decisions == checkpoint.decisions => *last = checkpoint,
+            _ => self.checkpoints.push(checkpoint),
+        }
+    }
+
+    // the decisions and checkpoints up to checkpoint `index`
+    fn prefix(&self, index: usize) -> Self {
+        let end = &self.checkpoints[index];
+        Self {
+            record: Record {
+                terminals: self.record.terminals[..end.terminals].to_vec(),
+                decisions: self.record.decisions[..end.decisions].to_vec(),
+            },
+            checkpoints: self.checkpoints[..=index].to_vec(),
+            spacing: self.spacing,
+            result: None,
+        }
+    }
+
+    // replays the decisions with other rules until they decide differently,
+    // which only evaluates the rules
+    fn resume(
+        &self,
+        problem: &Problem,
+        routing: &RoutingProgram,
+        sequencing: &SequencingProgram,
+    ) -> Option<Resume> {
+        let non_finite = Cell::new(0);
+        // a value is computed once for all the decisions sharing it, like the
+        // sequencing values of a queue
+        let mut values = HashMap::<usize, Float>::new();
+        let mut value = |routing_decision: bool, offset: usize| {
+            *values.entry(offset).or_insert_with(|| {
+                let value = if routing_decision {
+                    let terminals = &self.record.terminals[offset..];
+                    routing.calc_terminals(
+                        &terminals[..RoutingContext::num_terminals()],
+                        common_internal,
+                    )
+                } else {
+                    let terminals = &self.record.terminals[offset..];
+                    sequencing.calc_terminals(
+                        &terminals[..SequencingContext::num_terminals()],
+                        common_internal,
+                    )
+                };
+                protect(value, &non_finite)
+            })
+        };
+        let mut resume = None;
+        let mut checkpoints = self.checkpoints.iter().enumerate().peekable();
+        for (index, decision) in self.record.decisions.iter().enumerate() {
+            while let Some((checkpoint, _)) = checkpoints.next_if(|(_, c)| c.decisions <= index) {
+                resume = Some(Resume::Checkpoint(checkpoint, non_finite.get()));
+            }
+            let ranked = decision
+                .candidates
+                .iter()
+                .map(|&(offset, tie, _)| (value(decision.routing, offset), tie))
+                .collect::<Vec<_>>();
+            let chosen = (0..ranked.len())
+                .min_by_key(|i| (OrderedFloat(ranked[*i].0), OrderedFloat(ranked[*i].1)))
+                .expect("decisions are only recorded with candidates");
+            let deferred = decision.routing && problem.defers(ranked[chosen].0);
+            if chosen != decision.chosen || deferred != decision.deferred {
+                return resume;
+            }
+        }
+        match self.result {
+            Some(_) => Some(Resume::Finished(non_finite.get())),
+            None => resume,
+        }
+    }
+}
+
+// like `simulate_days` for a problem `Trace::supported` by, continuing the run
+// of `parent` from its last checkpoint before the rules decide differently;
+// the trace of the run keeps up to `checkpoints` checkpoints spread over the
+// day
+pub fn simulate_traced<'a>(
+    problem: &'a Problem,
+    routing_rule: &RoutingProgram<'a>,
+    sequencing_rule: &SequencingProgram<'a>,
+    scenario: Scenario,
+    time_slot: Float,
+    checkpoints: usize,
+    parent: Option<&Trace<'a>>,
+) -> (SimulationResult, Trace<'a>) {
+    let resume = parent.and_then(|parent| {
+        Some((
+            parent,
+            parent.resume(problem, routing_rule, sequencing_rule)?,
+        ))
+    });
+    let mut sim = match resume {
+        Some((parent, Resume::Finished(non_finite))) => {
+            let mut result = SimulationResult {
+                non_finite,
+                ..parent.result.clone().unwrap()
+            };
+            // the same evaluations, of other nodes, and none of them run
+            result
+                .profile
+                .count_nodes(Some(routing_rule), Some(sequencing_rule));
+            result.profile.rule_seconds = 0.0;
+            result.profile.event_seconds = 0.0;
+            return (result, parent.clone());
+        }
+        Some((parent, Resume::Checkpoint(index, non_finite))) => {
+            let mut sim = (*parent.checkpoints[index].simulation).clone();
+            sim.routing_rule = Entry::Program(routing_rule.clone());
+            sim.sequencing_rule = Entry::Program(sequencing_rule.clone());
+            sim.non_finite.set(non_finite);
+            // the replayed decisions were not evaluated again
+            sim.rule_seconds = 0.0;
+            sim.trace = Some(parent.prefix(index));
+            sim
+        }
+        None => {
+            let mut sim =
+                Simulation::with_scenario(problem, routing_rule, sequencing_rule, scenario);
+            sim.trace = Some(Trace::new(problem, checkpoints));
+            sim.start(time_slot);
+            sim
+        }
+    };
+    let result = sim.finish(Float::MAX);
+    let mut trace = sim.trace.take().unwrap();
+    trace.result = Some(result.clone());
+    (result, trace)
+}
+
+// the decisions of a run of a problem `Trace::supported` by
+pub fn simulate_recorded<'a>(
+    problem: &'a Problem,
+    routing_rule: &RoutingProgram<'a>,
+    sequencing_rule: &SequencingProgram<'a>,
+    scenario: Scenario,
+    time_slot: Float,
+) -> (SimulationResult, Record) {
+    let mut sim = Simulation::with_scenario(problem, routing_rule, sequencing_rule, scenario);
+    sim.trace = Some(Trace::new(problem, 1));
+    sim.start(time_slot);
+    let result = sim.finish(Float::MAX);
+    (result, sim.trace.take().unwrap().record)
+}
+
+#[test]
+fn resumed_traces() {
+    use super::baseline;
+    use crate::gp::program::{Node, Program};
+
+    let (problem, time_slot) = Problem::test_instance();
+    let routing = baseline::nearest_vehicle();
+    let sequencing = baseline::cost();
+    let (parent_result, parent) = simulate_traced(
+        &problem,
+        &routing,
+        &sequencing,
+        Scenario::default(),
+        time_slot,
+        10,
+        None,
+    );
+    let fresh = baseline::first_released();
+    assert_eq!(
+        serde_json::to_string(&parent_result).unwrap(),
+        serde_json::to_string(&super::simulate_days(
+            &problem,
+            &routing,
+            &sequencing,
+            Scenario::default(),
+            time_slot,
+            |_, _| ()
+        ))
+        .unwrap()
+    );
+    // the operands of `cost` swapped decide the same, the other rules do not
+    let swapped = Program::from_vec(
+        [Node::Internal(5), Node::Terminal(4), Node::Terminal(0)]
+            .into_iter()
+            .map(u8::from)
+            .collect(),
+    );
+    for (routing, sequencing) in [
+        (baseline::nearest_vehicle(), swapped),
+        (baseline::nearest_vehicle(), baseline::earliest_due_date()),
+        (baseline::work_in_queue(), baseline::cost()),
+        (baseline::earliest_available(), fresh),
+    ] {
+        let run = |parent| {
+            let (result, _) = simulate_traced(
+                &problem,
+                &routing,
+                &sequencing,
+                Scenario::default(),
+                time_slot,
+                10,
+                parent,
+            );
+            serde_json::to_string(&result).unwrap()
+        };
+        assert_eq!(run(Some(&parent)), run(None));
+    }
+}
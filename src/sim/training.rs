@@ -0,0 +1,249 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+
+use super::{problem::Problem, widen, Float};
+
+// turns the test instance into the one the GP is trained on, possibly
+// changing the time slot the training simulation dispatches at
+pub trait TrainingTransform: Send + Sync {
+    fn apply(&self, problem: &mut Problem, time_slot: &mut Float);
+}
+
+// a comma-separated chain applied from left to right: "warp:f", "stress:s",
+// "scale:s", "compress:f", "windows:f" or "subsample:p"; "none" keeps the instance as is
+pub fn parse(str: &str) -> Option<Vec<Box<dyn TrainingTransform>>> {
+    if str == "none" {
+        return Some(Vec::new());
+    }
+    str.split(',')
+        .map(|transform| {
+            let (kind, param) = transform.trim().split_once(':')?;
+            let param = param.parse::<Float>().ok().filter(|p| *p > 0.0)?;
+            Some(match kind {
+                "warp" => Box::new(Warp(param)) as Box<dyn TrainingTransform>,
+                "stress" => Box::new(Stress(param)),
+                "scale" => Box::new(Scale(param)),
+                "compress" => Box::new(Compress(param)),
+                "windows" => Box::new(Windows(param)),
+                "subsample" if param <= 1.0 => Box::new(Subsample(param)),
+                _ => return None,
+            })
+        })
+        .collect()
+}
+
+pub fn apply(
+    transforms: &[Box<dyn TrainingTransform>],
+    problem: &Problem,
+    time_slot: Float,
+) -> (Problem, Float) {
+    let mut problem = problem.clone();
+    let mut time_slot = time_slot;
+    for transform in transforms {
+        transform.apply(&mut problem, &mut time_slot);
+    }
+    (problem, time_slot)
+}
+
+// arrival-rate stress: requests released after the first `f` time slots are
+// folded back onto them, taking the windows of the earliest requests again
+// and again, each fold one period later
+pub struct Warp(pub Float);
+
+impl TrainingTransform for Warp {
+    fn apply(&self, problem: &mut Problem, time_slot: &mut Float) {
+        let time_limit = *time_slot * self.0;
+        let original = problem.requests.clone();
+        let mut current_index = 0;
+        let mut turn: Float = 0.0;
+        for req in problem.requests.iter_mut() {
+            if req.time > time_limit {
+                let time_req = original[current_index];
+                req.time = time_limit * turn + (time_req.time + time_req.open * 1.5) / 2.5;
+                req.open = time_limit * turn + time_req.open;
+                req.close = time_limit * turn + time_req.close;
+
+                current_index += 1;
+                if original[current_index].time > time_limit {
+                    current_index = 0;
+                    turn += 1.0;
+                }
+            }
+        }
+    }
+}
+
+// coordinates and service times grow by `s` while the time slots shrink by it
+pub struct Stress(pub Float);
+
+impl TrainingTransform for Stress {
+    fn apply(&self, problem: &mut Problem, time_slot: &mut Float) {
+        for req in problem.requests.iter_mut() {
+            req.x *= self.0;
+            req.y *= self.0;
+            req.service_time *= self.0;
+        }
+        *time_slot /= self.0;
+    }
+}
+
+// spatial scaling of the customers and satellites around the first depot;
+// distance matrices are not affected
+pub struct Scale(pub Float);
+
+impl TrainingTransform for Scale {
+    fn apply(&self, problem: &mut Problem, _: &mut Float) {
+        let (x, y) = (problem.depot().x, problem.depot().y);
+        for req in problem.requests.iter_mut().chain(&mut problem.satellites) {
+            req.x = x + (req.x - x) * self.0;
+            req.y = y + (req.y - y) * self.0;
+        }
+    }
+}
+
+// time compression: release times, windows and service times of the instance
+// shrink by `f`, while travel still takes as long
+pub struct Compress(pub Float);
+
+impl TrainingTransform for Compress {
+    fn apply(&self, problem: &mut Problem, time_slot: &mut Float) {
+        let nodes = problem
+            .depots
+            .iter_mut()
+            .chain(&mut problem.satellites)
+            .chain(&mut problem.requests);
+        for req in nodes {
+            req.time *= self.0;
+            req.open *= self.0;
+            req.close *= self.0;
+            req.service_time *= self.0;
+        }
+        problem.day_length *= self.0;
+        *time_slot *= self.0;
+    }
+}
+
+// time windows narrow (below 1) or widen by `f` around their middle, within
+// the day of the first depot and never before the release of their request
+pub struct Windows(pub Float);
+
+impl TrainingTransform for Windows {
+    fn apply(&self, problem: &mut Problem, _: &mut Float) {
+        let (open, close) = (problem.depot().open, problem.depot().close);
+        for req in problem.requests.iter_mut() {
+            let (middle, half) = ((req.open + req.close) / 2.0, (req.close - req.open) / 2.0);
+            req.close = (middle + half * self.0)
+                .min(close.max(req.close))
+                .max(req.time);
+            req.open = (middle - half * self.0)
+                .max(open)
+                .clamp(req.time, req.close);
+        }
+    }
+}
+
+// keeps a fixed random share `p` of the requests
+pub struct Subsample(pub Float);
+
+impl TrainingTransform for Subsample {
+    fn apply(&self, problem: &mut Problem, _: &mut Float) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        problem.requests.retain(|_| rng.gen_bool(widen(self.0)));
+    }
+}
+
+// a perturbed copy of the instance: the customers move by normal noise of
+// `xy`, their demands by noise of `demand` relative to them and their release
+// times by noise of `time`, drawn from `seed`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Perturb {
+    pub xy: Float,
+    pub demand: Float,
+    pub time: Float,
+    pub seed: u64,
+}
+
+impl Perturb {
+    // comma-separated "xy:s", "demand:s" and "time:s", unset noise is 0
+    pub fn parse(str: &str) -> Option<Self> {
+        let mut perturb = Self::default();
+        for noise in str.split(',').filter(|s| !s.is_empty()) {
+            let (kind, level) = noise.trim().split_once(':')?;
+            let level = level.parse::<Float>().ok().filter(|l| *l >= 0.0)?;
+            match kind {
+                "xy" => perturb.xy = level,
+                "demand" => perturb.demand = level,
+                "time" => perturb.time = level,
+                _ => return None,
+            }
+        }
+        Some(perturb)
+    }
+}
+
+impl TrainingTransform for Perturb {
+    fn apply(&self, problem: &mut Problem, _: &mut Float) {
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let mut noise = |level: Float| match level > 0.0 {
+            true => Normal::new(0.0, level)
+                .expect("invalid noise")
+                .sample(&mut rng),
+            false => 0.0,
+        };
+        let capacity = problem.truck_capacity;
+        for req in problem.requests.iter_mut() {
+            req.x += noise(self.xy);
+            req.y += noise(self.xy);
+            req.demand = (req.demand * (1.0 + noise(self.demand))).clamp(0.0, capacity);
+            req.time = (req.time + noise(self.time)).clamp(0.0, req.close);
+        }
+    }
+}
+
+#[test]
+fn training_transforms() {
+    let (problem, _) = Problem::test_instance();
+    let transforms = parse("subsample:0.5,compress:0.5").unwrap();
+    let (training, time_slot) = apply(&transforms, &problem, 10.0);
+    assert!(training.requests.len() < problem.requests.len());
+    assert_eq!(training.depot().close, problem.depot().close * 0.5);
+    assert_eq!(time_slot, 5.0);
+    let (narrow, _) = apply(&parse("windows:0.5").unwrap(), &problem, 10.0);
+    let width = |r: &super::problem::Request| r.close - r.open;
+    let (request, narrowed) = (&problem.requests[0], &narrow.requests[0]);
+    assert_eq!(width(narrowed), width(request) * 0.5);
+    assert_eq!(narrowed.open + narrowed.close, request.open + request.close);
+    let perturb = Perturb::parse("xy:2,time:5").unwrap();
+    assert_eq!((perturb.xy, perturb.demand, perturb.time), (2.0, 0.0, 5.0));
+    let (perturbed, _) = apply(&[Box::new(perturb)], &problem, 10.0);
+    let (request, moved) = (&problem.requests[0], &perturbed.requests[0]);
+    assert!(moved.x != request.x && moved.demand == request.demand);
+    assert!(Perturb::parse("xy:-1").is_none());
+    assert!(parse("warp:0.2,").is_none());
+    assert!(parse("subsample:2").is_none());
+}
+
+#[test]
+fn windows_after_release() {
+    use super::{baseline, scenario::Scenario, simulate_days};
+
+    let (mut problem, time_slot) = Problem::test_instance();
+    // released late in their windows, past the narrowed ones
+    for req in problem.requests.iter_mut().step_by(3) {
+        req.time = req.close - 1.0;
+    }
+    let (mut narrow, time_slot) = apply(&parse("windows:0.1").unwrap(), &problem, time_slot);
+    for req in &narrow.requests {
+        assert!(req.time <= req.open && req.open <= req.close);
+    }
+    narrow.cancel_rate = 0.5;
+    let result = simulate_days(
+        &narrow,
+        &baseline::nearest_vehicle(),
+        &baseline::cost(),
+        Scenario::default(),
+        time_slot,
+        |_, _| (),
+    );
+    assert!(result.cancelled > 0);
+}
@@ -0,0 +1,215 @@
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    io::BufRead,
+    process::{Command, Stdio},
+};
+
+use anyhow::{ensure, Context};
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+use serde::Deserialize;
+
+// loggers silenced in the runs of a sweep, only GP is read back
+const LOGGERS: [&str; 9] = [
+    "MAIN",
+    "HEU",
+    "SIM",
+    "LASTPOP",
+    "POP",
+    "LASTROUTE",
+    "ROUTE",
+    "ROUTEEVAL",
+    "DEBUG",
+];
+
+// an environment variable and the values it is swept over
+#[derive(Clone, Debug)]
+pub struct Parameter {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+// integral values are printed without a fraction so that integer parameters
+// parse them
+fn format_value(value: f64) -> String {
+    let value = (value * 1e9).round() / 1e9;
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{value}")
+    }
+}
+
+impl Parameter {
+    // `NAME=a,b,c` or `NAME=from..to[:step]`, ranges include `to` and step
+    // by 1 by default
+    pub fn parse(str: &str) -> anyhow::Result<Self> {
+        let (name, values) = str
+            .split_once('=')
+            .with_context(|| format!("expected NAME=values, found {str:?}"))?;
+        let values = match values.split_once("..") {
+            Some((from, rest)) => {
+                let (to, step) = rest.split_once(':').unwrap_or((rest, "1"));
+                let number = |s: &str| {
+                    s.parse::<f64>()
+                        .with_context(|| format!("{name}: {s:?} is not a number"))
+                };
+                let (from, to, step) = (number(from)?, number(to)?, number(step)?);
+                ensure!(
+                    step > 0.0 && from <= to,
+                    "{name}: empty range {from}..{to}:{step}"
+                );
+                let steps = ((to - from) / step + 1e-9).floor() as usize;
+                (0..=steps)
+                    .map(|i| format_value(from + step * i as f64))
+                    .collect()
+            }
+            None => values.split(',').map(str::to_string).collect(),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            values,
+        })
+    }
+}
+
+// environment variables set for one run
+pub type Config = Vec<(String, String)>;
+
+pub fn describe(config: &Config) -> String {
+    config
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn grid(parameters: &[Parameter]) -> Vec<Config> {
+    parameters
+        .iter()
+        .fold(vec![Vec::new()], |configs, parameter| {
+            configs
+                .into_iter()
+                .flat_map(|config| {
+                    parameter.values.iter().map(move |value| {
+                        let mut config = config.clone();
+                        config.push((parameter.name.clone(), value.clone()));
+                        config
+                    })
+                })
+                .collect()
+        })
+}
+
+// the grid, or `samples` random configurations of it
+pub fn configs(parameters: &[Parameter], samples: Option<usize>, seed: u64) -> Vec<Config> {
+    match samples {
+        Some(samples) => sample(grid(parameters), samples, seed),
+        None => grid(parameters),
+    }
+}
+
+// `samples` distinct configurations of the grid, in a fixed order per seed
+pub fn sample(grid: Vec<Config>, samples: usize, seed: u64) -> Vec<Config> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    grid.choose_multiple(&mut rng, samples).cloned().collect()
+}
+
+// the `full_result` line of the last generation: the best individual
+// evaluated on the instance itself
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct RunResult {
+    pub result: (f32, usize),
+    pub fitness: f32,
+}
+
+// runs the GP on `problem` in a child process, with `config` on top of the
+// environment
+pub fn run(problem: &str, config: &Config) -> anyhow::Result<RunResult> {
+    let mut command = Command::new(env::current_exe()?);
+    command.arg(problem);
+    for logger in LOGGERS {
+        command.env(format!("LOG_{logger}"), "");
+    }
+    command.env("LOG_GP", "stdout");
+    command.envs(config.iter().map(|(name, value)| (name, value)));
+    let output = command.stderr(Stdio::inherit()).output()?;
+    ensure!(
+        output.status.success(),
+        "the run with {} failed: {}",
+        describe(config),
+        output.status
+    );
+    let line = output
+        .stdout
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| line.contains(r#""_":"full_result""#))
+        .last()
+        .with_context(|| format!("the run with {} logged no result", describe(config)))?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+pub struct SweepResult {
+    pub config: Config,
+    pub runs: Vec<RunResult>,
+}
+
+impl SweepResult {
+    pub fn fitness(&self) -> Vec<f32> {
+        self.runs.iter().map(|run| run.fitness).collect()
+    }
+
+    pub fn mean_fitness(&self) -> f32 {
+        self.runs.iter().map(|run| run.fitness).sum::<f32>() / self.runs.len() as f32
+    }
+
+    pub fn std_fitness(&self) -> f32 {
+        let mean = self.mean_fitness();
+        let variance = self
+            .runs
+            .iter()
+            .map(|run| (run.fitness - mean).powi(2))
+            .sum::<f32>()
+            / self.runs.len() as f32;
+        variance.sqrt()
+    }
+
+    pub fn best_fitness(&self) -> f32 {
+        self.runs
+            .iter()
+            .map(|run| run.fitness)
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+impl Display for SweepResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let n = self.runs.len() as f32;
+        write!(
+            f,
+            "{}: fitness {} ± {} (best {}), distance {}, failed {} over {} runs",
+            describe(&self.config),
+            self.mean_fitness(),
+            self.std_fitness(),
+            self.best_fitness(),
+            self.runs.iter().map(|run| run.result.0).sum::<f32>() / n,
+            self.runs.iter().map(|run| run.result.1 as f32).sum::<f32>() / n,
+            self.runs.len()
+        )
+    }
+}
+
+#[test]
+fn sweep_grid() {
+    let depth = Parameter::parse("MAX_DEPTH=4..8:2").unwrap();
+    assert_eq!(depth.values, ["4", "6", "8"]);
+    let rate = Parameter::parse("CROSSOVER_RATE=0.7..0.9:0.1").unwrap();
+    assert_eq!(rate.values, ["0.7", "0.8", "0.9"]);
+    let size = Parameter::parse("POP_SIZE=50,100").unwrap();
+    let grid = grid(&[depth, size]);
+    assert_eq!(grid.len(), 6);
+    assert_eq!(describe(&grid[1]), "MAX_DEPTH=4 POP_SIZE=100");
+    assert_eq!(sample(grid, 4, 0).len(), 4);
+    assert!(Parameter::parse("MAX_DEPTH=8..4").is_err());
+}
@@ -0,0 +1,81 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    objective::Objective,
+    sim::{
+        problem::Problem,
+        registry::{RoutingEntry, SequencingEntry},
+        scenario::Scenario,
+        simulate_rules,
+        training::{self, TrainingTransform, Windows},
+        Float,
+    },
+};
+
+// a pair of rules run on the instance with its time windows scaled by `factor`
+pub struct TightnessRow {
+    pub factor: Float,
+    pub distance: Float,
+    pub failed: usize,
+    pub fitness: f32,
+}
+
+// how a pair of rules fares as the time windows tighten or loosen, against
+// the instance as is
+pub struct Tightness {
+    pub rows: Vec<TightnessRow>,
+    pub reference: f32,
+}
+
+impl Tightness {
+    pub fn new(
+        problem: &Problem,
+        routing: &RoutingEntry,
+        sequencing: &SequencingEntry,
+        time_slot: Float,
+        factors: &[Float],
+        objective: &dyn Objective,
+    ) -> Self {
+        let run = |factor: Float| {
+            let transforms: [Box<dyn TrainingTransform>; 1] = [Box::new(Windows(factor))];
+            let (scaled, time_slot) = training::apply(&transforms, problem, time_slot);
+            let result = simulate_rules(
+                &scaled,
+                routing,
+                sequencing,
+                Scenario::default(),
+                time_slot,
+                |_, _| (),
+            );
+            TightnessRow {
+                factor,
+                distance: result.distance,
+                failed: result.failed,
+                fitness: objective.fitness(&scaled, &result),
+            }
+        };
+        Self {
+            reference: run(1.0).fitness,
+            rows: factors.iter().map(|factor| run(*factor)).collect(),
+        }
+    }
+}
+
+// CSV, with the fitness lost over the instance as is
+impl Display for Tightness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "factor,distance,failed,fitness,degradation")?;
+        for row in &self.rows {
+            write!(
+                f,
+                "\n{},{},{},{},{}",
+                row.factor,
+                row.distance,
+                row.failed,
+                row.fitness,
+                row.fitness - self.reference
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use sha1_smol::Sha1;
+
+use crate::{
+    progress::Generation,
+    sim::{problem::Problem, Float, Leg},
+};
+
+const PAGE: &str = include_str!("web.html");
+
+// RFC 6455 appends it to the key of the client to accept the upgrade
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// clients that write slower than this are dropped rather than stalling the run
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// what pages are sent: the instance once, then every generation with the
+// legs of the best individual
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message<'a> {
+    Instance {
+        // index, x, y and whether it is a depot or satellite
+        nodes: Vec<(usize, Float, Float, bool)>,
+        day_length: Float,
+        num_days: usize,
+    },
+    Generation {
+        #[serde(flatten)]
+        generation: &'a Generation,
+        // day, vehicle and leg
+        legs: &'a [(usize, usize, Leg)],
+    },
+}
+
+#[derive(Default)]
+struct Shared {
+    clients: Vec<TcpStream>,
+    instance: String,
+    // the last generation, for pages opened midway
+    last: Option<String>,
+}
+
+// a page at `/` streaming the progress of a GP run to browsers over a
+// WebSocket at `/ws`; messages are JSON text frames
+pub struct WebDashboard {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl WebDashboard {
+    pub fn serve(address: impl ToSocketAddrs, problem: &Problem) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let nodes = problem
+            .depots
+            .iter()
+            .chain(&problem.satellites)
+            .chain(&problem.requests)
+            .map(|node| (node.idx, node.x, node.y, problem.is_depot(node)))
+            .collect();
+        let instance = Message::Instance {
+            nodes,
+            day_length: problem.day_length,
+            num_days: problem.num_days,
+        };
+        let shared = Arc::new(Mutex::new(Shared {
+            instance: serde_json::to_string(&instance)?,
+            ..Shared::default()
+        }));
+        let accepting = shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let shared = accepting.clone();
+                // a failed request only concerns its own connection
+                thread::spawn(move || _ = handle(stream, &shared));
+            }
+        });
+        Ok(Self { shared })
+    }
+
+    pub fn send(&self, generation: &Generation, legs: &[(usize, usize, Leg)]) -> io::Result<()> {
+        let message = serde_json::to_string(&Message::Generation { generation, legs })?;
+        let frame = frame(&message);
+        let mut shared = self.shared.lock().expect("mutex lock failure");
+        shared
+            .clients
+            .retain_mut(|client| client.write_all(&frame).is_ok());
+        shared.last = Some(message);
+        Ok(())
+    }
+}
+
+fn handle(mut stream: TcpStream, shared: &Mutex<Shared>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut key = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    match (path, key) {
+        ("/ws", Some(key)) => {
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            )?;
+            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            let mut shared = shared.lock().expect("mutex lock failure");
+            stream.write_all(&frame(&shared.instance))?;
+            if let Some(last) = &shared.last {
+                stream.write_all(&frame(last))?;
+            }
+            shared.clients.push(stream);
+        }
+        ("/", _) => write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{PAGE}",
+            PAGE.len()
+        )?,
+        _ => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?,
+    }
+    Ok(())
+}
+
+fn accept_key(key: &str) -> String {
+    STANDARD.encode(
+        Sha1::from(format!("{key}{WEBSOCKET_GUID}"))
+            .digest()
+            .bytes(),
+    )
+}
+
+// an unmasked, unfragmented text frame, as servers send them
+fn frame(text: &str) -> Vec<u8> {
+    let len = text.len();
+    let mut frame = vec![0x81];
+    match len {
+        0..=125 => frame.push(len as u8),
+        126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(text.as_bytes());
+    frame
+}
+
+#[test]
+fn websocket_frames() {
+    // the example of RFC 6455
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+    assert_eq!(frame("Hello"), b"\x81\x05Hello");
+    let long = "x".repeat(300);
+    assert_eq!(frame(&long)[..4], [0x81, 126, 1, 44]);
+    assert_eq!(frame(&"x".repeat(70000))[..2], [0x81, 127]);
+}